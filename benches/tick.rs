@@ -0,0 +1,127 @@
+//! How long a world tick takes as the population grows. A tick has to finish
+//! well inside `clock::TICK_INTERVAL` (2s) for the world to keep time.
+//!
+//! Run with `cargo bench --bench tick`.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::sync::Arc;
+
+use chott::actor::{ActorFlag, ActorManager};
+use chott::clock::{ClockConfig, WorldClock};
+use chott::conditions::Condition;
+use chott::definitions::ActorDefinition;
+use chott::environment::{EnvironmentManager, EnvironmentTtl};
+use chott::events::EventBus;
+use chott::pages::{PageConnection, PageGraph, PageId, load_page_graph};
+use chott::regions::Regions;
+use chott::scripting::ScriptHost;
+use chott::world::WorldGraph;
+
+/// The world is a square of pages this many on a side, each joined to its neighbours
+const GRID: usize = 20;
+
+fn grid_page(x: usize, y: usize) -> PageId {
+    PageId::from(format!("grid-{x}-{y}"))
+}
+
+/// A GRID x GRID world of plain outdoor pages
+fn grid_world() -> PageGraph {
+    let template = load_page_graph()
+        .remove(&PageId::from("route-1"))
+        .expect("route-1 is a built-in page");
+    let mut pages = PageGraph::new();
+    for x in 0..GRID {
+        for y in 0..GRID {
+            let neighbours = [
+                (x > 0).then(|| ("West", grid_page(x - 1, y))),
+                (x + 1 < GRID).then(|| ("East", grid_page(x + 1, y))),
+                (y > 0).then(|| ("North", grid_page(x, y - 1))),
+                (y + 1 < GRID).then(|| ("South", grid_page(x, y + 1))),
+            ];
+            let mut page = template.clone();
+            page.id = grid_page(x, y);
+            page.title = format!("Field {x},{y}");
+            page.region = None;
+            page.items.clear();
+            page.shop = None;
+            page.fixtures.clear();
+            page.connections = neighbours
+                .into_iter()
+                .flatten()
+                .map(|(name, target)| PageConnection {
+                    name: name.to_string(),
+                    target,
+                    requires: Condition::Always,
+                    secret: false,
+                    locked_text: None,
+                    distance: 1,
+                    hidden: 0,
+                    needs: None,
+                    tight: false,
+                    blocks: Vec::new(),
+                })
+                .collect();
+            pages.insert(page.id.clone(), page);
+        }
+    }
+    pages
+}
+
+/// `count` actors spread evenly over the grid, with a mix of temperaments and paces
+fn population(count: usize) -> Vec<ActorDefinition> {
+    let temperaments = [
+        vec![ActorFlag::Organic],
+        vec![ActorFlag::Organic, ActorFlag::Curious],
+        vec![ActorFlag::Organic, ActorFlag::Shy],
+        vec![ActorFlag::Organic, ActorFlag::Nocturnal],
+        vec![ActorFlag::Organic, ActorFlag::CanSpeak],
+    ];
+    (0..count)
+        .map(|n| ActorDefinition {
+            id: format!("critter-{n}").into(),
+            name: format!("Critter {n}"),
+            location: grid_page(n % GRID, (n / GRID) % GRID),
+            health: 10,
+            flags: temperaments[n % temperaments.len()].clone(),
+            tick_rate: 1 + (n % 4) as u32,
+            action_points: 3,
+            roams: None,
+            home: None,
+            faction: None,
+            pack: None,
+            items: Vec::new(),
+            coins: 0,
+            script: None,
+            seen_when: Condition::Always,
+            decision_overlays: Default::default(),
+            appointments: Vec::new(),
+        })
+        .collect()
+}
+
+fn tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick");
+    for count in [1_000, 10_000] {
+        let bus = EventBus::new();
+        let world = WorldGraph::new(grid_world(), bus.clone());
+        let clock = WorldClock::new(ClockConfig::default());
+        let regions = Arc::new(Regions::default());
+        let environments = EnvironmentManager::new(
+            bus.clone(),
+            EnvironmentTtl::default(),
+            clock.clone(),
+            world.clone(),
+            regions.clone(),
+        );
+        let mut actors = ActorManager::new(bus, population(count), regions, ScriptHost::default());
+        let pages = world.snapshot();
+        let page_flags = world.page_flags();
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| actors.tick_some(&clock.world_time(), &pages, &environments, &page_flags))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tick);
+criterion_main!(benches);
@@ -1,73 +1,632 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tracing::{debug, info, trace};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, info, info_span, trace, warn};
 
-use crate::environment::WorldTime;
-use crate::pages::{PageGraph, PageId};
+use crate::calendar::MoonPhase;
+use crate::clock::DEFAULT_TICK_BUDGET;
+use crate::combat;
+use crate::conditions::Condition;
+use crate::contagion;
+use crate::definitions::ActorDefinition;
+use crate::ecology::{self, Forage, HUNGRY, Pastures};
+use crate::environment::{Environment, EnvironmentManager, HazardKind, Season, WorldTime};
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::goals::{self, Appointment, Goal};
+use crate::items::ItemId;
+use crate::mood::{self, Mood};
+use crate::mounts::MOUNT_CAPACITY;
+use crate::overlays::{Behavior, DecisionOverlay, Routine};
+use crate::packs::{self, Packs};
+use crate::pages::{Page, PageGraph, PageId};
+use crate::perception::{Perception, SENSE_RANGE, Sense, Surroundings};
+use crate::regions::{RegionId, Regions};
+use crate::replay::{Recorder, Snapshot, TickRecord};
+use crate::rumors::{self, Rumor};
+use crate::schedule::Schedule;
+use crate::scripting::{ScriptContext, ScriptEffect, ScriptHost};
+use crate::spawner::Spawner;
+use crate::status::{self, SOAKED_TICKS, Status, StatusEffect};
+use crate::stimuli::{self, Stimuli, Stimulus, StimulusKind};
+use crate::weather::WeatherKind;
+use crate::world::PageFlags;
+
+/// Identifies an actor. Shared rather than owned, so the copies the tick
+/// hands around (events, targets, map keys) don't each allocate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ActorId(Arc<str>);
+
+impl ActorId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+impl From<&str> for ActorId {
+    fn from(s: &str) -> Self {
+        ActorId(s.into())
+    }
+}
+impl From<String> for ActorId {
+    fn from(s: String) -> Self {
+        ActorId(s.into())
+    }
+}
+// lets maps keyed by ActorId be looked up with a plain &str
+impl std::borrow::Borrow<str> for ActorId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+impl std::fmt::Display for ActorId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 /// Represents a general actor, ie NPC, in the world.
 /// Stores current page/location and state, `flags` for behaviors
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Actor {
-    pub id: String, // TODO: non stringly typed actor ID
+    pub id: ActorId,
     pub name: String,
     pub location: PageId, // page id
     pub state: ActorState,
     pub flags: Vec<ActorFlag>,
-    // actor-specific overrides/settings for routines etc:
-    //pub decision_overlays: Option<DecisionOverlay>, // combination of file loaded and inline
+    // acts once every `tick_rate` world ticks (1 = every tick)
+    #[serde(default = "default_tick_rate")]
+    pub tick_rate: u32,
+    // action points available to spend on queued actions each turn
+    #[serde(default = "default_action_points")]
+    pub action_points: u8,
+    // planned actions not yet taken
+    #[serde(default)]
+    pub queue: VecDeque<ActorAction>,
+    // most recent pages moved away from, oldest first
+    #[serde(default)]
+    pub trail: VecDeque<PageId>,
+    // wanders only within this region (and the regions inside it)
+    #[serde(default)]
+    pub roams: Option<RegionId>,
+    // the page it keeps near and sleeps at, and guards if territorial
+    #[serde(default)]
+    pub home: Option<PageId>,
+    // what players do to it, its faction hears about
+    #[serde(default)]
+    pub faction: Option<String>,
+    // runs with the others of this pack; see `packs`
+    #[serde(default)]
+    pub pack: Option<String>,
+    // what it carries, for players to barter for
+    #[serde(default)]
+    pub inventory: Vec<ItemId>,
+    // on the road along a long connection, still counted at the page it left
+    #[serde(default)]
+    pub travel: Option<Travel>,
+    // behavior script planning its turns ahead of the built-in behaviors
+    #[serde(default)]
+    pub script: Option<String>,
+    // players see this actor only while it holds
+    #[serde(default)]
+    pub seen_when: Condition,
+    // held still by an admin: sits out its turns and the weather alike
+    #[serde(default)]
+    pub paused: bool,
+    // what it has seen happen or been told, to pass on
+    #[serde(default)]
+    pub rumors: Vec<Rumor>,
+    // its own take on the built-in behaviors; see `overlays`
+    #[serde(default)]
+    pub decision_overlays: DecisionOverlay,
+    // where it means to be by when, every day; see `goals`
+    #[serde(default)]
+    pub appointments: Vec<Appointment>,
+}
+
+pub(crate) fn default_tick_rate() -> u32 {
+    1
+}
+
+pub(crate) fn default_action_points() -> u8 {
+    3
+}
+
+/// How many past locations an actor remembers in its trail
+const TRAIL_LEN: usize = 8;
+
+/// Fatigue from each tick spent moving along a connection
+const MOVE_FATIGUE: u8 = 4;
+
+/// Fatigue at which an actor turns in to sleep
+const SLEEP_FATIGUE: u8 = 20;
+
+/// Fatigue past turning in that an actor with a home still walks back to
+/// sleep there, rather than dropping where it stands
+const HOMEWARD_FATIGUE: u8 = 12;
+
+/// How many times likelier a wanderer is to take a step nearer home than any
+/// other way
+const HOME_PULL: u32 = 4;
+
+/// One in this many idle turns, a talker strikes up a conversation with
+/// another talker on its page
+const CONVERSE_ODDS: u8 = 20;
+
+/// Ticks without a request before a player's actor leaves the world
+const PLAYER_IDLE_TICKS: u64 = 300;
+
+/// Health a player's actor enters the world with, and can heal back up to
+pub const PLAYER_HEALTH: i32 = 10;
+
+/// A journey along a connection longer than one tick
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Travel {
+    pub to: PageId,
+    pub remaining: u32, // ticks still to go
+}
+
+impl Actor {
+    /// Fresh, rested actor at its spawn location
+    pub fn from_definition(definition: ActorDefinition) -> Self {
+        Actor {
+            id: definition.id,
+            name: definition.name,
+            location: definition.location,
+            state: ActorState {
+                health: definition.health,
+                awake: true,
+                fatigue: 0,
+                target: None,
+                coins: definition.coins,
+                following: None,
+                effects: Vec::new(),
+                hunger: 0,
+                bred_at: None,
+                mood: Mood::Content,
+                mood_ticks: 0,
+                goal: None,
+                plan: Vec::new(),
+            },
+            flags: definition.flags,
+            tick_rate: definition.tick_rate,
+            action_points: definition.action_points,
+            queue: VecDeque::new(),
+            trail: VecDeque::new(),
+            roams: definition.roams,
+            home: definition.home,
+            faction: definition.faction,
+            pack: definition.pack,
+            inventory: definition.items,
+            travel: None,
+            script: definition.script,
+            seen_when: definition.seen_when,
+            paused: false,
+            rumors: Vec::new(),
+            decision_overlays: definition.decision_overlays,
+            appointments: definition.appointments,
+        }
+    }
+
+    /// Stand-in for a player, so NPCs can notice them. Players don't take turns.
+    pub fn player(id: &ActorId, name: &str, location: &PageId) -> Self {
+        Actor {
+            id: id.clone(),
+            name: name.to_string(),
+            location: location.clone(),
+            state: ActorState {
+                health: PLAYER_HEALTH,
+                awake: true,
+                fatigue: 0,
+                target: None,
+                coins: 0, // players keep their purse in their session
+                following: None,
+                effects: Vec::new(),
+                hunger: 0,
+                bred_at: None,
+                mood: Mood::Content,
+                mood_ticks: 0,
+                goal: None,
+                plan: Vec::new(),
+            },
+            flags: vec![ActorFlag::Player, ActorFlag::Organic],
+            tick_rate: default_tick_rate(),
+            action_points: default_action_points(),
+            queue: VecDeque::new(),
+            trail: VecDeque::new(),
+            roams: None,
+            home: None,
+            faction: None,
+            pack: None,
+            inventory: Vec::new(), // players carry theirs in their session
+            travel: None,
+            script: None,
+            seen_when: Condition::Always,
+            paused: false,
+            rumors: Vec::new(),
+            decision_overlays: DecisionOverlay::default(),
+            appointments: Vec::new(),
+        }
+    }
+
+    /// Take on changed settings from a reloaded definition, keeping live state
+    pub fn redefine(&mut self, definition: &ActorDefinition) {
+        self.name = definition.name.clone();
+        self.flags = definition.flags.clone();
+        self.tick_rate = definition.tick_rate;
+        self.action_points = definition.action_points;
+        self.roams = definition.roams.clone();
+        self.home = definition.home.clone();
+        self.faction = definition.faction.clone();
+        self.pack = definition.pack.clone();
+        self.script = definition.script.clone();
+        self.seen_when = definition.seen_when.clone();
+        self.decision_overlays = definition.decision_overlays.clone();
+        self.appointments = definition.appointments.clone();
+    }
 }
 
 /// Decision-making for an Actor.
-/// Accepts current world time, actors at the same location, and page graph.
+/// Accepts current world time, actors at the same location, pages players are on,
+/// page graph and regions.
 impl Actor {
-    /// Choose which action this actor will try to take this tick
+    /// Plan the actions this actor will try to take, in order
     /// (pure function; dont mutate)
     pub fn decide(
         &self,
         world_time: &WorldTime,
-        local_actors: &[&Actor],
+        environment: &Environment,
+        perception: &Perception,
+        player_pages: &HashSet<&PageId>,
         page_graph: &PageGraph,
-    ) -> ActorAction {
+        regions: &Regions,
+    ) -> Vec<ActorAction> {
+        let local_actors = &perception.here().actors;
+        // lunar actors sleep through the month and come out only at full moon
+        if self.has_flag(ActorFlag::Lunar) && environment.calendar().moon != MoonPhase::Full {
+            return vec![ActorAction::Sleep];
+        }
+
+        // knocked down; out for a turn
+        if self.state.health <= 0 {
+            return vec![ActorAction::Sleep];
+        }
+
+        let is_predator = self.has_flag(ActorFlag::Predatory);
+        // nocturnal predators are emboldened by fog
+        let prowling = is_predator
+            && self.has_flag(ActorFlag::Nocturnal)
+            && environment.weather() == WeatherKind::Foggy;
+
         // fatigue-aware logic:
-        let fatigue_threshold = 20; // could be per-actor/future config
+        let overlay = &self.decision_overlays;
+        let mut fatigue_threshold = overlay.sleep_fatigue.unwrap_or(SLEEP_FATIGUE);
+        if prowling {
+            fatigue_threshold += 10;
+        }
         if self.state.fatigue >= fatigue_threshold {
-            // Too tired! Either sleep (if awake) or continue sleeping.
+            // Too tired! Head home to sleep while there's the strength left,
+            // else sleep (if awake) or continue sleeping.
+            if self.state.awake
+                && overlay.allows(Behavior::GoingHome)
+                && self.state.fatigue < fatigue_threshold + HOMEWARD_FATIGUE
+                && let Some(step) = self.way_home(page_graph)
+            {
+                debug!(%self.id, fatigue=%self.state.fatigue, "Tired, heading home to sleep.");
+                return vec![ActorAction::MoveTo(step)];
+            }
             if self.state.awake {
                 debug!(%self.id, fatigue=%self.state.fatigue, "Too tired, going to sleep.");
-                return ActorAction::Sleep;
-            } else {
-                return ActorAction::Sleep; // Already sleeping
             }
+            return vec![ActorAction::Sleep];
         }
 
         let mut actions = Vec::new();
 
-        // sleep pattern
+        // sleep pattern, unless a plan calls for getting up
         let is_nocturnal = self.has_flag(ActorFlag::Nocturnal);
-        let is_awake = self.state.awake;
-        if is_nocturnal && world_time.is_night() && !is_awake {
+        let mut is_awake = self.state.awake;
+        let called = matches!(self.state.plan.first(), Some(ActorAction::WakeUp));
+        if !is_awake
+            && (called
+                || (is_nocturnal && world_time.is_night())
+                || (!is_nocturnal && world_time.is_daytime()))
+        {
             actions.push(ActorAction::WakeUp);
+            is_awake = true; // later actions in the plan happen after waking
         }
-        if !is_nocturnal && world_time.is_daytime() && !is_awake {
-            actions.push(ActorAction::WakeUp);
+        // behavior: grudges. Fighters strike back at whoever hurt them,
+        // unless they've been frightened off, and everyone else gets away
+        // from them; an angry fighter goes after one that's nearby
+        let foe = self.state.target.as_ref().and_then(|id| {
+            local_actors
+                .iter()
+                .filter(|_| overlay.allows(Behavior::Grudges))
+                .find(|a| a.id == *id && a.location == self.location)
+        });
+        if is_awake && let Some(foe) = foe {
+            let fighter = is_predator || self.has_flag(ActorFlag::CanAttack);
+            if fighter && self.state.mood != Mood::Scared {
+                info!(attacker=%self.id, target=%foe.id, "Strikes back");
+                actions.push(ActorAction::Attack(foe.id.clone()));
+            } else if let Some(page) = self.step_to(|p| *p != self.location, page_graph, regions) {
+                actions.push(ActorAction::MoveTo(page));
+            }
+        } else if is_awake
+            && overlay.allows(Behavior::Grudges)
+            && self.state.mood == Mood::Angry
+            && let Some(target) = &self.state.target
+            && let Some((there, _)) = perception.nearest(Sense::Sight, |a| a.id == *target)
+            && let Some(step) = there
+                .step
+                .filter(|s| self.roams_into(s, page_graph, regions))
+        {
+            debug!(%self.id, %target, "Goes after its foe in a temper.");
+            actions.push(ActorAction::MoveTo(step.clone()));
         }
-        // behavior: predatory attack
-        if self.has_flag(ActorFlag::Predatory) && is_awake {
-            if let Some(target) = local_actors.iter().find(|a| {
-                a.location == self.location && a.has_flag(ActorFlag::Organic) && a.id != self.id
-            }) {
-                info!(attacker=%self.id, target=%target.id, "Predator will attack");
-                actions.push(ActorAction::Attack(target.id.clone()));
+        // behavior: companions keep to their player's side, and make their
+        // way back to them (or some player) if left behind
+        if is_awake
+            && foe.is_none()
+            && overlay.allows(Behavior::Following)
+            && let Some(leader) = &self.state.following
+        {
+            if local_actors
+                .iter()
+                .any(|a| a.id == *leader && a.location == self.location)
+            {
+                actions.push(ActorAction::Idle);
+            } else if let Some(page) =
+                self.step_to(|p| player_pages.contains(p), page_graph, regions)
+            {
+                actions.push(ActorAction::MoveTo(page));
             }
+            return actions;
+        }
+        // behavior: goals. One set on something sees its plan through a
+        // step at a time, and stays put once it's there
+        if is_awake && foe.is_none() && self.state.goal.is_some() {
+            let next = self
+                .state
+                .plan
+                .iter()
+                .find(|step| !matches!(step, ActorAction::WakeUp));
+            actions.push(next.cloned().unwrap_or(ActorAction::Idle));
+            return actions;
+        }
+        // behavior: migrants make for where their kind spends the season,
+        // or back to their own range once it's over, the whole way at once
+        if is_awake
+            && foe.is_none()
+            && self.has_flag(ActorFlag::Migratory)
+            && overlay.allows(Behavior::Migrating)
+            && let Some(route) = self.migration(environment.season(), page_graph, regions)
+        {
+            debug!(%self.id, season = %environment.season(), steps = route.len(), "Migrating");
+            actions.extend(route.into_iter().map(ActorAction::MoveTo));
+            return actions;
+        }
+        // behavior: predatory attack, once it's hungry again; herbivores
+        // first, as they're what it can eat, and never its own pack
+        let prey = |a: &&&Actor| {
+            a.location == self.location
+                && a.has_flag(ActorFlag::Organic)
+                && a.id != self.id
+                && (a.pack.is_none() || a.pack != self.pack)
+        };
+        if is_predator
+            && is_awake
+            && foe.is_none()
+            && overlay.allows(Behavior::Hunting)
+            && ecology::hunting(self)
+            && let Some(target) = local_actors
+                .iter()
+                .filter(prey)
+                .find(|a| a.has_flag(ActorFlag::Herbivore))
+                .or_else(|| local_actors.iter().find(prey))
+        {
+            info!(attacker=%self.id, target=%target.id, "Predator will attack");
+            actions.push(ActorAction::Attack(target.id.clone()));
+        }
+        // hungry predators with nothing to hunt here follow the scent of
+        // prey close by
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp));
+        if !busy
+            && is_predator
+            && is_awake
+            && foe.is_none()
+            && overlay.allows(Behavior::Tracking)
+            && ecology::hunting(self)
+            && let Some((there, quarry)) = perception.nearest(Sense::Smell, |a| {
+                a.has_flag(ActorFlag::Herbivore) && (a.pack.is_none() || a.pack != self.pack)
+            })
+            && let Some(step) = there
+                .step
+                .filter(|s| self.roams_into(s, page_graph, regions))
+        {
+            debug!(%self.id, quarry=%quarry.id, page=%there.page, "Picks up the scent of prey.");
+            actions.push(ActorAction::MoveTo(step.clone()));
+        }
+        // behavior: territorial actors set on strangers on their home page
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp));
+        if !busy
+            && is_awake
+            && self.has_flag(ActorFlag::Territorial)
+            && overlay.allows(Behavior::Guarding)
+            && self.home.as_ref() == Some(&self.location)
+            && let Some(intruder) = local_actors.iter().find(|a| self.resents(a))
+        {
+            info!(defender=%self.id, intruder=%intruder.id, "Drives off an intruder");
+            actions.push(ActorAction::Attack(intruder.id.clone()));
         }
-        // default: move if not tired/fatigued, else idle
-        if actions.is_empty() && is_awake {
-            actions.push(self.default_behavior(page_graph));
+        // shy actors slip away from players; curious ones go to see them
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp))
+            || !overlay.allows(Behavior::Players);
+        let player_here = local_actors.iter().any(|a| a.has_flag(ActorFlag::Player));
+        if !busy && is_awake && self.has_flag(ActorFlag::Shy) && player_here {
+            let away = |p: &PageId| !player_pages.contains(p);
+            if let Some(page) = self.step_to(away, page_graph, regions) {
+                actions.push(ActorAction::MoveTo(page));
+            }
+        } else if !busy && is_awake && self.has_flag(ActorFlag::Curious) {
+            if player_here {
+                actions.push(ActorAction::Idle); // stay and watch
+            } else if let Some(page) =
+                self.step_to(|p| player_pages.contains(p), page_graph, regions)
+            {
+                actions.push(ActorAction::MoveTo(page));
+            }
+        }
+        // noises and scents from round about: the timid keep away from a
+        // commotion, the curious go and see, and hungry predators go after
+        // the noise of a fight or the smell of blood
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp));
+        if !busy
+            && is_awake
+            && overlay.allows(Behavior::Heeding)
+            && let Some(page) = self.heed(&perception.here().stimuli, page_graph, regions)
+        {
+            actions.push(ActorAction::MoveTo(page));
+        }
+        // people head for (or stay under) shelter in wet or dangerous weather
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp));
+        let exposed = is_wet(environment)
+            || environment.hazard().is_some()
+            || self.has_status(Status::Soaked); // off to dry out
+        if !busy
+            && is_awake
+            && self.has_flag(ActorFlag::CanSpeak)
+            && exposed
+            && overlay.allows(Behavior::Sheltering)
+        {
+            actions.push(self.seek_shelter(page_graph));
+        }
+        // talkers pass the time of day with each other, for players to
+        // overhear, when they're in the mood for it
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp));
+        if !busy
+            && is_awake
+            && self.state.mood == Mood::Content
+            && self.has_flag(ActorFlag::CanSpeak)
+            && overlay.allows(Behavior::Conversing)
+            && rand::random::<u8>()
+                .is_multiple_of(overlay.converse_odds.unwrap_or(CONVERSE_ODDS).max(1))
+            && let Some(other) = local_actors.iter().find(|a| {
+                a.location == self.location
+                    && a.id != self.id
+                    && a.state.awake
+                    && a.has_flag(ActorFlag::CanSpeak)
+                    && !a.has_flag(ActorFlag::Player)
+            })
+        {
+            actions.push(ActorAction::Converse(other.id.clone()));
+        }
+        // default: one of its own routines, or move if not busy otherwise,
+        // else idle; the poisoned and the sick lie low
+        let busy = actions.iter().any(|a| !matches!(a, ActorAction::WakeUp))
+            || self.has_status(Status::Poisoned)
+            || self.has_status(Status::Sick);
+        if !busy && is_awake {
+            let range = self.range(environment.season(), regions);
+            actions.push(match overlay.pick() {
+                Some(routine) => self.routine(routine, page_graph),
+                None if overlay.allows(Behavior::Wandering) => {
+                    self.default_behavior(world_time, page_graph, regions, range, prowling)
+                }
+                None => ActorAction::Idle,
+            });
         }
 
-        actions.into_iter().next().unwrap_or(ActorAction::Idle)
+        if actions.is_empty() {
+            actions.push(ActorAction::Idle);
+        }
+        actions
+    }
+
+    /// Take queued actions in order until the action-point budget runs out.
+    /// The first action is always taken, so an expensive action can't stall the queue.
+    /// An actor on the road spends the whole turn travelling instead.
+    /// Returns the events produced by the actions taken.
+    /// Each action taken is counted in `taken_by_kind`.
+    pub fn take_turn(
+        &mut self,
+        environment: &Environment,
+        page_graph: &PageGraph,
+        taken_by_kind: &mut HashMap<&'static str, u64>,
+    ) -> Vec<WorldEvent> {
+        if let Some(travel) = &mut self.travel {
+            travel.remaining = travel.remaining.saturating_sub(1);
+            self.state.fatigue = self
+                .state
+                .fatigue
+                .saturating_add(exertion(environment, MOVE_FATIGUE));
+            trace!(%self.id, remaining = travel.remaining, "Travelling...");
+            if travel.remaining == 0 {
+                let to = self.travel.take().expect("travel was just checked").to;
+                return vec![self.arrive(to)];
+            }
+            return Vec::new();
+        }
+        let mut budget = self.action_points;
+        let mut taken = 0;
+        let mut events = Vec::new();
+        while let Some(action) = self.queue.front() {
+            let cost = action.cost();
+            if taken > 0 && cost > budget {
+                break;
+            }
+            budget = budget.saturating_sub(cost);
+            taken += 1;
+            let action = self.queue.pop_front().expect("front was just checked");
+            *taken_by_kind.entry(action.kind()).or_default() += 1;
+            events.extend(self.apply_action(action, environment, page_graph));
+        }
+        trace!(%self.id, taken, queued=self.queue.len(), "Turn finished.");
+        events
+    }
+
+    /// Reach `to`, remembering where we came from
+    fn arrive(&mut self, to: PageId) -> WorldEvent {
+        let from = std::mem::replace(&mut self.location, to);
+        if self.trail.len() == TRAIL_LEN {
+            self.trail.pop_front();
+        }
+        self.trail.push_back(from.clone());
+        debug!(%self.id, fatigue=%self.state.fatigue, "Moved to new location.");
+        WorldEvent::ActorMoved {
+            actor: self.id.clone(),
+            from,
+            to: self.location.clone(),
+        }
+    }
+
+    /// Pages this actor is queued to move to, in order
+    pub fn planned_path(&self) -> impl Iterator<Item = &PageId> {
+        self.queue.iter().filter_map(|action| match action {
+            ActorAction::MoveTo(page_id) => Some(page_id),
+            _ => None,
+        })
+    }
+
+    /// True if the last few moves just bounced between two pages (A-B-A-B).
+    /// Usually a sign of a schedule or pathing bug rather than intent.
+    pub fn is_ping_ponging(&self) -> bool {
+        // last three pages left, plus where the actor is now, oldest first
+        let skip = self.trail.len().saturating_sub(3);
+        let recent: Vec<&PageId> = self
+            .trail
+            .iter()
+            .skip(skip)
+            .chain(std::iter::once(&self.location))
+            .collect();
+        recent.len() == 4
+            && recent[0] == recent[2]
+            && recent[1] == recent[3]
+            && recent[0] != recent[1]
     }
 
     /// Return true if actor has specified flag (~component).
@@ -75,28 +634,326 @@ impl Actor {
         self.flags.contains(&flag)
     }
 
+    /// Weight this actor can carry, by how hale it is; a mount carries more,
+    /// and poison saps it
+    pub fn capacity(&self) -> u32 {
+        let mut capacity = 2 * self.state.health.max(0) as u32;
+        if self.has_flag(ActorFlag::Rideable) {
+            capacity += MOUNT_CAPACITY;
+        }
+        if status::has(&self.state.effects, Status::Poisoned) {
+            capacity /= 2;
+        }
+        capacity
+    }
+
+    pub fn has_status(&self, status: Status) -> bool {
+        status::has(&self.state.effects, status)
+    }
+
+    /// Stay put if the current page is sheltered, otherwise move to a sheltered neighbour
+    fn seek_shelter(&self, page_graph: &PageGraph) -> ActorAction {
+        let Some(page) = page_graph.get(&self.location) else {
+            return ActorAction::Idle;
+        };
+        if page.is_sheltered() {
+            return ActorAction::Idle;
+        }
+        page.connections
+            .iter()
+            .filter(|conn| conn.needs.is_none())
+            .find(|conn| {
+                page_graph
+                    .get(&conn.target)
+                    .is_some_and(|p| p.is_sheltered())
+            })
+            .map(|conn| ActorAction::MoveTo(conn.target.clone()))
+            .unwrap_or(ActorAction::Idle)
+    }
+
+    /// Whether a roaming actor may go onto `page_id` (any page, if it doesn't roam)
+    fn roams_into(&self, page_id: &PageId, page_graph: &PageGraph, regions: &Regions) -> bool {
+        within_range(page_id, self.roams.as_ref(), page_graph, regions)
+    }
+
+    /// Whether `other` is a stranger on this actor's ground: an awake,
+    /// living thing here that's neither its own kind, nor its faction, nor
+    /// anyone's companion
+    fn resents(&self, other: &Actor) -> bool {
+        other.id != self.id
+            && other.location == self.location
+            && other.travel.is_none()
+            && other.state.awake
+            && other.has_flag(ActorFlag::Organic)
+            && other.name != self.name
+            && (self.faction.is_none() || other.faction != self.faction)
+            && other.state.following.is_none()
+    }
+
+    /// Where the strongest stimulus here that this actor heeds sends it: a
+    /// step toward where it came from, or away from there for the timid and
+    /// the frightened
+    fn heed(
+        &self,
+        stimuli: &[&Stimulus],
+        page_graph: &PageGraph,
+        regions: &Regions,
+    ) -> Option<PageId> {
+        let scared = self.state.mood == Mood::Scared;
+        let hunting = !scared && self.has_flag(ActorFlag::Predatory) && ecology::hunting(self);
+        let timid = scared
+            || (!hunting
+                && (self.has_flag(ActorFlag::Shy)
+                    || (self.has_flag(ActorFlag::Herbivore)
+                        && !self.has_flag(ActorFlag::CanAttack))));
+        let curious = self.has_flag(ActorFlag::Curious);
+        let stimulus = stimuli
+            .iter()
+            .filter(|s| match s.kind {
+                StimulusKind::Noise => hunting || timid || curious,
+                StimulusKind::Scent => hunting,
+            })
+            .max_by_key(|s| s.strength)?;
+        let toward = self
+            .route(|p| *p == stimulus.from, page_graph)
+            .and_then(|route| route.into_iter().next());
+        if timid {
+            let away = |p: &PageId| *p != stimulus.from && Some(p) != toward.as_ref();
+            let step = self.step_to(away, page_graph, regions)?;
+            debug!(%self.id, from=%stimulus.from, "Keeps away from a commotion.");
+            return Some(step);
+        }
+        let step = toward.filter(|step| self.roams_into(step, page_graph, regions))?;
+        debug!(%self.id, from=%stimulus.from, kind=?stimulus.kind, "Goes to see what it was.");
+        Some(step)
+    }
+
+    /// What taking up one of its overlay's routines comes to this turn
+    fn routine(&self, routine: &Routine, page_graph: &PageGraph) -> ActorAction {
+        let step = match routine {
+            Routine::Idle => None,
+            Routine::Nap => return ActorAction::Sleep,
+            Routine::GoHome => self.way_home(page_graph),
+            Routine::Visit(page) => self
+                .route(|p| p == page, page_graph)
+                .and_then(|route| route.into_iter().next()),
+        };
+        trace!(%self.id, ?routine, "Takes up a routine.");
+        step.map_or(ActorAction::Idle, ActorAction::MoveTo)
+    }
+
+    /// The next step on the way home, if it's away from it and there's a way
+    fn way_home(&self, page_graph: &PageGraph) -> Option<PageId> {
+        let home = self.home.as_ref().filter(|home| **home != self.location)?;
+        self.route(|p| p == home, page_graph)?.into_iter().next()
+    }
+
+    /// How strongly wandering onto `page` draws the actor: a step nearer
+    /// home pulls harder, for one that has a home
+    fn pull(&self, page: &PageId, hops_home: &HashMap<&PageId, usize>) -> u32 {
+        let hops = |p: &PageId| hops_home.get(p).copied().unwrap_or(usize::MAX);
+        if hops(page) < hops(&self.location) {
+            HOME_PULL
+        } else {
+            1
+        }
+    }
+
+    /// The region the actor keeps to in `season`: where its kind goes then,
+    /// for a migrant, or else its own range. None if it goes anywhere.
+    fn range<'a>(&'a self, season: Season, regions: &'a Regions) -> Option<&'a RegionId> {
+        self.has_flag(ActorFlag::Migratory)
+            .then(|| regions.migrating_to(season))
+            .flatten()
+            .or(self.roams.as_ref())
+    }
+
+    /// A neighbouring page that is `wanted` and inside the actor's range, if any
+    fn step_to(
+        &self,
+        wanted: impl Fn(&PageId) -> bool,
+        page_graph: &PageGraph,
+        regions: &Regions,
+    ) -> Option<PageId> {
+        page_graph
+            .get(&self.location)?
+            .connections
+            .iter()
+            .filter(|conn| conn.needs.is_none()) // NPCs go on foot
+            .map(|conn| &conn.target)
+            .find(|target| wanted(target) && self.roams_into(target, page_graph, regions))
+            .cloned()
+    }
+
+    /// The shortest way on foot to the nearest page that is `wanted`, as the
+    /// pages to step onto in turn; None if there's no way there
+    pub(crate) fn route(
+        &self,
+        wanted: impl Fn(&PageId) -> bool,
+        page_graph: &PageGraph,
+    ) -> Option<Vec<PageId>> {
+        let mut came_from: HashMap<&PageId, &PageId> = HashMap::new();
+        let mut frontier = VecDeque::from([&self.location]);
+        while let Some(page) = frontier.pop_front() {
+            if *page != self.location && wanted(page) {
+                let mut route = vec![page.clone()];
+                let mut at = page;
+                while let Some(&before) = came_from.get(at).filter(|p| ***p != self.location) {
+                    route.push(before.clone());
+                    at = before;
+                }
+                route.reverse();
+                return Some(route);
+            }
+            let exits = page_graph
+                .get(page)
+                .into_iter()
+                .flat_map(|p| &p.connections);
+            for conn in exits.filter(|conn| conn.needs.is_none()) {
+                if conn.target != self.location && !came_from.contains_key(&conn.target) {
+                    came_from.insert(&conn.target, page);
+                    frontier.push_back(&conn.target);
+                }
+            }
+        }
+        None
+    }
+
+    /// The way to where this migrant ought to be in `season`, if it isn't
+    /// there already: the region its kind goes to then, or its own range
+    fn migration(
+        &self,
+        season: Season,
+        page_graph: &PageGraph,
+        regions: &Regions,
+    ) -> Option<Vec<PageId>> {
+        let bound_for = self.range(season, regions)?;
+        let inside = |page: &PageId| within_range(page, Some(bound_for), page_graph, regions);
+        if inside(&self.location) {
+            return None;
+        }
+        self.route(inside, page_graph)
+    }
+
     /// Default fallback behavior: randomly move somewhere, or idle if not.
-    fn default_behavior(&self, page_graph: &PageGraph) -> ActorAction {
+    /// Actors that fear the dark won't wander onto pages that are dark right now,
+    /// and actors that keep to a `range` stay inside it (or head back in if outside).
+    fn default_behavior(
+        &self,
+        world_time: &WorldTime,
+        page_graph: &PageGraph,
+        regions: &Regions,
+        range: Option<&RegionId>,
+        prowling: bool,
+    ) -> ActorAction {
         // For now: move very rarely (slow actors)
-        // Example: ~1/100 chance to move each tick
-        let move_chance = rand::random::<u8>() % 100 == 0;
-        if move_chance {
-            if let Some(page) = page_graph.get(&self.location) {
-                if !page.connections.is_empty() {
-                    // Pick a random connection
-                    let mut rng = rand::rng();
-                    // Specify the range and generate a random usize within it
-                    let idx = rng.random_range(0..page.connections.len());
-                    return ActorAction::MoveTo(page.connections[idx].target.clone());
-                }
+        // Example: ~1/100 chance to move each tick, ~1/10 for prowling predators
+        let odds = self.decision_overlays.move_odds.unwrap_or(100);
+        let odds = if prowling { odds / 10 } else { odds };
+        let move_chance = rand::random::<u8>().is_multiple_of(odds.max(1));
+        if !move_chance {
+            return ActorAction::Idle;
+        }
+        let fears_dark = self.has_flag(ActorFlag::FearsDark);
+        let in_region = |page_id: &PageId| within_range(page_id, range, page_graph, regions);
+        let strayed = !in_region(&self.location);
+        let options: Vec<&PageId> = page_graph
+            .get(&self.location)
+            .map(|page| {
+                page.connections
+                    .iter()
+                    .filter(|conn| conn.needs.is_none())
+                    .map(|conn| &conn.target)
+                    .filter(|target| {
+                        !fears_dark
+                            || page_graph
+                                .get(*target)
+                                .is_some_and(|p| !p.is_dark(world_time))
+                    })
+                    .filter(|target| strayed || in_region(target))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Pick a random connection, leaning toward home
+        let hops_home = self
+            .home
+            .as_ref()
+            .map(|home| hops_from(home, page_graph))
+            .unwrap_or_default();
+        options
+            .choose_weighted(&mut rand::rng(), |page| self.pull(page, &hops_home))
+            .map_or(ActorAction::Idle, |page| {
+                ActorAction::MoveTo((*page).clone())
+            })
+    }
+
+    /// Suffer whatever hazard there is where the actor stands: extreme
+    /// temperatures tire it out, storms (and deadly cold or heat) hurt it.
+    /// Only organic actors are affected. Returns an event if it was hurt.
+    pub fn endure(&mut self, environment: &Environment) -> Option<WorldEvent> {
+        if is_wet(environment) && !environment.is_sheltered() && self.travel.is_none() {
+            status::afflict(&mut self.state.effects, Status::Soaked, SOAKED_TICKS);
+        }
+        let hazard = environment.hazard()?;
+        if !self.has_flag(ActorFlag::Organic) || self.has_flag(ActorFlag::Player) {
+            return None;
+        }
+        let damage = match hazard.kind {
+            HazardKind::Storm => hazard.level as i32,
+            HazardKind::Cold | HazardKind::Heat => {
+                self.state.fatigue = self.state.fatigue.saturating_add(hazard.level * 2);
+                (hazard.level == 3) as i32
             }
+        };
+        self.state.health = (self.state.health - damage).max(0);
+        trace!(%self.id, ?hazard, health=%self.state.health, fatigue=%self.state.fatigue, "Endures hazard.");
+        if damage == 0 {
+            return None; // only worn out; not worth announcing
         }
-        ActorAction::Idle
+        Some(WorldEvent::ActorHarmed {
+            actor: self.id.clone(),
+            page: self.location.clone(),
+            hazard: hazard.kind,
+            health: self.state.health,
+        })
+    }
+
+    /// One tick of the actor's status effects wearing off, and of poison
+    /// and sickness doing their work; neither finishes anyone off. Returns
+    /// an event if a sickness it caught has just come out.
+    pub fn suffer_effects(&mut self) -> Option<WorldEvent> {
+        let was_sick = self.has_status(Status::Sick);
+        let hurt = status::wear(&mut self.state.effects);
+        if hurt > 0 && self.state.health > 1 {
+            self.state.health = (self.state.health - hurt).max(1);
+            trace!(%self.id, health=%self.state.health, "Poison takes its toll.");
+        }
+        if !self.has_status(Status::Sick) {
+            return None;
+        }
+        if self.state.awake {
+            self.state.fatigue = self.state.fatigue.saturating_add(1);
+        }
+        if was_sick {
+            return None;
+        }
+        debug!(%self.id, "Comes down sick.");
+        Some(WorldEvent::ActorFellIll {
+            actor: self.id.clone(),
+            page: self.location.clone(),
+        })
     }
 
     /// Applies the decided action to mutate this actor's state.
     /// Handles fatigue, waking/sleeping, moving, etc.
-    pub fn apply_action(&mut self, action: ActorAction) {
+    /// Returns an event if the action is something others could notice.
+    pub fn apply_action(
+        &mut self,
+        action: ActorAction,
+        environment: &Environment,
+        page_graph: &PageGraph,
+    ) -> Option<WorldEvent> {
+        let exertion = |base: u8| exertion(environment, base);
         // Modify state depending on action
         match action {
             ActorAction::Idle => {
@@ -105,23 +962,64 @@ impl Actor {
                     self.state.fatigue = self.state.fatigue.saturating_sub(1);
                 }
                 trace!(%self.id, fatigue=%self.state.fatigue, "Idling...");
+                None
             }
             ActorAction::MoveTo(page_id) => {
-                // Move increases fatigue
-                self.location = page_id;
-                self.state.fatigue = self.state.fatigue.saturating_add(4);
-                debug!(%self.id, fatigue=%self.state.fatigue, "Moved to new location.");
+                // Move increases fatigue, for every tick of the way; more so
+                // trudging along sodden
+                let mut effort = MOVE_FATIGUE;
+                if self.has_status(Status::Soaked) {
+                    effort += MOVE_FATIGUE / 2;
+                }
+                self.state.fatigue = self.state.fatigue.saturating_add(exertion(effort));
+                let distance = page_graph
+                    .get(&self.location)
+                    .and_then(|page| page.connections.iter().find(|c| c.target == page_id))
+                    .map_or(1, |conn| conn.distance);
+                if distance <= 1 {
+                    return Some(self.arrive(page_id));
+                }
+                debug!(%self.id, %page_id, distance, "Sets off on a long way.");
+                self.travel = Some(Travel {
+                    to: page_id.clone(),
+                    remaining: distance - 1,
+                });
+                Some(WorldEvent::ActorDeparted {
+                    actor: self.id.clone(),
+                    from: self.location.clone(),
+                    to: page_id,
+                })
             }
             ActorAction::Attack(target_id) => {
                 // Attack increases fatigue
-                self.state.fatigue = self.state.fatigue.saturating_add(6);
+                self.state.fatigue = self.state.fatigue.saturating_add(exertion(6));
                 info!(%self.id, %target_id, fatigue=%self.state.fatigue, "Attacks another actor.");
+                Some(WorldEvent::ActorAttacked {
+                    attacker: self.id.clone(),
+                    target: target_id,
+                    page: self.location.clone(),
+                })
+            }
+            ActorAction::Converse(listener) => {
+                // talking is restful, much like idling
+                self.state.fatigue = self.state.fatigue.saturating_sub(1);
+                debug!(%self.id, %listener, "Strikes up a conversation.");
+                Some(WorldEvent::ActorConversed {
+                    speaker: self.id.clone(),
+                    listener,
+                    page: self.location.clone(),
+                })
             }
             ActorAction::Sleep => {
-                self.state.awake = false;
-                // Sleeping reduces fatigue
+                let was_awake = std::mem::replace(&mut self.state.awake, false);
+                // Sleeping reduces fatigue, and brings round the knocked down
                 self.state.fatigue = self.state.fatigue.saturating_sub(1);
+                self.state.health = self.state.health.max(1);
                 debug!(%self.id, fatigue=%self.state.fatigue, "Goes to sleep.");
+                was_awake.then(|| WorldEvent::ActorSlept {
+                    actor: self.id.clone(),
+                    page: self.location.clone(),
+                })
             }
             ActorAction::WakeUp => {
                 self.state.awake = true;
@@ -129,25 +1027,107 @@ impl Actor {
                 if self.state.fatigue > 2 {
                     self.state.fatigue -= 2;
                 }
+                // and a good night's sleep puts whatever it was behind it
+                if Tiredness::of(self.state.fatigue) == Tiredness::Rested {
+                    mood::feel(&mut self.state, Mood::Content);
+                }
                 debug!(%self.id, fatigue=%self.state.fatigue, "Waking up.");
+                Some(WorldEvent::ActorWoke {
+                    actor: self.id.clone(),
+                    page: self.location.clone(),
+                })
             }
         }
     }
 }
 
+/// How many steps on foot every page is from `start`, by way of the paths
+/// leading out of it; pages it can't reach are left out
+fn hops_from<'a>(start: &'a PageId, page_graph: &'a PageGraph) -> HashMap<&'a PageId, usize> {
+    let mut hops = HashMap::from([(start, 0)]);
+    let mut frontier = VecDeque::from([start]);
+    while let Some(page) = frontier.pop_front() {
+        let next = hops[page] + 1;
+        let exits = page_graph
+            .get(page)
+            .into_iter()
+            .flat_map(|p| &p.connections);
+        for conn in exits.filter(|conn| conn.needs.is_none()) {
+            if !hops.contains_key(&conn.target) {
+                hops.insert(&conn.target, next);
+                frontier.push_back(&conn.target);
+            }
+        }
+    }
+    hops
+}
+
+/// Whether `page_id` lies in `range` (anywhere, with no range)
+fn within_range(
+    page_id: &PageId,
+    range: Option<&RegionId>,
+    page_graph: &PageGraph,
+    regions: &Regions,
+) -> bool {
+    range.is_none_or(|range| {
+        page_graph
+            .get(page_id)
+            .is_some_and(|p| regions.within(p.region.as_ref(), range))
+    })
+}
+
+/// Fatigue cost of `base` effort; exertion tires actors out faster in the cold
+fn exertion(environment: &Environment, base: u8) -> u8 {
+    if environment.season() == Season::Winter {
+        base + base / 2
+    } else {
+        base
+    }
+}
+
+/// Whether the weather drives people indoors
+fn is_wet(environment: &Environment) -> bool {
+    matches!(
+        environment.weather(),
+        WeatherKind::Rainy | WeatherKind::Stormy
+    )
+}
+
 /// Actions an actor can perform in a single tick
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ActorAction {
     Idle,
     MoveTo(PageId), // page id
-    Attack(String), // actor id
+    Attack(ActorId),
+    Converse(ActorId), // with another talker on the same page
     Sleep,
     WakeUp,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum ActorKind {
-    GenericNPC,
+impl ActorAction {
+    /// Name of the kind of action, for metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ActorAction::Idle => "idle",
+            ActorAction::MoveTo(_) => "move",
+            ActorAction::Attack(_) => "attack",
+            ActorAction::Converse(_) => "converse",
+            ActorAction::Sleep => "sleep",
+            ActorAction::WakeUp => "wake",
+        }
+    }
+
+    /// Action points spent taking this action
+    pub fn cost(&self) -> u8 {
+        match self {
+            ActorAction::Idle
+            | ActorAction::Converse(_)
+            | ActorAction::Sleep
+            | ActorAction::WakeUp => 1,
+            ActorAction::MoveTo(_) => 2,
+            ActorAction::Attack(_) => 3,
+        }
+    }
 }
 
 /// Track actor's health, fatigue, awake state, targeting, etc
@@ -156,145 +1136,1374 @@ pub struct ActorState {
     pub health: i32,
     pub awake: bool,
     pub fatigue: u8,
-    pub target: Option<String>, // optional id of another actor
+    pub target: Option<ActorId>, // another actor it has in mind
+    #[serde(default)]
+    pub coins: u32,
+    #[serde(default)]
+    pub following: Option<ActorId>, // a player it goes along with
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>, // poisoned, soaked and the like, for a while
+    #[serde(default)]
+    pub hunger: u8, // for wild things that eat; starving at the top
+    #[serde(default)]
+    pub bred_at: Option<u64>, // world tick it last had young
+    #[serde(default)]
+    pub mood: Mood,
+    #[serde(default)]
+    pub mood_ticks: u32, // before the mood wears off
+    #[serde(default)]
+    pub goal: Option<Goal>, // something it has set out to bring about
+    #[serde(default)]
+    pub plan: Vec<ActorAction>, // steps still to take toward it
 }
 
-/// long-term memory, tracking
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ActorMemory {
-    pub last_seen: HashMap<PageId, u64>, // page id -> timestamp
+impl ActorState {
+    /// How the actor looks to someone standing nearby, as `inspect.*` text
+    /// keys; never the numbers behind it
+    pub fn looks(&self) -> Vec<&'static str> {
+        let mut looks = Vec::new();
+        if self.health <= 0 {
+            looks.push("inspect.hurt");
+        }
+        looks.push(match (self.awake, Tiredness::of(self.fatigue)) {
+            (false, _) => "inspect.asleep",
+            (true, Tiredness::Exhausted) => "inspect.exhausted",
+            (true, Tiredness::Tired) => "inspect.tired",
+            (true, Tiredness::Rested) => "inspect.rested",
+        });
+        if self.hunger >= HUNGRY {
+            looks.push("inspect.hungry");
+        }
+        looks.extend(self.mood.key());
+        looks.extend(self.effects.iter().filter_map(|e| e.status.key()));
+        looks
+    }
+}
+
+/// How worn out an actor's fatigue leaves it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tiredness {
+    Rested,
+    Tired,
+    Exhausted,
+}
+
+impl Tiredness {
+    pub fn of(fatigue: u8) -> Self {
+        if fatigue >= SLEEP_FATIGUE * 3 / 4 {
+            Tiredness::Exhausted
+        } else if fatigue >= SLEEP_FATIGUE / 3 {
+            Tiredness::Tired
+        } else {
+            Tiredness::Rested
+        }
+    }
+
+    /// Key under `fatigue` in the translations
+    pub fn key(self) -> &'static str {
+        match self {
+            Tiredness::Rested => "rested",
+            Tiredness::Tired => "tired",
+            Tiredness::Exhausted => "exhausted",
+        }
+    }
 }
 
 /// Map actor id -> Actor for efficient lookup
-pub type ActorMap = HashMap<String, Actor>;
+pub type ActorMap = HashMap<ActorId, Actor>;
+
+/// Queue of upcoming actor turns, keyed by the world tick they are due on
+#[derive(Default)]
+pub struct TickScheduler {
+    due: BTreeMap<u64, Vec<ActorId>>, // tick -> actor ids
+}
+
+impl TickScheduler {
+    /// Queue actor `id` to act on world tick `at`
+    pub fn schedule(&mut self, id: &ActorId, at: u64) {
+        self.due.entry(at).or_default().push(id.clone());
+    }
+
+    /// Move every actor due on or before tick `now` into `into`
+    pub fn take_due(&mut self, now: u64, into: &mut Vec<ActorId>) {
+        while let Some(entry) = self.due.first_entry()
+            && *entry.key() <= now
+        {
+            into.extend(entry.remove());
+        }
+    }
+}
+
+/// Which actors are on each page, kept up to date as they spawn, move and
+/// leave so nobody has to sort the whole world by page to find neighbours.
+/// Each page's ids are kept in order, so the same world always goes
+/// through a page's actors in the same order.
+#[derive(Default)]
+pub struct PageIndex(HashMap<PageId, Vec<ActorId>>);
+
+impl PageIndex {
+    fn insert(&mut self, page: &PageId, id: &ActorId) {
+        let ids = self.0.entry(page.clone()).or_default();
+        if let Err(at) = ids.binary_search(id) {
+            ids.insert(at, id.clone());
+        }
+    }
+
+    fn remove(&mut self, page: &PageId, id: &ActorId) {
+        if let Some(ids) = self.0.get_mut(page) {
+            if let Ok(at) = ids.binary_search(id) {
+                ids.remove(at);
+            }
+            if ids.is_empty() {
+                self.0.remove(page);
+            }
+        }
+    }
+
+    fn moved(&mut self, id: &ActorId, from: &PageId, to: &PageId) {
+        if from != to {
+            self.remove(from, id);
+            self.insert(to, id);
+        }
+    }
+
+    /// Ids of the actors on `page`, in order
+    pub fn on(&self, page: &PageId) -> &[ActorId] {
+        self.0.get(page).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// Buffers a tick fills and empties again, kept so the next tick can reuse
+/// their allocations
+#[derive(Default)]
+struct TickScratch {
+    chosen: Vec<ActorId>,
+    plans: Vec<(ActorId, Vec<ActorAction>)>,
+    decided: Vec<(ActorId, Duration)>,
+    environments: HashMap<PageId, Environment>, // fetched once per page per tick
+}
+
+/// Fewest actors a thread decides for at a time; handing out smaller batches
+/// costs more than the deciding saves
+const DECISION_BATCH: usize = 64;
+
+/// What one actor decided to do this tick, and how long it took to decide
+struct Decision {
+    id: ActorId,
+    plan: Vec<ActorAction>,
+    effects: Vec<ScriptEffect>, // its script's asks of the wider world
+    took: Duration,
+}
+
+/// Everyone decides over the same snapshot, so several actors can set on one
+/// target at once. The first by id gets the fight; the others let it be,
+/// unless they run in its pack and hunt it together.
+fn settle_attacks(plans: &mut [(ActorId, Vec<ActorAction>)], packs: &Packs) {
+    plans.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut claimed: HashMap<ActorId, ActorId> = HashMap::new(); // target -> attacker
+    for (id, plan) in plans.iter_mut() {
+        plan.retain(|action| match action {
+            ActorAction::Attack(target) => {
+                let attacker = claimed.entry(target.clone()).or_insert_with(|| id.clone());
+                let joins = attacker == id || packs.together(attacker, id);
+                if !joins {
+                    debug!(%id, %target, %attacker, "Target already set upon, holding off.");
+                }
+                joins
+            }
+            _ => true,
+        });
+    }
+}
+
+/// `page`'s environment, looked up once a tick however many actors are there
+fn environment_on<'a>(
+    cache: &'a mut HashMap<PageId, Environment>,
+    source: &TickSource,
+    page: &PageId,
+) -> Result<&'a Environment, AppError> {
+    if !cache.contains_key(page) {
+        let TickSource::Live { environments, .. } = source else {
+            return Err(AppError::EnvironmentError(format!(
+                "No environment on {page} was recorded"
+            )));
+        };
+        cache.insert(page.clone(), environments.environment_for(page)?);
+    }
+    Ok(&cache[page])
+}
+
+/// What a tick is played from: the world as it is, or a recording of it
+enum TickSource<'a> {
+    Live {
+        environments: &'a EnvironmentManager,
+        page_flags: &'a Arc<PageFlags>,
+    },
+    Replay(TickRecord), // plans, spawns and environments are taken from it
+}
+
+/// How many of the slowest deciders a slow tick warning names
+const SLOWEST_REPORTED: usize = 3;
+
+/// Where the time went in one world tick
+#[derive(Clone, Debug, Default)]
+pub struct TickTimings {
+    pub tick: u64,
+    pub acted: usize,
+    pub planning: Duration, // actors deciding what to do
+    pub acting: Duration,   // taking queued actions
+    pub enduring: Duration, // the elements wearing on everyone
+    pub total: Duration,
+}
 
 /// Manage all actors in the world and their tick scheduling
 pub struct ActorManager {
-    pub actors: ActorMap, // actor_id -> Actor
+    pub actors: ActorMap,                           // actor_id -> Actor
+    definitions: HashMap<ActorId, ActorDefinition>, // as last loaded, for resets
+    players: HashMap<ActorId, u64>,                 // player actor id -> tick last seen
+    regions: Arc<Regions>,
+    scheduler: TickScheduler,
+    tick: u64,       // world ticks elapsed
+    generation: u64, // times the world has been reset since startup
+    bus: EventBus,
+    scripts: ScriptHost,
+    actions_taken: HashMap<&'static str, u64>, // by kind, since startup
+    tick_budget: Duration,                     // longer ticks are warned about
+    last_tick: TickTimings,
+    decision_times: HashMap<ActorId, Duration>, // actor id -> time its last decision took
+    spawner: Spawner,                           // keeps the wilds populated
+    schedule: Schedule,                         // markets, storms and such at set hours
+    brought_out: HashMap<ActorId, ActorId>,     // turned up for a player to run into -> whose
+    pastures: Pastures,                         // how far each page is grazed down
+    packs: Packs,                               // who runs with whom, gathered each tick
+    stimuli: Stimuli,                           // noises and scents about, fading
+    by_page: PageIndex,
+    scratch: TickScratch,
+    rng: StdRng,                // this tick's rolls, seeded afresh each tick
+    recorder: Option<Recorder>, // journals every tick, while recording
 }
 
 impl ActorManager {
-    pub fn new() -> Self {
-        let mut actors = HashMap::new();
-        actors.insert(
-            "prof".to_string(),
-            Actor {
-                id: "prof".to_string(),
-                name: "Professor Tree".to_string(),
-                location: PageId::from("small-town"),
-                state: ActorState {
-                    health: 10,
-                    awake: true,
-                    fatigue: 0,
-                    target: None,
-                },
-                flags: vec![ActorFlag::Organic, ActorFlag::CanSpeak],
-            },
-        );
-        actors.insert(
-            "joey".to_string(),
-            Actor {
-                id: "joey".to_string(),
-                name: "Young Joey".to_string(),
-                location: PageId::from("route-1"),
-                state: ActorState {
-                    health: 8,
-                    awake: true,
-                    fatigue: 0,
-                    target: None,
-                },
-                flags: vec![ActorFlag::Organic, ActorFlag::CanSpeak],
-            },
-        );
-        actors.insert(
-            "sneezer".to_string(),
-            Actor {
-                id: "sneezer".to_string(),
-                name: "Sneezer".to_string(),
-                location: PageId::from("route-1"),
-                state: ActorState {
-                    health: 2,
-                    awake: true,
-                    fatigue: 0,
-                    target: None,
-                },
-                flags: vec![ActorFlag::Organic],
-            },
-        );
-        actors.insert(
-            "susan".to_string(),
-            Actor {
-                id: "susan".to_string(),
-                name: "Susan B. Anthony".to_string(),
-                location: PageId::from("green-city"),
-                state: ActorState {
-                    health: 99,
-                    awake: true,
-                    fatigue: 1,
-                    target: None,
-                },
-                flags: vec![ActorFlag::Organic, ActorFlag::CanSpeak],
-            },
-        );
+    pub fn new(
+        bus: EventBus,
+        definitions: Vec<ActorDefinition>,
+        regions: Arc<Regions>,
+        scripts: ScriptHost,
+    ) -> Self {
+        let mut manager = ActorManager {
+            actors: HashMap::new(),
+            definitions: HashMap::new(),
+            players: HashMap::new(),
+            regions,
+            scheduler: TickScheduler::default(),
+            tick: 0,
+            generation: 0,
+            bus,
+            scripts,
+            actions_taken: HashMap::new(),
+            tick_budget: DEFAULT_TICK_BUDGET,
+            last_tick: TickTimings::default(),
+            decision_times: HashMap::new(),
+            spawner: Spawner::default(),
+            schedule: Schedule::default(),
+            brought_out: HashMap::new(),
+            pastures: Pastures::default(),
+            packs: Packs::default(),
+            stimuli: Stimuli::default(),
+            by_page: PageIndex::default(),
+            scratch: TickScratch::default(),
+            rng: StdRng::from_rng(&mut rand::rng()),
+            recorder: None,
+        };
+        manager.apply_definitions(definitions);
+        manager
+    }
 
-        ActorManager { actors }
+    /// Warn about ticks that take longer than `budget`
+    pub fn with_tick_budget(mut self, budget: Duration) -> Self {
+        self.tick_budget = budget;
+        self
     }
 
-    /// Advance world, updating only 1-2 randomly selected actors
-    // TODO: sequential ticking
-    pub fn tick_some(&mut self, world_time: &WorldTime, page_graph: &PageGraph) {
-        use rand::seq::IteratorRandom;
-        let num_to_tick: usize = 1 + (self.actors.len() / 10).max(1); // customizable
+    /// Spawn actors by `spawner`'s rules as the world ticks
+    pub fn with_spawner(mut self, spawner: Spawner) -> Self {
+        self.spawner = spawner;
+        self
+    }
+
+    /// Fire `schedule`'s events as the world ticks
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
 
-        let mut rng = rand::rng();
-        let chosen: Vec<String> = self
+    /// Record every tick of this world with `recorder`, for replaying later
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// A new world of these actors as defined, publishing on `bus`: the same
+    /// definitions, regions, scripts, spawn rules and schedule, but none of what has
+    /// happened since and no players
+    pub fn instance(&self, bus: EventBus) -> ActorManager {
+        ActorManager::new(
+            bus,
+            self.definitions.values().cloned().collect(),
+            self.regions.clone(),
+            self.scripts.clone(),
+        )
+        .with_tick_budget(self.tick_budget)
+        .with_spawner(self.spawner.fresh())
+        .with_schedule(self.schedule.fresh())
+    }
+
+    /// Merge a fresh set of definitions into the live world: existing actors keep
+    /// their dynamic state but take the new settings, new ones spawn, and actors
+    /// whose definitions are gone despawn.
+    pub fn apply_definitions(&mut self, definitions: Vec<ActorDefinition>) {
+        let defined: HashSet<&str> = definitions.iter().map(|d| d.id.as_str()).collect();
+        let removed: Vec<ActorId> = self
             .actors
             .keys()
-            .choose_multiple(&mut rng, num_to_tick)
-            .into_iter()
+            .filter(|id| {
+                !defined.contains(id.as_str())
+                    && !self.players.contains_key(*id)
+                    && !self.spawner.owns(id)
+                    && !self.schedule.owns(id)
+                    && !self.brought_out.contains_key(*id)
+            })
+            .cloned()
+            .collect();
+        for id in removed {
+            if let Some(actor) = self.actors.remove(&id) {
+                info!(%id, "Despawning actor, definition removed.");
+                self.by_page.remove(&actor.location, &id);
+                self.bus.publish(WorldEvent::ActorDespawned {
+                    actor: id,
+                    page: actor.location,
+                });
+            }
+        }
+
+        self.definitions = definitions
+            .iter()
+            .map(|d| (d.id.clone(), d.clone()))
+            .collect();
+        for definition in definitions {
+            match self.actors.get_mut(&definition.id) {
+                Some(actor) => actor.redefine(&definition),
+                None => self.spawn(Actor::from_definition(definition)),
+            }
+        }
+    }
+
+    /// Spawn a new actor from a definition that isn't in the definitions file.
+    /// It lasts until the file is next reloaded.
+    pub fn add_actor(&mut self, definition: ActorDefinition) -> Result<(), AppError> {
+        if self.actors.contains_key(&definition.id) {
+            return Err(AppError::Conflict(format!(
+                "Actor '{}' already exists",
+                definition.id
+            )));
+        }
+        info!(%definition.id, "Actor added.");
+        self.definitions
+            .insert(definition.id.clone(), definition.clone());
+        self.spawn(Actor::from_definition(definition));
+        Ok(())
+    }
+
+    /// Note that a player is on `page` now, adding their actor if they're new
+    pub fn sync_player(&mut self, id: &ActorId, name: &str, page: &PageId) {
+        self.players.insert(id.clone(), self.tick);
+        match self.actors.get_mut(id) {
+            Some(actor) => {
+                let from = std::mem::replace(&mut actor.location, page.clone());
+                self.by_page.moved(id, &from, page);
+                actor.name = name.to_string();
+                if from != *page {
+                    self.bring_companions(id, page);
+                }
+            }
+            None => {
+                debug!(%id, %page, "Player enters the world.");
+                self.bus.publish(WorldEvent::ActorSpawned {
+                    actor: id.clone(),
+                    page: page.clone(),
+                });
+                self.by_page.insert(page, id);
+                self.actors
+                    .insert(id.clone(), Actor::player(id, name, page));
+            }
+        }
+    }
+
+    /// Actors going along with the player `leader`
+    pub fn companions_of<'a>(&'a self, leader: &'a ActorId) -> impl Iterator<Item = &'a Actor> {
+        self.actors
+            .values()
+            .filter(move |a| a.state.following.as_ref() == Some(leader))
+    }
+
+    /// Companions of `leader` who are up and about catch up with them on
+    /// `page` at once, giving up whatever else they had planned
+    fn bring_companions(&mut self, leader: &ActorId, page: &PageId) {
+        let coming: Vec<ActorId> = self
+            .companions_of(leader)
+            .filter(|a| a.state.awake && !a.paused && a.location != *page)
+            .map(|a| a.id.clone())
+            .collect();
+        for id in coming {
+            let Some(companion) = self.actors.get_mut(&id) else {
+                continue;
+            };
+            companion.queue.clear();
+            companion.travel = None;
+            let event = companion.arrive(page.clone());
+            if let WorldEvent::ActorMoved { from, to, .. } = &event {
+                self.by_page.moved(&id, from, to);
+            }
+            debug!(%id, %leader, %page, "Keeps up with their companion.");
+            self.bus.publish(event);
+        }
+    }
+
+    /// Remove actors of players who haven't been seen for a while
+    fn expire_players(&mut self) {
+        let now = self.tick;
+        let idle: Vec<ActorId> = self
+            .players
+            .iter()
+            .filter(|(_, seen)| now - **seen > PLAYER_IDLE_TICKS)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in idle {
+            self.players.remove(&id);
+            for companion in self.actors.values_mut() {
+                if companion.state.following.as_ref() == Some(&id) {
+                    companion.state.following = None; // left to their own devices
+                }
+            }
+            // whatever turned up for them goes off again
+            let met: Vec<ActorId> = self
+                .brought_out
+                .iter()
+                .filter(|(_, player)| **player == id)
+                .map(|(met, _)| met.clone())
+                .collect();
+            for met in met {
+                self.slip_away(&met);
+            }
+            if let Some(actor) = self.actors.remove(&id) {
+                debug!(%id, "Player left the world.");
+                self.by_page.remove(&actor.location, &id);
+                self.bus.publish(WorldEvent::ActorDespawned {
+                    actor: id,
+                    page: actor.location,
+                });
+            }
+        }
+    }
+
+    /// Actions taken by kind since startup, sorted by kind
+    pub fn actions_taken(&self) -> Vec<(&'static str, u64)> {
+        let mut taken: Vec<_> = self.actions_taken.iter().map(|(k, v)| (*k, *v)).collect();
+        taken.sort();
+        taken
+    }
+
+    /// Timings of the most recent tick
+    pub fn last_tick(&self) -> &TickTimings {
+        &self.last_tick
+    }
+
+    /// How long each actor's most recent decision took, slowest first
+    pub fn decision_times(&self) -> Vec<(&str, Duration)> {
+        let mut times: Vec<_> = self
+            .decision_times
+            .iter()
+            .map(|(id, time)| (id.as_str(), *time))
+            .collect();
+        times.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        times
+    }
+
+    /// Players seen in the world lately
+    pub fn player_count(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Put an actor straight onto `page`, abandoning whatever it was doing
+    pub fn teleport(&mut self, id: &str, page: &PageId) -> Result<(), AppError> {
+        let actor = self
+            .actors
+            .get_mut(id)
+            .ok_or_else(|| AppError::ActorNotFound(id.to_string()))?;
+        info!(%id, %page, "Actor teleported.");
+        actor.queue.clear();
+        actor.travel = None;
+        let from = std::mem::replace(&mut actor.location, page.clone());
+        self.by_page.moved(&actor.id, &from, page);
+        self.bus.publish(WorldEvent::ActorMoved {
+            actor: actor.id.clone(),
+            from,
+            to: page.clone(),
+        });
+        Ok(())
+    }
+
+    /// Take an actor out of the world for good, or until the definitions file
+    /// next brings it back. Players can't be removed; they'd only walk back in.
+    pub fn remove_actor(&mut self, id: &str) -> Result<Actor, AppError> {
+        if self.players.contains_key(id) {
+            return Err(AppError::OtherError(format!("Actor '{id}' is a player")));
+        }
+        let actor = self
+            .actors
+            .remove(id)
+            .ok_or_else(|| AppError::ActorNotFound(id.to_string()))?;
+        info!(%id, "Actor removed.");
+        self.definitions.remove(id);
+        self.by_page.remove(&actor.location, &actor.id);
+        self.bus.publish(WorldEvent::ActorDespawned {
+            actor: actor.id.clone(),
+            page: actor.location.clone(),
+        });
+        Ok(actor)
+    }
+
+    /// Respawn an actor fresh from its definition, at its starting page
+    pub fn reset(&mut self, id: &str) -> Result<(), AppError> {
+        let definition = self
+            .definitions
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::ActorNotFound(id.to_string()))?;
+        if let Some(actor) = self.actors.remove(id) {
+            self.by_page.remove(&actor.location, &actor.id);
+            self.bus.publish(WorldEvent::ActorDespawned {
+                actor: actor.id,
+                page: actor.location,
+            });
+        }
+        info!(%id, "Actor reset.");
+        self.spawn(Actor::from_definition(definition));
+        Ok(())
+    }
+
+    /// Put the world back as the definitions have it: every actor but the
+    /// players respawned fresh at its starting page, and everything passing
+    /// forgotten, from what the spawner brought and bred to grazing, noises
+    /// and scents. Returns the world's new generation.
+    pub fn reset_world(&mut self) -> u64 {
+        let gone: Vec<ActorId> = self
+            .actors
+            .keys()
+            .filter(|id| !self.players.contains_key(*id))
             .cloned()
             .collect();
+        for id in gone {
+            self.despawn(&id);
+        }
+        self.scheduler = TickScheduler::default();
+        self.spawner = self.spawner.fresh();
+        self.schedule = self.schedule.fresh();
+        self.brought_out.clear();
+        self.pastures = Pastures::default();
+        self.packs = Packs::default();
+        self.stimuli = Stimuli::default();
+        self.decision_times.clear();
+        let mut definitions: Vec<ActorDefinition> = self.definitions.values().cloned().collect();
+        definitions.sort_by(|a, b| a.id.cmp(&b.id));
+        for definition in definitions {
+            self.spawn(Actor::from_definition(definition));
+        }
+        self.generation += 1;
+        info!(generation = self.generation, "World reset.");
+        self.bus.publish(WorldEvent::WorldReset {
+            generation: self.generation,
+        });
+        // a recording carries on from the reset world, as from a restored one
+        let reset = self.recorder.is_some().then(|| self.snapshot());
+        if let (Some(recorder), Some(snapshot)) = (&mut self.recorder, reset) {
+            recorder.snapshot(snapshot);
+        }
+        self.generation
+    }
+
+    /// Times the world has been reset since startup
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// World ticks elapsed
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
 
-        // location map for filtering
-        let mut location_map: HashMap<&PageId, Vec<&str>> = HashMap::new();
-        for (id, actor) in self.actors.iter() {
-            location_map
-                .entry(&actor.location)
-                .or_default()
-                .push(id.as_str());
+    /// Move everyone off a page that no longer exists, and drop plans that led there
+    pub fn page_removed(&mut self, page: &PageId, fallback: &PageId) {
+        for actor in self.actors.values_mut() {
+            if actor.travel.as_ref().is_some_and(|t| t.to == *page) {
+                actor.travel = None; // the road ends nowhere now; turn back
+            }
+            if actor.location == *page {
+                info!(%actor.id, %page, %fallback, "Page removed, actor relocated.");
+                self.by_page.moved(&actor.id, page, fallback);
+                actor.location = fallback.clone();
+                actor.queue.clear();
+                actor.travel = None;
+                self.bus.publish(WorldEvent::ActorMoved {
+                    actor: actor.id.clone(),
+                    from: page.clone(),
+                    to: fallback.clone(),
+                });
+            }
         }
+        self.forget_plans_through(page);
+    }
 
-        // Gather actions just for chosen actors
-        let mut events = Vec::new();
-        for id in &chosen {
-            if let Some(actor) = self.actors.get(id) {
-                let empty = Vec::<&str>::new();
-                let local_ids = location_map.get(&actor.location).unwrap_or(&empty);
-                let locals: Vec<&Actor> = local_ids
+    /// Clear the queue of any actor planning to move to `page`, so it plans afresh
+    pub fn forget_plans_through(&mut self, page: &PageId) {
+        for actor in self.actors.values_mut() {
+            if actor.planned_path().any(|p| p == page) {
+                debug!(%actor.id, %page, "Way changed, dropping plan.");
+                actor.queue.clear();
+            }
+        }
+    }
+
+    /// Bites of forage left on `page`, and what sort; None if nothing grows there
+    pub fn forage_left(&self, page: &Page) -> Option<(Forage, u32)> {
+        self.pastures.left(page)
+    }
+
+    /// Take a dead actor out of the world, and whatever it carried with it
+    fn perish(&mut self, id: &ActorId) -> Option<Actor> {
+        let actor = self.actors.remove(id)?;
+        self.by_page.remove(&actor.location, id);
+        Some(actor)
+    }
+
+    /// If `blow` brought a herbivore down to a predator, the predator eats
+    /// it: gone from the world, and the predator fed. The event of it, if so.
+    fn devoured_by(&mut self, blow: &WorldEvent) -> Option<WorldEvent> {
+        let WorldEvent::ActorWounded {
+            actor,
+            attacker,
+            page,
+            health,
+            ..
+        } = blow
+        else {
+            return None;
+        };
+        let eaten = *health <= 0
+            && self.actors.get(actor).is_some_and(|a| {
+                a.has_flag(ActorFlag::Herbivore) && !a.has_flag(ActorFlag::Player)
+            });
+        let predator = self
+            .actors
+            .get_mut(attacker)
+            .filter(|a| eaten && a.has_flag(ActorFlag::Predatory))?;
+        ecology::devour(predator);
+        packs::share_kill(&self.packs, &mut self.actors, attacker);
+        self.perish(actor)?;
+        info!(%actor, by = %attacker, "Devoured.");
+        Some(WorldEvent::ActorDevoured {
+            actor: actor.clone(),
+            by: attacker.clone(),
+            page: page.clone(),
+        })
+    }
+
+    /// Take an actor out of the world, as if it had gone off somewhere
+    fn despawn(&mut self, id: &ActorId) -> Option<Actor> {
+        let actor = self.actors.remove(id)?;
+        self.by_page.remove(&actor.location, id);
+        self.bus.publish(WorldEvent::ActorDespawned {
+            actor: id.clone(),
+            page: actor.location.clone(),
+        });
+        Some(actor)
+    }
+
+    /// Bring `actor` into the world for `player` to run into, until they
+    /// let it slip away again or leave the world. None if its id is taken.
+    pub fn bring_out(&mut self, actor: Actor, player: &ActorId) -> Option<ActorId> {
+        if self.actors.contains_key(&actor.id) {
+            warn!(%actor.id, "Not brought out; the id is taken.");
+            return None;
+        }
+        let id = actor.id.clone();
+        self.brought_out.insert(id.clone(), player.clone());
+        self.spawn(actor);
+        Some(id)
+    }
+
+    /// Let an actor brought out for a player go off again, if it's still
+    /// about; anyone else stays where they are
+    pub fn slip_away(&mut self, id: &ActorId) {
+        if self.brought_out.remove(id).is_some() && self.despawn(id).is_some() {
+            debug!(%id, "Slips away.");
+        }
+    }
+
+    /// Add a new actor to the world and book its first turn
+    fn spawn(&mut self, actor: Actor) {
+        // spread first turns over each actor's period so slow actors don't all act at once
+        let offset = self.rng.random_range(0..actor.tick_rate.max(1)) as u64;
+        self.scheduler.schedule(&actor.id, self.tick + 1 + offset);
+        debug!(%actor.id, %actor.location, "Spawning actor.");
+        self.bus.publish(WorldEvent::ActorSpawned {
+            actor: actor.id.clone(),
+            page: actor.location.clone(),
+        });
+        self.by_page.insert(&actor.location, &actor.id);
+        self.actors.insert(actor.id.clone(), actor);
+    }
+
+    /// Drop the actions queued by actor `id` that the world has overtaken since
+    /// it planned them: attacks on someone who has left, set off or gone down,
+    /// and moves along ways that are gone. Checked as each turn comes, so an
+    /// earlier actor's turn this tick counts.
+    fn drop_stale_actions(&mut self, id: &ActorId, page_graph: &PageGraph) {
+        let Some(actor) = self.actors.get(id) else {
+            return;
+        };
+        // where the actor will be by then; the end of the road, if it's on one
+        let mut at = actor.travel.as_ref().map_or(&actor.location, |t| &t.to);
+        let mut stale = Vec::new();
+        for (n, action) in actor.queue.iter().enumerate() {
+            let possible = match action {
+                ActorAction::Attack(target) => self.actors.get(target).is_some_and(|target| {
+                    target.location == *at && target.travel.is_none() && target.state.health > 0
+                }),
+                ActorAction::MoveTo(to) => page_graph
+                    .get(at)
+                    .is_some_and(|page| page.connections.iter().any(|c| c.target == *to)),
+                _ => true,
+            };
+            if !possible {
+                debug!(%id, ?action, "Action overtaken by events, dropped.");
+                stale.push(n);
+            } else if let ActorAction::MoveTo(to) = action {
+                at = to;
+            }
+        }
+        if let Some(actor) = self.actors.get_mut(id) {
+            for n in stale.into_iter().rev() {
+                actor.queue.remove(n);
+            }
+        }
+    }
+
+    /// Actors on `page`, in no particular order
+    pub fn actors_on(&self, page: &PageId) -> impl Iterator<Item = &Actor> {
+        self.by_page
+            .on(page)
+            .iter()
+            .filter_map(|id| self.actors.get(id))
+    }
+
+    /// Chosen actors with nothing left queued decide what to do next, into
+    /// `scratch`. Deciding only reads the world, so they all decide at once,
+    /// across threads. Returns what their scripts asked of the wider world.
+    fn plan(
+        &self,
+        scratch: &mut TickScratch,
+        world_time: &WorldTime,
+        page_graph: &PageGraph,
+        environments: &EnvironmentManager,
+        page_flags: &Arc<PageFlags>,
+    ) -> Vec<ScriptEffect> {
+        let player_pages: HashSet<&PageId> = self
+            .players
+            .keys()
+            .filter_map(|id| self.actors.get(id))
+            .map(|a| &a.location)
+            .collect();
+
+        let planners: Vec<&Actor> = scratch
+            .chosen
+            .iter()
+            .filter_map(|id| self.actors.get(id))
+            .filter(|actor| actor.queue.is_empty() && actor.travel.is_none())
+            .collect();
+        // fetch environments up front; the deciders share them read-only
+        for actor in &planners {
+            if scratch.environments.contains_key(&actor.location) {
+                continue;
+            }
+            match environments.environment_for(&actor.location) {
+                Ok(environment) => {
+                    scratch
+                        .environments
+                        .insert(actor.location.clone(), environment);
+                }
+                Err(e) => warn!(%actor.id, "No environment to plan with: {e}"),
+            }
+        }
+        // and the weather on the pages round about, for them to make out
+        for actor in &planners {
+            let exits = page_graph
+                .get(&actor.location)
+                .into_iter()
+                .flat_map(|p| &p.connections);
+            for conn in exits {
+                if !scratch.environments.contains_key(&conn.target)
+                    && let Ok(environment) = environments.environment_for(&conn.target)
+                {
+                    scratch
+                        .environments
+                        .insert(conn.target.clone(), environment);
+                }
+            }
+        }
+        let surroundings = Surroundings {
+            page_graph,
+            actors: &self.actors,
+            by_page: &self.by_page,
+            environments: &scratch.environments,
+            stimuli: &self.stimuli,
+        };
+        let planned_environments = &scratch.environments;
+        let decisions: Vec<Decision> = planners
+                .par_iter()
+                .with_min_len(DECISION_BATCH)
+                .map(|actor| {
+                    let environment = planned_environments.get(&actor.location)?;
+                    let perception = surroundings.perceive(actor, SENSE_RANGE);
+                    let deciding = Instant::now();
+                    let mut plan = Vec::new();
+                    let mut effects = Vec::new();
+                    if let Some(script) = &actor.script {
+                        let ctx =
+                            ScriptContext::new(&actor.location, world_time.hour, page_flags.clone())
+                                .acting(&actor.id, page_graph);
+                        (plan, effects) = self.scripts.behave(script, ctx);
+                    }
+                    if plan.is_empty() {
+                        plan = actor.decide(
+                            world_time,
+                            environment,
+                            &perception,
+                            &player_pages,
+                            page_graph,
+                            &self.regions,
+                        );
+                    }
+                    let took = deciding.elapsed();
+                    trace!(%actor.id, took_us = took.as_micros() as u64, planned = plan.len(), "Decided.");
+                    Some(Decision {
+                        id: actor.id.clone(),
+                        plan,
+                        effects,
+                        took,
+                    })
+                })
+                .flatten()
+                .collect();
+        let mut scripted = Vec::new();
+        for decision in decisions {
+            scripted.extend(decision.effects);
+            scratch.decided.push((decision.id.clone(), decision.took));
+            scratch.plans.push((decision.id, decision.plan));
+        }
+        scripted
+    }
+
+    /// Speakers awake on the page where `event` happened take it in as a rumor
+    pub fn witness(&mut self, event: &WorldEvent) {
+        let name = |id: &str| {
+            self.actors
+                .get(id)
+                .map_or_else(|| id.to_string(), |a| a.name.clone())
+        };
+        let Some(rumor) = Rumor::witnessed(event, name) else {
+            return;
+        };
+        for id in self.by_page.on(&rumor.page) {
+            if let Some(onlooker) = self.actors.get_mut(id)
+                && onlooker.state.awake
+                && onlooker.has_flag(ActorFlag::CanSpeak)
+                && !onlooker.has_flag(ActorFlag::Player)
+            {
+                rumors::learn(&mut onlooker.rumors, rumor.clone());
+            }
+        }
+    }
+
+    /// Stir the feelings of everyone on the page where `event` happened
+    pub fn feel(&mut self, event: &WorldEvent) {
+        let Some(page) = mood::stirred_at(event) else {
+            return;
+        };
+        for id in self.by_page.on(page) {
+            if let Some(actor) = self.actors.get_mut(id)
+                && !actor.has_flag(ActorFlag::Player)
+                && let Some(felt) = mood::felt(actor, event)
+            {
+                trace!(%actor.id, mood = ?felt, "Feels it.");
+                mood::feel(&mut actor.state, felt);
+            }
+        }
+    }
+
+    /// Leave the noise or smell of `event` about, for actors nearby to notice
+    pub fn sense(&mut self, event: &WorldEvent, page_graph: &PageGraph) {
+        if let Some((kind, page, strength)) = stimuli::stirred_by(event) {
+            trace!(%page, ?kind, strength, "Stirs things up.");
+            self.stimuli.emit(kind, page, strength, page_graph);
+        }
+    }
+
+    /// What can be heard and smelled on `page`
+    pub fn stimuli_on(&self, page: &PageId) -> &[Stimulus] {
+        self.stimuli.on(page)
+    }
+
+    /// `speaker` tells `listener` the freshest rumor it knows, perhaps not
+    /// quite as it heard it
+    fn pass_rumor(&mut self, speaker: &ActorId, listener: &ActorId, page_graph: &PageGraph) {
+        let told = self
+            .actors
+            .get(speaker)
+            .and_then(|a| rumors::freshest(&a.rumors))
+            .and_then(|rumor| rumor.retold(&mut self.rng, page_graph));
+        if let (Some(rumor), Some(listener)) = (told, self.actors.get_mut(listener)) {
+            trace!(%speaker, %listener.id, hops = rumor.hops, "Passes on a rumor.");
+            rumors::learn(&mut listener.rumors, rumor);
+        }
+    }
+
+    /// Every actor and the schedule, for a recording or a save
+    pub fn snapshot(&self) -> Snapshot {
+        let mut actors: Vec<Actor> = self.actors.values().cloned().collect();
+        actors.sort_by(|a, b| a.id.cmp(&b.id));
+        Snapshot {
+            tick: self.tick,
+            actors,
+            schedule: self
+                .scheduler
+                .due
+                .iter()
+                .map(|(tick, ids)| (*tick, ids.clone()))
+                .collect(),
+            players: self
+                .players
+                .iter()
+                .map(|(id, seen)| (id.clone(), *seen))
+                .collect(),
+            grazed: self.pastures.grazed(),
+            stimuli: self.stimuli.all(),
+        }
+    }
+
+    /// Put the world back as `snapshot` has it, forgetting what it held before
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.tick = snapshot.tick;
+        self.actors.clear();
+        self.by_page = PageIndex::default();
+        for actor in snapshot.actors {
+            self.by_page.insert(&actor.location, &actor.id);
+            self.actors.insert(actor.id.clone(), actor);
+        }
+        self.scheduler = TickScheduler {
+            due: snapshot.schedule.into_iter().collect(),
+        };
+        self.players = snapshot.players.into_iter().collect();
+        self.pastures = Pastures::restore(snapshot.grazed);
+        self.stimuli = Stimuli::restore(snapshot.stimuli);
+        // a recording carries on from the restored world
+        let restored = self.recorder.is_some().then(|| self.snapshot());
+        if let (Some(recorder), Some(snapshot)) = (&mut self.recorder, restored) {
+            recorder.snapshot(snapshot);
+        }
+    }
+
+    /// Advance the world by one tick, updating only the actors whose turn is due.
+    /// Each actor is rescheduled `tick_rate` ticks ahead once it has acted.
+    /// Returns what behavior scripts asked of the world beyond their own actors.
+    pub fn tick_some(
+        &mut self,
+        world_time: &WorldTime,
+        page_graph: &PageGraph,
+        environments: &EnvironmentManager,
+        page_flags: &Arc<PageFlags>,
+    ) -> Vec<ScriptEffect> {
+        let source = TickSource::Live {
+            environments,
+            page_flags,
+        };
+        self.run_tick(world_time, page_graph, source)
+    }
+
+    /// Play a recorded tick over again, with its rolls, plans, spawns and
+    /// weather as they were, then the moves its scripts asked for
+    pub fn replay_tick(&mut self, mut record: TickRecord, page_graph: &PageGraph) {
+        let world_time = record.world_time.clone();
+        let teleports = std::mem::take(&mut record.teleports);
+        self.run_tick(&world_time, page_graph, TickSource::Replay(record));
+        for (actor, to) in teleports {
+            if page_graph.contains_key(&to)
+                && let Err(e) = self.teleport(actor.as_str(), &to)
+            {
+                warn!("Recorded teleport not replayed: {e}");
+            }
+        }
+    }
+
+    fn run_tick(
+        &mut self,
+        world_time: &WorldTime,
+        page_graph: &PageGraph,
+        mut source: TickSource,
+    ) -> Vec<ScriptEffect> {
+        let started = Instant::now();
+        let mut scripted = Vec::new();
+        if self
+            .recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.snapshot_due(self.tick))
+        {
+            let snapshot = self.snapshot();
+            if let Some(recorder) = &mut self.recorder {
+                recorder.snapshot(snapshot);
+            }
+        }
+        let seed = match &source {
+            TickSource::Live { .. } => rand::rng().random(),
+            TickSource::Replay(record) => record.seed,
+        };
+        self.rng = StdRng::seed_from_u64(seed);
+        self.tick += 1;
+        let _tick_span = info_span!("tick", tick = self.tick).entered();
+        // what went into this tick, while recording
+        let mut record = self.recorder.is_some().then(|| TickRecord {
+            tick: self.tick,
+            seed,
+            world_time: world_time.clone(),
+            players: self
+                .players
+                .iter()
+                .filter_map(|(id, seen)| Some((self.actors.get(id)?.clone(), *seen)))
+                .collect(),
+            ..TickRecord::default()
+        });
+        if let TickSource::Replay(replayed) = &mut source {
+            for (player, seen) in std::mem::take(&mut replayed.players) {
+                self.players.insert(player.id.clone(), seen);
+                if let Some(old) = self.actors.get(&player.id) {
+                    self.by_page
+                        .moved(&player.id, &old.location, &player.location);
+                } else {
+                    self.by_page.insert(&player.location, &player.id);
+                }
+                self.actors.insert(player.id.clone(), player);
+            }
+        }
+        self.expire_players();
+        // those brought by scheduled events that are over go on their way
+        let departed = match &mut source {
+            TickSource::Live { .. } => self.schedule.over(world_time, &self.actors),
+            TickSource::Replay(replayed) => std::mem::take(&mut replayed.departed),
+        };
+        for id in &departed {
+            if self.despawn(id).is_some() {
+                debug!(%id, "Leaves, the event over.");
+            }
+        }
+        let born = match &mut source {
+            TickSource::Live { .. } => {
+                let mut born = self.spawner.due(
+                    self.tick,
+                    world_time,
+                    &self.actors,
+                    page_graph,
+                    &self.regions,
+                );
+                let (brought, effects) = self.schedule.due(world_time, &self.actors, page_graph);
+                born.extend(brought);
+                scripted.extend(effects);
+                born
+            }
+            TickSource::Replay(replayed) => std::mem::take(&mut replayed.spawned),
+        };
+        if let Some(record) = &mut record {
+            record.spawned = born.clone();
+            record.departed = departed;
+        }
+        for actor in born {
+            self.spawn(actor);
+        }
+        self.packs = Packs::gather(&self.actors);
+        self.decision_times
+            .retain(|id, _| self.actors.contains_key(id));
+        self.brought_out
+            .retain(|id, _| self.actors.contains_key(id));
+        let mut scratch = std::mem::take(&mut self.scratch);
+        if let TickSource::Replay(replayed) = &mut source {
+            scratch.environments = std::mem::take(&mut replayed.environments);
+        }
+        self.scheduler.take_due(self.tick, &mut scratch.chosen);
+        // drop ids of actors that no longer exist; paused ones keep their
+        // place in the schedule but sit this turn out
+        scratch.chosen.retain(|id| match self.actors.get(id) {
+            Some(actor) if actor.paused => {
+                self.scheduler
+                    .schedule(id, self.tick + actor.tick_rate.max(1) as u64);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        });
+        // the nimblest act first, and the same world always plays out the same way
+        scratch.chosen.sort_by(|a, b| {
+            let (first, second) = (&self.actors[a], &self.actors[b]);
+            second
+                .action_points
+                .cmp(&first.action_points)
+                .then_with(|| a.cmp(b))
+        });
+
+        // Plan for chosen actors who have nothing left queued, or take the
+        // plans they made when this tick was recorded
+        let planning_span = debug_span!("planning").entered();
+        // those set on a goal bring their plans for it up to date first;
+        // it's all in the world's own state, so a replay does the same
+        for id in &scratch.chosen {
+            if let Some(actor) = self
+                .actors
+                .get_mut(id)
+                .filter(|a| a.travel.is_none() && !a.has_flag(ActorFlag::Player))
+            {
+                goals::pursue(actor, world_time, &self.pastures, page_graph);
+            }
+        }
+        match &mut source {
+            TickSource::Live {
+                environments,
+                page_flags,
+            } => {
+                scripted.extend(self.plan(
+                    &mut scratch,
+                    world_time,
+                    page_graph,
+                    environments,
+                    page_flags,
+                ));
+            }
+            TickSource::Replay(replayed) => scratch.plans.append(&mut replayed.plans),
+        }
+        if let Some(record) = &mut record {
+            record.plans = scratch.plans.clone();
+        }
+        self.decision_times.extend(scratch.decided.iter().cloned());
+        let planning = started.elapsed();
+        drop(planning_span);
+        packs::rally(
+            &self.packs,
+            &mut self.actors,
+            &mut scratch.plans,
+            page_graph,
+        );
+        settle_attacks(&mut scratch.plans, &self.packs);
+        for (id, plan) in scratch.plans.drain(..) {
+            if let Some(actor) = self.actors.get_mut(&id) {
+                actor.queue.extend(plan);
+            }
+        }
+        // Now spend their action points and book their next turn
+        let acting_span = debug_span!("acting").entered();
+        let acting_started = Instant::now();
+        for id in &scratch.chosen {
+            self.drop_stale_actions(id, page_graph);
+            if let Some(actor) = self.actors.get_mut(id) {
+                let environment =
+                    match environment_on(&mut scratch.environments, &source, &actor.location) {
+                        Ok(environment) => environment,
+                        Err(e) => {
+                            warn!(%id, "No environment to act in: {e}");
+                            continue;
+                        }
+                    };
+                let events = actor.take_turn(environment, page_graph, &mut self.actions_taken);
+                rumors::fade(&mut actor.rumors);
+                let moved = events
                     .iter()
-                    .filter_map(|oid| {
-                        if *oid != id {
-                            self.actors.get(*oid)
-                        } else {
+                    .any(|event| matches!(event, WorldEvent::ActorMoved { .. }));
+                if moved && actor.is_ping_ponging() {
+                    let trail: Vec<&PageId> = actor.trail.iter().collect();
+                    let planned: Vec<&PageId> = actor.planned_path().collect();
+                    warn!(%actor.id, ?trail, ?planned, "Actor is ping-ponging between two pages");
+                }
+                self.scheduler
+                    .schedule(id, self.tick + actor.tick_rate.max(1) as u64);
+                for event in events {
+                    // whoever was to be talked to may have wandered off or
+                    // dozed off since the plan was made
+                    if let WorldEvent::ActorConversed { listener, page, .. } = &event
+                        && !self
+                            .actors
+                            .get(listener)
+                            .is_some_and(|l| l.location == *page && l.state.awake)
+                    {
+                        continue;
+                    }
+                    let blow = match &event {
+                        WorldEvent::ActorMoved {
+                            actor: mover,
+                            from,
+                            to,
+                        } => {
+                            self.by_page.moved(mover, from, to);
                             None
                         }
-                    })
-                    .collect();
-                let action = actor.decide(world_time, &locals, page_graph);
-                events.push((id.clone(), action));
+                        WorldEvent::ActorAttacked {
+                            attacker, target, ..
+                        } => combat::strike(&mut self.actors, attacker, target, &mut self.rng),
+                        WorldEvent::ActorConversed {
+                            speaker, listener, ..
+                        } => {
+                            self.pass_rumor(speaker, listener, page_graph);
+                            None
+                        }
+                        _ => None,
+                    };
+                    self.witness(&event);
+                    self.sense(&event, page_graph);
+                    self.bus.publish(event);
+                    if let Some(blow) = blow {
+                        packs::take_up(&self.packs, &mut self.actors, &blow);
+                        let devoured = self.devoured_by(&blow);
+                        self.witness(&blow);
+                        self.feel(&blow);
+                        self.sense(&blow, page_graph);
+                        self.bus.publish(blow);
+                        if let Some(devoured) = devoured {
+                            self.feel(&devoured);
+                            self.bus.publish(devoured);
+                        }
+                    }
+                }
             }
         }
-        // Now apply their actions
-        for (id, action) in events {
-            if let Some(actor) = self.actors.get_mut(&id) {
-                actor.apply_action(action);
+        let acting = acting_started.elapsed();
+        drop(acting_span);
+        // the elements wear on everyone out in them, whether it's their turn or not
+        let enduring_span = debug_span!("enduring").entered();
+        let enduring_started = Instant::now();
+        let mut harmed = Vec::new();
+        let mut starved = Vec::new();
+        self.pastures.regrow(self.tick, page_graph);
+        self.stimuli.fade();
+        // in a set order: contagion rolls dice, and a replay has to roll
+        // them for the same actors
+        let mut pages: Vec<(&PageId, &Vec<ActorId>)> = self.by_page.0.iter().collect();
+        pages.sort_by_key(|(a, _)| *a);
+        for (page, ids) in pages {
+            let Ok(environment) = environment_on(&mut scratch.environments, &source, page) else {
+                continue;
+            };
+            for id in ids {
+                let Some(actor) = self.actors.get_mut(id).filter(|a| !a.paused) else {
+                    continue;
+                };
+                mood::settle(&mut actor.state);
+                harmed.extend(actor.suffer_effects());
+                if let Some(event) = actor.endure(environment) {
+                    harmed.push(event);
+                }
+                if let Some(here) = page_graph.get(page)
+                    && ecology::hunger(actor, here, &mut self.pastures)
+                {
+                    starved.push(id.clone());
+                }
             }
+            contagion::spread(&mut self.actors, ids, &mut self.rng);
+        }
+        for event in harmed {
+            self.witness(&event);
+            self.bus.publish(event);
+        }
+        for id in starved {
+            if let Some(actor) = self.perish(&id) {
+                info!(%id, "Starved to death.");
+                let starved = WorldEvent::ActorStarved {
+                    actor: id,
+                    page: actor.location,
+                };
+                self.feel(&starved);
+                self.bus.publish(starved);
+            }
+        }
+        // the fed and well have young; it's all in the world's own state, so
+        // a replay breeds the same ones without them being recorded
+        for young in self.spawner.breed(self.tick, &mut self.actors, page_graph) {
+            self.spawn(young);
         }
+        let enduring = enduring_started.elapsed();
+        drop(enduring_span);
+        self.last_tick = TickTimings {
+            tick: self.tick,
+            acted: scratch.chosen.len(),
+            planning,
+            acting,
+            enduring,
+            total: started.elapsed(),
+        };
         debug!(
-            "World tick: updated {} of {} actors.",
-            num_to_tick,
+            "World tick {}: updated {} of {} actors.",
+            self.tick,
+            scratch.chosen.len(),
             self.actors.len()
         );
+        if self.last_tick.total > self.tick_budget {
+            scratch
+                .decided
+                .sort_by_key(|(_, took)| std::cmp::Reverse(*took));
+            let slowest: Vec<String> = scratch
+                .decided
+                .iter()
+                .take(SLOWEST_REPORTED)
+                .map(|(id, took)| format!("{id}={}us", took.as_micros()))
+                .collect();
+            let timings = &self.last_tick;
+            warn!(
+                tick = timings.tick,
+                total_us = timings.total.as_micros() as u64,
+                budget_ms = self.tick_budget.as_millis() as u64,
+                planning_us = timings.planning.as_micros() as u64,
+                acting_us = timings.acting.as_micros() as u64,
+                enduring_us = timings.enduring.as_micros() as u64,
+                acted = timings.acted,
+                actors = self.actors.len(),
+                ?slowest,
+                "World tick over budget"
+            );
+        }
+        self.bus.publish(WorldEvent::WorldTicked {
+            tick: self.tick,
+            acted: scratch.chosen.len(),
+        });
+        scratch.chosen.clear();
+        scratch.decided.clear();
+        if let (Some(recorder), Some(mut record)) = (&mut self.recorder, record) {
+            record.environments = scratch.environments.clone();
+            record.teleports = scripted
+                .iter()
+                .filter_map(|effect| match effect {
+                    ScriptEffect::MoveActor { actor, to } => Some((actor.clone(), to.clone())),
+                    _ => None,
+                })
+                .collect();
+            recorder.tick(record);
+        }
+        scratch.environments.clear(); // weather may have moved on by next tick
+        self.scratch = scratch;
+        scripted
     }
 }
 
@@ -306,4 +2515,350 @@ pub enum ActorFlag {
     CanSpeak,
     Nocturnal,
     Predatory,
+    FearsDark,   // avoids moving onto dark pages
+    Lunar,       // only stirs under a full moon
+    Player,      // stands in for a player; never takes turns
+    Shy,         // slips away when a player turns up
+    Curious,     // goes to see players nearby, and stays while they're around
+    Rideable,    // carries a player it goes along with, as a mount
+    Herbivore,   // grazes what grows on a page, and is prey to predators
+    Migratory,   // goes where its kind spends the season, and back again after
+    Territorial, // drives off strangers that come onto its home page
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::clock::{ClockConfig, WorldClock};
+    use crate::environment::EnvironmentTtl;
+    use crate::pages::tests::page;
+    use crate::world::WorldGraph;
+
+    /// Pages around the ring, each joined to the next both ways
+    const RING: usize = 6;
+
+    fn ring_page(n: usize) -> PageId {
+        PageId::from(format!("ring-{}", n % RING))
+    }
+
+    /// A ring of plain outdoor pages; every other stretch takes a few ticks
+    /// to get along, so actors travel as well as step
+    pub(crate) fn ring() -> PageGraph {
+        (0..RING)
+            .map(|n| {
+                let distance = if n % 2 == 0 { 1 } else { 3 };
+                let page = page(
+                    ring_page(n).as_str(),
+                    &format!("Ring {n}"),
+                    serde_json::json!({
+                        "connections": [
+                            { "name": "on", "target": ring_page(n + 1), "distance": distance },
+                            { "name": "back", "target": ring_page(n + RING - 1), "distance": distance },
+                        ],
+                    }),
+                );
+                (page.id.clone(), page)
+            })
+            .collect()
+    }
+
+    /// `actors` indexed by the page each stands on
+    pub(crate) fn indexed(actors: &ActorMap) -> PageIndex {
+        let mut index = PageIndex::default();
+        for actor in actors.values() {
+            index.insert(&actor.location, &actor.id);
+        }
+        index
+    }
+
+    /// `count` actors spread around the ring, hunters and shy ones among them
+    pub(crate) fn critters(count: usize) -> Vec<ActorDefinition> {
+        let temperaments = [
+            vec![ActorFlag::Organic],
+            vec![ActorFlag::Organic, ActorFlag::Curious],
+            vec![ActorFlag::Organic, ActorFlag::Shy],
+            vec![ActorFlag::Organic, ActorFlag::CanSpeak],
+            vec![
+                ActorFlag::Organic,
+                ActorFlag::Predatory,
+                ActorFlag::CanAttack,
+            ],
+        ];
+        (0..count)
+            .map(|n| ActorDefinition {
+                id: format!("critter-{n}").as_str().into(),
+                name: format!("Critter {n}"),
+                location: ring_page(n),
+                health: 10,
+                flags: temperaments[n % temperaments.len()].clone(),
+                tick_rate: 1 + (n % 3) as u32,
+                action_points: 3,
+                roams: None,
+                home: None,
+                faction: None,
+                pack: None,
+                items: Vec::new(),
+                coins: 0,
+                script: None,
+                seen_when: Condition::Always,
+                decision_overlays: Default::default(),
+                appointments: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// What a world needs around it to tick
+    pub(crate) struct Setting {
+        pub(crate) bus: EventBus,
+        pub(crate) pages: Arc<PageGraph>,
+        clock: WorldClock,
+        environments: EnvironmentManager,
+        page_flags: Arc<PageFlags>,
+    }
+
+    impl Setting {
+        pub(crate) fn new() -> Self {
+            let bus = EventBus::new();
+            let world = WorldGraph::new(ring(), bus.clone());
+            let clock = WorldClock::new(ClockConfig::default());
+            let environments = EnvironmentManager::new(
+                bus.clone(),
+                EnvironmentTtl::default(),
+                clock.clone(),
+                world.clone(),
+                Arc::new(Regions::default()),
+            );
+            Setting {
+                pages: world.snapshot(),
+                page_flags: world.page_flags(),
+                bus,
+                clock,
+                environments,
+            }
+        }
+
+        pub(crate) fn world(&self, count: usize) -> ActorManager {
+            ActorManager::new(
+                self.bus.clone(),
+                critters(count),
+                Arc::new(Regions::default()),
+                ScriptHost::default(),
+            )
+        }
+
+        pub(crate) fn tick(&self, world: &mut ActorManager) {
+            world.tick_some(
+                &self.clock.world_time(),
+                &self.pages,
+                &self.environments,
+                &self.page_flags,
+            );
+        }
+    }
+
+    /// A world where `ids` have gathered on the first page of the ring and
+    /// are the only ones due next tick
+    fn gathered(around: &Setting, ids: &[&str]) -> ActorManager {
+        let mut world = around.world(10);
+        for id in ids {
+            world.teleport(id, &ring_page(0)).unwrap();
+        }
+        let mut snapshot = world.snapshot();
+        snapshot.schedule = vec![(
+            snapshot.tick + 1,
+            ids.iter().map(|&id| ActorId::from(id)).collect(),
+        )];
+        world.restore(snapshot);
+        world
+    }
+
+    /// The next tick with its rolls seeded and `plans` made, to play as recorded
+    pub(crate) fn seeded(
+        around: &Setting,
+        world: &ActorManager,
+        plans: &[(&str, ActorAction)],
+    ) -> TickRecord {
+        TickRecord {
+            tick: world.tick() + 1,
+            seed: 7,
+            world_time: around.clock.world_time(),
+            plans: plans
+                .iter()
+                .map(|(id, action)| (ActorId::from(*id), vec![action.clone()]))
+                .collect(),
+            environments: (0..RING)
+                .map(|n| {
+                    let page = ring_page(n);
+                    let environment = around.environments.environment_for(&page).unwrap();
+                    (page, environment)
+                })
+                .collect(),
+            ..TickRecord::default()
+        }
+    }
+
+    /// Every actor is indexed on the page it stands on, and nothing else is
+    fn assert_indexed(world: &ActorManager) {
+        let indexed: usize = world.by_page.0.values().map(Vec::len).sum();
+        assert_eq!(indexed, world.actors.len(), "index holds strays");
+        for actor in world.actors.values() {
+            let here = world.by_page.on(&actor.location);
+            assert_eq!(
+                here.iter().filter(|id| **id == actor.id).count(),
+                1,
+                "{} not indexed once on {}",
+                actor.id,
+                actor.location
+            );
+        }
+    }
+
+    #[test]
+    fn page_index_follows_actors_as_they_move_and_travel() {
+        let around = Setting::new();
+        let mut world = around.world(20);
+        assert_indexed(&world);
+        let started: HashMap<ActorId, PageId> = world
+            .actors
+            .values()
+            .map(|a| (a.id.clone(), a.location.clone()))
+            .collect();
+        let mut travelled = false;
+        for _ in 0..50 {
+            around.tick(&mut world);
+            assert_indexed(&world);
+            travelled |= world.actors.values().any(|a| a.travel.is_some());
+        }
+        assert!(travelled, "nobody set off on a long stretch");
+        assert!(
+            world
+                .actors
+                .values()
+                .any(|a| started.get(&a.id) != Some(&a.location)),
+            "nobody moved"
+        );
+    }
+
+    #[test]
+    fn page_index_follows_teleports_players_and_removals() {
+        let around = Setting::new();
+        let mut world = around.world(10);
+        world.teleport("critter-0", &ring_page(3)).unwrap();
+        world.teleport("critter-1", &ring_page(1)).unwrap();
+        assert_indexed(&world);
+        let player = ActorId::from("player-1");
+        world.sync_player(&player, "Pat", &ring_page(0));
+        world.sync_player(&player, "Pat", &ring_page(2));
+        assert_indexed(&world);
+        world.page_removed(&ring_page(3), &ring_page(0));
+        assert_indexed(&world);
+        world.reset("critter-0").unwrap();
+        assert_indexed(&world);
+        assert!(
+            world
+                .by_page
+                .on(&ring_page(0))
+                .contains(&"critter-0".into())
+        );
+    }
+
+    #[test]
+    fn page_index_is_rebuilt_on_reset_and_restore() {
+        let around = Setting::new();
+        let mut world = around.world(10);
+        let before = world.snapshot();
+        for _ in 0..20 {
+            around.tick(&mut world);
+        }
+        world.sync_player(&"player-1".into(), "Pat", &ring_page(4));
+        world.reset_world();
+        assert_indexed(&world);
+        assert!(world.by_page.on(&ring_page(4)).contains(&"player-1".into()));
+        for _ in 0..20 {
+            around.tick(&mut world);
+        }
+        world.restore(before);
+        assert_indexed(&world);
+    }
+
+    #[test]
+    fn a_seeded_tick_of_fighting_plays_out_the_same_whatever_order_plans_come_in() {
+        let around = Setting::new();
+        let fighters = ["critter-0", "critter-4", "critter-9"];
+        // two set on each other, and a third on one of them
+        let plans = [
+            ("critter-4", ActorAction::Attack("critter-9".into())),
+            ("critter-9", ActorAction::Attack("critter-4".into())),
+            ("critter-0", ActorAction::Attack("critter-9".into())),
+        ];
+        let mut outcomes = Vec::new();
+        for reversed in [false, true] {
+            let mut world = gathered(&around, &fighters);
+            let mut record = seeded(&around, &world, &plans);
+            if reversed {
+                record.plans.reverse();
+            }
+            world.replay_tick(record, &around.pages);
+            for hurt in ["critter-4", "critter-9"] {
+                assert!(world.actors[hurt].state.health < 10, "{hurt} went unhurt");
+            }
+            outcomes.push(serde_json::to_value(world.snapshot()).unwrap());
+        }
+        assert_eq!(outcomes[0], outcomes[1], "plans settled in another order");
+
+        let world = gathered(&around, &fighters);
+        let mut settled = seeded(&around, &world, &plans).plans;
+        settle_attacks(&mut settled, &Packs::gather(&world.actors));
+        // the first by id has critter-9 to itself; critter-4 still answers back
+        let attacking: Vec<&str> = settled
+            .iter()
+            .filter(|(_, plan)| !plan.is_empty())
+            .map(|(id, _)| id.as_str())
+            .collect();
+        assert_eq!(attacking, ["critter-0", "critter-9"]);
+    }
+
+    #[test]
+    fn attacks_on_actors_gone_or_downed_during_the_tick_are_dropped() {
+        let around = Setting::new();
+        let about = [
+            "critter-0",
+            "critter-1",
+            "critter-2",
+            "critter-3",
+            "critter-4",
+        ];
+        let mut world = gathered(&around, &about);
+        // the pack can both set on critter-1, which the first blow fells
+        for wolf in ["critter-2", "critter-3"] {
+            world.actors.get_mut(wolf).unwrap().pack = Some("wolves".to_string());
+        }
+        world.actors.get_mut("critter-1").unwrap().state.health = 1;
+        // critter-0 takes its turn, and leaves, before critter-4 takes its own
+        let plans = [
+            ("critter-0", ActorAction::MoveTo(ring_page(1))),
+            ("critter-2", ActorAction::Attack("critter-1".into())),
+            ("critter-3", ActorAction::Attack("critter-1".into())),
+            ("critter-4", ActorAction::Attack("critter-0".into())),
+        ];
+        let record = seeded(&around, &world, &plans);
+        let mut events = around.bus.subscribe();
+        world.replay_tick(record, &around.pages);
+
+        let mut blows = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let WorldEvent::ActorWounded { attacker, .. } = event {
+                blows.push(attacker.to_string());
+            }
+        }
+        assert_eq!(blows, ["critter-2"]);
+        assert_eq!(world.actors["critter-0"].location, ring_page(1));
+        assert_eq!(world.actors["critter-0"].state.health, 10);
+        for stale in ["critter-3", "critter-4"] {
+            assert!(
+                world.actors[stale].queue.is_empty(),
+                "{stale} kept its attack"
+            );
+        }
+    }
 }
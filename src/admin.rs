@@ -0,0 +1,493 @@
+use actix_session::SessionExt;
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use chrono::TimeDelta;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::actor::{Actor, ActorManager};
+use crate::clock::WorldClock;
+use crate::dashboard;
+use crate::environment::EnvironmentManager;
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::generator::AreaSpec;
+use crate::instances::Instances;
+use crate::pages::{Page, PageConnection, PageId};
+use crate::replay::{self, ReplaySource};
+use crate::saves::SaveSlots;
+use crate::tick::tick_world;
+use crate::world::WorldGraph;
+
+/// Most ticks a single fast-forward request may run
+const MAX_FAST_FORWARD: u32 = 1_000;
+
+/// Session key marking a browser that has logged in to the dashboard
+pub const ADMIN_SESSION_KEY: &str = "admin";
+
+/// Shared secret for the admin endpoints, read from `CHOTT_ADMIN_TOKEN`.
+/// Admin is disabled entirely when it is unset.
+#[derive(Clone)]
+pub struct AdminToken(Option<String>);
+
+impl AdminToken {
+    pub fn from_env() -> Self {
+        AdminToken(
+            std::env::var("CHOTT_ADMIN_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty()),
+        )
+    }
+
+    /// Whether `given` is the admin token
+    pub fn matches(&self, given: &str) -> bool {
+        self.0.as_deref() == Some(given)
+    }
+
+    /// Check the request carries `Authorization: Bearer <token>`,
+    /// or comes from a browser logged in to the dashboard
+    pub fn check(&self, req: &HttpRequest) -> Result<(), AppError> {
+        let Some(expected) = &self.0 else {
+            return Err(AppError::Unauthorized("Admin is disabled".to_string()));
+        };
+        let logged_in = req
+            .get_session()
+            .get::<bool>(ADMIN_SESSION_KEY)
+            .ok()
+            .flatten();
+        if logged_in == Some(true) {
+            return Ok(());
+        }
+        let given = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if given == Some(expected.as_str()) {
+            Ok(())
+        } else {
+            Err(AppError::Unauthorized("Bad admin token".to_string()))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .route("", web::get().to(dashboard::dashboard_handler))
+            .route("/login", web::get().to(dashboard::login_page_handler))
+            .route("/login", web::post().to(dashboard::login_handler))
+            .route("/logout", web::post().to(dashboard::logout_handler))
+            .route("/ui/tick", web::post().to(dashboard::tick_handler))
+            .route("/ui/pause", web::post().to(dashboard::pause_handler))
+            .route("/ui/resume", web::post().to(dashboard::resume_handler))
+            .route("/ui/interval", web::post().to(dashboard::interval_handler))
+            .route("/ui/teleport", web::post().to(dashboard::teleport_handler))
+            .route("/ui/reset", web::post().to(dashboard::reset_actor_handler))
+            .route(
+                "/ui/reset-world",
+                web::post().to(dashboard::reset_world_handler),
+            )
+            .route("/ui/weather", web::post().to(dashboard::weather_handler))
+            .route("/ui/save", web::post().to(dashboard::save_handler))
+            .route("/ui/restore", web::post().to(dashboard::restore_handler))
+            .route("/clock/jump", web::post().to(jump_clock_handler))
+            .route("/clock/skip-day", web::post().to(skip_day_handler))
+            .route("/clock/pause", web::post().to(pause_clock_handler))
+            .route("/clock/resume", web::post().to(resume_clock_handler))
+            .route("/clock/scale", web::post().to(scale_clock_handler))
+            .route("/clock/interval", web::post().to(tick_interval_handler))
+            .route("/tick", web::post().to(fast_forward_handler))
+            .route("/replay", web::get().to(replay_handler))
+            .route("/saves", web::get().to(list_saves_handler))
+            .route("/saves", web::post().to(save_handler))
+            .route("/saves/restore", web::post().to(restore_handler))
+            .route(
+                "/environment/invalidate",
+                web::post().to(invalidate_environment_handler),
+            )
+            .route("/world/reset", web::post().to(reset_world_handler))
+            .route("/world/pages", web::post().to(add_page_handler))
+            .route("/world/pages/remove", web::post().to(remove_page_handler))
+            .route("/world/generate", web::post().to(generate_area_handler))
+            .route("/world/connect", web::post().to(connect_handler))
+            .route("/world/disconnect", web::post().to(disconnect_handler)),
+    );
+}
+
+fn announce_clock(clock: &WorldClock, bus: &EventBus) {
+    let time = clock.world_time();
+    bus.publish(WorldEvent::ClockChanged {
+        hour: time.hour,
+        minute: time.minute,
+    });
+}
+
+#[derive(Deserialize)]
+pub struct JumpQuery {
+    to: String, // dawn, noon, dusk, midnight or an hour 0-23
+}
+
+/// Jump the world clock forward to the next named time of day or hour
+pub async fn jump_clock_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    bus: web::Data<EventBus>,
+    query: web::Query<JumpQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let hour = match query.to.as_str() {
+        "dawn" => 6,
+        "noon" => 12,
+        "dusk" => 18,
+        "midnight" => 0,
+        other => other
+            .parse::<u8>()
+            .ok()
+            .filter(|h| *h < 24)
+            .ok_or_else(|| AppError::OtherError(format!("Unknown time '{other}'")))?,
+    };
+    clock.jump_to_hour(hour);
+    info!("Admin jumped world clock to {hour}:00");
+    announce_clock(&clock, &bus);
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+/// Skip the world clock ahead by one day
+pub async fn skip_day_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    bus: web::Data<EventBus>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.advance(TimeDelta::days(1));
+    info!("Admin skipped world clock ahead a day");
+    announce_clock(&clock, &bus);
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+/// Stop world time (and with it the background ticks)
+pub async fn pause_clock_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.pause();
+    info!("Admin paused world clock");
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+pub async fn resume_clock_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.resume();
+    info!("Admin resumed world clock");
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+#[derive(Deserialize)]
+pub struct ScaleQuery {
+    x: f64, // world seconds per real second
+}
+
+/// Speed the world clock up or slow it down
+pub async fn scale_clock_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    query: web::Query<ScaleQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    if !query.x.is_finite() || query.x < 0.0 {
+        return Err(AppError::OtherError(format!("Bad time scale {}", query.x)));
+    }
+    clock.set_scale(query.x);
+    let status = clock.status();
+    // may be less than asked for, held to MAX_TIME_SCALE
+    info!("Admin set world clock scale to {}", status.scale);
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[derive(Deserialize)]
+pub struct IntervalQuery {
+    pub(crate) ms: u64, // real milliseconds between background ticks
+}
+
+/// Tick the world more or less often, from the next tick on
+pub async fn tick_interval_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    query: web::Query<IntervalQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.set_tick_interval(Duration::from_millis(query.ms));
+    info!(
+        "Admin set tick interval to {}ms",
+        clock.tick_interval().as_millis()
+    );
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+#[derive(Deserialize)]
+pub struct FastForwardQuery {
+    n: u32,
+}
+
+/// Run `n` world ticks back to back, moving the clock on by one tick interval each
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn fast_forward_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    bus: web::Data<EventBus>,
+    instances: web::Data<Instances>,
+    environment: web::Data<EnvironmentManager>,
+    world: web::Data<WorldGraph>,
+    query: web::Query<FastForwardQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    fast_forward(query.n, clock.clone(), bus, instances, environment, world).await?;
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+#[derive(Deserialize)]
+pub struct ReplayQuery {
+    tick: u64,
+}
+
+/// The actors as they stood after a recorded tick, played over again from
+/// the recording in a world of their own; the live world is left be
+pub async fn replay_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    source: web::Data<ReplaySource>,
+    actors: web::Data<Arc<Mutex<ActorManager>>>,
+    world: web::Data<WorldGraph>,
+    query: web::Query<ReplayQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let path = source.0.as_ref().ok_or_else(|| {
+        AppError::OtherError("Nothing to replay; set CHOTT_RECORD or CHOTT_REPLAY".to_string())
+    })?;
+    let path = path.clone();
+    let tick = query.tick;
+    let blank = actors.lock().instance(EventBus::new());
+    let page_graph = world.snapshot();
+    // reading and playing a long recording over takes a while; keep it off the async workers
+    let replayed = web::block(move || replay::replay(&path, tick, blank, &page_graph))
+        .await
+        .map_err(|e| AppError::OtherError(e.to_string()))??;
+    let mut actors: Vec<&Actor> = replayed.actors.values().collect();
+    actors.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tick": tick,
+        "actors": actors,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct SaveQuery {
+    pub(crate) name: String, // lowercase letters, digits and dashes
+}
+
+/// Every save slot, newest first
+pub async fn list_saves_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    slots: web::Data<SaveSlots>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    Ok(HttpResponse::Ok().json(slots.list()?))
+}
+
+/// Save the whole world to a named slot, replacing one of the same name
+pub async fn save_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    slots: web::Data<SaveSlots>,
+    query: web::Query<SaveQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    slots.save(&query.name)?;
+    Ok(HttpResponse::Ok().json(slots.list()?))
+}
+
+/// Roll the whole world back to a named slot
+pub async fn restore_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    slots: web::Data<SaveSlots>,
+    clock: web::Data<WorldClock>,
+    query: web::Query<SaveQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    slots.restore(&query.name)?;
+    Ok(HttpResponse::Ok().json(clock.status()))
+}
+
+/// Run up to `n` ticks, moving the clock on by one tick interval each.
+/// The ticks run on a blocking thread, taking the actor lock a tick at a
+/// time, so pages and the background ticker carry on around them.
+pub(crate) async fn fast_forward(
+    n: u32,
+    clock: web::Data<WorldClock>,
+    bus: web::Data<EventBus>,
+    instances: web::Data<Instances>,
+    environment: web::Data<EnvironmentManager>,
+    world: web::Data<WorldGraph>,
+) -> Result<(), AppError> {
+    let n = n.min(MAX_FAST_FORWARD);
+    let step = TimeDelta::from_std(clock.tick_interval())
+        .map_err(|e| AppError::OtherError(format!("Tick interval out of range: {e}")))?;
+    let ticking = clock.clone();
+    web::block(move || {
+        for _ in 0..n {
+            ticking.advance(step);
+            tick_world(&instances, &environment, &world, &ticking);
+        }
+    })
+    .await
+    .map_err(|e| AppError::OtherError(e.to_string()))?;
+    info!("Admin fast-forwarded {n} ticks");
+    announce_clock(&clock, &bus);
+    Ok(())
+}
+
+/// Put the actors back as the definitions have them, in every instance
+pub async fn reset_world_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    instances: web::Data<Instances>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let generation = reset_world(&instances);
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "generation": generation })))
+}
+
+/// Reset the actors in every instance; returns the shared world's new generation
+pub(crate) fn reset_world(instances: &Instances) -> u64 {
+    let mut all = instances.all().into_iter();
+    let generation = all.next().map_or(0, |shared| shared.lock().reset_world());
+    for solo in all {
+        solo.lock().reset_world();
+    }
+    info!("Admin reset the world, now generation {generation}");
+    generation
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateQuery {
+    page: Option<String>, // every page when absent
+}
+
+/// Drop cached environments so they are regenerated on next visit
+pub async fn invalidate_environment_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    environment: web::Data<EnvironmentManager>,
+    query: web::Query<InvalidateQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    match &query.page {
+        Some(page) => {
+            let removed = environment.invalidate(&PageId::from(page.as_str()))?;
+            info!("Admin invalidated environment for {page} (was cached: {removed})");
+        }
+        None => {
+            environment.invalidate_all()?;
+            info!("Admin invalidated all environments");
+        }
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Add a page to the running world (JSON body), replacing any with the same id
+pub async fn add_page_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    page: web::Json<Page>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    world.add_page(page.into_inner())?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Generate an area from a recipe (JSON body) and attach it to the running world
+pub async fn generate_area_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    spec: web::Json<AreaSpec>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    world.add_area(spec.generate()?)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct RemovePageQuery {
+    page: String,
+    fallback: String, // where anyone on the page is sent
+}
+
+/// Remove a page and every way into it
+pub async fn remove_page_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    query: web::Query<RemovePageQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    world.remove_page(
+        &PageId::from(query.page.as_str()),
+        &PageId::from(query.fallback.as_str()),
+    )?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct ConnectBody {
+    from: PageId,
+    connection: PageConnection,
+}
+
+/// Open a new way out of a page (JSON body)
+pub async fn connect_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    body: web::Json<ConnectBody>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let ConnectBody { from, connection } = body.into_inner();
+    world.connect(&from, connection)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct DisconnectQuery {
+    from: String,
+    name: String,
+}
+
+/// Close a way out of a page, e.g. when a tunnel collapses
+pub async fn disconnect_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    query: web::Query<DisconnectQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    world.disconnect(&PageId::from(query.from.as_str()), &query.name)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,105 @@
+//! Ambient hints for templates to dress a page in: the sounds about and a
+//! few theme names, from the environment, the time of day and how many are
+//! about. Nothing here is shown as text; a template might loop an audio
+//! file per sound, or put the theme on `<body>` as CSS classes.
+
+use serde::Serialize;
+
+use crate::environment::{Environment, PartOfDay, Season};
+use crate::pages::Biome;
+use crate::weather::WeatherKind;
+
+/// Others awake on a page for a murmur of voices
+const MURMUR_AT: usize = 2;
+/// ...and for the hubbub of a crowd
+const CROWD_AT: usize = 5;
+/// Storms stronger than this howl as well as rain
+const HOWLING_STORM: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sound {
+    Birdsong,
+    Crickets,
+    Owls,
+    Rain,
+    Thunder,
+    Wind,
+    Surf,
+    Gulls,
+    Dripping,
+    Murmur,
+    Crowd,
+}
+
+/// What a page sounds and looks like right now, as `ambient` in page templates
+#[derive(Debug, Default, Serialize)]
+pub struct Ambience {
+    pub sounds: Vec<Sound>,
+    pub theme: Vec<String>, // e.g. ["night", "rainy", "autumn", "crowded"]
+}
+
+impl Ambience {
+    /// The ambience of `environment` at `part_of_day`, with `awake` others
+    /// about to be heard
+    pub fn of(environment: &Environment, part_of_day: PartOfDay, awake: usize) -> Self {
+        let mut ambience = Ambience::default();
+        let weather = environment.weather();
+        let night = matches!(part_of_day, PartOfDay::Night | PartOfDay::DeepNight);
+        let outdoors = environment.biome() != Biome::Cave && !environment.is_sheltered();
+
+        if environment.biome() == Biome::Cave {
+            ambience.sounds.push(Sound::Dripping);
+        } else {
+            // heard on the roof as well as in the open
+            match weather {
+                WeatherKind::Rainy => ambience.sounds.push(Sound::Rain),
+                WeatherKind::Stormy => ambience.sounds.extend([Sound::Rain, Sound::Thunder]),
+                _ => {}
+            }
+        }
+        if outdoors {
+            let howling = weather == WeatherKind::Stormy && environment.intensity() > HOWLING_STORM;
+            if weather == WeatherKind::Windy || howling {
+                ambience.sounds.push(Sound::Wind);
+            }
+            let fair = matches!(weather, WeatherKind::Clear | WeatherKind::Cloudy);
+            let mild = environment.season() != Season::Winter;
+            if fair && mild && night {
+                ambience.sounds.push(Sound::Crickets);
+            }
+            if night && environment.biome() == Biome::Forest && weather != WeatherKind::Stormy {
+                ambience.sounds.push(Sound::Owls);
+            }
+            if fair && mild && matches!(part_of_day, PartOfDay::Dawn | PartOfDay::Morning) {
+                ambience.sounds.push(Sound::Birdsong);
+            }
+            if environment.biome() == Biome::Coastal {
+                ambience.sounds.push(Sound::Surf);
+                if !night && weather != WeatherKind::Stormy {
+                    ambience.sounds.push(Sound::Gulls);
+                }
+            }
+        }
+        if awake >= CROWD_AT {
+            ambience.sounds.push(Sound::Crowd);
+        } else if awake >= MURMUR_AT {
+            ambience.sounds.push(Sound::Murmur);
+        }
+
+        ambience.theme.push(part_of_day.key().replace('_', "-"));
+        ambience.theme.push(weather.to_string().to_lowercase());
+        ambience
+            .theme
+            .push(environment.season().to_string().to_lowercase());
+        if environment.biome() == Biome::Cave {
+            ambience.theme.push("underground".to_string());
+        } else if !outdoors {
+            ambience.theme.push("indoors".to_string());
+        }
+        if awake >= CROWD_AT {
+            ambience.theme.push("crowded".to_string());
+        }
+        ambience
+    }
+}
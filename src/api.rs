@@ -0,0 +1,506 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::{Next, from_fn};
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use chrono::TimeDelta;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::actor::{Actor, ActorFlag, ActorManager};
+use crate::admin::AdminToken;
+use crate::census::{self, Census};
+use crate::definitions::ActorDefinition;
+use crate::environment::{EnvironmentManager, OverrideSource, WeatherOverride};
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::forecast::{DEFAULT_FORECAST_HOURS, MAX_FORECAST_HOURS};
+use crate::pages::{Biome, Page, PageId};
+use crate::regions::{RegionId, Regions};
+use crate::weather::WeatherState;
+use crate::world::WorldGraph;
+
+/// Items per page of results when `limit` isn't given
+const DEFAULT_LIMIT: usize = 50;
+/// Most items a single request may ask for
+const MAX_LIMIT: usize = 200;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/v1")
+            .wrap(from_fn(require_token))
+            .route("/pages", web::get().to(list_pages_handler))
+            .route("/pages/{id}", web::get().to(page_handler))
+            .route("/weather", web::get().to(weather_overrides_handler))
+            .route("/forecast", web::get().to(forecast_handler))
+            .route("/pages/{id}/weather", web::post().to(set_weather_handler))
+            .route(
+                "/pages/{id}/weather",
+                web::delete().to(clear_weather_handler),
+            )
+            .route("/pages/{id}/flags", web::get().to(page_flags_handler))
+            .route("/pages/{id}/census", web::get().to(page_census_handler))
+            .route(
+                "/pages/{id}/flags/{flag}",
+                web::put().to(set_page_flag_handler),
+            )
+            .route(
+                "/pages/{id}/flags/{flag}",
+                web::delete().to(clear_page_flag_handler),
+            )
+            .route("/world", web::get().to(world_handler))
+            .route("/actors", web::get().to(list_actors_handler))
+            .route("/actors", web::post().to(spawn_actor_handler))
+            .route("/actors/{id}", web::get().to(actor_handler))
+            .route("/actors/{id}", web::patch().to(update_actor_handler))
+            .route("/actors/{id}", web::delete().to(delete_actor_handler))
+            .route("/actors/{id}/pause", web::post().to(pause_actor_handler))
+            .route("/actors/{id}/resume", web::post().to(resume_actor_handler)),
+    );
+}
+
+/// Reading is open to anyone, but for actors, whose handlers check for
+/// themselves; anything that changes the world needs the admin token
+async fn require_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        let token = req
+            .app_data::<web::Data<AdminToken>>()
+            .ok_or_else(|| AppError::Unauthorized("Admin is disabled".to_string()))?;
+        token.check(req.request())?;
+    }
+    next.call(req).await
+}
+
+fn require_page(world: &WorldGraph, page: &PageId) -> Result<(), AppError> {
+    if world.snapshot().contains_key(page) {
+        Ok(())
+    } else {
+        Err(AppError::PageNotFound(page.to_string()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Pagination {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+/// One page of a sorted result list, with the total before paging
+#[derive(Serialize)]
+struct Paged<T> {
+    items: Vec<T>,
+    total: usize,
+    offset: usize,
+    limit: usize,
+}
+
+impl Pagination {
+    fn apply<T>(&self, items: Vec<T>) -> Paged<T> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        let total = items.len();
+        Paged {
+            items: items.into_iter().skip(self.offset).take(limit).collect(),
+            total,
+            offset: self.offset,
+            limit,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PageFilter {
+    region: Option<RegionId>, // pages in this region or any region inside it
+    biome: Option<Biome>,
+}
+
+/// Every page in the world, sorted by id
+pub async fn list_pages_handler(
+    world: web::Data<WorldGraph>,
+    regions: web::Data<Arc<Regions>>,
+    filter: web::Query<PageFilter>,
+    paging: web::Query<Pagination>,
+) -> impl Responder {
+    let pages = world.snapshot();
+    let mut matching: Vec<&Page> = pages
+        .values()
+        .filter(|page| {
+            filter
+                .region
+                .as_ref()
+                .is_none_or(|region| regions.within(page.region.as_ref(), region))
+                && filter.biome.is_none_or(|biome| page.biome == biome)
+        })
+        .collect();
+    matching.sort_by(|a, b| a.id.cmp(&b.id));
+    HttpResponse::Ok().json(paging.apply(matching))
+}
+
+pub async fn page_handler(
+    world: web::Data<WorldGraph>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let pages = world.snapshot();
+    let page = pages
+        .get(&PageId::from(id.as_str()))
+        .ok_or(AppError::PageNotFound(id))?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[derive(Deserialize)]
+pub struct ActorFilter {
+    page: Option<PageId>,
+    flag: Option<ActorFlag>,
+    awake: Option<bool>,
+}
+
+/// Where the world's at: ticks run, times reset and actors about
+pub async fn world_handler(actor_manager: web::Data<Arc<Mutex<ActorManager>>>) -> impl Responder {
+    let manager = actor_manager.lock();
+    HttpResponse::Ok().json(serde_json::json!({
+        "tick": manager.tick(),
+        "generation": manager.generation(),
+        "actors": manager.actors.len(),
+    }))
+}
+
+/// Every actor in the world, sorted by id. Admins only, as it shows
+/// players and hidden actors alike.
+pub async fn list_actors_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    filter: web::Query<ActorFilter>,
+    paging: web::Query<Pagination>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let manager = actor_manager.lock();
+    let mut matching: Vec<&Actor> = manager
+        .actors
+        .values()
+        .filter(|actor| {
+            filter
+                .page
+                .as_ref()
+                .is_none_or(|page| actor.location == *page)
+                && filter
+                    .flag
+                    .as_ref()
+                    .is_none_or(|flag| actor.has_flag(flag.clone()))
+                && filter.awake.is_none_or(|awake| actor.state.awake == awake)
+        })
+        .collect();
+    matching.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(HttpResponse::Ok().json(paging.apply(matching)))
+}
+
+/// One actor, whoever and wherever; admins only, like the list
+pub async fn actor_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let id = path.into_inner();
+    let manager = actor_manager.lock();
+    let actor = manager
+        .actors
+        .get(id.as_str())
+        .ok_or_else(|| AppError::ActorNotFound(id.clone()))?;
+    Ok(HttpResponse::Ok().json(actor))
+}
+
+/// Spawn an actor from a definition (JSON body)
+pub async fn spawn_actor_handler(
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    world: web::Data<WorldGraph>,
+    bus: web::Data<EventBus>,
+    definition: web::Json<ActorDefinition>,
+) -> Result<impl Responder, AppError> {
+    let definition = definition.into_inner();
+    require_page(&world, &definition.location)?;
+    let id = definition.id.clone();
+    let mut manager = actor_manager.lock();
+    manager.add_actor(definition)?;
+    bus.publish(WorldEvent::ActorEdited {
+        actor: id.clone(),
+        change: "created".to_string(),
+    });
+    Ok(HttpResponse::Created().json(&manager.actors[&id]))
+}
+
+#[derive(Deserialize)]
+pub struct ActorPatch {
+    location: Option<PageId>, // moved there at once
+    name: Option<String>,
+    flags: Option<Vec<ActorFlag>>, // replaces them all
+    health: Option<i32>,
+    awake: Option<bool>,
+    action_points: Option<u8>,
+    tick_rate: Option<u32>, // from its next turn
+    paused: Option<bool>,
+}
+
+/// Change some of an actor's settings or live state (JSON body). It acts on
+/// them from the next tick; a reload of the definitions file sets its
+/// settings back.
+pub async fn update_actor_handler(
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    world: web::Data<WorldGraph>,
+    bus: web::Data<EventBus>,
+    path: web::Path<String>,
+    patch: web::Json<ActorPatch>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let patch = patch.into_inner();
+    if let Some(page) = &patch.location {
+        require_page(&world, page)?;
+    }
+    let mut manager = actor_manager.lock();
+    if !manager.actors.contains_key(id.as_str()) {
+        return Err(AppError::ActorNotFound(id));
+    }
+    let mut changes = Vec::new();
+    if let Some(page) = &patch.location {
+        manager.teleport(&id, page)?;
+        changes.push(format!("location={page}"));
+    }
+    let actor = manager
+        .actors
+        .get_mut(id.as_str())
+        .ok_or_else(|| AppError::ActorNotFound(id.clone()))?;
+    if let Some(name) = patch.name {
+        changes.push(format!("name={name}"));
+        actor.name = name;
+    }
+    if let Some(flags) = patch.flags {
+        changes.push(format!("flags={flags:?}"));
+        actor.flags = flags;
+    }
+    if let Some(health) = patch.health {
+        actor.state.health = health;
+        changes.push(format!("health={health}"));
+    }
+    if let Some(awake) = patch.awake {
+        actor.state.awake = awake;
+        changes.push(format!("awake={awake}"));
+    }
+    if let Some(action_points) = patch.action_points {
+        actor.action_points = action_points;
+        changes.push(format!("action_points={action_points}"));
+    }
+    if let Some(tick_rate) = patch.tick_rate {
+        actor.tick_rate = tick_rate.max(1);
+        changes.push(format!("tick_rate={}", actor.tick_rate));
+    }
+    if let Some(paused) = patch.paused {
+        actor.paused = paused;
+        changes.push(format!("paused={paused}"));
+    }
+    if !changes.is_empty() {
+        bus.publish(WorldEvent::ActorEdited {
+            actor: actor.id.clone(),
+            change: changes.join(", "),
+        });
+    }
+    Ok(HttpResponse::Ok().json(&*actor))
+}
+
+/// Hold an actor still, or let it go again
+fn set_paused(
+    actor_manager: &Mutex<ActorManager>,
+    bus: &EventBus,
+    id: &str,
+    paused: bool,
+) -> Result<HttpResponse, AppError> {
+    let mut manager = actor_manager.lock();
+    let actor = manager
+        .actors
+        .get_mut(id)
+        .ok_or_else(|| AppError::ActorNotFound(id.to_string()))?;
+    actor.paused = paused;
+    bus.publish(WorldEvent::ActorEdited {
+        actor: actor.id.clone(),
+        change: format!("paused={paused}"),
+    });
+    Ok(HttpResponse::Ok().json(&*actor))
+}
+
+/// Stop an actor taking turns until it is resumed
+pub async fn pause_actor_handler(
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    bus: web::Data<EventBus>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    set_paused(&actor_manager, &bus, &path, true)
+}
+
+/// Let a paused actor take its turns again
+pub async fn resume_actor_handler(
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    bus: web::Data<EventBus>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    set_paused(&actor_manager, &bus, &path, false)
+}
+
+/// Take an actor out of the world
+pub async fn delete_actor_handler(
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    bus: web::Data<EventBus>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let actor = actor_manager.lock().remove_actor(&path)?;
+    bus.publish(WorldEvent::ActorEdited {
+        actor: actor.id,
+        change: "deleted".to_string(),
+    });
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct WeatherOverrideBody {
+    #[serde(flatten)]
+    state: WeatherState,
+    #[serde(default)]
+    minutes: Option<u32>, // of world time; held until cleared without
+}
+
+#[derive(Serialize)]
+struct OverrideEntry {
+    page: PageId,
+    #[serde(flatten)]
+    held: WeatherOverride,
+}
+
+/// Pages whose weather is held by an override, sorted by page
+pub async fn weather_overrides_handler(
+    environment: web::Data<EnvironmentManager>,
+) -> Result<impl Responder, AppError> {
+    let overrides: Vec<OverrideEntry> = environment
+        .weather_overrides()?
+        .into_iter()
+        .map(|(page, held)| OverrideEntry { page, held })
+        .collect();
+    Ok(HttpResponse::Ok().json(overrides))
+}
+
+#[derive(Deserialize)]
+pub struct ForecastQuery {
+    hours: Option<u32>, // DEFAULT_FORECAST_HOURS if absent, at most MAX_FORECAST_HOURS
+    region: Option<RegionId>,
+}
+
+/// The likely weather over each region for the coming hours, sorted by region
+pub async fn forecast_handler(
+    environment: web::Data<EnvironmentManager>,
+    query: web::Query<ForecastQuery>,
+) -> Result<impl Responder, AppError> {
+    let hours = query
+        .hours
+        .unwrap_or(DEFAULT_FORECAST_HOURS)
+        .clamp(1, MAX_FORECAST_HOURS);
+    let mut forecast = environment.forecast(hours)?;
+    if let Some(region) = &query.region {
+        forecast.retain(|f| f.region == *region);
+    }
+    Ok(HttpResponse::Ok().json(forecast))
+}
+
+/// Hold the weather on a page over the simulation, for a while or until
+/// cleared (JSON body: `{"kind": "Stormy", "intensity": 0.8, "minutes": 90}`)
+pub async fn set_weather_handler(
+    environment: web::Data<EnvironmentManager>,
+    world: web::Data<WorldGraph>,
+    path: web::Path<String>,
+    body: web::Json<WeatherOverrideBody>,
+) -> Result<impl Responder, AppError> {
+    let page = PageId::from(path.as_str());
+    require_page(&world, &page)?;
+    let state = WeatherState {
+        kind: body.state.kind,
+        intensity: body.state.intensity.clamp(0.0, 1.0),
+    };
+    let lasts = body
+        .minutes
+        .map(|minutes| TimeDelta::minutes(minutes.into()));
+    environment.override_weather(&page, state, lasts, OverrideSource::Admin)?;
+    let held = environment
+        .weather_overrides()?
+        .into_iter()
+        .find(|(id, _)| *id == page)
+        .map(|(_, held)| held);
+    Ok(HttpResponse::Ok().json(held))
+}
+
+/// Let the simulated weather on a page show again
+pub async fn clear_weather_handler(
+    environment: web::Data<EnvironmentManager>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    environment.release_weather(&PageId::from(path.as_str()), OverrideSource::Admin)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Flags set on a page, sorted
+pub async fn page_flags_handler(
+    world: web::Data<WorldGraph>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let page = PageId::from(path.as_str());
+    require_page(&world, &page)?;
+    let all = world.page_flags();
+    let mut flags: Vec<&String> = all.get(&page).into_iter().flatten().collect();
+    flags.sort();
+    Ok(HttpResponse::Ok().json(flags))
+}
+
+/// Head count of the actors on a page, and whether players see them as a
+/// crowd. Admins only, like the actor reads; it counts players and hidden actors.
+pub async fn page_census_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let id = PageId::from(path.as_str());
+    let pages = world.snapshot();
+    let page = pages
+        .get(&id)
+        .ok_or_else(|| AppError::PageNotFound(id.to_string()))?;
+    let census = Census::of(actor_manager.lock().actors_on(&id));
+    let threshold = census::threshold(page);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "crowded": census.total > threshold,
+        "threshold": threshold,
+        "census": census,
+    })))
+}
+
+/// Set a flag on a page, as a fixture or an event in the world would
+pub async fn set_page_flag_handler(
+    world: web::Data<WorldGraph>,
+    path: web::Path<(String, String)>,
+) -> Result<impl Responder, AppError> {
+    let (page, flag) = path.into_inner();
+    world.set_page_flag(&PageId::from(page.as_str()), &flag, true)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Clear a flag on a page
+pub async fn clear_page_flag_handler(
+    world: web::Data<WorldGraph>,
+    path: web::Path<(String, String)>,
+) -> Result<impl Responder, AppError> {
+    let (page, flag) = path.into_inner();
+    world.set_page_flag(&PageId::from(page.as_str()), &flag, false)?;
+    Ok(HttpResponse::NoContent().finish())
+}
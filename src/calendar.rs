@@ -0,0 +1,70 @@
+use chrono::{DateTime, Datelike, Local, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Length of a lunar cycle in days (new moon to new moon)
+const SYNODIC_MONTH: f64 = 29.530_588;
+
+/// Calendar day 1: a new moon, 2000-01-06 18:14 UTC
+fn epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2000, 1, 6, 18, 14, 0)
+        .single()
+        .expect("calendar epoch is a valid date")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoonPhase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+impl std::fmt::Display for MoonPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl MoonPhase {
+    const ALL: [MoonPhase; 8] = [
+        MoonPhase::New,
+        MoonPhase::WaxingCrescent,
+        MoonPhase::FirstQuarter,
+        MoonPhase::WaxingGibbous,
+        MoonPhase::Full,
+        MoonPhase::WaningGibbous,
+        MoonPhase::LastQuarter,
+        MoonPhase::WaningCrescent,
+    ];
+
+    /// Phase for a moon `age` days into its cycle
+    fn from_age(age: f64) -> Self {
+        let eighth = (age / SYNODIC_MONTH * 8.0).round() as usize;
+        MoonPhase::ALL[eighth % 8]
+    }
+}
+
+/// The game calendar on a given world date
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Calendar {
+    pub day: i64, // days since the calendar epoch, starting at 1
+    pub weekday: Weekday,
+    pub moon: MoonPhase,
+}
+
+impl Calendar {
+    /// Calendar for world time `now`
+    pub fn at(now: DateTime<Local>) -> Self {
+        let since = now.with_timezone(&Utc) - epoch();
+        let days = since.num_seconds() as f64 / 86_400.0;
+        Calendar {
+            day: since.num_days() + 1,
+            weekday: now.weekday(),
+            moon: MoonPhase::from_age(days.rem_euclid(SYNODIC_MONTH)),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Crowds on a page. Past a page's threshold the actors there are summed up
+//! ("a crowd of 37") instead of listed, with a "look closer" view that goes
+//! through them a few at a time.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::actor::Actor;
+use crate::pages::Page;
+
+/// Actors a page lists one by one before they become a crowd, unless its
+/// `crowd` metadata says otherwise
+pub const DEFAULT_CROWD_THRESHOLD: usize = 12;
+
+/// Actors per page of the "look closer" view
+pub const CROWD_PAGE_SIZE: usize = 10;
+
+/// Head count of the actors on a page
+#[derive(Debug, Serialize)]
+pub struct Census {
+    pub total: usize,
+    pub kinds: Vec<(String, usize)>, // name -> how many go by it, most first
+}
+
+impl Census {
+    pub fn of<'a>(actors: impl IntoIterator<Item = &'a Actor>) -> Self {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for actor in actors {
+            *counts.entry(actor.name.as_str()).or_default() += 1;
+        }
+        let total = counts.values().sum();
+        let mut kinds: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        kinds.sort_by_key(|(_, count)| std::cmp::Reverse(*count)); // stable, so ties stay by name
+        Census { total, kinds }
+    }
+}
+
+/// One page of the "look closer" view, as `crowd` in page templates
+#[derive(Debug, Serialize)]
+pub struct CrowdPage {
+    pub census: Census,
+    pub closer: Option<usize>, // page being looked at, from 1; none for the summary
+    pub pages: usize,
+}
+
+/// Actors past this many on `page` are a crowd
+pub fn threshold(page: &Page) -> usize {
+    page.metadata
+        .get("crowd")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CROWD_THRESHOLD)
+}
+
+/// Cut `actors` down to what the player sees of a crowd: nobody on the
+/// summary, or page `closer` of the close look. Returns `None`, leaving
+/// everyone listed, when they don't make a crowd.
+pub fn thin_out(actors: &mut Vec<&Actor>, page: &Page, closer: Option<usize>) -> Option<CrowdPage> {
+    if actors.len() <= threshold(page) {
+        return None;
+    }
+    let census = Census::of(actors.iter().copied());
+    actors.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    let pages = actors.len().div_ceil(CROWD_PAGE_SIZE);
+    let closer = closer.map(|n| n.clamp(1, pages));
+    match closer {
+        Some(n) => {
+            let shown: Vec<&Actor> = actors
+                .iter()
+                .skip((n - 1) * CROWD_PAGE_SIZE)
+                .take(CROWD_PAGE_SIZE)
+                .copied()
+                .collect();
+            *actors = shown;
+        }
+        None => actors.clear(),
+    }
+    Some(CrowdPage {
+        census,
+        closer,
+        pages,
+    })
+}
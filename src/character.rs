@@ -0,0 +1,152 @@
+use actix_session::Session;
+use actix_web::{HttpResponse, Responder, web};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tera::{Context, Tera};
+use tracing::{error, info};
+
+use crate::clock::WorldClock;
+use crate::environment::EnvironmentManager;
+use crate::error::AppError;
+use crate::handler::START_PAGE;
+use crate::i18n::Translations;
+use crate::render;
+use crate::session::{flash, get_or_create_user_session, set_user_session};
+use crate::users::{ACCOUNT_KEY, AccountStore};
+use crate::worlds::Mount;
+
+/// Points to spread over stats (and the Nocturnal trait) at creation
+pub const STAT_POINTS: u8 = 6;
+/// Range each stat may take
+pub const MIN_STAT: u8 = 1;
+pub const MAX_STAT: u8 = 5;
+/// Stats of a player who hasn't made a character
+pub const DEFAULT_STAT: u8 = 2;
+
+const MAX_NAME_LEN: usize = 24;
+
+/// Who the player is playing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Character {
+    pub name: String,
+    pub stamina: u8,    // how many moves a minute before needing a rest
+    pub perception: u8, // spots hidden ways up to this
+    #[serde(default)]
+    pub nocturnal: bool, // sees by night without a light, but not underground
+}
+
+#[derive(Deserialize)]
+pub struct CharacterForm {
+    name: String,
+    stamina: u8,
+    perception: u8,
+    #[serde(default)]
+    nocturnal: Option<String>, // checkbox, present when ticked
+}
+
+impl CharacterForm {
+    /// Check the choices add up, returning the character or what's wrong
+    fn into_character(self) -> Result<Character, String> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(format!("Names are 1-{MAX_NAME_LEN} characters"));
+        }
+        let stats = [self.stamina, self.perception];
+        if stats.iter().any(|s| !(MIN_STAT..=MAX_STAT).contains(s)) {
+            return Err(format!("Stats go from {MIN_STAT} to {MAX_STAT}"));
+        }
+        let nocturnal = self.nocturnal.is_some();
+        let spent = self.stamina + self.perception + u8::from(nocturnal);
+        if spent != STAT_POINTS {
+            return Err(format!(
+                "Spend exactly {STAT_POINTS} points (Nocturnal costs 1); you spent {spent}"
+            ));
+        }
+        Ok(Character {
+            name,
+            stamina: self.stamina,
+            perception: self.perception,
+            nocturnal,
+        })
+    }
+}
+
+fn creation_page(
+    tera: &Tera,
+    mount: &Mount,
+    theme: &str,
+    error: Option<&str>,
+) -> Result<HttpResponse, AppError> {
+    let mut context = Context::new();
+    context.insert("base", &mount.base);
+    context.insert("theme", theme);
+    context.insert("error", &error);
+    context.insert("points", &STAT_POINTS);
+    context.insert("min_stat", &MIN_STAT);
+    context.insert("max_stat", &MAX_STAT);
+    let html = tera.render("character.html", &context)?;
+    Ok(match error {
+        Some(_) => HttpResponse::BadRequest().body(html),
+        None => HttpResponse::Ok().body(html),
+    })
+}
+
+fn back_to_game(mount: &Mount) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header(("Location", mount.path("/")))
+        .finish()
+}
+
+/// Character creation form, for players who don't have one yet
+pub async fn character_page_handler(
+    tera: web::Data<Tera>,
+    mount: web::Data<Mount>,
+    session: Session,
+    clock: web::Data<WorldClock>,
+    environment: web::Data<EnvironmentManager>,
+) -> Result<impl Responder, AppError> {
+    let user_session = get_or_create_user_session(&session, START_PAGE)?;
+    if user_session.character.is_some() {
+        return Ok(back_to_game(&mount));
+    }
+    let theme = render::theme(&clock, &environment, &user_session.current_page);
+    creation_page(&tera, &mount, &theme, None)
+}
+
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn create_character_handler(
+    tera: web::Data<Tera>,
+    mount: web::Data<Mount>,
+    session: Session,
+    clock: web::Data<WorldClock>,
+    environment: web::Data<EnvironmentManager>,
+    accounts: web::Data<AccountStore>,
+    translations: web::Data<Arc<Translations>>,
+    form: web::Form<CharacterForm>,
+) -> Result<impl Responder, AppError> {
+    let mut user_session = get_or_create_user_session(&session, START_PAGE)?;
+    if user_session.character.is_some() {
+        return Ok(back_to_game(&mount));
+    }
+    let character = match form.into_inner().into_character() {
+        Ok(character) => character,
+        Err(reason) => {
+            let theme = render::theme(&clock, &environment, &user_session.current_page);
+            return creation_page(&tera, &mount, &theme, Some(&reason));
+        }
+    };
+    info!("New character {}", character.name);
+    let lang = translations.language(user_session.language.as_deref());
+    flash(
+        &session,
+        translations.text(lang, "flash.welcome", &[("name", &character.name)]),
+    );
+    user_session.character = Some(character);
+    set_user_session(&session, &user_session);
+    if let Some(username) = session.get::<String>(ACCOUNT_KEY).ok().flatten()
+        && let Err(e) = accounts.save_character(&username, &user_session)
+    {
+        error!("Failed to save character for {username}: {e}");
+    }
+    Ok(back_to_game(&mount))
+}
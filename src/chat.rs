@@ -0,0 +1,105 @@
+use chrono::{DateTime, Local};
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::events::{EventBus, WorldEvent};
+use crate::pages::PageId;
+
+/// Messages kept per page; older ones scroll away
+const MESSAGES_PER_PAGE: usize = 20;
+/// Longest message a player may say, in characters
+pub const MAX_MESSAGE_LEN: usize = 200;
+/// Chance that a speaking NPC answers someone who talks near it
+const REPLY_CHANCE: f64 = 0.4;
+
+/// What NPCs say back when their dialogue has nothing that fits
+const CANNED_REPLIES: &[&str] = &[
+    "Mm-hm.",
+    "Is that so?",
+    "Can't say I've heard that before.",
+    "Funny you should mention it.",
+    "Hah! Good one.",
+    "If you say so.",
+];
+
+/// One line said on a page
+#[derive(Serialize, Debug, Clone)]
+pub struct ChatMessage {
+    pub at: String, // world time, as shown to players
+    pub speaker: String,
+    pub text: String,
+}
+
+/// Recent chat on every page, in memory only
+#[derive(Clone)]
+pub struct ChatLog {
+    pages: Arc<Mutex<HashMap<PageId, VecDeque<ChatMessage>>>>,
+    bus: EventBus,
+}
+
+impl ChatLog {
+    pub fn new(bus: EventBus) -> Self {
+        ChatLog {
+            pages: Arc::new(Mutex::new(HashMap::new())),
+            bus,
+        }
+    }
+
+    /// Record `text` as said by `speaker` on `page` and tell anyone listening
+    pub fn say(&self, at: DateTime<Local>, page: &PageId, speaker: &str, text: &str) {
+        let message = ChatMessage {
+            at: at.format("%H:%M").to_string(),
+            speaker: speaker.to_string(),
+            text: text.to_string(),
+        };
+        {
+            let mut pages = self.pages.lock().expect("Failed to lock Mutex");
+            let messages = pages.entry(page.clone()).or_default();
+            if messages.len() == MESSAGES_PER_PAGE {
+                messages.pop_front();
+            }
+            messages.push_back(message);
+        }
+        self.bus.publish(WorldEvent::ChatSaid {
+            page: page.clone(),
+            speaker: speaker.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    /// Everything still remembered on `page`, oldest first
+    pub fn messages(&self, page: &PageId) -> Vec<ChatMessage> {
+        self.pages
+            .lock()
+            .expect("Failed to lock Mutex")
+            .get(page)
+            .map(|messages| messages.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Tidy up what a player typed: trimmed, on one line, and not too long.
+/// `None` if there's nothing left to say.
+pub fn clean_message(text: &str) -> Option<String> {
+    let text: String = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_MESSAGE_LEN)
+        .collect();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Maybe answer a player: the NPC's dialogue line if it has one, or something canned
+pub fn npc_reply(line: Option<&str>) -> Option<String> {
+    let mut rng = rand::rng();
+    if !rng.random_bool(REPLY_CHANCE) {
+        return None;
+    }
+    line.or_else(|| CANNED_REPLIES.choose(&mut rng).copied())
+        .map(str::to_string)
+}
@@ -0,0 +1,287 @@
+use chrono::{DateTime, Local, TimeDelta, Timelike, Utc};
+use parking_lot::{Mutex, MutexGuard};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::environment::WorldTime;
+
+/// Real time between background world ticks, unless `CHOTT_TICK_INTERVAL_MS`
+/// or an admin says otherwise
+pub const TICK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Shortest tick interval allowed, so a typo can't spin the ticker
+pub const MIN_TICK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest tick interval allowed; slower than this the world hardly moves,
+/// and a fast-forward by whole intervals would run the clock off the end
+pub const MAX_TICK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Fastest the world clock may run, in world seconds per real second: a
+/// world day in under ten real seconds. Faster would soon carry world time
+/// past what a date can hold.
+pub const MAX_TIME_SCALE: f64 = 10_000.0;
+
+/// Time one tick may take before it is warned about, when
+/// `CHOTT_TICK_BUDGET_MS` isn't set
+pub const DEFAULT_TICK_BUDGET: Duration = Duration::from_millis(250);
+
+/// The tick budget from `CHOTT_TICK_BUDGET_MS`, or the default
+pub fn tick_budget_from_env() -> Duration {
+    std::env::var("CHOTT_TICK_BUDGET_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_TICK_BUDGET)
+}
+
+/// How the world clock starts out
+#[derive(Clone, Copy, Debug)]
+pub struct ClockConfig {
+    pub start: DateTime<Local>, // world time at startup
+    pub scale: f64,             // world seconds per real second
+    pub paused: bool,
+    pub tick_interval: Duration, // real time between background ticks
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            start: Local::now(),
+            scale: 1.0,
+            paused: false,
+            tick_interval: TICK_INTERVAL,
+        }
+    }
+}
+
+impl ClockConfig {
+    /// Defaults, overridden by `CHOTT_WORLD_START` (RFC 3339), `CHOTT_TIME_SCALE`
+    /// (up to `MAX_TIME_SCALE`), `CHOTT_CLOCK_PAUSED` and `CHOTT_TICK_INTERVAL_MS` if set
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+        let defaults = ClockConfig::default();
+        ClockConfig {
+            start: var("CHOTT_WORLD_START")
+                .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                .map(|t| t.with_timezone(&Local))
+                .unwrap_or(defaults.start),
+            scale: var("CHOTT_TIME_SCALE")
+                .and_then(|v| v.parse().ok())
+                .filter(|s: &f64| s.is_finite() && *s >= 0.0)
+                .unwrap_or(defaults.scale)
+                .min(MAX_TIME_SCALE),
+            paused: var("CHOTT_CLOCK_PAUSED").is_some_and(|v| v == "1" || v == "true"),
+            tick_interval: var("CHOTT_TICK_INTERVAL_MS")
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.tick_interval)
+                .clamp(MIN_TICK_INTERVAL, MAX_TICK_INTERVAL),
+        }
+    }
+}
+
+/// The last date a clock can show
+fn last_date() -> DateTime<Local> {
+    DateTime::<Utc>::MAX_UTC.with_timezone(&Local)
+}
+
+/// `at` moved on by `by`, stopping at the first or last date there is
+fn later(at: DateTime<Local>, by: TimeDelta) -> DateTime<Local> {
+    at.checked_add_signed(by).unwrap_or_else(|| {
+        if by < TimeDelta::zero() {
+            DateTime::<Utc>::MIN_UTC.with_timezone(&Local)
+        } else {
+            last_date()
+        }
+    })
+}
+
+/// Where the clock was at some real instant; world time runs on from there
+/// at `scale` unless paused.
+struct ClockState {
+    epoch: DateTime<Local>,
+    anchor: Instant,
+    scale: f64,
+    paused: bool,
+    tick_interval: Duration,
+}
+
+impl ClockState {
+    fn now(&self) -> DateTime<Local> {
+        if self.paused {
+            return self.epoch;
+        }
+        let elapsed = self.anchor.elapsed().mul_f64(self.scale);
+        // a clock left running long enough stops at the end of time
+        TimeDelta::from_std(elapsed)
+            .map_or_else(|_| last_date(), |elapsed| later(self.epoch, elapsed))
+    }
+
+    /// Fold the time passed so far into `epoch`, so the rate can change
+    fn reanchor(&mut self) {
+        self.epoch = self.now();
+        self.anchor = Instant::now();
+    }
+}
+
+/// The world's clock, decoupled from the host clock: it starts at a
+/// configurable date, runs at a configurable rate and can be paused or
+/// jumped ahead. Ticks, environment and templates all read time from here.
+#[derive(Clone)]
+pub struct WorldClock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+/// Snapshot of the clock for templates and admin responses
+#[derive(Serialize)]
+pub struct ClockStatus {
+    pub now: String,
+    pub hour: u8,
+    pub minute: u8,
+    pub scale: f64,
+    pub paused: bool,
+    pub tick_interval_ms: u64,
+}
+
+impl WorldClock {
+    pub fn new(config: ClockConfig) -> Self {
+        WorldClock {
+            state: Arc::new(Mutex::new(ClockState {
+                epoch: config.start,
+                anchor: Instant::now(),
+                scale: config.scale,
+                paused: config.paused,
+                tick_interval: config.tick_interval,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ClockState> {
+        self.state.lock()
+    }
+
+    /// Current date and time in the world
+    pub fn now(&self) -> DateTime<Local> {
+        self.lock().now()
+    }
+
+    /// Current time of day in the world
+    pub fn world_time(&self) -> WorldTime {
+        WorldTime::from_datetime(&self.now())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    /// Stop world time; it stays put until `resume`
+    pub fn pause(&self) {
+        let mut state = self.lock();
+        state.reanchor();
+        state.paused = true;
+    }
+
+    pub fn resume(&self) {
+        let mut state = self.lock();
+        state.anchor = Instant::now();
+        state.paused = false;
+    }
+
+    /// Change how many world seconds pass per real second, no more than
+    /// `MAX_TIME_SCALE` and no less than none
+    pub fn set_scale(&self, scale: f64) {
+        let mut state = self.lock();
+        state.reanchor();
+        state.scale = scale.clamp(0.0, MAX_TIME_SCALE);
+    }
+
+    /// Real time between background ticks; also how far each forced tick
+    /// moves the clock on
+    pub fn tick_interval(&self) -> Duration {
+        self.lock().tick_interval
+    }
+
+    /// Tick more or less often from the next tick on, no faster than
+    /// `MIN_TICK_INTERVAL` and no slower than `MAX_TICK_INTERVAL`
+    pub fn set_tick_interval(&self, interval: Duration) {
+        self.lock().tick_interval = interval.clamp(MIN_TICK_INTERVAL, MAX_TICK_INTERVAL);
+    }
+
+    /// Move the world clock forward by `by`, no further than the last date
+    pub fn advance(&self, by: TimeDelta) {
+        let mut state = self.lock();
+        state.epoch = later(state.epoch, by);
+    }
+
+    /// Move forward to the next time the clock reads `hour`:00
+    pub fn jump_to_hour(&self, hour: u8) {
+        let now = self.now();
+        let since_midnight = TimeDelta::seconds(now.num_seconds_from_midnight() as i64);
+        let mut delta = TimeDelta::hours(hour as i64) - since_midnight;
+        if delta <= TimeDelta::zero() {
+            delta += TimeDelta::days(1);
+        }
+        self.advance(delta);
+    }
+
+    pub fn status(&self) -> ClockStatus {
+        let state = self.lock();
+        let now = state.now();
+        ClockStatus {
+            now: now.to_rfc3339(),
+            hour: now.hour() as u8,
+            minute: now.minute() as u8,
+            scale: state.scale,
+            paused: state.paused,
+            tick_interval_ms: state.tick_interval.as_millis() as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_huge_scale_is_held_to_the_maximum() {
+        let clock = WorldClock::new(ClockConfig::default());
+        clock.set_scale(1e300);
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(clock.status().scale, MAX_TIME_SCALE);
+        assert!(clock.now() > Local::now());
+    }
+
+    #[test]
+    fn a_clock_past_the_last_date_stops_there() {
+        let clock = WorldClock::new(ClockConfig {
+            start: DateTime::<Utc>::MAX_UTC.with_timezone(&Local) - TimeDelta::seconds(1),
+            scale: MAX_TIME_SCALE,
+            ..ClockConfig::default()
+        });
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(clock.now(), last_date());
+    }
+
+    #[test]
+    fn a_huge_tick_interval_is_held_to_the_maximum() {
+        let clock = WorldClock::new(ClockConfig::default());
+        clock.set_tick_interval(Duration::from_millis(u64::MAX));
+        assert_eq!(clock.tick_interval(), MAX_TICK_INTERVAL);
+        clock.set_tick_interval(Duration::ZERO);
+        assert_eq!(clock.tick_interval(), MIN_TICK_INTERVAL);
+    }
+
+    #[test]
+    fn jumping_past_the_last_date_stops_there() {
+        let clock = WorldClock::new(ClockConfig {
+            start: last_date() - TimeDelta::days(1),
+            paused: true,
+            ..ClockConfig::default()
+        });
+        clock.advance(TimeDelta::days(2));
+        assert_eq!(clock.now(), last_date());
+        clock.jump_to_hour(6);
+        assert_eq!(clock.now(), last_date());
+    }
+}
@@ -0,0 +1,168 @@
+use rand::Rng;
+use tracing::info;
+
+use crate::actor::{ActorFlag, ActorId, ActorMap};
+use crate::events::WorldEvent;
+use crate::status::{self, POISON_ODDS, POISON_TICKS, Status};
+
+/// Most damage anyone's blow does, before counting what sort they are
+const BASE_MIGHT: i32 = 2;
+
+/// Land a blow from `attacker` on `target`, both actor ids: NPC against NPC,
+/// NPC against player or player against NPC alike. The target wakes up,
+/// loses health and holds a grudge against the attacker, going along with
+/// them no longer. None if either is gone or they aren't on the same page.
+pub fn strike(
+    actors: &mut ActorMap,
+    attacker: &ActorId,
+    target: &ActorId,
+    rng: &mut impl Rng,
+) -> Option<WorldEvent> {
+    let striker = actors.get(attacker)?;
+    let mut might = BASE_MIGHT;
+    if striker.has_flag(ActorFlag::Predatory) {
+        might += 2;
+    }
+    if striker.has_flag(ActorFlag::CanAttack) {
+        might += 1;
+    }
+    if striker.has_status(Status::Blessed) {
+        might += 1;
+    }
+    if striker.has_status(Status::Poisoned) {
+        might = (might - 1).max(1);
+    }
+    let venomous = striker.has_flag(ActorFlag::Predatory);
+    let page = striker.location.clone();
+
+    let victim = actors
+        .get_mut(target)
+        .filter(|a| a.location == page && a.travel.is_none())?;
+    let mut damage = rng.random_range(1..=might);
+    if !victim.state.awake {
+        damage += 1; // caught napping
+    }
+    victim.state.awake = true;
+    victim.state.health = (victim.state.health - damage).max(0);
+    victim.state.target = Some(attacker.clone());
+    if victim.state.following.as_ref() == Some(attacker) {
+        victim.state.following = None;
+    }
+    if venomous && victim.has_flag(ActorFlag::Organic) && rng.random_ratio(1, POISON_ODDS) {
+        status::afflict(&mut victim.state.effects, Status::Poisoned, POISON_TICKS);
+    }
+    info!(%attacker, %target, damage, health = victim.state.health, "Blow lands.");
+    Some(WorldEvent::ActorWounded {
+        actor: target.clone(),
+        attacker: attacker.clone(),
+        page,
+        damage,
+        health: victim.state.health,
+    })
+}
+
+/// A player's attack on the actor `target`, as the events it makes; or the
+/// text key of why they can't. Only awake actors on the player's page can be
+/// attacked, and never other players.
+pub fn player_attack(
+    actors: &mut ActorMap,
+    player: &ActorId,
+    target: &str,
+) -> Result<Vec<WorldEvent>, &'static str> {
+    let page = actors
+        .get(player)
+        .ok_or("flash.nothing_here")?
+        .location
+        .clone();
+    let victim = actors
+        .get(target)
+        .filter(|a| a.id != *player && a.location == page && a.travel.is_none() && a.state.awake)
+        .ok_or("flash.nothing_here")?;
+    if victim.has_flag(ActorFlag::Player) {
+        return Err("flash.no_brawling");
+    }
+    let target = victim.id.clone();
+    let attacked = WorldEvent::ActorAttacked {
+        attacker: player.clone(),
+        target: target.clone(),
+        page,
+    };
+    let wounded = strike(actors, player, &target, &mut rand::rng()).ok_or("flash.nothing_here")?;
+    Ok(vec![attacked, wounded])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use crate::actor::tests::critters;
+    use crate::pages::PageId;
+
+    /// A player and a plain critter of `health` face to face on one page
+    fn standoff(health: i32) -> (ActorMap, ActorId, ActorId) {
+        let mut actors = ActorMap::new();
+        for (n, mut definition) in critters(2).into_iter().enumerate() {
+            definition.location = PageId::from("ring-0");
+            if n == 0 {
+                definition.flags = vec![ActorFlag::Player];
+            } else {
+                definition.flags = vec![ActorFlag::Organic];
+                definition.health = health;
+            }
+            let actor = Actor::from_definition(definition);
+            actors.insert(actor.id.clone(), actor);
+        }
+        (
+            actors,
+            ActorId::from("critter-0"),
+            ActorId::from("critter-1"),
+        )
+    }
+
+    #[test]
+    fn a_player_lands_a_blow_and_makes_an_enemy() {
+        let (mut actors, player, hare) = standoff(10);
+        let events = player_attack(&mut actors, &player, hare.as_str()).unwrap();
+        assert!(matches!(
+            &events[0],
+            WorldEvent::ActorAttacked { attacker, target, .. } if *attacker == player && *target == hare
+        ));
+        let WorldEvent::ActorWounded { damage, health, .. } = events[1] else {
+            panic!("no wound: {events:?}");
+        };
+        assert!((1..=BASE_MIGHT).contains(&damage));
+        assert_eq!(health, 10 - damage);
+        assert_eq!(actors[&hare].state.health, health);
+        assert_eq!(actors[&hare].state.target.as_ref(), Some(&player));
+    }
+
+    #[test]
+    fn a_blow_that_takes_the_last_health_strikes_down() {
+        let (mut actors, player, hare) = standoff(1);
+        let events = player_attack(&mut actors, &player, hare.as_str()).unwrap();
+        assert!(matches!(
+            events[1],
+            WorldEvent::ActorWounded { health: 0, .. }
+        ));
+        assert_eq!(actors[&hare].state.health, 0);
+    }
+
+    #[test]
+    fn there_is_no_attacking_what_isnt_there_to_attack() {
+        let (mut actors, player, hare) = standoff(10);
+        assert_eq!(
+            player_attack(&mut actors, &player, "nobody").err(),
+            Some("flash.nothing_here")
+        );
+        assert_eq!(
+            player_attack(&mut actors, &player, player.as_str()).err(),
+            Some("flash.nothing_here")
+        );
+        actors.get_mut(&hare).unwrap().location = PageId::from("ring-1");
+        assert_eq!(
+            player_attack(&mut actors, &player, hare.as_str()).err(),
+            Some("flash.nothing_here")
+        );
+        assert_eq!(actors[&hare].state.health, 10);
+    }
+}
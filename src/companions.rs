@@ -0,0 +1,57 @@
+//! Companions: NPCs who think well enough of a player to go along with
+//! them. A companion keeps to the player's side in place of its usual
+//! doings, and arrives with them wherever they go, on the same page render.
+//! Rideable companions carry the player as mounts; see `mounts`.
+
+use crate::actor::{ActorFlag, ActorManager};
+use crate::reputation::Regard;
+use crate::session::UserSession;
+
+/// Most companions one player can have along at once
+pub const MAX_COMPANIONS: usize = 2;
+
+/// Ask the actor `id` to come along with the player. Either way comes back
+/// as a `flash.*` text key to tell the player, with `{name}` to fill in.
+pub fn ask_along(
+    manager: &mut ActorManager,
+    player: &UserSession,
+    id: &str,
+) -> Result<&'static str, &'static str> {
+    let leader = &player.player_id;
+    let company = manager.companions_of(leader).count();
+    let actor = manager.actors.get_mut(id).ok_or("flash.nothing_here")?;
+    let comes_along = actor.has_flag(ActorFlag::CanSpeak) || actor.has_flag(ActorFlag::Rideable);
+    if !comes_along || actor.has_flag(ActorFlag::Player) {
+        return Err("flash.companion_no_mind");
+    }
+    if actor.state.following.as_ref() == Some(leader) {
+        return Ok("flash.companion_joins");
+    }
+    // only friends, and not anyone set on a fight or already spoken for
+    if player.reputation.regard(actor) < Regard::Friendly
+        || actor.state.target.is_some()
+        || actor.state.following.is_some()
+    {
+        return Err("flash.companion_declined");
+    }
+    if company >= MAX_COMPANIONS {
+        return Err("flash.companion_full");
+    }
+    actor.state.following = Some(leader.clone());
+    actor.queue.clear(); // whatever it meant to do can wait
+    Ok("flash.companion_joins")
+}
+
+/// Send the player's companion `id` on its own way again
+pub fn part_ways(
+    manager: &mut ActorManager,
+    player: &UserSession,
+    id: &str,
+) -> Result<&'static str, &'static str> {
+    let actor = manager.actors.get_mut(id).ok_or("flash.nothing_here")?;
+    if actor.state.following.as_ref() != Some(&player.player_id) {
+        return Err("flash.companion_not_yours");
+    }
+    actor.state.following = None;
+    Ok("flash.companion_leaves")
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::calendar::{Calendar, MoonPhase};
+use crate::environment::{Environment, PartOfDay, WorldTime};
+use crate::events::EventLog;
+use crate::items::ItemId;
+use crate::pages::PageId;
+use crate::scripting::{ScriptContext, ScriptHost};
+use crate::session::{JournalKind, UserSession};
+use crate::weather::WeatherKind;
+use crate::world::PageFlags;
+
+/// A predicate over the player, the page they are on, and recent world
+/// history. Shared by description variants, dialogue and anything else that
+/// needs to vary content by circumstance.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub enum Condition {
+    #[default]
+    Always,
+    FirstVisit,
+    ReturnVisit,
+    Flag(String), // session/quest flag is set
+    HasItem(ItemId),
+    Day,
+    Night,
+    TimeOfDay(PartOfDay), // finer than Day/Night, e.g. "EarlyEvening"
+    Weather(WeatherKind), // current weather on this page
+    Moon(MoonPhase),
+    /// An event of `kind` (e.g. "ActorAttacked") happened within the last
+    /// `within_hours`, on `page` if given
+    RecentEvent {
+        kind: String,
+        page: Option<PageId>,
+        within_hours: u32,
+    },
+    /// The weather was `weather` at some point within the last `within_hours`
+    RecentWeather {
+        weather: WeatherKind,
+        page: Option<PageId>,
+        within_hours: u32,
+    },
+    /// The player's journal shows them on `page` within the last `within_hours`
+    PlayerVisited {
+        page: PageId,
+        within_hours: u32,
+    },
+    /// The player has been to `page` at some point
+    Visited(PageId),
+    /// An NPC with this actor id has spoken to the player
+    TalkedTo(String),
+    /// The player has finished the quest with this id
+    QuestDone(String),
+    /// The player's standing with an actor id or faction is at least `at_least`
+    Reputation {
+        with: String,
+        at_least: i32,
+    },
+    /// `flag` is set on `page`, or on the page in question if not given
+    PageFlag {
+        flag: String,
+        page: Option<PageId>,
+    },
+    /// A script function, "file::function", returns true
+    Script(String),
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+/// Everything a condition may look at
+pub struct ConditionContext<'a> {
+    pub session: &'a UserSession,
+    pub page: &'a PageId,
+    pub now: DateTime<Local>, // world clock
+    pub environment: Option<&'a Environment>,
+    pub events: &'a EventLog,
+    pub page_flags: Arc<PageFlags>,      // as they stand
+    pub scripts: Option<&'a ScriptHost>, // without, script conditions never hold
+}
+
+impl ConditionContext<'_> {
+    fn since(&self, hours: u32) -> DateTime<Local> {
+        self.now - TimeDelta::hours(hours as i64)
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, ctx: &ConditionContext) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::FirstVisit => ctx.session.visit_count(ctx.page) <= 1,
+            Condition::ReturnVisit => ctx.session.visit_count(ctx.page) > 1,
+            Condition::Flag(flag) => ctx.session.has_flag(flag),
+            Condition::HasItem(item) => ctx.session.has_item(item),
+            Condition::Day => WorldTime::from_datetime(&ctx.now).is_daytime(),
+            Condition::Night => WorldTime::from_datetime(&ctx.now).is_night(),
+            Condition::TimeOfDay(part) => WorldTime::from_datetime(&ctx.now).part_of_day() == *part,
+            Condition::Weather(weather) => ctx.environment.is_some_and(|e| e.weather() == *weather),
+            Condition::Moon(phase) => Calendar::at(ctx.now).moon == *phase,
+            Condition::RecentEvent {
+                kind,
+                page,
+                within_hours,
+            } => ctx.events.any_since(ctx.since(*within_hours), |event| {
+                event.kind() == kind && page.as_ref().is_none_or(|p| event.concerns(p))
+            }),
+            Condition::RecentWeather {
+                weather,
+                page,
+                within_hours,
+            } => ctx.events.any_since(ctx.since(*within_hours), |event| {
+                event.weather() == Some(*weather) && page.as_ref().is_none_or(|p| event.concerns(p))
+            }),
+            Condition::PlayerVisited { page, within_hours } => {
+                let since = ctx.since(*within_hours).timestamp();
+                ctx.session.journal.iter().any(|entry| {
+                    entry.at >= since && entry.page == *page && entry.kind == JournalKind::Arrived
+                })
+            }
+            Condition::Visited(page) => ctx.session.has_visited(page),
+            Condition::TalkedTo(actor) => ctx.session.talked_to.contains(actor),
+            Condition::QuestDone(quest) => ctx.session.quests.get(quest).is_some_and(|q| q.done),
+            Condition::Reputation { with, at_least } => {
+                ctx.session.reputation.with(with) >= *at_least
+            }
+            Condition::PageFlag { flag, page } => ctx
+                .page_flags
+                .get(page.as_ref().unwrap_or(ctx.page))
+                .is_some_and(|flags| flags.contains(flag)),
+            Condition::Script(script) => ctx.scripts.is_some_and(|scripts| {
+                let hour = WorldTime::from_datetime(&ctx.now).hour;
+                let script_ctx = ScriptContext::new(ctx.page, hour, ctx.page_flags.clone())
+                    .player(&ctx.session.flags);
+                scripts.check(script, script_ctx)
+            }),
+            Condition::Not(inner) => !inner.holds(ctx),
+            Condition::All(all) => all.iter().all(|c| c.holds(ctx)),
+            Condition::Any(any) => any.iter().any(|c| c.holds(ctx)),
+        }
+    }
+}
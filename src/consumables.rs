@@ -0,0 +1,90 @@
+//! Using items: what an item does when used, and how many uses it has in
+//! it. Uses spent are kept per kind of item against the one in hand; once
+//! it has none left it is used up and gone, and the next one starts fresh.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::actor::{Actor, PLAYER_HEALTH};
+use crate::items::{ItemCatalog, ItemId};
+use crate::session::UserSession;
+use crate::status::{self, Status};
+
+/// Something using an item does for the player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ItemEffect {
+    Rest(usize), // forgets this many recent moves against their stamina
+    Heal(i32),   // health back, up to what they started with
+    Grant { flag: String, minutes: i64 }, // a flag, for this long in world time
+    Afflict { status: Status, ticks: u32 }, // a status, for this many world ticks
+    Cure(Status), // takes a status off
+}
+
+/// Uses left in the `item` the player has in hand; None if it never runs out
+pub fn uses_left(player: &UserSession, item: &ItemId, catalog: &ItemCatalog) -> Option<u32> {
+    let charges = catalog.get(item)?.charges?;
+    let spent = player.wear.get(item).copied().unwrap_or_default();
+    Some(charges.saturating_sub(spent))
+}
+
+/// Use one of the player's `item`, healing their `actor` if it heals, and
+/// putting on or taking off its statuses. Either
+/// way comes back as a `flash.*` text key to tell them, with `{item}` to
+/// fill in.
+pub fn use_item(
+    player: &mut UserSession,
+    item: &ItemId,
+    catalog: &ItemCatalog,
+    actor: Option<&mut Actor>,
+    now: DateTime<Local>,
+) -> Result<&'static str, &'static str> {
+    if !player.has_item(item) {
+        return Err("flash.use_not_carried");
+    }
+    let definition = catalog.get(item).ok_or("flash.not_usable")?;
+    if definition.effects.is_empty() {
+        return Err("flash.not_usable");
+    }
+
+    let mut actor = actor;
+    for effect in &definition.effects {
+        match effect {
+            ItemEffect::Rest(moves) => {
+                let kept = player.recent_moves.len().saturating_sub(*moves);
+                player.recent_moves.truncate(kept);
+            }
+            ItemEffect::Heal(amount) => {
+                if let Some(actor) = actor.as_deref_mut() {
+                    actor.state.health = (actor.state.health + amount).min(PLAYER_HEALTH);
+                }
+            }
+            ItemEffect::Grant { flag, minutes } => {
+                player.grant_flag(flag, now.timestamp() + minutes * 60);
+            }
+            ItemEffect::Afflict { status, ticks } => {
+                if let Some(actor) = actor.as_deref_mut() {
+                    status::afflict(&mut actor.state.effects, *status, *ticks);
+                }
+            }
+            ItemEffect::Cure(status) => {
+                if let Some(actor) = actor.as_deref_mut() {
+                    status::cure(&mut actor.state.effects, *status);
+                }
+            }
+        }
+    }
+
+    let Some(charges) = definition.charges else {
+        return Ok("flash.used");
+    };
+    let spent = player.wear.entry(item.clone()).or_default();
+    *spent += 1;
+    if *spent < charges {
+        return Ok("flash.used");
+    }
+    player.wear.remove(item);
+    if let Some(at) = player.inventory.iter().position(|i| i == item) {
+        player.inventory.remove(at);
+    }
+    Ok("flash.used_up")
+}
@@ -0,0 +1,162 @@
+//! Contagion: a sickness that goes around. Organic actors sharing a page
+//! with the sick may catch it, the more sick about the likelier; it lies
+//! low a while before it shows, runs its course, and leaves them proof
+//! against it for a good while after, so an outbreak burns itself out.
+//! Getting soaked through now and then starts one off.
+
+use rand::Rng;
+
+use crate::actor::{Actor, ActorFlag, ActorId, ActorMap};
+use crate::status::{self, Status};
+
+/// Ticks a sickness lies low, catching but not yet showing
+pub const INCUBATION_TICKS: u32 = 30;
+
+/// Ticks it takes to run its course once it shows
+pub const SICK_TICKS: u32 = 60;
+
+/// Ticks someone who got over it can't catch it again
+pub const IMMUNE_TICKS: u32 = 400;
+
+/// Each sick actor on a page gives the others one chance in this many a
+/// tick of catching it off them
+const CATCH_ODDS: u32 = 40;
+
+/// One in this many ticks spent soaked through starts a sickness
+const CHILL_ODDS: u32 = 2000;
+
+/// Whether `actor` could catch it: organic, and neither has it nor had it lately
+pub fn susceptible(actor: &Actor) -> bool {
+    actor.has_flag(ActorFlag::Organic)
+        && !actor
+            .state
+            .effects
+            .iter()
+            .any(|e| matches!(e.status, Status::Incubating | Status::Sick | Status::Immune))
+}
+
+/// Whether `actor` gives it to those about it; it's catching before it shows
+pub fn contagious(actor: &Actor) -> bool {
+    actor.travel.is_none()
+        && (actor.has_status(Status::Incubating) || actor.has_status(Status::Sick))
+}
+
+/// One tick of the sickness going around among the actors `here`, all on one
+/// page. Those on the road are passing through and neither give nor catch it.
+pub fn spread(actors: &mut ActorMap, here: &[ActorId], rng: &mut impl Rng) {
+    let carriers = here
+        .iter()
+        .filter_map(|id| actors.get(id))
+        .filter(|a| !a.paused && contagious(a))
+        .count() as u32;
+    let mut here: Vec<&ActorId> = here.iter().collect();
+    here.sort(); // each rolls in turn, the same way every time
+    for id in here {
+        let Some(actor) = actors
+            .get_mut(id)
+            .filter(|a| !a.paused && a.travel.is_none() && susceptible(a))
+        else {
+            continue;
+        };
+        let caught = rng.random_range(0..CATCH_ODDS) < carriers
+            || (actor.has_status(Status::Soaked) && rng.random_ratio(1, CHILL_ODDS));
+        if caught {
+            status::afflict(
+                &mut actor.state.effects,
+                Status::Incubating,
+                INCUBATION_TICKS,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Travel;
+    use crate::actor::tests::{Setting, critters, seeded};
+    use crate::pages::PageId;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn sicken(actor: &mut Actor) {
+        status::afflict(&mut actor.state.effects, Status::Sick, SICK_TICKS);
+    }
+
+    fn caught(actor: &Actor) -> bool {
+        actor.has_status(Status::Incubating)
+    }
+
+    #[test]
+    fn a_page_thick_with_the_sick_passes_it_to_all_who_can_catch_it() {
+        // as many sick about as the odds, so whoever can catch it does
+        let mut actors = ActorMap::new();
+        for mut definition in critters(CATCH_ODDS as usize + 5) {
+            definition.location = PageId::from("ring-0");
+            definition.flags = vec![ActorFlag::Organic];
+            let actor = Actor::from_definition(definition);
+            actors.insert(actor.id.clone(), actor);
+        }
+        let id = |n: u32| ActorId::from(format!("critter-{n}").as_str());
+        for n in 0..CATCH_ODDS {
+            sicken(actors.get_mut(&id(n)).unwrap());
+        }
+        let [healthy, immune, stone, on_the_road, elsewhere] =
+            [0, 1, 2, 3, 4].map(|n| id(CATCH_ODDS + n));
+        status::afflict(
+            &mut actors.get_mut(&immune).unwrap().state.effects,
+            Status::Immune,
+            1,
+        );
+        actors.get_mut(&stone).unwrap().flags.clear();
+        actors.get_mut(&on_the_road).unwrap().travel = Some(Travel {
+            to: PageId::from("ring-1"),
+            remaining: 2,
+        });
+        let here: Vec<ActorId> = actors
+            .keys()
+            .filter(|id| **id != elsewhere)
+            .cloned()
+            .collect();
+
+        spread(&mut actors, &here, &mut StdRng::seed_from_u64(7));
+        assert!(caught(&actors[&healthy]));
+        for spared in [&immune, &stone, &on_the_road, &elsewhere] {
+            assert!(!caught(&actors[spared]), "{spared} caught it");
+        }
+    }
+
+    /// Who has caught it after one seeded tick of half a crowd sick
+    fn outbreak(around: &Setting, seed: u64) -> Vec<ActorId> {
+        let mut world = around.world(60);
+        for n in 0..60 {
+            let id = format!("critter-{n}");
+            world.teleport(&id, &PageId::from("ring-0")).unwrap();
+            let actor = world.actors.get_mut(id.as_str()).unwrap();
+            actor.flags.push(ActorFlag::Organic);
+            if n % 3 == 0 {
+                sicken(actor);
+            }
+        }
+        let mut record = seeded(around, &world, &[]);
+        record.seed = seed;
+        world.replay_tick(record, &around.pages);
+        let mut caught: Vec<ActorId> = world
+            .actors
+            .values()
+            .filter(|a| caught(a))
+            .map(|a| a.id.clone())
+            .collect();
+        caught.sort();
+        caught
+    }
+
+    #[test]
+    fn sickness_goes_round_by_the_ticks_own_rolls() {
+        let around = Setting::new();
+        let first = outbreak(&around, 7);
+        assert!(!first.is_empty() && first.len() < 40, "{first:?}");
+        assert_eq!(first, outbreak(&around, 7), "same seed, another outbreak");
+        assert_ne!(first, outbreak(&around, 8), "the seed made no difference");
+    }
+}
@@ -0,0 +1,211 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::actor::ActorId;
+use crate::error::AppError;
+
+/// Things a player does that are throttled, each on its own cooldown
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerAction {
+    Move,
+    Take,
+    Emote,
+    Say,
+    Trade,
+    Craft,
+    Interact,
+    Attack,
+}
+
+impl PlayerAction {
+    const ALL: [PlayerAction; 8] = [
+        PlayerAction::Move,
+        PlayerAction::Take,
+        PlayerAction::Emote,
+        PlayerAction::Say,
+        PlayerAction::Trade,
+        PlayerAction::Craft,
+        PlayerAction::Interact,
+        PlayerAction::Attack,
+    ];
+
+    fn verb(self) -> &'static str {
+        match self {
+            PlayerAction::Move => "move on",
+            PlayerAction::Take => "pick something up",
+            PlayerAction::Emote => "do that",
+            PlayerAction::Say => "speak",
+            PlayerAction::Trade => "trade",
+            PlayerAction::Craft => "make anything",
+            PlayerAction::Interact => "do that",
+            PlayerAction::Attack => "strike",
+        }
+    }
+}
+
+/// Least real time, in milliseconds, between two of the same action, and
+/// when each player last took each one. Those times are kept here on the
+/// server, so an old cookie played back can't reset them.
+#[derive(Clone, Debug)]
+pub struct Cooldowns {
+    pub move_ms: i64,
+    pub take_ms: i64,
+    pub emote_ms: i64,
+    pub say_ms: i64,
+    pub trade_ms: i64,
+    pub craft_ms: i64,
+    pub interact_ms: i64,
+    pub attack_ms: i64,
+    last_acted: Arc<Mutex<HashMap<ActorId, HashMap<PlayerAction, i64>>>>, // real unix milliseconds
+}
+
+impl Default for Cooldowns {
+    fn default() -> Self {
+        Cooldowns {
+            move_ms: 1_000,
+            take_ms: 1_000,
+            emote_ms: 3_000,
+            say_ms: 2_000,
+            trade_ms: 1_000,
+            craft_ms: 2_000,
+            interact_ms: 1_000,
+            attack_ms: 2_000,
+            last_acted: Arc::default(),
+        }
+    }
+}
+
+impl Cooldowns {
+    /// Defaults, overridden by `CHOTT_COOLDOWN_{MOVE,TAKE,EMOTE,SAY,TRADE,CRAFT,INTERACT,ATTACK}_MS` if set
+    pub fn from_env() -> Self {
+        let ms = |var: &str| std::env::var(var).ok().and_then(|v| v.parse().ok());
+        let defaults = Cooldowns::default();
+        Cooldowns {
+            move_ms: ms("CHOTT_COOLDOWN_MOVE_MS").unwrap_or(defaults.move_ms),
+            take_ms: ms("CHOTT_COOLDOWN_TAKE_MS").unwrap_or(defaults.take_ms),
+            emote_ms: ms("CHOTT_COOLDOWN_EMOTE_MS").unwrap_or(defaults.emote_ms),
+            say_ms: ms("CHOTT_COOLDOWN_SAY_MS").unwrap_or(defaults.say_ms),
+            trade_ms: ms("CHOTT_COOLDOWN_TRADE_MS").unwrap_or(defaults.trade_ms),
+            craft_ms: ms("CHOTT_COOLDOWN_CRAFT_MS").unwrap_or(defaults.craft_ms),
+            interact_ms: ms("CHOTT_COOLDOWN_INTERACT_MS").unwrap_or(defaults.interact_ms),
+            attack_ms: ms("CHOTT_COOLDOWN_ATTACK_MS").unwrap_or(defaults.attack_ms),
+            ..defaults
+        }
+    }
+
+    fn for_action(&self, action: PlayerAction) -> i64 {
+        match action {
+            PlayerAction::Move => self.move_ms,
+            PlayerAction::Take => self.take_ms,
+            PlayerAction::Emote => self.emote_ms,
+            PlayerAction::Say => self.say_ms,
+            PlayerAction::Trade => self.trade_ms,
+            PlayerAction::Craft => self.craft_ms,
+            PlayerAction::Interact => self.interact_ms,
+            PlayerAction::Attack => self.attack_ms,
+        }
+    }
+
+    /// Let `player` take all of `actions` at real time `now` (unix
+    /// milliseconds), or, if any is too soon, none of them, refusing with
+    /// how long they have to wait. An action asked for twice is too soon
+    /// the second time.
+    pub fn spend(
+        &self,
+        player: &ActorId,
+        actions: &[PlayerAction],
+        now: i64,
+    ) -> Result<(), AppError> {
+        let longest = PlayerAction::ALL
+            .into_iter()
+            .map(|action| self.for_action(action))
+            .max()
+            .unwrap_or_default();
+        let mut last_acted = self.last_acted.lock();
+        // forget players whose every cooldown has run out
+        last_acted.retain(|_, acted| acted.values().any(|last| last + longest > now));
+        let mut acted = last_acted.get(player).cloned().unwrap_or_default();
+        for &action in actions {
+            if let Some(last) = acted.get(&action) {
+                let wait = last + self.for_action(action) - now;
+                if wait > 0 {
+                    return Err(AppError::TooFast(format!(
+                        "You are out of breath. You can {} again in {:.1} seconds.",
+                        action.verb(),
+                        wait as f64 / 1000.0
+                    )));
+                }
+            }
+            acted.insert(action, now);
+        }
+        if !acted.is_empty() {
+            last_acted.insert(player.clone(), acted);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: &str) -> ActorId {
+        ActorId::from(id)
+    }
+
+    #[test]
+    fn an_action_waits_out_its_cooldown() {
+        let cooldowns = Cooldowns::default();
+        let alice = player("alice");
+        cooldowns.spend(&alice, &[PlayerAction::Move], 0).unwrap();
+        assert!(cooldowns.spend(&alice, &[PlayerAction::Move], 999).is_err());
+        cooldowns
+            .spend(&alice, &[PlayerAction::Move], 1_000)
+            .unwrap();
+    }
+
+    #[test]
+    fn each_action_and_each_player_has_its_own_cooldown() {
+        let cooldowns = Cooldowns::default();
+        let (alice, bob) = (player("alice"), player("bob"));
+        cooldowns.spend(&alice, &[PlayerAction::Move], 0).unwrap();
+        cooldowns.spend(&alice, &[PlayerAction::Take], 1).unwrap();
+        cooldowns.spend(&bob, &[PlayerAction::Move], 2).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_spent_if_any_action_is_too_soon() {
+        let cooldowns = Cooldowns::default();
+        let alice = player("alice");
+        cooldowns.spend(&alice, &[PlayerAction::Say], 0).unwrap();
+        let refused = cooldowns.spend(&alice, &[PlayerAction::Move, PlayerAction::Say], 500);
+        assert!(matches!(refused, Err(AppError::TooFast(_))));
+        // the move wasn't spent along with the refused say
+        cooldowns.spend(&alice, &[PlayerAction::Move], 600).unwrap();
+    }
+
+    #[test]
+    fn an_action_asked_for_twice_at_once_is_too_soon() {
+        let cooldowns = Cooldowns::default();
+        let alice = player("alice");
+        let twice = [PlayerAction::Take, PlayerAction::Take];
+        assert!(cooldowns.spend(&alice, &twice, 0).is_err());
+        cooldowns.spend(&alice, &[PlayerAction::Take], 0).unwrap();
+    }
+
+    #[test]
+    fn clones_share_what_players_have_spent() {
+        let cooldowns = Cooldowns::default();
+        let alice = player("alice");
+        cooldowns.spend(&alice, &[PlayerAction::Attack], 0).unwrap();
+        assert!(
+            cooldowns
+                .clone()
+                .spend(&alice, &[PlayerAction::Attack], 1)
+                .is_err()
+        );
+    }
+}
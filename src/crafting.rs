@@ -0,0 +1,172 @@
+use serde::Deserialize;
+use std::path::Path;
+use tracing::info;
+
+use crate::error::AppError;
+use crate::items::{ItemCatalog, ItemId};
+use crate::session::UserSession;
+
+/// Default location of the recipes file, relative to the working directory
+pub const DEFAULT_RECIPES_PATH: &str = "data/recipes.toml";
+
+/// Items that combine into another. The inputs are used up.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Recipe {
+    pub id: String,
+    pub inputs: Vec<ItemId>, // in any order; list an item twice to need two
+    pub output: ItemId,
+    #[serde(default)]
+    pub text: Option<String>, // told to the player on crafting it
+}
+
+#[derive(Deserialize)]
+struct RecipeFile {
+    #[serde(default)]
+    recipe: Vec<Recipe>,
+}
+
+/// Every known recipe, in file order
+#[derive(Default)]
+pub struct RecipeBook {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    /// Read recipes from a TOML file, checking they only name known items
+    pub fn load(path: &Path, catalog: &ItemCatalog) -> Result<Self, AppError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: RecipeFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        for recipe in &file.recipe {
+            if recipe.inputs.len() < 2 {
+                return Err(AppError::OtherError(format!(
+                    "Recipe '{}' needs at least two inputs",
+                    recipe.id
+                )));
+            }
+            if let Some(unknown) = recipe
+                .inputs
+                .iter()
+                .chain([&recipe.output])
+                .find(|item| !catalog.contains_key(item))
+            {
+                return Err(AppError::OtherError(format!(
+                    "Recipe '{}' uses unknown item '{unknown}'",
+                    recipe.id
+                )));
+            }
+        }
+        Ok(RecipeBook {
+            recipes: file.recipe,
+        })
+    }
+
+    /// The recipe using exactly `items`, in whatever order
+    pub fn find(&self, items: &[ItemId]) -> Option<&Recipe> {
+        let mut wanted: Vec<&ItemId> = items.iter().collect();
+        wanted.sort_by(|a, b| a.0.cmp(&b.0));
+        self.recipes.iter().find(|recipe| {
+            let mut inputs: Vec<&ItemId> = recipe.inputs.iter().collect();
+            inputs.sort_by(|a, b| a.0.cmp(&b.0));
+            inputs == wanted
+        })
+    }
+
+    /// Combine `items` from the player's inventory. On success returns what
+    /// to tell them; otherwise why it didn't work.
+    pub fn craft(&self, player: &mut UserSession, items: &[ItemId]) -> Result<String, String> {
+        let mut remaining = player.inventory.clone();
+        for item in items {
+            let Some(at) = remaining.iter().position(|carried| carried == item) else {
+                return Err(format!("You don't have enough {item}."));
+            };
+            remaining.remove(at);
+        }
+        let Some(recipe) = self.find(items) else {
+            return Err("Those don't go together.".to_string());
+        };
+
+        info!("Crafted {} from {:?}", recipe.output, recipe.inputs);
+        remaining.push(recipe.output.clone());
+        player.inventory = remaining;
+        Ok(recipe
+            .text
+            .clone()
+            .unwrap_or_else(|| format!("You make a {}.", recipe.output)))
+    }
+}
+
+/// Item ids from a comma-separated form field
+pub fn parse_items(field: &str) -> Vec<ItemId> {
+    field
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(ItemId::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(ids: &[&str]) -> Vec<ItemId> {
+        ids.iter().copied().map(ItemId::from).collect()
+    }
+
+    /// Rope and a hook make a grapple
+    fn book() -> RecipeBook {
+        RecipeBook {
+            recipes: vec![Recipe {
+                id: "grapple".to_string(),
+                inputs: items(&["rope", "hook"]),
+                output: ItemId::from("grapple"),
+                text: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn crafting_uses_up_the_inputs_in_any_order() {
+        let mut player = UserSession::new("workshop");
+        player.inventory = items(&["hook", "apple", "rope"]);
+        assert!(book().craft(&mut player, &items(&["hook", "rope"])).is_ok());
+        assert_eq!(player.inventory, items(&["apple", "grapple"]));
+    }
+
+    #[test]
+    fn nothing_is_used_up_when_a_craft_fails() {
+        let mut player = UserSession::new("workshop");
+        player.inventory = items(&["rope", "apple"]);
+        // short of a hook
+        assert!(
+            book()
+                .craft(&mut player, &items(&["rope", "hook"]))
+                .is_err()
+        );
+        // no such recipe
+        assert!(
+            book()
+                .craft(&mut player, &items(&["rope", "apple"]))
+                .is_err()
+        );
+        assert_eq!(player.inventory, items(&["rope", "apple"]));
+    }
+
+    #[test]
+    fn an_input_listed_twice_needs_two_of_it() {
+        let mut player = UserSession::new("workshop");
+        player.inventory = items(&["rope"]);
+        assert!(
+            book()
+                .craft(&mut player, &items(&["rope", "rope"]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn form_fields_split_into_item_ids() {
+        assert_eq!(parse_items(" rope, hook ,,"), items(&["rope", "hook"]));
+    }
+}
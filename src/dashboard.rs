@@ -0,0 +1,358 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use chrono::TimeDelta;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tera::{Context, Tera};
+use tracing::{info, warn};
+
+use crate::actor::ActorManager;
+use crate::admin::{
+    ADMIN_SESSION_KEY, AdminToken, IntervalQuery, SaveQuery, fast_forward, reset_world,
+};
+use crate::clock::WorldClock;
+use crate::environment::{EnvironmentManager, OverrideSource};
+use crate::error::AppError;
+use crate::events::{EventBus, EventLog};
+use crate::instances::Instances;
+use crate::pages::PageId;
+use crate::saves::SaveSlots;
+use crate::weather::{WeatherKind, WeatherState};
+use crate::world::WorldGraph;
+use crate::worlds::Mount;
+
+/// How many recent events the dashboard lists
+const DASHBOARD_EVENTS: usize = 50;
+
+/// One actor as listed on the dashboard
+#[derive(Serialize)]
+struct ActorRow {
+    id: String,
+    name: String,
+    location: String,
+    health: i32,
+    fatigue: u8,
+    hunger: u8,
+    awake: bool,
+    travelling_to: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EventRow {
+    at: String,
+    kind: &'static str,
+    detail: String,
+}
+
+#[derive(Serialize)]
+struct OverrideRow {
+    page: String,
+    weather: String,
+    intensity: f32,
+    until: Option<String>, // world time it lapses
+    source: String,
+}
+
+/// Back to the dashboard after a form post
+fn to_dashboard() -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header(("Location", "/admin"))
+        .finish()
+}
+
+/// Overview of the running world: clock, actors, recent events and weather overrides.
+/// Browsers that aren't logged in are sent to the login form.
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn dashboard_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    tera: web::Data<Tera>,
+    mount: web::Data<Mount>,
+    clock: web::Data<WorldClock>,
+    actors: web::Data<Arc<Mutex<ActorManager>>>,
+    world: web::Data<WorldGraph>,
+    environment: web::Data<EnvironmentManager>,
+    event_log: web::Data<EventLog>,
+    slots: web::Data<SaveSlots>,
+) -> Result<impl Responder, AppError> {
+    if token.check(&req).is_err() {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header(("Location", "/admin/login"))
+            .finish());
+    }
+
+    let manager = actors.lock();
+    let generation = manager.generation();
+    let mut actor_rows: Vec<ActorRow> = manager
+        .actors
+        .values()
+        .map(|actor| ActorRow {
+            id: actor.id.to_string(),
+            name: actor.name.clone(),
+            location: actor.location.to_string(),
+            health: actor.state.health,
+            fatigue: actor.state.fatigue,
+            hunger: actor.state.hunger,
+            awake: actor.state.awake,
+            travelling_to: actor.travel.as_ref().map(|t| t.to.to_string()),
+        })
+        .collect();
+    drop(manager);
+    actor_rows.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut pages: Vec<String> = world.snapshot().keys().map(|id| id.to_string()).collect();
+    pages.sort();
+
+    let events: Vec<EventRow> = event_log
+        .recent(DASHBOARD_EVENTS)
+        .into_iter()
+        .map(|entry| EventRow {
+            at: entry.at.format("%Y-%m-%d %H:%M").to_string(),
+            kind: entry.event.kind(),
+            detail: format!("{:?}", entry.event),
+        })
+        .collect();
+
+    let overrides: Vec<OverrideRow> = environment
+        .weather_overrides()?
+        .into_iter()
+        .map(|(page, held)| OverrideRow {
+            page: page.to_string(),
+            weather: held.state.kind.to_string(),
+            intensity: held.state.intensity,
+            until: held
+                .until
+                .map(|until| until.format("%Y-%m-%d %H:%M").to_string()),
+            source: format!("{:?}", held.source),
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("base", &mount.base);
+    context.insert("clock", &clock.status());
+    context.insert("actors", &actor_rows);
+    context.insert("generation", &generation);
+    context.insert("pages", &pages);
+    context.insert("events", &events);
+    context.insert("overrides", &overrides);
+    context.insert("saves", &slots.list()?);
+    context.insert("weathers", &WeatherKind::ALL.map(|kind| kind.to_string()));
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(tera.render("admin.html", &context)?))
+}
+
+pub async fn login_page_handler(tera: web::Data<Tera>) -> Result<impl Responder, AppError> {
+    let mut context = Context::new();
+    context.insert("failed", &false);
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(tera.render("admin_login.html", &context)?))
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    token: String,
+}
+
+/// Trade the admin token for a logged-in session cookie
+pub async fn login_handler(
+    session: actix_session::Session,
+    token: web::Data<AdminToken>,
+    tera: web::Data<Tera>,
+    form: web::Form<LoginForm>,
+) -> Result<impl Responder, AppError> {
+    if !token.matches(&form.token) {
+        warn!("Failed admin login");
+        let mut context = Context::new();
+        context.insert("failed", &true);
+        return Ok(HttpResponse::Forbidden()
+            .content_type("text/html")
+            .body(tera.render("admin_login.html", &context)?));
+    }
+    session
+        .insert(ADMIN_SESSION_KEY, true)
+        .map_err(|e| AppError::SessionError(e.to_string()))?;
+    info!("Admin logged in to the dashboard");
+    Ok(to_dashboard())
+}
+
+pub async fn logout_handler(session: actix_session::Session) -> impl Responder {
+    session.remove(ADMIN_SESSION_KEY);
+    HttpResponse::SeeOther()
+        .insert_header(("Location", "/admin/login"))
+        .finish()
+}
+
+#[derive(Deserialize)]
+pub struct TickForm {
+    n: u32,
+}
+
+/// Force the world on by a number of ticks
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn tick_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    bus: web::Data<EventBus>,
+    instances: web::Data<Instances>,
+    environment: web::Data<EnvironmentManager>,
+    world: web::Data<WorldGraph>,
+    form: web::Form<TickForm>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    fast_forward(form.n, clock, bus, instances, environment, world).await?;
+    Ok(to_dashboard())
+}
+
+/// Stop world time and the background ticks; ticks can still be forced
+pub async fn pause_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.pause();
+    info!("Admin paused world clock");
+    Ok(to_dashboard())
+}
+
+pub async fn resume_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.resume();
+    info!("Admin resumed world clock");
+    Ok(to_dashboard())
+}
+
+/// Change how often the background ticks come
+pub async fn interval_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    clock: web::Data<WorldClock>,
+    form: web::Form<IntervalQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    clock.set_tick_interval(Duration::from_millis(form.ms));
+    info!(
+        "Admin set tick interval to {}ms",
+        clock.tick_interval().as_millis()
+    );
+    Ok(to_dashboard())
+}
+
+pub async fn save_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    slots: web::Data<SaveSlots>,
+    form: web::Form<SaveQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    slots.save(&form.name)?;
+    Ok(to_dashboard())
+}
+
+pub async fn restore_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    slots: web::Data<SaveSlots>,
+    form: web::Form<SaveQuery>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    slots.restore(&form.name)?;
+    Ok(to_dashboard())
+}
+
+#[derive(Deserialize)]
+pub struct TeleportForm {
+    actor: String,
+    page: String,
+}
+
+pub async fn teleport_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    actors: web::Data<Arc<Mutex<ActorManager>>>,
+    world: web::Data<WorldGraph>,
+    form: web::Form<TeleportForm>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let page = PageId::from(form.page.as_str());
+    if !world.snapshot().contains_key(&page) {
+        return Err(AppError::PageNotFound(form.page.clone()));
+    }
+    actors.lock().teleport(&form.actor, &page)?;
+    Ok(to_dashboard())
+}
+
+#[derive(Deserialize)]
+pub struct ResetForm {
+    actor: String,
+}
+
+pub async fn reset_actor_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    actors: web::Data<Arc<Mutex<ActorManager>>>,
+    form: web::Form<ResetForm>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    actors.lock().reset(&form.actor)?;
+    Ok(to_dashboard())
+}
+
+/// Put every actor back at its spawn, fresh
+pub async fn reset_world_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    instances: web::Data<Instances>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    reset_world(&instances);
+    Ok(to_dashboard())
+}
+
+#[derive(Deserialize)]
+pub struct WeatherForm {
+    page: String,
+    weather: Option<WeatherKind>, // absent to release the override
+    intensity: Option<f32>,
+    #[serde(default)]
+    minutes: u32, // of world time; 0 holds until released
+}
+
+/// Override the weather on a page, or release it
+pub async fn weather_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    world: web::Data<WorldGraph>,
+    environment: web::Data<EnvironmentManager>,
+    form: web::Form<WeatherForm>,
+) -> Result<impl Responder, AppError> {
+    token.check(&req)?;
+    let page = PageId::from(form.page.as_str());
+    if !world.snapshot().contains_key(&page) {
+        return Err(AppError::PageNotFound(form.page.clone()));
+    }
+    match form.weather {
+        Some(kind) => {
+            let state = WeatherState {
+                kind,
+                intensity: form.intensity.unwrap_or(0.5).clamp(0.0, 1.0),
+            };
+            let lasts = (form.minutes > 0).then(|| TimeDelta::minutes(form.minutes.into()));
+            info!("Admin overrode weather on {page} with {kind}");
+            environment.override_weather(&page, state, lasts, OverrideSource::Admin)?;
+        }
+        None => {
+            info!("Admin released weather on {page}");
+            environment.release_weather(&page, OverrideSource::Admin)?;
+        }
+    }
+    Ok(to_dashboard())
+}
@@ -0,0 +1,213 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+use crate::actor::{ActorFlag, ActorId};
+use crate::conditions::Condition;
+use crate::error::AppError;
+use crate::goals::Appointment;
+use crate::instances::Instances;
+use crate::items::ItemId;
+use crate::overlays::DecisionOverlay;
+use crate::pages::PageId;
+use crate::regions::RegionId;
+
+/// Default location of the actor definitions file, relative to the working directory
+pub const DEFAULT_ACTORS_PATH: &str = "data/actors.toml";
+
+/// How often the definitions file is checked for changes
+const RELOAD_POLL: Duration = Duration::from_secs(5);
+
+/// Static, file-defined settings for an actor. Dynamic state (position,
+/// fatigue, plans) lives on the live `Actor` and survives reloads.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActorDefinition {
+    pub id: ActorId,
+    pub name: String,
+    pub location: PageId, // where the actor spawns
+    pub health: i32,
+    #[serde(default)]
+    pub flags: Vec<ActorFlag>,
+    #[serde(default = "crate::actor::default_tick_rate")]
+    pub tick_rate: u32,
+    #[serde(default = "crate::actor::default_action_points")]
+    pub action_points: u8,
+    #[serde(default)]
+    pub roams: Option<RegionId>, // wanders only within this region
+    #[serde(default)]
+    pub home: Option<PageId>, // keeps near it, sleeps there, and guards it if territorial
+    #[serde(default)]
+    pub faction: Option<String>, // shares in what players do to its fellows
+    #[serde(default)]
+    pub pack: Option<String>, // runs with the others of the same pack
+    #[serde(default)]
+    pub items: Vec<ItemId>, // what it carries when it spawns
+    #[serde(default)]
+    pub coins: u32, // starting purse, for merchants
+    #[serde(default)]
+    pub script: Option<String>, // behavior script, "file::function"
+    #[serde(default)]
+    pub seen_when: Condition, // players see the actor only while this holds
+    #[serde(default)]
+    pub decision_overlays: DecisionOverlay, // its own take on the built-in behaviors
+    #[serde(default)]
+    pub appointments: Vec<Appointment>, // where it means to be by when, every day
+}
+
+/// Named bundles of actor settings, e.g. `wild_critter`. A definition (or a
+/// spawn rule's species) naming one with `archetype = "..."` starts from its
+/// settings and overrides whichever it gives itself; archetypes can build on
+/// one another the same way. Decision overlays are the exception: they're
+/// laid over one another a setting at a time.
+#[derive(Clone, Debug, Default)]
+pub struct Archetypes(HashMap<String, toml::Table>);
+
+impl Archetypes {
+    /// `table` laid over the archetypes it names, nearest first
+    pub fn resolve(&self, mut table: toml::Table) -> Result<toml::Table, String> {
+        let mut chain: Vec<String> = Vec::new();
+        while let Some(name) = table.remove("archetype") {
+            let name = name
+                .as_str()
+                .ok_or_else(|| "archetype must be a name".to_string())?
+                .to_string();
+            if chain.contains(&name) {
+                return Err(format!("Archetype '{name}' builds on itself"));
+            }
+            let mut merged = self
+                .0
+                .get(&name)
+                .ok_or_else(|| format!("Unknown archetype '{name}'"))?
+                .clone();
+            let overlays = overlay(merged.remove(OVERLAYS), table.remove(OVERLAYS));
+            merged.extend(table); // the more specific settings win, flags included
+            merged.extend(overlays.map(|o| (OVERLAYS.to_string(), o)));
+            table = merged;
+            chain.push(name);
+        }
+        Ok(table)
+    }
+
+    /// Deserialize `table` once its archetypes are filled in
+    pub fn build<T: serde::de::DeserializeOwned>(&self, table: toml::Table) -> Result<T, String> {
+        toml::Value::Table(self.resolve(table)?)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())
+    }
+}
+
+/// Key of the decision overlays in an actor's (or an archetype's) settings
+const OVERLAYS: &str = "decision_overlays";
+
+/// `specific` laid over `general`, a setting at a time
+fn overlay(general: Option<toml::Value>, specific: Option<toml::Value>) -> Option<toml::Value> {
+    match (general, specific) {
+        (Some(toml::Value::Table(mut general)), Some(toml::Value::Table(specific))) => {
+            general.extend(specific);
+            Some(toml::Value::Table(general))
+        }
+        (general, specific) => specific.or(general),
+    }
+}
+
+/// A named place actors start out at, e.g. `town-square`. A definition can
+/// give `spawn = "..."` in place of a `location`, so moving the spawn point
+/// moves everyone who starts there.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnPoint {
+    pub page: PageId,
+}
+
+/// `table` with the spawn point it names, if any, put down as its location
+fn place(
+    mut table: toml::Table,
+    spawn_points: &HashMap<String, SpawnPoint>,
+) -> Result<toml::Table, String> {
+    let Some(name) = table.remove("spawn") else {
+        return Ok(table);
+    };
+    let name = name
+        .as_str()
+        .ok_or_else(|| "spawn must be a name".to_string())?;
+    let point = spawn_points
+        .get(name)
+        .ok_or_else(|| format!("Unknown spawn point '{name}'"))?;
+    table.insert("location".to_string(), point.page.to_string().into());
+    Ok(table)
+}
+
+#[derive(Deserialize)]
+struct ActorFile {
+    #[serde(default)]
+    archetype: HashMap<String, toml::Table>,
+    #[serde(default)]
+    spawn_point: HashMap<String, SpawnPoint>,
+    #[serde(default)]
+    actor: Vec<toml::Table>,
+}
+
+fn read_actor_file(path: &Path) -> Result<ActorFile, AppError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+    toml::from_str(&text)
+        .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))
+}
+
+/// Read and parse actor definitions from a TOML file, archetypes applied
+/// and spawn points put down
+pub fn load_actor_definitions(path: &Path) -> Result<Vec<ActorDefinition>, AppError> {
+    let file = read_actor_file(path)?;
+    let archetypes = Archetypes(file.archetype);
+    file.actor
+        .into_iter()
+        .enumerate()
+        .map(|(i, table)| {
+            let placed = archetypes
+                .resolve(table)
+                .and_then(|table| place(table, &file.spawn_point));
+            placed
+                .and_then(|table| archetypes.build(table))
+                .map_err(|e| {
+                    AppError::OtherError(format!("Actor #{} in {}: {e}", i + 1, path.display()))
+                })
+        })
+        .collect()
+}
+
+/// Read just the archetypes from the definitions file, for the spawner
+pub fn load_archetypes(path: &Path) -> Result<Archetypes, AppError> {
+    Ok(Archetypes(read_actor_file(path)?.archetype))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watch the definitions file and merge changes into every live instance.
+/// Parsing happens outside the actor lock so ticks are only held up by the merge.
+pub fn spawn_reload_watcher(path: PathBuf, instances: Instances) {
+    actix_rt::spawn(async move {
+        let mut last_seen = modified(&path);
+        let mut intvl = actix_rt::time::interval(RELOAD_POLL);
+        loop {
+            intvl.tick().await;
+            let current = modified(&path);
+            if current == last_seen {
+                continue;
+            }
+            last_seen = current;
+            match load_actor_definitions(&path) {
+                Ok(definitions) => {
+                    info!("Reloading actor definitions from {}", path.display());
+                    for actors in instances.all() {
+                        actors.lock().apply_definitions(definitions.clone());
+                    }
+                }
+                // keep running the last good definitions
+                Err(e) => error!("Actor definitions not reloaded: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,87 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::conditions::{Condition, ConditionContext};
+use crate::error::AppError;
+
+/// Default location of the dialogue file, relative to the working directory
+pub const DEFAULT_DIALOGUE_PATH: &str = "data/dialogue.toml";
+
+/// Something an NPC can say, when `when` holds
+#[derive(Clone, Debug, Deserialize)]
+pub struct DialogueLine {
+    pub actor: String,
+    pub text: String,
+    #[serde(default)]
+    pub when: Condition,
+    #[serde(default)]
+    pub gives_quest: Option<String>, // quest the player picks up on hearing this
+    #[serde(default)]
+    pub sets_flag: Option<String>, // story flag set on the player on hearing this
+    #[serde(default)]
+    pub rumor: bool, // only said with a rumor to tell, which fills in `{rumor}`
+}
+
+/// Something an NPC says to another NPC, for players to overhear
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChatterLine {
+    pub actor: String,
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+struct DialogueFile {
+    #[serde(default)]
+    line: Vec<DialogueLine>,
+    #[serde(default)]
+    chatter: Vec<ChatterLine>,
+}
+
+/// All dialogue lines, grouped by actor id in file order
+#[derive(Default)]
+pub struct DialogueBook {
+    lines: HashMap<String, Vec<DialogueLine>>,
+    chatter: HashMap<String, Vec<String>>, // actor id -> lines for other NPCs
+}
+
+impl DialogueBook {
+    /// Read and parse dialogue lines from a TOML file
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: DialogueFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        let mut lines: HashMap<String, Vec<DialogueLine>> = HashMap::new();
+        for line in file.line {
+            lines.entry(line.actor.clone()).or_default().push(line);
+        }
+        let mut chatter: HashMap<String, Vec<String>> = HashMap::new();
+        for line in file.chatter {
+            chatter.entry(line.actor).or_default().push(line.text);
+        }
+        Ok(DialogueBook { lines, chatter })
+    }
+
+    /// The first line for `actor` whose condition holds, if any. Rumor
+    /// lines are passed over unless the actor `knows_rumor`.
+    pub fn line_for(
+        &self,
+        actor: &str,
+        ctx: &ConditionContext,
+        knows_rumor: bool,
+    ) -> Option<&DialogueLine> {
+        self.lines
+            .get(actor)?
+            .iter()
+            .find(|line| (knows_rumor || !line.rumor) && line.when.holds(ctx))
+    }
+
+    /// One of the lines `actor` has for other NPCs, picked by `roll`, if it has any
+    pub fn chatter_for(&self, actor: &str, roll: u64) -> Option<&str> {
+        let lines = self.chatter.get(actor)?;
+        lines
+            .get((roll % lines.len() as u64) as usize)
+            .map(String::as_str)
+    }
+}
@@ -0,0 +1,169 @@
+//! Ecology: what there is to eat, and who eats whom. Pages grow grass or
+//! berries, by biome, which herbivores graze down and which grow back a
+//! little at a time. Predators eat the herbivores they bring down. Anything
+//! that eats goes hungry without, and starves in the end, so the wild
+//! keeps itself in check over a long run.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::actor::{Actor, ActorFlag};
+use crate::pages::{Biome, Page, PageGraph, PageId};
+
+/// Hunger an actor stops being full at, and goes looking for food
+pub const PECKISH: u8 = 40;
+
+/// Hunger it looks hungry at
+pub const HUNGRY: u8 = 150;
+
+/// Hunger it is starving at, losing health every tick it goes on
+pub const STARVING: u8 = u8::MAX;
+
+/// Ticks between each bite's worth growing back, on every page grazed
+const REGROW_EVERY: u64 = 12;
+
+/// What a page grows for herbivores to eat
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Forage {
+    Grass,
+    Berries, // fewer, and slower to come back, but more filling
+}
+
+impl Forage {
+    /// What grows on `page` and how many bites of it there are when it's
+    /// grown right back; a `forage` metadata number says how many instead
+    pub fn of(page: &Page) -> Option<(Forage, u32)> {
+        let (forage, bites) = match page.biome {
+            Biome::Plains => (Forage::Grass, 12),
+            Biome::Coastal => (Forage::Grass, 4),
+            Biome::Forest => (Forage::Berries, 8),
+            Biome::Cave => return None,
+        };
+        let bites = page
+            .metadata
+            .get("forage")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(bites);
+        (bites > 0).then_some((forage, bites))
+    }
+
+    /// Hunger a bite of it takes away
+    fn fills(self) -> u8 {
+        match self {
+            Forage::Grass => 40,
+            Forage::Berries => 80,
+        }
+    }
+
+    /// Regrowth rounds it takes a bite of it to grow back
+    fn slowness(self) -> u64 {
+        match self {
+            Forage::Grass => 1,
+            Forage::Berries => 3,
+        }
+    }
+}
+
+/// How far each page's forage has been eaten down; pages nobody has
+/// grazed are grown right back and aren't kept
+#[derive(Clone, Debug, Default)]
+pub struct Pastures {
+    grazed: HashMap<PageId, u32>, // page -> bites eaten that haven't grown back
+}
+
+impl Pastures {
+    /// Bites of forage left on `page`, and what sort
+    pub fn left(&self, page: &Page) -> Option<(Forage, u32)> {
+        let (forage, bites) = Forage::of(page)?;
+        let grazed = self.grazed.get(&page.id).copied().unwrap_or_default();
+        Some((forage, bites.saturating_sub(grazed)))
+    }
+
+    /// Take a bite of what grows on `page`, if there's any left
+    fn graze(&mut self, page: &Page) -> Option<Forage> {
+        let (forage, left) = self.left(page)?;
+        if left == 0 {
+            return None;
+        }
+        *self.grazed.entry(page.id.clone()).or_default() += 1;
+        Some(forage)
+    }
+
+    /// Grow back a bite on every grazed page that's due one on world tick
+    /// `tick`; pages gone from the world are forgotten
+    pub fn regrow(&mut self, tick: u64, page_graph: &PageGraph) {
+        if !tick.is_multiple_of(REGROW_EVERY) {
+            return;
+        }
+        let round = tick / REGROW_EVERY;
+        self.grazed.retain(|id, grazed| {
+            let Some((forage, _)) = page_graph.get(id).and_then(Forage::of) else {
+                return false;
+            };
+            if round.is_multiple_of(forage.slowness()) {
+                *grazed = grazed.saturating_sub(1);
+            }
+            *grazed > 0
+        });
+    }
+
+    /// Pages and how far they're grazed, for snapshots
+    pub fn grazed(&self) -> Vec<(PageId, u32)> {
+        let mut grazed: Vec<_> = self
+            .grazed
+            .iter()
+            .map(|(id, bites)| (id.clone(), *bites))
+            .collect();
+        grazed.sort_by(|(a, _), (b, _)| a.cmp(b));
+        grazed
+    }
+
+    pub fn restore(grazed: Vec<(PageId, u32)>) -> Self {
+        Pastures {
+            grazed: grazed.into_iter().collect(),
+        }
+    }
+}
+
+/// Whether `actor` needs to eat: wild things that graze or hunt. Townsfolk
+/// have their own kitchens, and players look after themselves.
+pub fn eats(actor: &Actor) -> bool {
+    actor.has_flag(ActorFlag::Organic)
+        && !actor.has_flag(ActorFlag::Player)
+        && (actor.has_flag(ActorFlag::Herbivore) || actor.has_flag(ActorFlag::Predatory))
+}
+
+/// Whether a predator is hungry enough to hunt
+pub fn hunting(actor: &Actor) -> bool {
+    actor.state.hunger >= PECKISH
+}
+
+/// One tick of hunger for `actor`, on `page`: it gets hungrier while it's
+/// awake, and grazes if it's a herbivore with room for a bite and there is
+/// one. Starving costs it health. Returns whether it has starved to death.
+pub fn hunger(actor: &mut Actor, page: &Page, pastures: &mut Pastures) -> bool {
+    if !eats(actor) {
+        return false;
+    }
+    if actor.state.awake {
+        actor.state.hunger = actor.state.hunger.saturating_add(1);
+    }
+    if actor.has_flag(ActorFlag::Herbivore)
+        && actor.state.awake
+        && actor.travel.is_none()
+        && actor.state.hunger >= PECKISH
+        && let Some(forage) = pastures.graze(page)
+    {
+        actor.state.hunger = actor.state.hunger.saturating_sub(forage.fills());
+    }
+    if actor.state.hunger < STARVING {
+        return false;
+    }
+    actor.state.health -= 1;
+    actor.state.health <= 0
+}
+
+/// A predator's fill of the herbivore it has brought down
+pub fn devour(predator: &mut Actor) {
+    predator.state.hunger = 0;
+}
@@ -0,0 +1,160 @@
+//! Random encounters: something springing out at a player passing through
+//! the wilds. A wild page (`wild` metadata) can have a table of what there
+//! is to run into there, read from a data file. A player arriving on one
+//! rolls against it, and either a new actor turns up or one lying hidden
+//! there breaks cover, for them at least. Either stays in plain sight for
+//! them until they move on, and one that turned up just for them slips
+//! away again once they do.
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, warn};
+
+use crate::actor::{Actor, ActorId, ActorManager};
+use crate::definitions::Archetypes;
+use crate::error::AppError;
+use crate::pages::{Page, PageId};
+use crate::spawner::{self, Species};
+
+/// Default location of the encounter tables file, relative to the working directory
+pub const DEFAULT_ENCOUNTERS_PATH: &str = "data/encounters.toml";
+
+/// One thing to run into, and how often it's the one
+#[derive(Clone, Debug, Deserialize)]
+pub struct EncounterEntry {
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub surface: Option<ActorId>, // one lying hidden on the page...
+    #[serde(default)]
+    pub species: Option<Species>, // ...or a new one of these
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// What there is to run into on a page, and the percent chance of running
+/// into anything on arriving there
+#[derive(Clone, Debug, Deserialize)]
+pub struct EncounterTable {
+    pub page: PageId,
+    pub chance: u8,
+    #[serde(skip)]
+    pub entries: Vec<EncounterEntry>, // read apart, for their species' archetypes
+}
+
+#[derive(Deserialize)]
+struct EncounterFile {
+    #[serde(default)]
+    encounter: Vec<toml::Table>,
+}
+
+/// Every wild page's encounter table
+#[derive(Default)]
+pub struct Encounters {
+    tables: HashMap<PageId, EncounterTable>,
+}
+
+impl Encounters {
+    /// Read encounter tables from a TOML file, species archetypes taken
+    /// from `archetypes`. A missing file just means no encounters.
+    pub fn load(path: &Path, archetypes: &Archetypes) -> Result<Self, AppError> {
+        if !path.exists() {
+            return Ok(Encounters::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: EncounterFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        let mut tables = HashMap::new();
+        for (i, mut table) in file.encounter.into_iter().enumerate() {
+            let invalid = |e: String| {
+                AppError::OtherError(format!(
+                    "Encounter table #{} in {}: {e}",
+                    i + 1,
+                    path.display()
+                ))
+            };
+            let entries = match table.remove("entry") {
+                Some(toml::Value::Array(entries)) => entries,
+                Some(_) => return Err(invalid("entry must be a list".to_string())),
+                None => Vec::new(),
+            };
+            let mut encounters: EncounterTable = toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| invalid(e.to_string()))?;
+            for entry in entries {
+                let toml::Value::Table(entry) = entry else {
+                    return Err(invalid("each entry must be a table".to_string()));
+                };
+                let entry: EncounterEntry =
+                    spawner::with_species(entry, archetypes).map_err(invalid)?;
+                if entry.surface.is_none() == entry.species.is_none() {
+                    return Err(invalid(
+                        "each entry needs exactly one of surface or species".to_string(),
+                    ));
+                }
+                encounters.entries.push(entry);
+            }
+            tables.insert(encounters.page.clone(), encounters);
+        }
+        Ok(Encounters { tables })
+    }
+
+    /// What, if anything, a player arriving on `page` runs into
+    pub fn roll(&self, page: &Page, rng: &mut impl Rng) -> Option<&EncounterEntry> {
+        if !page.is_wild() {
+            return None;
+        }
+        let table = self.tables.get(&page.id)?;
+        if rng.random_range(0..100) >= table.chance {
+            return None;
+        }
+        table
+            .entries
+            .choose_weighted(rng, |entry| entry.weight)
+            .ok()
+    }
+}
+
+/// Have `entry` come to meet `player` on `page`: a new actor brought out
+/// for them, or the one it names breaking cover, if it's there and
+/// `hidden` from them. The id of whoever they've met, if anyone.
+pub fn meet(
+    entry: &EncounterEntry,
+    page: &Page,
+    player: &ActorId,
+    manager: &mut ActorManager,
+    hidden: impl Fn(&Actor) -> bool,
+) -> Option<ActorId> {
+    if let Some(id) = &entry.surface {
+        let lying_low = manager
+            .actors
+            .get(id)
+            .is_some_and(|a| a.location == page.id && a.travel.is_none() && hidden(a));
+        if !lying_low {
+            debug!(%id, page = %page.id, "Nobody hidden to break cover.");
+            return None;
+        }
+        debug!(%id, page = %page.id, "Breaks cover.");
+        return Some(id.clone());
+    }
+    let Some(species) = entry.species.clone() else {
+        warn!(page = %page.id, "Encounter entry with nobody to meet");
+        return None;
+    };
+    let id = loop {
+        let id = ActorId::from(format!("{}-{:08x}", page.id, rand::random::<u32>()));
+        if !manager.actors.contains_key(&id) {
+            break id;
+        }
+    };
+    manager.bring_out(
+        Actor::from_definition(species.at(id, page.id.clone())),
+        player,
+    )
+}
@@ -1,18 +1,45 @@
+use crate::calendar::Calendar;
+use crate::clock::WorldClock;
 use crate::error::AppError;
-use crate::pages::PageId;
-use chrono::Datelike;
+use crate::events::{EventBus, WorldEvent};
+use crate::forecast::{self, FORECAST_RUNS, MAX_FORECAST_HOURS, RegionForecast, WeatherMap};
+use crate::pages::{Biome, PageGraph, PageId};
+use crate::regions::Regions;
+use crate::weather::{WeatherEngine, WeatherKind, WeatherState};
+use crate::world::WorldGraph;
+use chrono::{DateTime, Datelike, DurationRound, Local, TimeDelta, Timelike};
+use parking_lot::Mutex;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tracing::trace;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct WorldTime {
     pub hour: u8,
-    pub _minute: u8,
+    pub minute: u8,
+    #[serde(default)]
+    pub day: i64, // days since the common era began, turning over at midnight
 }
 impl WorldTime {
+    /// Time of day of a (world clock) timestamp
+    pub fn from_datetime<Tz: chrono::TimeZone>(datetime: &chrono::DateTime<Tz>) -> Self {
+        WorldTime {
+            hour: datetime.hour() as u8,
+            minute: datetime.minute() as u8,
+            day: datetime.date_naive().num_days_from_ce() as i64,
+        }
+    }
+
+    /// Hours since the common era began, for timing things across midnight
+    pub fn hours(&self) -> i64 {
+        self.day * 24 + self.hour as i64
+    }
+
     /// Returns true if time is daytime (6:00 <= hour < 18:00)
     pub fn is_daytime(&self) -> bool {
         self.hour >= 6 && self.hour < 18
@@ -27,91 +54,693 @@ impl WorldTime {
     pub fn _is_twilight(&self) -> bool {
         (self.hour >= 5 && self.hour < 7) || (self.hour >= 17 && self.hour < 19)
     }
+
+    /// Roughly when in the day it is, for describing it
+    pub fn part_of_day(&self) -> PartOfDay {
+        PartOfDay::at(self.hour)
+    }
+}
+
+/// The day in a handful of stretches, for describing the time in words
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartOfDay {
+    DeepNight,
+    Dawn,
+    Morning,
+    Midday,
+    Afternoon,
+    EarlyEvening,
+    Night,
+}
+
+impl PartOfDay {
+    pub fn at(hour: u8) -> Self {
+        match hour {
+            0..=4 => PartOfDay::DeepNight,
+            5..=6 => PartOfDay::Dawn,
+            7..=10 => PartOfDay::Morning,
+            11..=13 => PartOfDay::Midday,
+            14..=16 => PartOfDay::Afternoon,
+            17..=19 => PartOfDay::EarlyEvening,
+            _ => PartOfDay::Night,
+        }
+    }
+
+    /// Key under `time` in the translations, e.g. "early_evening"
+    pub fn key(self) -> &'static str {
+        match self {
+            PartOfDay::DeepNight => "deep_night",
+            PartOfDay::Dawn => "dawn",
+            PartOfDay::Morning => "morning",
+            PartOfDay::Midday => "midday",
+            PartOfDay::Afternoon => "afternoon",
+            PartOfDay::EarlyEvening => "early_evening",
+            PartOfDay::Night => "night",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Season {
+    Winter,
+    Spring,
+    Summer,
+    Autumn,
+}
+
+impl std::fmt::Display for Season {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Environment {
-    season: String,
-    weather: String,
-    timestamp: SystemTime,
+    season: Season,
+    weather: WeatherKind,
+    intensity: f32, // 0.0 (barely) to 1.0 (severe)
+    calendar: Calendar,
+    biome: Biome,
+    sheltered: bool,
+    warmth: i32, // region temperature offset
+    // derived from the above by `settle`
+    temperature: i32, // degrees C
+    hazard: Option<Hazard>,
+    season_updated: DateTime<Local>, // world clock
+    weather_updated: DateTime<Local>,
+}
+
+impl Environment {
+    pub fn season(&self) -> Season {
+        self.season
+    }
+
+    pub fn weather(&self) -> WeatherKind {
+        self.weather
+    }
+
+    pub fn calendar(&self) -> Calendar {
+        self.calendar
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn biome(&self) -> Biome {
+        self.biome
+    }
+
+    /// Under cover, by the page or its region
+    pub fn is_sheltered(&self) -> bool {
+        self.sheltered
+    }
+
+    /// Scenery line for the current weather in this biome, if it has one
+    pub fn ambience(&self) -> Option<&'static str> {
+        self.biome.ambience(self.weather)
+    }
+
+    pub fn hazard(&self) -> Option<Hazard> {
+        self.hazard
+    }
+
+    /// Recompute temperature and hazard after season or weather changed
+    fn settle(&mut self) {
+        self.temperature =
+            temperature(self.season, self.weather, self.intensity, self.biome) + self.warmth;
+        self.hazard =
+            Hazard::assess(self.temperature, self.weather, self.intensity).and_then(|hazard| {
+                if self.sheltered {
+                    hazard.sheltered()
+                } else {
+                    Some(hazard)
+                }
+            });
+    }
+}
+
+/// Air temperature from season, weather and biome
+fn temperature(season: Season, weather: WeatherKind, intensity: f32, biome: Biome) -> i32 {
+    if biome == Biome::Cave {
+        return 10; // caves keep the same temperature all year
+    }
+    let base = match season {
+        Season::Winter => 0.0,
+        Season::Spring => 12.0,
+        Season::Summer => 24.0,
+        Season::Autumn => 11.0,
+    };
+    let swing = match (weather, season) {
+        (WeatherKind::Clear, Season::Summer) => 8.0, // beating sun
+        (WeatherKind::Clear, _) => 2.0,
+        (WeatherKind::Cloudy, _) => 0.0,
+        (WeatherKind::Foggy, _) => -2.0,
+        (WeatherKind::Rainy, _) => -3.0,
+        (WeatherKind::Windy, _) => -4.0,
+        (WeatherKind::Stormy, _) => -5.0,
+        (WeatherKind::Snowy, _) => -6.0,
+    };
+    let coast = if biome == Biome::Coastal { -2.0 } else { 0.0 };
+    (base + swing * (0.5 + intensity) + coast).round() as i32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HazardKind {
+    Cold,
+    Heat,
+    Storm,
+}
+
+/// Conditions dangerous enough to wear down anyone out in them.
+/// `level` runs from 1 (unpleasant) to 3 (deadly).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    pub level: u8,
+}
+
+impl Hazard {
+    /// The worst hazard in these conditions, if any
+    fn assess(temperature: i32, weather: WeatherKind, intensity: f32) -> Option<Hazard> {
+        let cold = match temperature {
+            ..=-10 => 3,
+            -9..=-5 => 2,
+            -4..=0 => 1,
+            _ => 0,
+        };
+        let heat = match temperature {
+            40.. => 3,
+            35..=39 => 2,
+            30..=34 => 1,
+            _ => 0,
+        };
+        let storm = if weather == WeatherKind::Stormy {
+            match intensity {
+                0.9.. => 3,
+                0.75.. => 2,
+                0.5.. => 1,
+                _ => 0,
+            }
+        } else {
+            0
+        };
+        [
+            (HazardKind::Cold, cold),
+            (HazardKind::Heat, heat),
+            (HazardKind::Storm, storm),
+        ]
+        .into_iter()
+        .filter(|(_, level)| *level > 0)
+        .max_by_key(|(_, level)| *level)
+        .map(|(kind, level)| Hazard { kind, level })
+    }
+
+    /// What is left of this hazard under cover: storms can't reach, the rest eases a step
+    fn sheltered(self) -> Option<Hazard> {
+        if self.kind == HazardKind::Storm || self.level <= 1 {
+            return None;
+        }
+        Some(Hazard {
+            level: self.level - 1,
+            ..self
+        })
+    }
+
+    /// Warning shown to players standing in it
+    pub fn warning(&self) -> &'static str {
+        match (self.kind, self.level) {
+            (HazardKind::Cold, 1) => "It is bitterly cold here.",
+            (HazardKind::Cold, _) => "It is dangerously cold here.",
+            (HazardKind::Heat, 1) => "The heat here is oppressive.",
+            (HazardKind::Heat, _) => "It is dangerously hot here.",
+            (HazardKind::Storm, 1) => "The storm buffets you.",
+            (HazardKind::Storm, _) => "The storm is dangerous to be out in.",
+        }
+    }
+}
+
+/// How long (in world time) each environment field stays valid before it is regenerated
+#[derive(Clone, Copy, Debug)]
+pub struct EnvironmentTtl {
+    pub weather: Duration,
+    pub season: Duration,
+}
+
+impl Default for EnvironmentTtl {
+    fn default() -> Self {
+        EnvironmentTtl {
+            weather: Duration::from_secs(60 * 60),
+            season: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl EnvironmentTtl {
+    /// Defaults, overridden by `CHOTT_WEATHER_TTL_SECS` / `CHOTT_SEASON_TTL_SECS` if set
+    pub fn from_env() -> Self {
+        let secs = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+        };
+        let defaults = EnvironmentTtl::default();
+        EnvironmentTtl {
+            weather: secs("CHOTT_WEATHER_TTL_SECS").unwrap_or(defaults.weather),
+            season: secs("CHOTT_SEASON_TTL_SECS").unwrap_or(defaults.season),
+        }
+    }
+}
+
+/// Who set a weather override. An admin's outranks a script's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum OverrideSource {
+    Script,
+    Admin,
+}
+
+/// Weather held on a page by hand, over whatever the simulation says there
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeatherOverride {
+    pub state: WeatherState,
+    pub until: Option<DateTime<Local>>, // world time it lapses at; None holds until released
+    pub source: OverrideSource,
+}
+
+impl WeatherOverride {
+    fn is_active(&self, now: DateTime<Local>) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
 }
 
 #[derive(Clone)]
 pub struct EnvironmentManager {
     pub cache: Arc<Mutex<HashMap<PageId, Environment>>>,
+    cache_hits: Arc<AtomicU64>,
+    cache_misses: Arc<AtomicU64>,
+    weather: Arc<Mutex<WeatherEngine>>,
+    overrides: Arc<Mutex<HashMap<PageId, WeatherOverride>>>, // held by hand over the simulation
+    last_weather_step: Arc<Mutex<DateTime<Local>>>,
+    ttl: EnvironmentTtl,
+    clock: WorldClock,
+    world: WorldGraph, // for each page's biome and shelter
+    regions: Arc<Regions>,
+    bus: EventBus,
 }
 
 impl EnvironmentManager {
-    pub fn new() -> Self {
+    pub fn new(
+        bus: EventBus,
+        ttl: EnvironmentTtl,
+        clock: WorldClock,
+        world: WorldGraph,
+        regions: Arc<Regions>,
+    ) -> Self {
         EnvironmentManager {
             cache: Arc::new(Mutex::new(HashMap::new())),
+            cache_hits: Arc::default(),
+            cache_misses: Arc::default(),
+            weather: Arc::new(Mutex::new(WeatherEngine::new())),
+            overrides: Arc::default(),
+            last_weather_step: Arc::new(Mutex::new(clock.now())),
+            ttl,
+            clock,
+            world,
+            regions,
+            bus,
+        }
+    }
+
+    /// Step the weather simulation if a weather period of world time has passed since the last step.
+    /// Called from the world tick loop; changed pages get their cached weather updated.
+    pub fn advance_weather(&self, pages: &PageGraph) -> Result<(), AppError> {
+        let now = self.clock.now();
+        self.lapse_overrides(now)?;
+        {
+            let mut last_step = self.last_weather_step.lock();
+            if !expired(*last_step, now, self.ttl.weather) {
+                return Ok(());
+            }
+            *last_step = now;
         }
+
+        let season = compute_season(now);
+        let held: HashMap<PageId, WeatherState> = self
+            .overrides
+            .lock()
+            .iter()
+            .map(|(page_id, held)| (page_id.clone(), held.state))
+            .collect();
+        let changed = self.weather.lock().step(season, pages, &held);
+
+        let mut cache = self.cache.lock();
+        for (page_id, state) in changed {
+            if let Some(env) = cache.get_mut(&page_id) {
+                env.weather = state.kind;
+                env.intensity = state.intensity;
+                env.weather_updated = now;
+                env.settle();
+            }
+            if held.contains_key(&page_id) {
+                continue; // announced when it was overridden
+            }
+            trace!("Weather on {page_id} is now {}", state.kind);
+            self.bus.publish(WorldEvent::WeatherChanged {
+                page: page_id,
+                weather: state.kind,
+                intensity: state.intensity,
+            });
+        }
+        Ok(())
     }
 
     pub async fn get_environment_for_page(
         &self,
         page_id: &PageId,
     ) -> Result<Environment, AppError> {
-        let mut cache = self
-            .cache
-            .lock()
-            .map_err(|e| AppError::MutexError(format!("Failed to lock cache: {e}")))?;
-        if let Some(env) = cache.get(page_id) {
-            //let elapsed = env.timestamp.elapsed()?;
-            {
+        self.environment_for(page_id)
+    }
+
+    /// Blocking variant of `get_environment_for_page`, for the tick loop.
+    /// Overridden weather shows through whatever the simulation says.
+    pub fn environment_for(&self, page_id: &PageId) -> Result<Environment, AppError> {
+        let mut env = self.generated_for(page_id)?;
+        if let Some(held) = self.override_on(page_id)? {
+            env.weather = held.state.kind;
+            env.intensity = held.state.intensity;
+            env.settle();
+        }
+        Ok(env)
+    }
+
+    /// The environment as generated and cached, without overrides
+    fn generated_for(&self, page_id: &PageId) -> Result<Environment, AppError> {
+        let mut cache = self.cache.lock();
+        if let Some(env) = cache.get_mut(page_id) {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            // Regenerate only the fields that have expired
+            let now = self.clock.now();
+            env.calendar = Calendar::at(now); // follows the clock, never stale
+            let mut changed = false;
+            if expired(env.season_updated, now, self.ttl.season) {
+                env.season = compute_season(now);
+                env.season_updated = now;
+                changed = true;
+            }
+            if expired(env.weather_updated, now, self.ttl.weather) {
+                let state = self
+                    .weather
+                    .lock()
+                    .weather_at(page_id, env.season, env.biome);
+                env.weather = state.kind;
+                env.intensity = state.intensity;
+                env.weather_updated = now;
+                changed = true;
+            }
+            if changed {
+                env.settle();
+                trace!("Env cache refreshed expired fields for {page_id}");
+                self.publish_generated(page_id, env);
+            } else {
                 trace!("Env cache hit for {page_id}");
-                return Ok(env.clone());
             }
+            return Ok(env.clone());
         }
         trace!("Env cache miss for {page_id}");
-        // Generate new environment if missing or expired
-        let new_env = self.generate_environment(page_id).map_err(|e| {
-            AppError::EnvironmentError(format!("Failed to generate environment: {e}"))
-        })?;
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        // Generate new environment if missing
+        let new_env = self.generate_environment(page_id);
         cache.insert(page_id.to_owned(), new_env.clone());
+        self.publish_generated(page_id, &new_env);
         Ok(new_env)
     }
 
-    fn generate_environment(&self, _page_id: &PageId) -> Result<Environment, AppError> {
-        // Use system time, rng, etc.
-        let now = SystemTime::now();
+    /// Hold the weather on a page at `state` over the simulated weather,
+    /// for `lasts` of world time or until released. A script's override
+    /// can't displace an admin's; returns false if it was refused.
+    pub fn override_weather(
+        &self,
+        page_id: &PageId,
+        state: WeatherState,
+        lasts: Option<TimeDelta>,
+        source: OverrideSource,
+    ) -> Result<bool, AppError> {
+        let now = self.clock.now();
+        let mut overrides = self.overrides.lock();
+        if let Some(held) = overrides.get(page_id)
+            && held.is_active(now)
+            && held.source > source
+        {
+            return Ok(false);
+        }
+        overrides.insert(
+            page_id.clone(),
+            WeatherOverride {
+                state,
+                until: lasts.map(|lasts| now + lasts),
+                source,
+            },
+        );
+        drop(overrides);
+        self.bus.publish(WorldEvent::WeatherChanged {
+            page: page_id.clone(),
+            weather: state.kind,
+            intensity: state.intensity,
+        });
+        Ok(true)
+    }
+
+    /// Let the simulated weather show on a page again. As with setting one,
+    /// a script can't release an admin's override; returns whether one was
+    /// released.
+    pub fn release_weather(
+        &self,
+        page_id: &PageId,
+        source: OverrideSource,
+    ) -> Result<bool, AppError> {
+        let mut overrides = self.overrides.lock();
+        if overrides
+            .get(page_id)
+            .is_none_or(|held| held.source > source)
+        {
+            return Ok(false);
+        }
+        overrides.remove(page_id);
+        drop(overrides);
+        self.publish_simulated(page_id)?;
+        Ok(true)
+    }
+
+    /// The override holding the weather on `page_id`, if one still holds
+    fn override_on(&self, page_id: &PageId) -> Result<Option<WeatherOverride>, AppError> {
+        let now = self.clock.now();
+        Ok(self
+            .overrides
+            .lock()
+            .get(page_id)
+            .filter(|held| held.is_active(now))
+            .cloned())
+    }
+
+    /// Drop overrides whose time is up, announcing the weather underneath
+    fn lapse_overrides(&self, now: DateTime<Local>) -> Result<(), AppError> {
+        let mut lapsed = Vec::new();
+        self.overrides.lock().retain(|page_id, held| {
+            let active = held.is_active(now);
+            if !active {
+                lapsed.push(page_id.clone());
+            }
+            active
+        });
+        for page_id in lapsed {
+            trace!("Weather override on {page_id} lapsed");
+            self.publish_simulated(&page_id)?;
+        }
+        Ok(())
+    }
+
+    /// Announce the simulated weather on `page_id`, once an override stops hiding it
+    fn publish_simulated(&self, page_id: &PageId) -> Result<(), AppError> {
+        if let Some(state) = self.weather.lock().current(page_id) {
+            self.bus.publish(WorldEvent::WeatherChanged {
+                page: page_id.clone(),
+                weather: state.kind,
+                intensity: state.intensity,
+            });
+        }
+        Ok(())
+    }
+
+    /// The weather likely over each region for the next `hours` whole
+    /// hours. Weather steps fall where they will on the world clock, and
+    /// overrides hold until they lapse. The same until the weather next
+    /// steps, so asking again doesn't change the outlook.
+    pub fn forecast(&self, hours: u32) -> Result<Vec<RegionForecast>, AppError> {
+        let now = self.clock.now();
+        let last_step = *self.last_weather_step.lock();
+        let step = TimeDelta::from_std(self.ttl.weather)
+            .unwrap_or(TimeDelta::hours(1))
+            .max(TimeDelta::minutes(1));
+        let first = now.duration_trunc(TimeDelta::hours(1)).unwrap_or(now) + TimeDelta::hours(1);
+        let times: Vec<DateTime<Local>> = (0..hours.min(MAX_FORECAST_HOURS))
+            .map(|h| first + TimeDelta::hours(h.into()))
+            .collect();
+        let Some(&last) = times.last() else {
+            return Ok(Vec::new());
+        };
+
+        let overrides = self.overrides.lock().clone();
+        let held_at = |at: DateTime<Local>| -> HashMap<PageId, WeatherState> {
+            overrides
+                .iter()
+                .filter(|(_, held)| held.is_active(at))
+                .map(|(page_id, held)| (page_id.clone(), held.state))
+                .collect()
+        };
+        let mut step_times = Vec::new();
+        let mut at = last_step + step;
+        while at <= last {
+            step_times.push(at);
+            at += step;
+        }
+        let steps: Vec<(Season, HashMap<PageId, WeatherState>)> = step_times
+            .iter()
+            .map(|at| (compute_season(*at), held_at(*at)))
+            .collect();
+
+        let pages = self.world.snapshot();
+        let engine = {
+            // the weather starts everywhere the forecast looks, as it would
+            // for anyone going there
+            let mut engine = self.weather.lock();
+            let season = compute_season(now);
+            for page in pages.values() {
+                engine.weather_at(&page.id, season, page.biome);
+            }
+            engine.clone()
+        };
+        let mut rng = StdRng::seed_from_u64(last_step.timestamp_millis() as u64);
+        // each run starts from the weather as it is now
+        let mut current = engine.states().clone();
+        current.extend(held_at(now));
+        let runs: Vec<Vec<WeatherMap>> = (0..FORECAST_RUNS)
+            .map(|_| {
+                let mut run = vec![current.clone()];
+                run.extend(engine.play_forward(&steps, &pages, &mut rng));
+                run
+            })
+            .collect();
+        let hours: Vec<(DateTime<Local>, Vec<&WeatherMap>)> = times
+            .iter()
+            .map(|at| {
+                let done = step_times.iter().filter(|step| *step <= at).count();
+                (*at, runs.iter().map(|run| &run[done]).collect())
+            })
+            .collect();
+        Ok(forecast::summarize(&self.regions, &pages, &hours))
+    }
+
+    /// Pages whose weather is overridden, sorted by page
+    pub fn weather_overrides(&self) -> Result<Vec<(PageId, WeatherOverride)>, AppError> {
+        let now = self.clock.now();
+        let mut overrides: Vec<(PageId, WeatherOverride)> = self
+            .overrides
+            .lock()
+            .iter()
+            .filter(|(_, held)| held.is_active(now))
+            .map(|(id, held)| (id.clone(), held.clone()))
+            .collect();
+        overrides.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(overrides)
+    }
+
+    /// The simulated weather everywhere, for a save
+    pub fn weather(&self) -> Result<WeatherEngine, AppError> {
+        Ok(self.weather.lock().clone())
+    }
+
+    /// Put the weather and its overrides back as a save had them; every
+    /// environment is regenerated to match
+    pub fn restore_weather(
+        &self,
+        weather: WeatherEngine,
+        overrides: Vec<(PageId, WeatherOverride)>,
+    ) -> Result<(), AppError> {
+        *self.weather.lock() = weather;
+        *self.overrides.lock() = overrides.into_iter().collect();
+        self.invalidate_all()
+    }
+
+    /// Drop the cached environment for one page so it is regenerated on next use.
+    /// Returns whether anything was cached.
+    pub fn invalidate(&self, page_id: &PageId) -> Result<bool, AppError> {
+        let removed = self.cache.lock().remove(page_id).is_some();
+        trace!("Env cache invalidated for {page_id}");
+        Ok(removed)
+    }
+
+    /// Cache lookups that found an environment, and ones that had to generate it
+    pub fn cache_stats(&self) -> (u64, u64) {
+        (
+            self.cache_hits.load(Ordering::Relaxed),
+            self.cache_misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Drop every cached environment
+    pub fn invalidate_all(&self) -> Result<(), AppError> {
+        self.cache.lock().clear();
+        trace!("Env cache cleared");
+        Ok(())
+    }
+
+    fn publish_generated(&self, page_id: &PageId, env: &Environment) {
+        self.bus.publish(WorldEvent::EnvironmentGenerated {
+            page: page_id.clone(),
+            season: env.season,
+            weather: env.weather,
+        });
+    }
+
+    fn generate_environment(&self, page_id: &PageId) -> Environment {
+        // Season from the calendar, weather from the simulation
+        let now = self.clock.now();
         let season = compute_season(now);
-        let weather = random_weather();
-        Ok(Environment {
+        let pages = self.world.snapshot();
+        let page = pages.get(page_id);
+        let biome = page.map(|p| p.biome).unwrap_or_default();
+        let weather = self.weather.lock().weather_at(page_id, season, biome);
+        let mut env = Environment {
             season,
-            weather,
-            timestamp: now,
-        })
+            weather: weather.kind,
+            intensity: weather.intensity,
+            calendar: Calendar::at(now),
+            biome,
+            // region defaults apply on top of the page's own
+            sheltered: page.is_some_and(|p| p.is_sheltered() || self.regions.shelters(p)),
+            warmth: page.map_or(0, |p| self.regions.temperature_offset(p)),
+            temperature: 0,
+            hazard: None,
+            season_updated: now,
+            weather_updated: now,
+        };
+        env.settle();
+        env
     }
 }
 
-fn compute_season(now: SystemTime) -> String {
+/// Whether `ttl` of world time has passed between `since` and `now`
+fn expired(since: DateTime<Local>, now: DateTime<Local>, ttl: Duration) -> bool {
+    TimeDelta::from_std(ttl).is_ok_and(|ttl| now - since >= ttl)
+}
+
+fn compute_season(now: DateTime<Local>) -> Season {
     // Use month for season
-    let datetime = chrono::DateTime::<chrono::Utc>::from(now);
-    let month = datetime.month();
-    match month {
-        12 | 1 | 2 => "Winter",
-        3 | 4 | 5 => "Spring",
-        6 | 7 | 8 => "Summer",
-        9 | 10 | 11 => "Autumn",
-        _ => "Unknown",
-    }
-    .to_string()
-}
-
-fn random_weather() -> String {
-    // use system time as pseudorandom source for weather, rotates every minute
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let idx = (now / 60) % 4;
-    match idx {
-        0 => "Clear",
-        1 => "Rainy",
-        2 => "Cloudy",
-        3 => "Windy",
-        _ => "Foggy",
-    }
-    .to_string()
+    match now.month() {
+        3..=5 => Season::Spring,
+        6..=8 => Season::Summer,
+        9..=11 => Season::Autumn,
+        _ => Season::Winter,
+    }
 }
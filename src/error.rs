@@ -1,6 +1,8 @@
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use std::time::SystemTimeError;
 use thiserror::Error;
+use tracing::error;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -10,6 +12,9 @@ pub enum AppError {
     #[error("Page not found: {0}")]
     PageNotFound(String),
 
+    #[error("Actor not found: {0}")]
+    ActorNotFound(String),
+
     #[error("Session error")]
     SessionError(String),
 
@@ -19,8 +24,14 @@ pub enum AppError {
     #[error("DateTime error: {0}")]
     DateTimeError(#[from] SystemTimeError),
 
-    #[error("Mutex error: {0}")]
-    MutexError(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Too fast: {0}")]
+    TooFast(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
 
     #[error("Other: {0}")]
     OtherError(String),
@@ -32,24 +43,43 @@ impl From<String> for AppError {
     }
 }
 
-impl ResponseError for AppError {
-    fn error_response(&self) -> HttpResponse {
-        let (status, msg) = match self {
-            AppError::PageNotFound(path) => (
-                actix_web::http::StatusCode::NOT_FOUND,
-                format!("Page not found: {path}"),
-            ),
-            AppError::TemplateError(e) => (
-                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Rendering error: {e}"),
-            ),
-            other => (
-                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                other.to_string(),
+impl AppError {
+    /// The status to answer with, and what to tell the player
+    pub fn describe(&self) -> (StatusCode, String) {
+        match self {
+            AppError::PageNotFound(path) => {
+                (StatusCode::NOT_FOUND, format!("Page not found: {path}"))
+            }
+            AppError::ActorNotFound(id) => {
+                (StatusCode::NOT_FOUND, format!("Actor not found: {id}"))
+            }
+            AppError::Unauthorized(reason) => {
+                (StatusCode::FORBIDDEN, format!("Not allowed: {reason}"))
+            }
+            AppError::TooFast(reason) => (StatusCode::TOO_MANY_REQUESTS, reason.clone()),
+            AppError::Conflict(reason) => (StatusCode::CONFLICT, reason.clone()),
+            AppError::TemplateError(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "This page can't be shown right now.".to_string(),
             ),
-        };
+            other => (StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        self.describe().0
+    }
 
-        // placeholder HTML error page
+    /// Plain page; `render::error_pages` replaces it with a themed one where it can
+    fn error_response(&self) -> HttpResponse {
+        if let AppError::TemplateError(e) = self {
+            // Tera's messages name templates and variables; keep them in the log
+            error!("Rendering error: {e:?}");
+        }
+        let (status, msg) = self.describe();
+        let msg = tera::escape_html(&msg); // may quote what was asked for
         let body = format!(
             "<html><head><title>Chott Error</title></head>\
             <body><h1>Oops!</h1><p><center>{msg}</center></p></body></html>"
@@ -0,0 +1,494 @@
+use chrono::{DateTime, Local};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::trace;
+
+use crate::actor::ActorId;
+use crate::clock::WorldClock;
+
+use crate::environment::{HazardKind, Season};
+use crate::items::ItemId;
+use crate::pages::PageId;
+use crate::session::Emote;
+use crate::weather::WeatherKind;
+
+/// How many events a slow subscriber may fall behind before it starts missing them
+const BUS_CAPACITY: usize = 1024;
+
+/// How many recent events the in-memory log keeps
+const LOG_CAPACITY: usize = 2048;
+
+/// Something that happened in the world, published on the `EventBus`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum WorldEvent {
+    WorldTicked {
+        tick: u64,
+        acted: usize,
+    },
+    ClockChanged {
+        hour: u8,
+        minute: u8,
+    },
+    TickerRestarted {
+        restarts: u32,  // since startup
+        reason: String, // the panic message
+        backoff_ms: u64,
+    },
+    ActorSpawned {
+        actor: ActorId,
+        page: PageId,
+    },
+    ActorDespawned {
+        actor: ActorId,
+        page: PageId,
+    },
+    ActorMoved {
+        actor: ActorId,
+        from: PageId,
+        to: PageId,
+    },
+    ActorDeparted {
+        actor: ActorId,
+        from: PageId,
+        to: PageId,
+    },
+    ActorAttacked {
+        attacker: ActorId,
+        target: ActorId,
+        page: PageId,
+    },
+    ActorWounded {
+        actor: ActorId,
+        attacker: ActorId,
+        page: PageId,
+        damage: i32,
+        health: i32, // left after the blow
+    },
+    ActorConversed {
+        speaker: ActorId,
+        listener: ActorId,
+        page: PageId,
+    },
+    ThiefCaught {
+        thief: ActorId,
+        victim: ActorId,
+        page: PageId,
+    },
+    ActorSlept {
+        actor: ActorId,
+        page: PageId,
+    },
+    ActorWoke {
+        actor: ActorId,
+        page: PageId,
+    },
+    ActorHarmed {
+        actor: ActorId,
+        page: PageId,
+        hazard: HazardKind,
+        health: i32,
+    },
+    ActorFellIll {
+        actor: ActorId,
+        page: PageId,
+    },
+    ActorDevoured {
+        actor: ActorId,
+        by: ActorId,
+        page: PageId,
+    },
+    ActorStarved {
+        actor: ActorId,
+        page: PageId,
+    },
+    ActorEdited {
+        actor: ActorId,
+        change: String, // what an admin did, e.g. "health=3, paused=true"
+    },
+    PlayerMoved {
+        from: PageId,
+        to: PageId,
+    },
+    PlayerEmoted {
+        player: ActorId, // the player's character
+        name: String,
+        page: PageId,
+        emote: Emote,
+    },
+    FixtureUsed {
+        page: PageId,
+        fixture: String,
+        verb: String,
+    },
+    PageFlagChanged {
+        page: PageId,
+        flag: String,
+        set: bool, // false: cleared
+    },
+    ScriptEmitted {
+        page: PageId,
+        name: String, // chosen by the script
+    },
+    ChatSaid {
+        page: PageId,
+        speaker: String, // player's character or NPC name
+        text: String,
+    },
+    PageAdded {
+        page: PageId,
+    },
+    PageRemoved {
+        page: PageId,
+        fallback: PageId,
+    },
+    ConnectionAdded {
+        from: PageId,
+        to: PageId,
+        name: String,
+    },
+    ConnectionRemoved {
+        from: PageId,
+        to: PageId,
+        name: String,
+    },
+    ItemTaken {
+        item: ItemId,
+        page: PageId,
+    },
+    EnvironmentGenerated {
+        page: PageId,
+        season: Season,
+        weather: WeatherKind,
+    },
+    WeatherChanged {
+        page: PageId,
+        weather: WeatherKind,
+        intensity: f32,
+    },
+    WorldRestored {
+        save: String, // name of the save slot
+    },
+    WorldReset {
+        generation: u64, // times reset since startup
+    },
+}
+
+impl WorldEvent {
+    /// Short name of the event kind, matching its serialized `kind` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WorldEvent::WorldTicked { .. } => "WorldTicked",
+            WorldEvent::ClockChanged { .. } => "ClockChanged",
+            WorldEvent::TickerRestarted { .. } => "TickerRestarted",
+            WorldEvent::ActorSpawned { .. } => "ActorSpawned",
+            WorldEvent::ActorDespawned { .. } => "ActorDespawned",
+            WorldEvent::ActorMoved { .. } => "ActorMoved",
+            WorldEvent::ActorDeparted { .. } => "ActorDeparted",
+            WorldEvent::ActorAttacked { .. } => "ActorAttacked",
+            WorldEvent::ActorWounded { .. } => "ActorWounded",
+            WorldEvent::ActorConversed { .. } => "ActorConversed",
+            WorldEvent::ThiefCaught { .. } => "ThiefCaught",
+            WorldEvent::ActorSlept { .. } => "ActorSlept",
+            WorldEvent::ActorWoke { .. } => "ActorWoke",
+            WorldEvent::ActorHarmed { .. } => "ActorHarmed",
+            WorldEvent::ActorFellIll { .. } => "ActorFellIll",
+            WorldEvent::ActorDevoured { .. } => "ActorDevoured",
+            WorldEvent::ActorStarved { .. } => "ActorStarved",
+            WorldEvent::ActorEdited { .. } => "ActorEdited",
+            WorldEvent::PlayerMoved { .. } => "PlayerMoved",
+            WorldEvent::PlayerEmoted { .. } => "PlayerEmoted",
+            WorldEvent::FixtureUsed { .. } => "FixtureUsed",
+            WorldEvent::PageFlagChanged { .. } => "PageFlagChanged",
+            WorldEvent::ScriptEmitted { .. } => "ScriptEmitted",
+            WorldEvent::ChatSaid { .. } => "ChatSaid",
+            WorldEvent::PageAdded { .. } => "PageAdded",
+            WorldEvent::PageRemoved { .. } => "PageRemoved",
+            WorldEvent::ConnectionAdded { .. } => "ConnectionAdded",
+            WorldEvent::ConnectionRemoved { .. } => "ConnectionRemoved",
+            WorldEvent::ItemTaken { .. } => "ItemTaken",
+            WorldEvent::EnvironmentGenerated { .. } => "EnvironmentGenerated",
+            WorldEvent::WeatherChanged { .. } => "WeatherChanged",
+            WorldEvent::WorldRestored { .. } => "WorldRestored",
+            WorldEvent::WorldReset { .. } => "WorldReset",
+        }
+    }
+
+    /// The weather involved, for weather events
+    pub fn weather(&self) -> Option<WeatherKind> {
+        match self {
+            WorldEvent::EnvironmentGenerated { weather, .. }
+            | WorldEvent::WeatherChanged { weather, .. } => Some(*weather),
+            _ => None,
+        }
+    }
+
+    /// Whether someone standing on `page` would notice this event
+    pub fn concerns(&self, page: &PageId) -> bool {
+        match self {
+            WorldEvent::WorldTicked { .. }
+            | WorldEvent::TickerRestarted { .. }
+            | WorldEvent::ActorEdited { .. } => false,
+            WorldEvent::ClockChanged { .. } => true, // everyone notices the sky change
+            WorldEvent::WorldRestored { .. } | WorldEvent::WorldReset { .. } => true, // anything may have changed anywhere
+            WorldEvent::ActorMoved { from, to, .. }
+            | WorldEvent::ActorDeparted { from, to, .. }
+            | WorldEvent::PlayerMoved { from, to } => from == page || to == page,
+            WorldEvent::PageRemoved { page: at, fallback } => at == page || fallback == page,
+            WorldEvent::ConnectionAdded { from, .. }
+            | WorldEvent::ConnectionRemoved { from, .. } => from == page,
+            WorldEvent::ActorSpawned { page: at, .. }
+            | WorldEvent::ActorDespawned { page: at, .. }
+            | WorldEvent::ActorAttacked { page: at, .. }
+            | WorldEvent::ActorWounded { page: at, .. }
+            | WorldEvent::ActorConversed { page: at, .. }
+            | WorldEvent::ThiefCaught { page: at, .. }
+            | WorldEvent::ActorSlept { page: at, .. }
+            | WorldEvent::ActorWoke { page: at, .. }
+            | WorldEvent::ActorHarmed { page: at, .. }
+            | WorldEvent::ActorFellIll { page: at, .. }
+            | WorldEvent::ActorDevoured { page: at, .. }
+            | WorldEvent::ActorStarved { page: at, .. }
+            | WorldEvent::ItemTaken { page: at, .. }
+            | WorldEvent::PlayerEmoted { page: at, .. }
+            | WorldEvent::FixtureUsed { page: at, .. }
+            | WorldEvent::PageFlagChanged { page: at, .. }
+            | WorldEvent::ScriptEmitted { page: at, .. }
+            | WorldEvent::ChatSaid { page: at, .. }
+            | WorldEvent::PageAdded { page: at }
+            | WorldEvent::EnvironmentGenerated { page: at, .. }
+            | WorldEvent::WeatherChanged { page: at, .. } => at == page,
+        }
+    }
+
+    /// The actors the event is about, any of whom a player has to be able
+    /// to see to hear of it
+    pub fn actors(&self) -> Vec<&ActorId> {
+        match self {
+            WorldEvent::ActorSpawned { actor, .. }
+            | WorldEvent::ActorDespawned { actor, .. }
+            | WorldEvent::ActorMoved { actor, .. }
+            | WorldEvent::ActorDeparted { actor, .. }
+            | WorldEvent::ActorSlept { actor, .. }
+            | WorldEvent::ActorWoke { actor, .. }
+            | WorldEvent::ActorHarmed { actor, .. }
+            | WorldEvent::ActorFellIll { actor, .. }
+            | WorldEvent::ActorStarved { actor, .. }
+            | WorldEvent::ActorEdited { actor, .. }
+            | WorldEvent::PlayerEmoted { player: actor, .. } => vec![actor],
+            WorldEvent::ActorAttacked {
+                attacker: a,
+                target: b,
+                ..
+            }
+            | WorldEvent::ActorWounded {
+                actor: a,
+                attacker: b,
+                ..
+            }
+            | WorldEvent::ActorConversed {
+                speaker: a,
+                listener: b,
+                ..
+            }
+            | WorldEvent::ThiefCaught {
+                thief: a,
+                victim: b,
+                ..
+            }
+            | WorldEvent::ActorDevoured {
+                actor: a, by: b, ..
+            } => vec![a, b],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Broadcast channel the tick loop, handlers and environment publish to.
+/// Cheap to clone; every clone publishes to the same subscribers.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<WorldEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BUS_CAPACITY);
+        EventBus { sender }
+    }
+
+    /// Publish an event to whoever is currently subscribed
+    pub fn publish(&self, event: WorldEvent) {
+        trace!(kind = event.kind(), "Publishing event");
+        // an error just means nobody is listening right now
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WorldEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// An event as remembered by the log, stamped with the world time it was seen
+#[derive(Clone, Debug)]
+pub struct LoggedEvent {
+    pub at: DateTime<Local>,
+    pub event: WorldEvent,
+}
+
+/// Bounded in-memory history of recent events, so content can react to
+/// what happened a while ago ("it stormed last night").
+#[derive(Clone, Default)]
+pub struct EventLog {
+    entries: Arc<Mutex<VecDeque<LoggedEvent>>>,
+}
+
+impl EventLog {
+    /// Subscribe to the bus and record everything but tick heartbeats
+    pub fn spawn_subscriber(&self, bus: &EventBus, clock: WorldClock) {
+        let mut rx = bus.subscribe();
+        let log = self.clone();
+        actix_rt::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(WorldEvent::WorldTicked { .. }) => continue,
+                    Ok(event) => log.record(clock.now(), event),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    fn record(&self, at: DateTime<Local>, event: WorldEvent) {
+        let mut entries = self.entries.lock();
+        if entries.len() == LOG_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(LoggedEvent { at, event });
+    }
+
+    /// The `n` most recent events, newest first
+    pub fn recent(&self, n: usize) -> Vec<LoggedEvent> {
+        self.entries.lock().iter().rev().take(n).cloned().collect()
+    }
+
+    /// Events logged at or after `since` that match `pred`, newest first
+    pub fn matching_since(
+        &self,
+        since: DateTime<Local>,
+        pred: impl Fn(&WorldEvent) -> bool,
+    ) -> Vec<LoggedEvent> {
+        self.entries
+            .lock()
+            .iter()
+            .rev()
+            .take_while(|entry| entry.at >= since)
+            .filter(|entry| pred(&entry.event))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether any event logged at or after `since` matches `pred`
+    pub fn any_since(&self, since: DateTime<Local>, pred: impl Fn(&WorldEvent) -> bool) -> bool {
+        self.entries
+            .lock()
+            .iter()
+            .rev()
+            .take_while(|entry| entry.at >= since)
+            .any(|entry| pred(&entry.event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moved(n: usize) -> WorldEvent {
+        WorldEvent::ActorMoved {
+            actor: ActorId::from(format!("critter-{n}").as_str()),
+            from: PageId::from("meadow"),
+            to: PageId::from("ford"),
+        }
+    }
+
+    #[test]
+    fn a_subscriber_left_behind_skips_to_what_it_can_still_get() {
+        let bus = EventBus::new();
+        let mut rx = bus.subscribe();
+        for n in 0..BUS_CAPACITY + 10 {
+            bus.publish(moved(n));
+        }
+        assert!(matches!(
+            rx.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(10))
+        ));
+        // and carries on from the oldest event still held
+        let Ok(WorldEvent::ActorMoved { actor, .. }) = rx.try_recv() else {
+            panic!("nothing after the lag");
+        };
+        assert_eq!(actor.as_str(), "critter-10");
+    }
+
+    #[test]
+    fn events_reach_only_those_on_the_pages_they_concern() {
+        let (meadow, ford, elsewhere) = (
+            PageId::from("meadow"),
+            PageId::from("ford"),
+            PageId::from("elsewhere"),
+        );
+        let bus = EventBus::new();
+        let mut rx = bus.subscribe();
+        bus.publish(moved(0));
+        bus.publish(WorldEvent::ActorSlept {
+            actor: ActorId::from("critter-1"),
+            page: meadow.clone(),
+        });
+        bus.publish(WorldEvent::ClockChanged { hour: 6, minute: 0 });
+        bus.publish(WorldEvent::WorldTicked { tick: 1, acted: 2 });
+        let mut heard: Vec<WorldEvent> = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            heard.push(event);
+        }
+        let noticed = |page: &PageId| -> Vec<&str> {
+            heard
+                .iter()
+                .filter(|event| event.concerns(page))
+                .map(WorldEvent::kind)
+                .collect()
+        };
+        assert_eq!(
+            noticed(&meadow),
+            ["ActorMoved", "ActorSlept", "ClockChanged"]
+        );
+        assert_eq!(noticed(&ford), ["ActorMoved", "ClockChanged"]);
+        assert_eq!(noticed(&elsewhere), ["ClockChanged"]);
+    }
+
+    #[test]
+    fn events_name_everyone_a_player_must_see_to_hear_of_them() {
+        let (wolf, hare) = (ActorId::from("wolf"), ActorId::from("hare"));
+        let devoured = WorldEvent::ActorDevoured {
+            actor: hare.clone(),
+            by: wolf.clone(),
+            page: PageId::from("meadow"),
+        };
+        assert_eq!(devoured.actors(), [&hare, &wolf]);
+        assert_eq!(moved(0).actors(), [&ActorId::from("critter-0")]);
+        assert!(
+            WorldEvent::ClockChanged { hour: 6, minute: 0 }
+                .actors()
+                .is_empty()
+        );
+    }
+}
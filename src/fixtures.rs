@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::conditions::Condition;
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::pages::{Page, PageConnection};
+use crate::scripting::{ScriptContext, ScriptEffect, ScriptHost};
+use crate::session::UserSession;
+use crate::world::WorldGraph;
+
+/// Something fixed in place on a page that players can do things with
+/// (a sign to read, a lever to pull)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub actions: Vec<FixtureAction>,
+}
+
+/// One thing a player can do with a fixture
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FixtureAction {
+    pub verb: String, // "read", "pull"
+    pub text: String, // what the player is told when it works
+    #[serde(default)]
+    pub requires: Condition,
+    #[serde(default)]
+    pub refused: Option<String>, // told instead when `requires` doesn't hold
+    #[serde(default)]
+    pub effects: Vec<FixtureEffect>,
+}
+
+/// What using a fixture changes
+#[derive(Clone, Serialize, Deserialize)]
+pub enum FixtureEffect {
+    /// Set a story flag on the player who used it
+    SetFlag(String),
+    /// Set, clear or flip a flag on the page itself, for everyone
+    SetPageFlag(String),
+    ClearPageFlag(String),
+    TogglePageFlag(String),
+    /// Open this way out of the page if it isn't there, close it if it is.
+    /// Applies to everyone.
+    ToggleConnection(PageConnection),
+    /// Run a script function, "file::function"
+    Script(String),
+}
+
+/// Parse an `interact` form field, "fixture:verb"
+pub fn parse_interaction(field: &str) -> Result<(&str, &str), AppError> {
+    field
+        .split_once(':')
+        .ok_or_else(|| AppError::SessionError(format!("Bad interaction '{field}'")))
+}
+
+/// The action `verb` on the fixture `fixture` on `here`
+pub fn find_action<'a>(
+    here: &'a Page,
+    fixture: &str,
+    verb: &str,
+) -> Result<&'a FixtureAction, AppError> {
+    here.fixtures
+        .iter()
+        .find(|f| f.id == fixture)
+        .and_then(|f| f.actions.iter().find(|a| a.verb == verb))
+        .ok_or_else(|| AppError::SessionError(format!("Can't {verb} {fixture} here")))
+}
+
+/// Carry out what `action` does, as done by `player` on `here` at world
+/// hour `hour`. Returns what its scripts asked for, for the caller to apply.
+#[allow(clippy::too_many_arguments)]
+pub fn apply(
+    action: &FixtureAction,
+    fixture: &str,
+    here: &Page,
+    player: &mut UserSession,
+    world: &WorldGraph,
+    bus: &EventBus,
+    scripts: &ScriptHost,
+    hour: u8,
+) -> Result<Vec<ScriptEffect>, AppError> {
+    let mut scripted = Vec::new();
+    for effect in &action.effects {
+        match effect {
+            FixtureEffect::SetFlag(flag) => {
+                player.flags.insert(flag.clone());
+            }
+            FixtureEffect::SetPageFlag(flag) => {
+                world.set_page_flag(&here.id, flag, true)?;
+            }
+            FixtureEffect::ClearPageFlag(flag) => {
+                world.set_page_flag(&here.id, flag, false)?;
+            }
+            FixtureEffect::TogglePageFlag(flag) => {
+                let set = world
+                    .page_flags()
+                    .get(&here.id)
+                    .is_none_or(|flags| !flags.contains(flag));
+                world.set_page_flag(&here.id, flag, set)?;
+            }
+            FixtureEffect::Script(script) => {
+                let ctx =
+                    ScriptContext::new(&here.id, hour, world.page_flags()).player(&player.flags);
+                scripted.extend(scripts.run(script, ctx)?);
+            }
+            FixtureEffect::ToggleConnection(connection) => {
+                let open = world.snapshot().get(&here.id).is_some_and(|page| {
+                    page.connections
+                        .iter()
+                        .any(|conn| conn.name == connection.name)
+                });
+                if open {
+                    world.disconnect(&here.id, &connection.name)?;
+                } else {
+                    world.connect(&here.id, connection.clone())?;
+                }
+            }
+        }
+    }
+    info!("{} {} on {}", action.verb, fixture, here.id);
+    bus.publish(WorldEvent::FixtureUsed {
+        page: here.id.clone(),
+        fixture: fixture.to_string(),
+        verb: action.verb.clone(),
+    });
+    Ok(scripted)
+}
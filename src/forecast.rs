@@ -0,0 +1,109 @@
+//! Weather forecasts. The weather simulation is played forward from now a
+//! number of times, and for each region the weather that came up most over
+//! its open-air pages is given hour by hour, with how often it came up.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::pages::{Biome, PageGraph, PageId};
+use crate::regions::{RegionId, Regions};
+use crate::weather::{WeatherKind, WeatherState};
+
+/// Hours forecast when none are asked for
+pub const DEFAULT_FORECAST_HOURS: u32 = 12;
+/// Most hours a forecast reaches ahead
+pub const MAX_FORECAST_HOURS: u32 = 48;
+/// Times the simulation is played forward for one forecast
+pub const FORECAST_RUNS: usize = 20;
+/// The page template a weather station uses; pages rendered with it get
+/// `forecast` in their context
+pub const FORECAST_TEMPLATE: &str = "forecast.html";
+
+/// The weather everywhere at one moment in one run of the simulation
+pub type WeatherMap = HashMap<PageId, WeatherState>;
+
+/// The likeliest weather over a region at one hour
+#[derive(Serialize)]
+pub struct ForecastHour {
+    pub at: DateTime<Local>,
+    pub weather: WeatherKind,
+    pub chance: u8,     // percent of pages and runs it came up in
+    pub intensity: f32, // on average, where it did
+}
+
+#[derive(Serialize)]
+pub struct RegionForecast {
+    pub region: RegionId,
+    pub name: String,
+    pub hours: Vec<ForecastHour>,
+}
+
+/// Sum up the simulated weather per region. `hours` pairs each forecast
+/// hour with the weather everywhere at that hour in every run. Regions
+/// without open sky (every page sheltered or underground) are left out.
+pub fn summarize(
+    regions: &Regions,
+    pages: &PageGraph,
+    hours: &[(DateTime<Local>, Vec<&WeatherMap>)],
+) -> Vec<RegionForecast> {
+    let mut forecasts: Vec<RegionForecast> = regions
+        .iter()
+        .filter_map(|region| {
+            let open_air: Vec<&PageId> = pages
+                .values()
+                .filter(|page| {
+                    regions.within(page.region.as_ref(), &region.id)
+                        && page.biome != Biome::Cave
+                        && !page.is_sheltered()
+                        && !regions.shelters(page)
+                })
+                .map(|page| &page.id)
+                .collect();
+            if open_air.is_empty() {
+                return None;
+            }
+            let hours = hours
+                .iter()
+                .filter_map(|(at, runs)| {
+                    let states = runs
+                        .iter()
+                        .flat_map(|run| open_air.iter().filter_map(|id| run.get(*id)));
+                    likeliest(states).map(|(weather, chance, intensity)| ForecastHour {
+                        at: *at,
+                        weather,
+                        chance,
+                        intensity,
+                    })
+                })
+                .collect();
+            Some(RegionForecast {
+                region: region.id.clone(),
+                name: region.name.clone(),
+                hours,
+            })
+        })
+        .collect();
+    forecasts.sort_by(|a, b| a.region.0.cmp(&b.region.0));
+    forecasts
+}
+
+/// The weather that came up most among `states`, how often (percent) and
+/// how strong on average
+fn likeliest<'a>(states: impl Iterator<Item = &'a WeatherState>) -> Option<(WeatherKind, u8, f32)> {
+    let mut tally: HashMap<WeatherKind, (u32, f32)> = HashMap::new();
+    let mut total = 0;
+    for state in states {
+        let (count, intensity) = tally.entry(state.kind).or_default();
+        *count += 1;
+        *intensity += state.intensity;
+        total += 1;
+    }
+    // ties go to the calmer weather, the order of `WeatherKind::ALL`
+    let (kind, (count, intensity)) = WeatherKind::ALL
+        .into_iter()
+        .filter_map(|kind| tally.get(&kind).map(|t| (kind, *t)))
+        .rev()
+        .max_by_key(|(_, (count, _))| *count)?;
+    Some((kind, (count * 100 / total) as u8, intensity / count as f32))
+}
@@ -0,0 +1,205 @@
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use crate::conditions::Condition;
+use crate::error::AppError;
+use crate::pages::{Biome, DEFAULT_TEMPLATE, Lighting, Page, PageConnection, PageGraph, PageId};
+use crate::regions::RegionId;
+
+pub const DEFAULT_AREAS_PATH: &str = "data/areas.toml";
+
+/// Compass exits tried first when linking rooms, with the way back
+const COMPASS: [(&str, &str); 4] = [
+    ("North", "South"),
+    ("East", "West"),
+    ("South", "North"),
+    ("West", "East"),
+];
+
+/// Recipe for a generated area: how big, what it looks like and where it hangs off
+#[derive(Clone, Debug, Deserialize)]
+pub struct AreaSpec {
+    pub id: String,        // page ids become `<id>-1`, `<id>-2`, ...
+    pub attach_to: PageId, // existing page the area opens off
+    pub entrance: String,  // connection name from `attach_to` into the area
+    pub exit: String,      // connection name from the first room back out
+    pub rooms: usize,
+    #[serde(default)]
+    pub biome: Biome,
+    #[serde(default)]
+    pub lighting: Lighting,
+    #[serde(default)]
+    pub region: Option<RegionId>,
+    pub nouns: Vec<String>,      // "Glade", "Hollow"
+    pub adjectives: Vec<String>, // "Mossy", "Silent"
+    pub details: Vec<String>,    // sentences, two are picked per room
+    #[serde(default)]
+    pub seed: Option<u64>, // same seed, same area
+}
+
+#[derive(Deserialize)]
+struct AreaFile {
+    #[serde(default)]
+    area: Vec<AreaSpec>,
+}
+
+/// Read area recipes from a TOML file. A missing file just means no areas.
+pub fn load_area_specs(path: &Path) -> Result<Vec<AreaSpec>, AppError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+    let file: AreaFile = toml::from_str(&text)
+        .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+    Ok(file.area)
+}
+
+/// A generated sub-graph, not yet part of the world
+pub struct Area {
+    pub pages: Vec<Page>,
+    pub attach_to: PageId,
+    pub entrance: PageConnection, // to add to `attach_to`
+}
+
+impl AreaSpec {
+    /// Build the area: a random spanning tree of rooms plus a few loops,
+    /// so it is always fully connected but not just a corridor.
+    pub fn generate(&self) -> Result<Area, AppError> {
+        if self.rooms == 0 || self.nouns.is_empty() || self.adjectives.is_empty() {
+            return Err(AppError::OtherError(format!(
+                "Area '{}' needs rooms, nouns and adjectives",
+                self.id
+            )));
+        }
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+
+        let ids: Vec<PageId> = (1..=self.rooms)
+            .map(|i| PageId::from(format!("{}-{i}", self.id)))
+            .collect();
+        let mut pages: Vec<Page> = ids
+            .iter()
+            .map(|id| self.room(id.clone(), &mut rng))
+            .collect();
+
+        let mut linked = HashSet::new();
+        for i in 1..pages.len() {
+            let j = rng.random_range(0..i);
+            link(&mut pages, i, j, &mut rng);
+            linked.insert((i.min(j), i.max(j)));
+        }
+        for _ in 0..self.rooms / 4 {
+            let (i, j) = (
+                rng.random_range(0..self.rooms),
+                rng.random_range(0..self.rooms),
+            );
+            if i != j && linked.insert((i.min(j), i.max(j))) {
+                link(&mut pages, i, j, &mut rng);
+            }
+        }
+
+        pages[0]
+            .connections
+            .push(open_way(&self.exit, &self.attach_to));
+        Ok(Area {
+            pages,
+            attach_to: self.attach_to.clone(),
+            entrance: open_way(&self.entrance, &ids[0]),
+        })
+    }
+
+    fn room(&self, id: PageId, rng: &mut impl Rng) -> Page {
+        let pick = |words: &[String], rng: &mut _| -> String {
+            words.choose(rng).cloned().unwrap_or_default()
+        };
+        let title = format!("{} {}", pick(&self.adjectives, rng), pick(&self.nouns, rng));
+        let details: Vec<&String> = self.details.choose_multiple(rng, 2).collect();
+        let description = if details.is_empty() {
+            format!("A {}.", title.to_lowercase())
+        } else {
+            details
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        Page {
+            id,
+            template: DEFAULT_TEMPLATE.to_string(),
+            connections: Vec::new(),
+            title,
+            description,
+            metadata: HashMap::from([("generated".to_string(), self.id.clone())]),
+            variants: Vec::new(),
+            alternates: BTreeMap::new(),
+            lighting: self.lighting,
+            biome: self.biome,
+            region: self.region.clone(),
+            items: Vec::new(),
+            shop: None,
+            fixtures: Vec::new(),
+        }
+    }
+}
+
+/// Join rooms `a` and `b` both ways, by compass direction where one is free on both sides
+fn link(pages: &mut [Page], a: usize, b: usize, rng: &mut impl Rng) {
+    let taken = |page: &Page, name: &str| page.connections.iter().any(|c| c.name == name);
+    let start = rng.random_range(0..COMPASS.len());
+    let compass = (0..COMPASS.len())
+        .map(|k| COMPASS[(start + k) % COMPASS.len()])
+        .find(|(there, back)| !taken(&pages[a], there) && !taken(&pages[b], back));
+    let (there, back) = match compass {
+        Some((there, back)) => (there.to_string(), back.to_string()),
+        None => (
+            format!("To the {}", pages[b].title),
+            format!("To the {}", pages[a].title),
+        ),
+    };
+    let (to_b, to_a) = (pages[b].id.clone(), pages[a].id.clone());
+    pages[a].connections.push(open_way(&there, &to_b));
+    pages[b].connections.push(open_way(&back, &to_a));
+}
+
+fn open_way(name: &str, target: &PageId) -> PageConnection {
+    PageConnection {
+        name: name.to_string(),
+        target: target.clone(),
+        requires: Condition::Always,
+        secret: false,
+        locked_text: None,
+        distance: 1,
+        hidden: 0,
+        needs: None,
+        tight: false,
+        blocks: Vec::new(),
+    }
+}
+
+/// Add a generated area to `pages`, opening the way in from its attach page
+pub fn attach(pages: &mut PageGraph, area: Area) -> Result<(), AppError> {
+    if let Some(clash) = area.pages.iter().find(|p| pages.contains_key(&p.id)) {
+        return Err(AppError::Conflict(format!(
+            "Generated page {} already exists",
+            clash.id
+        )));
+    }
+    let attach_page = pages
+        .get_mut(&area.attach_to)
+        .ok_or_else(|| AppError::PageNotFound(area.attach_to.to_string()))?;
+    attach_page
+        .connections
+        .retain(|conn| conn.name != area.entrance.name);
+    attach_page.connections.push(area.entrance);
+    for page in area.pages {
+        pages.insert(page.id.clone(), page);
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Goals: something an actor sets out to bring about over many ticks, such
+//! as keeping an appointment or finding somewhere to graze. An actor with a
+//! goal works out a plan for it (waking up, then the steps of the way
+//! there) and keeps it in its state, seeing it through a step a tick. A
+//! plan that's gone stale, because the actor was driven off its way or the
+//! grazing it was making for has been eaten bare, is worked out afresh.
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::actor::{Actor, ActorAction, ActorFlag};
+use crate::ecology::{self, HUNGRY, PECKISH, Pastures};
+use crate::environment::WorldTime;
+use crate::pages::{PageGraph, PageId};
+
+/// Hours ahead of an appointment an actor sets out for it
+pub const APPOINTMENT_LEAD: u8 = 2;
+
+/// Somewhere an actor means to be every day by a given hour
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Appointment {
+    pub page: PageId,
+    pub by: u8, // hour of the day
+}
+
+impl Appointment {
+    /// Whether it's time to be setting out for it, or waiting there
+    fn due(&self, world_time: &WorldTime) -> bool {
+        let hours_to_go = (self.by % 24 + 24 - world_time.hour % 24) % 24;
+        (1..=APPOINTMENT_LEAD).contains(&hours_to_go)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    BeAt(PageId), // until the appointment's hour comes
+    Eat,          // somewhere there's grazing left, until it's had its fill
+}
+
+/// The goal `actor` should be pursuing now, if any: a due appointment
+/// first, then, for a hungry grazer with nothing left to eat where it
+/// stands, finding some; one it's already after it keeps until it's had
+/// its fill
+pub fn choose(
+    actor: &Actor,
+    world_time: &WorldTime,
+    pastures: &Pastures,
+    page_graph: &PageGraph,
+) -> Option<Goal> {
+    if let Some(appointment) = actor.appointments.iter().find(|a| a.due(world_time)) {
+        return Some(Goal::BeAt(appointment.page.clone()));
+    }
+    let grazer = ecology::eats(actor) && actor.has_flag(ActorFlag::Herbivore);
+    let eating = actor.state.goal == Some(Goal::Eat) && actor.state.hunger >= PECKISH;
+    let starving = actor.state.hunger >= HUNGRY && !grazing(&actor.location, pastures, page_graph);
+    (grazer && (eating || starving)).then_some(Goal::Eat)
+}
+
+/// Whether there's grazing left on `page`
+fn grazing(page: &PageId, pastures: &Pastures, page_graph: &PageGraph) -> bool {
+    page_graph
+        .get(page)
+        .and_then(|page| pastures.left(page))
+        .is_some_and(|(_, left)| left > 0)
+}
+
+/// Whether `page` is where `goal` is met
+fn met_at(goal: &Goal, page: &PageId, pastures: &Pastures, page_graph: &PageGraph) -> bool {
+    match goal {
+        Goal::BeAt(there) => page == there,
+        Goal::Eat => grazing(page, pastures, page_graph),
+    }
+}
+
+/// The steps that bring `actor` to where `goal` is met: waking up if it's
+/// asleep, then the shortest way there on foot. Empty if it's there and
+/// awake already; None if there's no way there.
+pub fn plan(
+    actor: &Actor,
+    goal: &Goal,
+    pastures: &Pastures,
+    page_graph: &PageGraph,
+) -> Option<Vec<ActorAction>> {
+    let mut steps = Vec::new();
+    if !actor.state.awake {
+        steps.push(ActorAction::WakeUp);
+    }
+    if !met_at(goal, &actor.location, pastures, page_graph) {
+        let route = actor.route(|p| met_at(goal, p, pastures, page_graph), page_graph)?;
+        steps.extend(route.into_iter().map(ActorAction::MoveTo));
+    }
+    Some(steps)
+}
+
+/// Whether `actor`'s plan still holds: its next step can be taken from
+/// where it stands, and where it leads still meets its goal
+fn holds(actor: &Actor, goal: &Goal, pastures: &Pastures, page_graph: &PageGraph) -> bool {
+    let next_ok = match actor.state.plan.first() {
+        Some(ActorAction::MoveTo(next)) => page_graph.get(&actor.location).is_some_and(|here| {
+            here.connections
+                .iter()
+                .any(|conn| conn.target == *next && conn.needs.is_none())
+        }),
+        _ => true,
+    };
+    let end = actor
+        .state
+        .plan
+        .iter()
+        .rev()
+        .find_map(|step| match step {
+            ActorAction::MoveTo(page) => Some(page),
+            _ => None,
+        })
+        .unwrap_or(&actor.location);
+    next_ok && met_at(goal, end, pastures, page_graph)
+}
+
+/// Bring `actor`'s goal and plan up to date at the start of its turn:
+/// take up (or drop) a goal, strike off the steps it has already made, and
+/// work the plan out afresh if it's new or no longer holds
+pub fn pursue(
+    actor: &mut Actor,
+    world_time: &WorldTime,
+    pastures: &Pastures,
+    page_graph: &PageGraph,
+) {
+    let goal = choose(actor, world_time, pastures, page_graph);
+    if goal != actor.state.goal {
+        debug!(%actor.id, ?goal, "Takes up a goal.");
+        actor.state.plan.clear();
+        actor.state.goal = goal.clone();
+    }
+    let Some(goal) = goal else {
+        return;
+    };
+    while let Some(done) = actor.state.plan.first() {
+        let made = match done {
+            ActorAction::WakeUp => actor.state.awake,
+            ActorAction::MoveTo(page) => *page == actor.location,
+            _ => false,
+        };
+        if !made {
+            break;
+        }
+        actor.state.plan.remove(0);
+    }
+    if actor.state.plan.is_empty() && met_at(&goal, &actor.location, pastures, page_graph) {
+        return; // there, and seeing it through
+    }
+    if actor.state.plan.is_empty() || !holds(actor, &goal, pastures, page_graph) {
+        match plan(actor, &goal, pastures, page_graph) {
+            Some(steps) => {
+                debug!(%actor.id, ?goal, steps = steps.len(), "Plans the way.");
+                actor.state.plan = steps;
+            }
+            None => {
+                debug!(%actor.id, ?goal, "Sees no way to it; gives up.");
+                actor.state.goal = None;
+                actor.state.plan.clear();
+            }
+        }
+    }
+}
@@ -1,72 +1,1131 @@
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap};
+use std::future::{Ready, ready};
+use std::sync::Arc;
 
-use actix_web::{HttpResponse, Responder, web};
-use tera::{Context, Tera};
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder, web};
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use serde::Serialize;
+use tera::Tera;
 use tracing::{error, info, instrument};
 
-use crate::actor::{Actor, ActorManager};
+use crate::actor::{Actor, ActorFlag, ActorId, ActorMap};
+use crate::ambience::Ambience;
+use crate::census;
+use crate::chat::{self, ChatLog};
+use crate::clock::WorldClock;
+use crate::combat;
+use crate::companions;
+use crate::conditions::ConditionContext;
+use crate::consumables;
+use crate::cooldown::Cooldowns;
+use crate::crafting::{self, RecipeBook};
+use crate::dialogue::{DialogueBook, DialogueLine};
+use crate::ecology::Forage;
+use crate::encounters::{self, Encounters};
 use crate::environment::EnvironmentManager;
+use crate::environment::{Environment, PartOfDay, WorldTime};
 use crate::error::AppError;
-use crate::pages::{PageGraph, valid_move};
+use crate::events::{EventBus, EventLog, WorldEvent};
+use crate::fixtures;
+use crate::forecast::{DEFAULT_FORECAST_HOURS, FORECAST_TEMPLATE};
+use crate::i18n::Translations;
+use crate::inspect;
+use crate::instances::Instances;
+use crate::items::{self, ItemCatalog, ItemId};
+use crate::mounts::Means;
+use crate::pages::{Page, PageGraph, PageId, render_page, valid_move, visible_exits};
+use crate::plugins::PluginHost;
+use crate::quests::QuestBook;
+use crate::regions::{self, Regions};
+use crate::render::{self, ViewMode, ViewQuery};
+use crate::reputation::{Deed, Regard};
+use crate::rumors::{self, Rumor};
+use crate::scripting::{self, ScriptHost};
 use crate::session::{
-    SESSION_KEY, UserAction, UserSession, get_or_create_user_session, set_user_session,
+    Emote, JournalKind, SESSION_KEY, UserAction, UserSession, flash, get_or_create_user_session,
+    set_user_session,
 };
-// TODO: refactor
-#[instrument(skip(tera, pages, session, actor_manager, environment_manager, form))] // tracing 
+use crate::shops::ShopManager;
+use crate::status::Status;
+use crate::stimuli::StimulusKind;
+use crate::trade;
+use crate::users::{ACCOUNT_KEY, AccountStore};
+use crate::visibility::Visibility;
+use crate::world::WorldGraph;
+use crate::worlds::Mount;
+/// Where new players start, and where lost ones are sent
+pub const START_PAGE: &str = "small-town";
+
+/// How long, in world time, emotes stay on the page
+const EMOTE_MEMORY: TimeDelta = TimeDelta::minutes(10);
+/// Most emotes listed on a page
+const EMOTES_SHOWN: usize = 5;
+
+/// How long, in world time, NPC conversations can still be overheard
+const OVERHEARD_MEMORY: TimeDelta = TimeDelta::minutes(10);
+/// Most overheard conversations listed on a page
+const OVERHEARD_SHOWN: usize = 3;
+
+/// What players do on a page beyond moving and taking things, extracted as
+/// one tuple since actix handlers take at most 16 extractors
+type PlayerSystems = (
+    web::Data<ChatLog>,
+    web::Data<Cooldowns>,
+    web::Data<Arc<QuestBook>>,
+    web::Data<ShopManager>,
+    web::Data<Arc<RecipeBook>>,
+    web::Data<PluginHost>,
+    web::Data<Arc<Translations>>,
+    web::Data<Arc<Encounters>>,
+);
+
+/// What conditions look at beyond the player and their page, taken from
+/// app data as one extractor
+pub struct ConditionSources {
+    pub clock: web::Data<WorldClock>,
+    pub event_log: web::Data<EventLog>,
+    pub world: web::Data<WorldGraph>,
+    pub scripts: web::Data<ScriptHost>,
+}
+
+impl FromRequest for ConditionSources {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(ConditionSources::of(req))
+    }
+}
+
+/// Shared state registered as app data, as the `web::Data` extractor finds it
+fn app_data<T: 'static>(req: &HttpRequest) -> Result<web::Data<T>, AppError> {
+    req.app_data::<web::Data<T>>().cloned().ok_or_else(|| {
+        AppError::OtherError(format!("No {} configured", std::any::type_name::<T>()))
+    })
+}
+
+impl ConditionSources {
+    fn of(req: &HttpRequest) -> Result<Self, AppError> {
+        Ok(ConditionSources {
+            clock: app_data(req)?,
+            event_log: app_data(req)?,
+            world: app_data(req)?,
+            scripts: app_data(req)?,
+        })
+    }
+
+    /// Conditions as they stand now for `session` on `page`
+    pub fn context<'a>(
+        &'a self,
+        session: &'a UserSession,
+        page: &'a PageId,
+        environment: &'a Environment,
+    ) -> ConditionContext<'a> {
+        ConditionContext {
+            session,
+            page,
+            now: self.clock.now(),
+            environment: Some(environment),
+            events: &self.event_log,
+            page_flags: self.world.page_flags(),
+            scripts: Some(&self.scripts),
+        }
+    }
+}
+
+#[instrument(skip(
+    tera,
+    mount,
+    sources,
+    items,
+    regions,
+    session,
+    instances,
+    environment_manager,
+    bus,
+    dialogue,
+    accounts,
+    chat_log,
+    cooldowns,
+    quests,
+    shops,
+    recipes,
+    plugins,
+    translations,
+    encounters,
+    view,
+    form
+))] // tracing
+#[allow(clippy::too_many_arguments)] // actix extractors
 pub async fn index_handler(
     tera: web::Data<Tera>,
-    pages: web::Data<Arc<PageGraph>>,
+    mount: web::Data<Mount>,
+    sources: ConditionSources,
+    items: web::Data<Arc<ItemCatalog>>,
+    regions: web::Data<Arc<Regions>>,
     session: actix_session::Session,
-    actor_manager: web::Data<Arc<Mutex<ActorManager>>>,
+    instances: web::Data<Instances>,
     environment_manager: web::Data<EnvironmentManager>,
+    bus: web::Data<EventBus>,
+    dialogue: web::Data<Arc<DialogueBook>>,
+    accounts: web::Data<AccountStore>,
+    (chat_log, cooldowns, quests, shops, recipes, plugins, translations, encounters): PlayerSystems,
+    view: web::Query<ViewQuery>,
     form: Option<web::Form<UserAction>>,
 ) -> impl Responder {
+    let ConditionSources {
+        clock,
+        event_log,
+        world,
+        scripts,
+    } = &sources;
     info!(
         "Serving page handler for session {:?}",
         session.get::<UserSession>(SESSION_KEY)
     );
 
     // Retrieve or create a user session (hardcoded start at palette-town)
-    let mut user_session = get_or_create_user_session(&session, "small-town")?;
+    let mut user_session = get_or_create_user_session(&session, START_PAGE)?;
+    if user_session.character.is_none() {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header(("Location", mount.path("/character")))
+            .finish());
+    }
+    // the actors this player shares the world with, or their own copy of them
+    let (actor_manager, actor_bus) = instances.for_player(&user_session.player_id);
+
+    if let Some(mode) = view.view {
+        user_session.text_only = mode == ViewMode::Text;
+        set_user_session(&session, &user_session);
+    }
+
+    // The page the player was on may have been removed since their last request
+    let pages = world.snapshot();
+    let resolved = world
+        .resolve(&user_session.current_page)
+        .unwrap_or_else(|| PageId::from(START_PAGE));
+    if resolved != user_session.current_page {
+        info!(
+            "Page {} is gone, moving player to {resolved}",
+            user_session.current_page
+        );
+        user_session.current_page = resolved;
+        set_user_session(&session, &user_session);
+    }
+
+    // flags granted for a while wear off
+    if user_session.expire_flags(clock.now().timestamp()) {
+        set_user_session(&session, &user_session);
+    }
 
-    // Handle navigation action
+    let world_time = clock.world_time();
+    let lang = translations.language(user_session.language.as_deref());
+    let text = |key: &str| translations.text(lang, key, &[]);
+    let mut spoke = false; // NPCs here may answer
+    let mut inspecting: Option<String> = None; // actor or fixture to describe up close
+
+    // Handle player actions (movement, picking things up)
     if let Some(action) = form {
-        if let Some(conn) = valid_move(&user_session.current_page, &action.go_to, &pages).await {
-            info!("User session {} is moving {}", SESSION_KEY, action.go_to);
-            user_session.current_page = conn.target.clone();
+        // Refusals (a wrong turn, acting too soon) aren't errors: the player
+        // is sent back to the page and told why
+        let acted = async {
+            let here = pages
+                .get(&user_session.current_page)
+                .ok_or_else(|| AppError::PageNotFound(user_session.current_page.to_string()))?;
+            let dark = dark_for_player(here, &world_time, &user_session, &items);
+            // every action asked for is checked before any is taken
+            let now_ms = Utc::now().timestamp_millis();
+            cooldowns.spend(&user_session.player_id, &action.throttled(), now_ms)?;
+
+            if let Some(go_to) = &action.go_to {
+                let environment = environment_manager
+                    .get_environment_for_page(&here.id)
+                    .await?;
+                let conditions = sources.context(&user_session, &here.id, &environment);
+                let means = {
+                    let manager = actor_manager.lock();
+                    Means::of(
+                        &user_session,
+                        &items,
+                        manager.companions_of(&user_session.player_id),
+                    )
+                };
+                // exits hidden by darkness (or secret) can't be taken
+                let can_see =
+                    visible_exits(here, &pages, &world_time, dark, &items, &means, &conditions)
+                        .iter()
+                        .any(|exit| exit.name == go_to);
+                let target = valid_move(
+                    &user_session.current_page,
+                    go_to,
+                    &pages,
+                    &means,
+                    &conditions,
+                )
+                .await
+                .filter(|_| can_see)
+                .map(|conn| conn.target.clone());
+                if target.is_some() && !user_session.spend_stamina(Utc::now().timestamp(), &means) {
+                    flash(&session, text("flash.out_of_breath"));
+                } else if let Some(target) = target {
+                    info!("User session {} is moving {}", SESSION_KEY, go_to);
+                    // whatever sprang out at them here is left behind
+                    if let Some(met) = user_session.encounter.take() {
+                        actor_manager.lock().slip_away(&met);
+                    }
+                    user_session.record_visit(&target);
+                    user_session.note(clock.now(), JournalKind::Arrived, &target);
+                    let from = std::mem::replace(&mut user_session.current_page, target);
+                    set_user_session(&session, &user_session);
+                    bus.publish(WorldEvent::PlayerMoved {
+                        from,
+                        to: user_session.current_page.clone(),
+                    });
+                    // passing through the wilds, something may spring out at them
+                    let there = pages.get(&user_session.current_page);
+                    if let Some(there) = there
+                        && !dark_for_player(there, &world_time, &user_session, &items)
+                        && let Some(entry) = encounters.roll(there, &mut rand::rng())
+                    {
+                        let environment = environment_manager
+                            .get_environment_for_page(&there.id)
+                            .await?;
+                        let conditions = sources.context(&user_session, &there.id, &environment);
+                        let met = encounters::meet(
+                            entry,
+                            there,
+                            &user_session.player_id,
+                            &mut actor_manager.lock(),
+                            |a| Visibility::of(a, false, &conditions) == Visibility::Hidden,
+                        );
+                        if met.is_some() {
+                            user_session.encounter = met;
+                            set_user_session(&session, &user_session);
+                        }
+                    }
+                } else {
+                    info!("Tried invalid direction {}", go_to);
+                    return Err(AppError::SessionError(text("flash.cant_go")));
+                }
+            }
+
+            if let Some(take) = &action.take {
+                let item_id = ItemId::from(take.as_str());
+                if dark || !here.items.contains(&item_id) {
+                    info!("Tried to take missing item {}", take);
+                    return Err(AppError::SessionError(text("flash.nothing_here")));
+                }
+                if !user_session.has_item(&item_id) {
+                    info!("User session {} took {}", SESSION_KEY, item_id);
+                    user_session.inventory.push(item_id.clone());
+                    user_session.note(
+                        clock.now(),
+                        JournalKind::TookItem(item_id.clone()),
+                        &here.id,
+                    );
+                    set_user_session(&session, &user_session);
+                    let name = items.get(&item_id).map_or(take.as_str(), |i| &i.name);
+                    flash(
+                        &session,
+                        translations.text(lang, "flash.picked_up", &[("item", name)]),
+                    );
+                    bus.publish(WorldEvent::ItemTaken {
+                        item: item_id,
+                        page: here.id.clone(),
+                    });
+                }
+            }
+
+            for (item, buying) in [(&action.buy, true), (&action.sell, false)] {
+                let Some(item) = item else { continue };
+                let item = ItemId::from(item.as_str());
+                let outcome = if buying {
+                    shops.buy(here, &item, &mut user_session, &actor_manager)?
+                } else {
+                    shops.sell(here, &item, &mut user_session, &actor_manager)?
+                };
+                flash(&session, outcome.unwrap_or_else(|why| why));
+            }
+
+            // bartering, giving or stealing: whichever button was pressed
+            // names who with, and the form's item fields say what
+            let dealing = [&action.trade_with, &action.give_to, &action.steal_from]
+                .into_iter()
+                .find_map(Option::as_ref);
+            if let Some(with) = dealing {
+                if dark {
+                    return Err(AppError::SessionError(text("flash.too_dark")));
+                }
+                let environment = environment_manager
+                    .get_environment_for_page(&here.id)
+                    .await?;
+                let conditions = sources.context(&user_session, &here.id, &environment);
+                let mut manager = actor_manager.lock();
+                if let Some(character) = &user_session.character {
+                    manager.sync_player(&user_session.player_id, &character.name, &here.id);
+                }
+                // only with someone here, awake and in plain sight
+                let in_sight = manager.actors.get(with.as_str()).is_some_and(|a| {
+                    a.location == here.id
+                        && a.travel.is_none()
+                        && Visibility::of(a, dark, &conditions) == Visibility::Seen
+                });
+                let Some(npc) = manager.actors.get_mut(with.as_str()).filter(|_| in_sight) else {
+                    return Err(AppError::SessionError(text("flash.nothing_here")));
+                };
+                let item = |field: &Option<String>| {
+                    let id = ItemId::from(field.as_deref().unwrap_or_default());
+                    let name = items
+                        .get(&id)
+                        .map_or_else(|| id.to_string(), |i| i.name.clone());
+                    (id, name)
+                };
+                let ((give, give_name), (want, want_name)) =
+                    (item(&action.give), item(&action.want));
+                let name = npc.name.clone();
+                let mut caught = None;
+                let outcome = if action.trade_with.is_some() {
+                    trade::barter(&mut user_session, npc, &give, &want, &items)
+                } else if action.give_to.is_some() {
+                    trade::gift(&mut user_session, npc, &give, &items)
+                } else {
+                    trade::steal(&mut user_session, npc, &want, &mut rand::rng()).map(
+                        |(key, event)| {
+                            caught = event;
+                            key
+                        },
+                    )
+                };
+                if let Some(event) = caught {
+                    manager.witness(&event); // word gets round
+                    drop(manager);
+                    actor_bus.publish(event);
+                }
+                let key = outcome.unwrap_or_else(|why| why);
+                flash(
+                    &session,
+                    translations.text(
+                        lang,
+                        key,
+                        &[("name", &name), ("give", &give_name), ("want", &want_name)],
+                    ),
+                );
+            }
+
+            for (id, joining) in [(&action.ask_along, true), (&action.part_ways, false)] {
+                let Some(id) = id else { continue };
+                if dark {
+                    return Err(AppError::SessionError(text("flash.too_dark")));
+                }
+                let mut manager = actor_manager.lock();
+                let name = manager
+                    .actors
+                    .get(id.as_str())
+                    .filter(|a| a.location == here.id)
+                    .map(|a| a.name.clone())
+                    .ok_or_else(|| AppError::SessionError(text("flash.nothing_here")))?;
+                let outcome = if joining {
+                    companions::ask_along(&mut manager, &user_session, id)
+                } else {
+                    companions::part_ways(&mut manager, &user_session, id)
+                };
+                drop(manager);
+                let key = outcome.unwrap_or_else(|why| why);
+                flash(&session, translations.text(lang, key, &[("name", &name)]));
+            }
+
+            if let Some(item) = &action.use_item {
+                let item = ItemId::from(item.as_str());
+                let outcome = {
+                    let mut manager = actor_manager.lock();
+                    let actor = manager.actors.get_mut(&user_session.player_id);
+                    consumables::use_item(&mut user_session, &item, &items, actor, clock.now())
+                };
+                let name = items.get(&item).map_or(item.0.as_str(), |i| &i.name);
+                let key = outcome.unwrap_or_else(|why| why);
+                flash(&session, translations.text(lang, key, &[("item", name)]));
+            }
+
+            if let Some(combine) = &action.combine {
+                let outcome = recipes.craft(&mut user_session, &crafting::parse_items(combine));
+                flash(&session, outcome.unwrap_or_else(|why| why));
+            }
+
+            if let Some(interact) = &action.interact {
+                let (fixture, verb) = fixtures::parse_interaction(interact)?;
+                if dark {
+                    flash(&session, text("flash.too_dark"));
+                } else {
+                    let chosen = fixtures::find_action(here, fixture, verb)?;
+                    let environment = environment_manager
+                        .get_environment_for_page(&here.id)
+                        .await?;
+                    let allowed = chosen.requires.holds(&sources.context(
+                        &user_session,
+                        &here.id,
+                        &environment,
+                    ));
+                    let told = if allowed {
+                        let effects = fixtures::apply(
+                            chosen,
+                            fixture,
+                            here,
+                            &mut user_session,
+                            world,
+                            &bus,
+                            scripts,
+                            world_time.hour,
+                        )?;
+                        scripting::apply(
+                            effects,
+                            Some(&mut user_session),
+                            world,
+                            &actor_manager,
+                            &environment_manager,
+                            &bus,
+                        )?;
+                        chosen.text.clone()
+                    } else {
+                        chosen
+                            .refused
+                            .clone()
+                            .unwrap_or_else(|| text("flash.nothing_happens"))
+                    };
+                    flash(&session, told);
+                }
+            }
+
+            if let Some(id) = &action.let_be {
+                let met = user_session
+                    .encounter
+                    .take_if(|met| met.as_str() == id)
+                    .ok_or_else(|| AppError::SessionError(text("flash.nothing_here")))?;
+                let name = {
+                    let mut manager = actor_manager.lock();
+                    let name = manager.actors.get(&met).map(|a| a.name.clone());
+                    manager.slip_away(&met);
+                    name.unwrap_or_default()
+                };
+                set_user_session(&session, &user_session);
+                flash(
+                    &session,
+                    translations.text(lang, "flash.let_be", &[("name", &name)]),
+                );
+            }
+
+            if let Some(target) = &action.inspect {
+                if dark {
+                    return Err(AppError::SessionError(text("flash.too_dark")));
+                }
+                inspecting = Some(target.clone());
+            }
+
+            if let Some(target) = &action.attack
+                && let Some(character) = user_session.character.clone()
+            {
+                if dark {
+                    return Err(AppError::SessionError(text("flash.too_dark")));
+                }
+                let environment = environment_manager
+                    .get_environment_for_page(&here.id)
+                    .await?;
+                let conditions = sources.context(&user_session, &here.id, &environment);
+                let (outcome, name) = {
+                    let mut manager = actor_manager.lock();
+                    manager.sync_player(&user_session.player_id, &character.name, &here.id);
+                    // no picking fights with what the player can't see
+                    if !manager
+                        .actors
+                        .get(target.as_str())
+                        .is_some_and(|a| Visibility::of(a, dark, &conditions) == Visibility::Seen)
+                    {
+                        return Err(AppError::SessionError(text("flash.nothing_here")));
+                    }
+                    let name = manager.actors.get(target.as_str()).map(|a| a.name.clone());
+                    let outcome =
+                        combat::player_attack(&mut manager.actors, &user_session.player_id, target);
+                    (outcome, name.unwrap_or_default())
+                };
+                for event in outcome.map_err(|key| AppError::SessionError(text(key)))? {
+                    let mut manager = actor_manager.lock();
+                    if let WorldEvent::ActorWounded { actor, health, .. } = &event {
+                        let key = if *health > 0 {
+                            "flash.struck"
+                        } else {
+                            "flash.struck_down"
+                        };
+                        flash(&session, translations.text(lang, key, &[("name", &name)]));
+                        // nobody forgets being set upon, nor do their friends
+                        if let Some(hurt) = manager.actors.get(actor) {
+                            user_session.reputation.record(Deed::Attacked, hurt);
+                            if *health <= 0 {
+                                user_session.reputation.record(Deed::StruckDown, hurt);
+                            }
+                        }
+                    }
+                    manager.witness(&event); // word gets round
+                    manager.feel(&event); // it shakes those that saw it
+                    manager.sense(&event, &pages); // and the noise of it carries
+                    drop(manager);
+                    actor_bus.publish(event);
+                }
+            }
+
+            if let Some(emote) = action.emote
+                && let Some(character) = user_session.character.clone()
+            {
+                info!("{} {}", character.name, emote.describe());
+                let emoted = WorldEvent::PlayerEmoted {
+                    player: user_session.player_id.clone(),
+                    name: character.name.clone(),
+                    page: here.id.clone(),
+                    emote,
+                };
+                actor_manager.lock().sense(&emoted, &pages); // a shout is heard about
+                bus.publish(emoted);
+            }
+
+            if let Some(text) = action.say.as_deref().and_then(chat::clean_message)
+                && let Some(character) = user_session.character.clone()
+            {
+                info!("{} says {:?}", character.name, text);
+                chat_log.say(clock.now(), &here.id, &character.name, &text);
+                spoke = true;
+            }
+
             set_user_session(&session, &user_session);
-        } else {
-            error!("Tried invalid direction {}", action.go_to);
-            return Err(AppError::SessionError("Invalid direction!".to_string()));
+            save_to_account(&session, &accounts, &user_session);
+            Ok::<(), AppError>(())
+        }
+        .await;
+        match acted {
+            Ok(()) => {}
+            Err(AppError::SessionError(why) | AppError::TooFast(why)) => {
+                flash(&session, why);
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header(("Location", mount.path("/")))
+                    .finish());
+            }
+            Err(e) => return Err(e),
         }
     }
 
-    // Find the current page
+    // Find the current page, in the graph as any action above left it
+    let pages = world.snapshot();
+    let page_flags = world.page_flags();
     let page = pages
         .get(&user_session.current_page)
         .ok_or_else(|| AppError::PageNotFound(user_session.current_page.to_string()))?;
+    let dark = dark_for_player(page, &world_time, &user_session, &items);
 
     // Get environment data for this page
     let environment = environment_manager
         .get_environment_for_page(&page.id)
         .await?;
 
-    let actor_manager_ref = actor_manager
-        .get_ref()
-        .lock()
-        .expect("Failed to lock Mutex");
-    let actors_here: Vec<&Actor> = actor_manager_ref
-        .actors
-        .values()
-        .filter(|a| a.location == page.id && a.state.awake) // Show only awake actors, optionally filter more
+    let mut actor_manager_ref = actor_manager.lock();
+    // the player is in the world too, for NPCs (and other players) to notice
+    if let Some(character) = &user_session.character {
+        actor_manager_ref.sync_player(&user_session.player_id, &character.name, &page.id);
+    }
+    // fighters who can't stand the player go for them on sight
+    let hostile: Vec<ActorId> = actor_manager_ref
+        .actors_on(&page.id)
+        .filter(|a| {
+            a.state.awake
+                && a.state.target.is_none()
+                && (a.has_flag(ActorFlag::CanAttack) || a.has_flag(ActorFlag::Predatory))
+                && !a.has_flag(ActorFlag::Player)
+                && user_session.reputation.regard(a) == Regard::Hostile
+        })
+        .map(|a| a.id.clone())
+        .collect();
+    for id in hostile {
+        if let Some(actor) = actor_manager_ref.actors.get_mut(&id) {
+            actor.state.target = Some(user_session.player_id.clone());
+        }
+    }
+
+    // Conditional content: who is about, exits, description variants and
+    // what NPCs here have to say
+    let conditions = sources.context(&user_session, &page.id, &environment);
+    let mut actors_here: Vec<&Actor> = Vec::new();
+    let mut travelling: Vec<&Actor> = Vec::new(); // setting off down a long road from here
+    let mut sleepers = 0; // seen only as shapes
+    for actor in actor_manager_ref
+        .actors_on(&page.id)
+        .filter(|a| a.id != user_session.player_id)
+    {
+        match (Visibility::of(actor, dark, &conditions), &actor.travel) {
+            (Visibility::Hidden, _) => {}
+            (_, Some(_)) => travelling.push(actor),
+            (Visibility::Sleeping, None) => sleepers += 1,
+            (Visibility::Seen, None) => actors_here.push(actor),
+        }
+    }
+    let means = Means::of(
+        &user_session,
+        &items,
+        actor_manager_ref.companions_of(&user_session.player_id),
+    );
+    let exits = visible_exits(page, &pages, &world_time, dark, &items, &means, &conditions);
+    // anyone the player has hurt, or who has heard enough about them, gives
+    // them the cold shoulder
+    let bears_grudge = |a: &Actor| {
+        a.state.target.as_ref() == Some(&user_session.player_id)
+            || user_session.reputation.regard(a) == Regard::Hostile
+    };
+    let heard: HashMap<&str, &DialogueLine> = actors_here
+        .iter()
+        .filter(|a| a.has_flag(ActorFlag::CanSpeak) && !bears_grudge(a))
+        .filter_map(|a| {
+            Some((
+                a.id.as_str(),
+                dialogue.line_for(a.id.as_str(), &conditions, !a.rumors.is_empty())?,
+            ))
+        })
+        .collect();
+    let tell_rumor = |rumor: &Rumor| {
+        let place = translations
+            .lookup(lang, &format!("pages.{}.title", rumor.page))
+            .or_else(|| pages.get(&rumor.page).map(|p| p.title.clone()))
+            .unwrap_or_else(|| rumor.page.to_string());
+        let mut args = rumor.fact.args();
+        args.push(("page", &place));
+        translations.text(lang, &format!("rumor.{}", rumor.fact.key()), &args)
+    };
+    let says: HashMap<&str, String> = actors_here
+        .iter()
+        .filter_map(|a| {
+            let line = heard.get(a.id.as_str())?;
+            let text = match rumors::freshest(&a.rumors) {
+                Some(rumor) if line.rumor => line.text.replace("{rumor}", &tell_rumor(rumor)),
+                _ => line.text.clone(),
+            };
+            Some((a.id.as_str(), text))
+        })
         .collect();
+    if spoke {
+        for actor in actors_here.iter().filter(|a| {
+            a.has_flag(ActorFlag::CanSpeak) && !a.has_flag(ActorFlag::Player) && !bears_grudge(a)
+        }) {
+            if let Some(reply) = chat::npc_reply(says.get(actor.id.as_str()).map(String::as_str)) {
+                chat_log.say(clock.now(), &page.id, &actor.name, &reply);
+            }
+        }
+    }
+
+    let inspected = inspecting.as_deref().filter(|_| !dark).and_then(|target| {
+        inspect::inspect(
+            target,
+            page,
+            &actors_here,
+            &actor_manager_ref.actors,
+            &translations,
+            lang,
+        )
+    });
+    if inspecting.is_some() && inspected.is_none() {
+        flash(&session, text("flash.nothing_here"));
+    }
+
+    let items_here = if dark {
+        Vec::new()
+    } else {
+        items::resolve(&page.items, &items)
+    };
 
     // Build template context
-    let mut ctx = Context::new();
+    let mut ctx = render::base_context(
+        &session,
+        &user_session,
+        clock,
+        &environment_manager,
+        &translations,
+    );
+    ctx.insert("base", &mount.base);
+    render::insert_surroundings(&mut ctx, &environment, &exits);
+    ctx.insert(
+        "ambient",
+        &Ambience::of(
+            &environment,
+            PartOfDay::at(world_time.hour),
+            actors_here.len(),
+        ),
+    );
     ctx.insert("page", page);
-    ctx.insert("environment", &environment);
-    ctx.insert("npcs", &actors_here);
+    // translations may retitle a page, and replace its usual description
+    let page_text = |field: &str| translations.lookup(lang, &format!("pages.{}.{field}", page.id));
+    ctx.insert(
+        "title",
+        &page_text("title").unwrap_or_else(|| page.title.clone()),
+    );
+    if dark {
+        ctx.insert("description", &text("page.pitch_dark"));
+    } else {
+        let description = page.description_for(&conditions);
+        match page_text("description") {
+            Some(translated) if description == page.description => {
+                ctx.insert("description", &translated)
+            }
+            _ => ctx.insert("description", description),
+        }
+    }
+    let template = page.template_for(&conditions).to_string();
+    ctx.insert("dark", &dark);
+    ctx.insert("inspected", &inspected);
+    ctx.insert("items", &items_here);
+    ctx.insert(
+        "inventory",
+        &items::resolve(&user_session.inventory, &items),
+    );
+    let uses_left: HashMap<&ItemId, u32> = user_session
+        .inventory
+        .iter()
+        .filter_map(|id| Some((id, consumables::uses_left(&user_session, id, &items)?)))
+        .collect();
+    ctx.insert("uses_left", &uses_left); // item id -> uses left in the one in hand
+    ctx.insert("means", &means);
+    ctx.insert("encumbered", &means.encumbered());
+    ctx.insert("visit_count", &user_session.visit_count(&page.id));
+    ctx.insert("visited", &visited_places(&user_session, &pages));
+    ctx.insert("breadcrumb", &regions::breadcrumb(page, &regions));
+    if let Some(region) = &page.region {
+        ctx.insert(
+            "region_map",
+            &regions::region_map(region, &pages, &regions, &user_session),
+        );
+    }
+    // a crowd is summed up, unless the player is looking through it
+    let mut listed = actors_here.clone();
+    let crowd = census::thin_out(&mut listed, page, view.closer);
+    if let Some(crowd) = &crowd {
+        ctx.insert(
+            "crowd_summary",
+            &translations.text(
+                lang,
+                "page.crowd",
+                &[("count", &crowd.census.total.to_string())],
+            ),
+        );
+    }
+    ctx.insert("crowd", &crowd);
+    ctx.insert("npcs", &listed);
+    ctx.insert("travelling", &travelling);
+    let sleeping = match sleepers {
+        0 => None,
+        1 => Some(text("page.sleeper")),
+        n => Some(translations.text(lang, "page.sleepers", &[("count", &n.to_string())])),
+    };
+    ctx.insert("sleepers", &sleeping);
+    // one poorly face is in its description; more are a bug going round
+    let ailing = actors_here
+        .iter()
+        .filter(|a| a.has_status(Status::Sick))
+        .count();
+    let ailing = (ailing > 1)
+        .then(|| translations.text(lang, "page.ailing", &[("count", &ailing.to_string())]));
+    ctx.insert("ailing", &ailing);
+    // migrants on their way through, by kind and where they're bound
+    let mut flocks: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for actor in actors_here
+        .iter()
+        .filter(|a| a.has_flag(ActorFlag::Migratory))
+    {
+        let Some(bound_for) = actor.planned_path().last().and_then(|p| pages.get(p)) else {
+            continue;
+        };
+        if bound_for.region != page.region
+            && let Some(region) = regions.ancestry(bound_for.region.as_ref()).pop()
+        {
+            *flocks.entry((&actor.name, &region.name)).or_default() += 1;
+        }
+    }
+    let passing: Vec<String> = flocks
+        .into_iter()
+        .map(|((name, region), count)| {
+            let key = if count == 1 {
+                "page.migrating"
+            } else {
+                "page.migrating_flock"
+            };
+            let count = count.to_string();
+            translations.text(
+                lang,
+                key,
+                &[("name", name), ("region", region), ("count", &count)],
+            )
+        })
+        .collect();
+    ctx.insert("passing", &passing);
+    // packs about, by kind, once there's more than one of one together
+    let mut packs: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for actor in actors_here.iter() {
+        if let Some(pack) = &actor.pack {
+            *packs.entry((pack, &actor.name)).or_default() += 1;
+        }
+    }
+    let packs: Vec<String> = packs
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|((_, name), count)| {
+            translations.text(
+                lang,
+                "page.pack",
+                &[("name", name), ("count", &count.to_string())],
+            )
+        })
+        .collect();
+    ctx.insert("packs", &packs);
+    // anyone about too frightened or angry to hide it
+    let moods: Vec<String> = actors_here
+        .iter()
+        .filter(|a| a.state.awake && !a.has_flag(ActorFlag::Player))
+        .filter_map(|a| {
+            let key = a.state.mood.key()?;
+            Some(translations.text(lang, key, &[("name", &a.name)]))
+        })
+        .collect();
+    ctx.insert("moods", &moods);
+    // whatever sprang out at the player, while it's still here to deal with
+    let encounter = user_session
+        .encounter
+        .as_ref()
+        .and_then(|id| actors_here.iter().find(|a| a.id == *id));
+    if let Some(met) = encounter {
+        ctx.insert(
+            "encounter_prompt",
+            &translations.text(lang, "page.encounter", &[("name", &met.name)]),
+        );
+    }
+    ctx.insert("encounter", &encounter);
+    // the loudest commotion elsewhere that carries this far
+    let heard_from = actor_manager_ref
+        .stimuli_on(&page.id)
+        .iter()
+        .filter(|s| s.kind == StimulusKind::Noise && s.from != page.id)
+        .max_by_key(|s| s.strength)
+        .map(|noise| {
+            let place = translations
+                .lookup(lang, &format!("pages.{}.title", noise.from))
+                .or_else(|| pages.get(&noise.from).map(|p| p.title.clone()))
+                .unwrap_or_else(|| noise.from.to_string());
+            translations.text(lang, "page.heard_from", &[("page", &place)])
+        });
+    ctx.insert("heard_from", &heard_from);
+    let forage = match actor_manager_ref.forage_left(page).filter(|_| !dark) {
+        Some((Forage::Grass, 0)) => Some(text("page.grazed_bare")),
+        Some((Forage::Berries, 0)) => Some(text("page.picked_clean")),
+        _ => None,
+    };
+    ctx.insert("forage", &forage); // only worth a mention once it's eaten bare
+    let feeling_poorly = actor_manager_ref
+        .actors
+        .get(&user_session.player_id)
+        .is_some_and(|a| a.has_status(Status::Sick));
+    ctx.insert("feeling_poorly", &feeling_poorly);
+    ctx.insert("dialogue", &says); // actor id -> line
+    let company: Vec<&str> = actor_manager_ref
+        .companions_of(&user_session.player_id)
+        .map(|a| a.id.as_str())
+        .collect();
+    ctx.insert("companions", &company); // actor ids
+    // what NPCs willing to deal with the player carry, to barter for
+    let wares: HashMap<&str, Vec<_>> = actors_here
+        .iter()
+        .filter(|a| {
+            a.has_flag(ActorFlag::CanSpeak)
+                && !a.has_flag(ActorFlag::Player)
+                && !a.inventory.is_empty()
+                && !bears_grudge(a)
+        })
+        .map(|a| (a.id.as_str(), items::resolve(&a.inventory, &items)))
+        .collect();
+    ctx.insert("wares", &wares); // actor id -> items
+    ctx.insert(
+        "shop",
+        &shops.listings(page, &items, &user_session, &actor_manager_ref),
+    );
+    ctx.insert("fixtures", if dark { &[][..] } else { &page.fixtures });
+    let mut flags_here: Vec<&String> = page_flags.get(&page.id).into_iter().flatten().collect();
+    flags_here.sort();
+    ctx.insert("page_flags", &flags_here);
+    ctx.insert("plugin_notes", &plugins.render_notes(&page.id));
+    ctx.insert("emotes", &recent_emotes(event_log, &page.id, clock.now()));
+    let overheard = if dark {
+        Vec::new() // voices in the dark, but no telling whose
+    } else {
+        overheard(
+            event_log,
+            &page.id,
+            clock.now(),
+            &dialogue,
+            &actor_manager_ref.actors,
+            |key, args| translations.text(lang, key, args),
+        )
+    };
+    ctx.insert("overheard", &overheard);
+    ctx.insert("chat", &chat_log.messages(&page.id));
+    ctx.insert("chat_max_len", &chat::MAX_MESSAGE_LEN);
+    ctx.insert("emote_options", &Emote::ALL.map(|e| (e, e.describe())));
+    if template == FORECAST_TEMPLATE {
+        ctx.insert(
+            "forecast",
+            &environment_manager.forecast(DEFAULT_FORECAST_HOURS)?,
+        );
+    }
 
-    let html = tera.render(&page.template, &ctx)?;
+    // Quests move on with what the player has now seen and done
+    let mut changed = false;
+    for actor in &actors_here {
+        changed |= user_session.saw(&actor.id, &page.id);
+    }
+    for (actor, line) in &heard {
+        changed |= user_session.talked_to.insert(actor.to_string());
+        if let Some(flag) = &line.sets_flag {
+            changed |= user_session.flags.insert(flag.clone());
+        }
+    }
+    let mut quest_news: Vec<String> = heard
+        .values()
+        .filter_map(|line| line.gives_quest.as_deref())
+        .filter_map(|quest| quests.start(&mut user_session, quest))
+        .collect();
+    quest_news.extend(quests.advance(
+        &mut user_session,
+        &page.id,
+        clock.now(),
+        Some(&environment),
+        event_log,
+        &page_flags,
+        Some(scripts),
+    ));
+    if changed || !quest_news.is_empty() {
+        set_user_session(&session, &user_session);
+        save_to_account(&session, &accounts, &user_session);
+    }
+    ctx.insert("quest_news", &quest_news);
+    ctx.insert("quests", &quests.status(&user_session));
+
+    let html = render_page(&tera, page, &template, &ctx, user_session.text_only)?;
     Ok(HttpResponse::Ok().body(html))
 }
+
+/// A logged-in player's character outlives the cookie
+fn save_to_account(
+    session: &actix_session::Session,
+    accounts: &AccountStore,
+    player: &UserSession,
+) {
+    if let Some(username) = session.get::<String>(ACCOUNT_KEY).ok().flatten()
+        && let Err(e) = accounts.save_character(&username, player)
+    {
+        error!("Failed to save character for {username}: {e}");
+    }
+}
+
+/// A page the player has been to, for the "places you've been" list
+#[derive(Serialize)]
+struct Place<'a> {
+    id: &'a PageId,
+    title: &'a str,
+    visits: u32,
+}
+
+/// Pages the player has been to that still exist, by title
+fn visited_places<'a>(player: &UserSession, pages: &'a PageGraph) -> Vec<Place<'a>> {
+    let mut places: Vec<Place> = player
+        .visited()
+        .filter_map(|id| pages.get(id))
+        .map(|page| Place {
+            id: &page.id,
+            title: &page.title,
+            visits: player.visit_count(&page.id),
+        })
+        .collect();
+    places.sort_by(|a, b| a.title.cmp(b.title));
+    places
+}
+
+/// "Ash waves" for each emote made on `page` lately, newest first
+fn recent_emotes(event_log: &EventLog, page: &PageId, now: DateTime<Local>) -> Vec<String> {
+    event_log
+        .matching_since(
+            now - EMOTE_MEMORY,
+            |event| matches!(event, WorldEvent::PlayerEmoted { page: at, .. } if at == page),
+        )
+        .into_iter()
+        .take(EMOTES_SHOWN)
+        .filter_map(|entry| match entry.event {
+            WorldEvent::PlayerEmoted { name, emote, .. } => {
+                Some(format!("{name} {}", emote.describe()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// What NPCs on `page` have lately said to each other, newest first, each
+/// exchange as one line of text. The lines said are picked by when it was
+/// said, so they stay the same from one look at the page to the next.
+fn overheard(
+    event_log: &EventLog,
+    page: &PageId,
+    now: DateTime<Local>,
+    dialogue: &DialogueBook,
+    actors: &ActorMap,
+    text: impl Fn(&str, &[(&str, &str)]) -> String,
+) -> Vec<String> {
+    let name = |id: &ActorId| {
+        actors
+            .get(id)
+            .map_or_else(|| id.to_string(), |a| a.name.clone())
+    };
+    event_log
+        .matching_since(
+            now - OVERHEARD_MEMORY,
+            |event| matches!(event, WorldEvent::ActorConversed { page: at, .. } if at == page),
+        )
+        .into_iter()
+        .filter_map(|entry| {
+            let WorldEvent::ActorConversed {
+                speaker, listener, ..
+            } = &entry.event
+            else {
+                return None;
+            };
+            let roll = entry.at.timestamp() as u64;
+            let (speaker_name, listener_name) = (name(speaker), name(listener));
+            let mut exchange = text(
+                "page.overheard",
+                &[
+                    ("speaker", &speaker_name),
+                    ("listener", &listener_name),
+                    ("line", dialogue.chatter_for(speaker.as_str(), roll)?),
+                ],
+            );
+            if let Some(reply) = dialogue.chatter_for(listener.as_str(), roll) {
+                exchange.push(' ');
+                exchange.push_str(&text(
+                    "page.overheard_reply",
+                    &[("listener", &listener_name), ("line", reply)],
+                ));
+            }
+            Some(exchange)
+        })
+        .take(OVERHEARD_SHOWN)
+        .collect()
+}
+
+/// Whether the player sees darkness on `page`. Nocturnal players see by
+/// night without a light, though not where daylight never reaches.
+pub(crate) fn dark_for_player(
+    page: &Page,
+    world_time: &WorldTime,
+    player: &UserSession,
+    items: &ItemCatalog,
+) -> bool {
+    page.is_dark_for(world_time, player.carries_light(items), items)
+        && !(player.is_nocturnal() && page.is_night_dark(world_time))
+}
@@ -0,0 +1,174 @@
+use actix_session::Session;
+use actix_web::{HttpResponse, Responder, web};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::handler::START_PAGE;
+use crate::session::{get_or_create_user_session, set_user_session};
+use crate::worlds::Mount;
+
+/// Default directory of translation files, one `<language>.toml` each
+pub const DEFAULT_LOCALES_PATH: &str = "data/locales";
+
+/// Language used until a player picks another, and for any text missing
+/// from a translation
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// UI text in every language there is a file for. Each language's table is
+/// laid over the default language's, so a partial translation still has
+/// every string. Nested tables give dotted keys: `[flash] too_dark` is
+/// `flash.too_dark`.
+#[derive(Default)]
+pub struct Translations {
+    tables: HashMap<String, Arc<Value>>, // language -> merged table
+    names: BTreeMap<String, String>,     // language -> what it calls itself
+}
+
+impl Translations {
+    /// Read every `.toml` file in `dir`; the file stem is the language code.
+    /// A missing directory means only keys are shown.
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                warn!("No translations in {}", dir.display());
+                return Ok(Translations::default());
+            }
+            Err(e) => {
+                return Err(AppError::OtherError(format!(
+                    "Reading {}: {e}",
+                    dir.display()
+                )));
+            }
+        };
+        let mut raw: HashMap<String, Value> = HashMap::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", dir.display())))?
+                .path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let Some(language) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+            let table: toml::Table = toml::from_str(&text)
+                .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+            let table = serde_json::to_value(table)
+                .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+            raw.insert(language.to_string(), table);
+        }
+
+        let default = raw
+            .get(DEFAULT_LANGUAGE)
+            .cloned()
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        let mut translations = Translations::default();
+        for (language, table) in raw {
+            let name = table
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or(&language)
+                .to_string();
+            let mut merged = default.clone();
+            merge(&mut merged, table);
+            translations.names.insert(language.clone(), name);
+            translations.tables.insert(language, Arc::new(merged));
+        }
+        info!(
+            "Loaded translations: {:?}",
+            translations.names.keys().collect::<Vec<_>>()
+        );
+        Ok(translations)
+    }
+
+    /// `requested` if there is a translation for it, otherwise the default
+    pub fn language(&self, requested: Option<&str>) -> &str {
+        requested
+            .and_then(|lang| self.tables.get_key_value(lang))
+            .map_or(DEFAULT_LANGUAGE, |(lang, _)| lang.as_str())
+    }
+
+    /// Every language, by code, with what it calls itself
+    pub fn languages(&self) -> &BTreeMap<String, String> {
+        &self.names
+    }
+
+    /// Everything in `lang`, for templates to read as `t`
+    pub fn table(&self, lang: &str) -> Arc<Value> {
+        self.tables
+            .get(lang)
+            .or_else(|| self.tables.get(DEFAULT_LANGUAGE))
+            .cloned()
+            .unwrap_or_else(|| Arc::new(Value::Object(Map::new())))
+    }
+
+    /// The text at dotted `key` in `lang`, if there is any
+    pub fn lookup(&self, lang: &str, key: &str) -> Option<String> {
+        let table = self.table(lang);
+        key.split('.')
+            .try_fold(&*table, |value, part| value.get(part))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    }
+
+    /// The text at `key` in `lang` with each `{name}` filled in from `args`.
+    /// Missing text shows as the key itself.
+    pub fn text(&self, lang: &str, key: &str, args: &[(&str, &str)]) -> String {
+        let mut text = self.lookup(lang, key).unwrap_or_else(|| key.to_string());
+        for (name, value) in args {
+            text = text.replace(&format!("{{{name}}}"), value);
+        }
+        text
+    }
+}
+
+/// Lay `over` onto `base`, table by table
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LanguageForm {
+    lang: String,
+}
+
+/// Switch the player's language, then back to the game
+pub async fn language_handler(
+    session: Session,
+    mount: web::Data<Mount>,
+    translations: web::Data<Arc<Translations>>,
+    form: web::Form<LanguageForm>,
+) -> Result<impl Responder, AppError> {
+    let mut player = get_or_create_user_session(&session, START_PAGE)?;
+    if !translations.languages().contains_key(&form.lang) {
+        return Err(AppError::SessionError(format!(
+            "No translation for '{}'",
+            form.lang
+        )));
+    }
+    player.language = Some(form.lang.clone());
+    set_user_session(&session, &player);
+    Ok(HttpResponse::SeeOther()
+        .insert_header(("Location", mount.path("/")))
+        .finish())
+}
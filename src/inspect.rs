@@ -0,0 +1,57 @@
+use serde::Serialize;
+
+use crate::actor::{Actor, ActorMap};
+use crate::i18n::Translations;
+use crate::pages::Page;
+
+/// A closer look at someone or something on the player's page
+#[derive(Serialize)]
+pub struct Inspection {
+    pub name: String,
+    pub details: Vec<String>, // sentences, in the player's language
+}
+
+/// Look closely at the actor or fixture `target` on `page`; None if there's
+/// nothing by that id the player can see here (`actors_here`)
+pub fn inspect(
+    target: &str,
+    page: &Page,
+    actors_here: &[&Actor],
+    actors: &ActorMap,
+    translations: &Translations,
+    lang: &str,
+) -> Option<Inspection> {
+    if let Some(actor) = actors_here.iter().find(|a| a.id.as_str() == target) {
+        let name = actor.name.as_str();
+        let mut details: Vec<String> = actor
+            .state
+            .looks()
+            .into_iter()
+            .map(|key| translations.text(lang, key, &[("name", name)]))
+            .collect();
+        if let Some(other) = actor.state.target.as_ref().and_then(|id| actors.get(id))
+            && other.location == page.id
+        {
+            details.push(translations.text(
+                lang,
+                "inspect.intent",
+                &[("name", name), ("target", &other.name)],
+            ));
+        }
+        return Some(Inspection {
+            name: actor.name.clone(),
+            details,
+        });
+    }
+
+    let fixture = page.fixtures.iter().find(|f| f.id == target)?;
+    let mut details = vec![fixture.description.clone()];
+    if !fixture.actions.is_empty() {
+        let verbs: Vec<&str> = fixture.actions.iter().map(|a| a.verb.as_str()).collect();
+        details.push(translations.text(lang, "inspect.uses", &[("verbs", &verbs.join(", "))]));
+    }
+    Some(Inspection {
+        name: fixture.name.clone(),
+        details,
+    })
+}
@@ -0,0 +1,123 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::actor::{ActorId, ActorManager};
+use crate::events::EventBus;
+
+/// Solo instances nobody has played in for this long are dropped, unless
+/// `CHOTT_INSTANCE_IDLE_SECS` says otherwise
+const DEFAULT_INSTANCE_IDLE: Duration = Duration::from_secs(30 * 60);
+
+/// Whether everyone plays among the same actors, or each player in a world of their own
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InstanceMode {
+    #[default]
+    Shared,
+    Solo, // for single-player interactive fiction
+}
+
+impl InstanceMode {
+    /// `CHOTT_INSTANCES=solo` gives each player their own instance; anything else shares one
+    pub fn from_env() -> Self {
+        match std::env::var("CHOTT_INSTANCES").as_deref() {
+            Ok("solo") => InstanceMode::Solo,
+            _ => InstanceMode::Shared,
+        }
+    }
+}
+
+/// One player's own copy of the actors, and the bus they publish on
+struct Instance {
+    actors: Arc<Mutex<ActorManager>>,
+    bus: EventBus,
+    used: Instant, // last request made in it
+}
+
+/// The actor worlds players play in. Shared mode has just the one; in solo
+/// mode each player gets a fresh copy of it on first visit, with its own
+/// event bus, so their fights and NPCs' doings touch nobody else. Pages,
+/// page flags, items on the ground and the weather are shared either way.
+#[derive(Clone)]
+pub struct Instances {
+    mode: InstanceMode,
+    shared: Arc<Mutex<ActorManager>>, // in solo mode, the pattern instances copy; it doesn't tick
+    bus: EventBus,
+    solo: Arc<Mutex<HashMap<ActorId, Instance>>>, // player id -> their instance
+    idle: Duration,
+}
+
+impl Instances {
+    pub fn new(mode: InstanceMode, shared: Arc<Mutex<ActorManager>>, bus: EventBus) -> Self {
+        let idle = std::env::var("CHOTT_INSTANCE_IDLE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_INSTANCE_IDLE);
+        Instances {
+            mode,
+            shared,
+            bus,
+            solo: Arc::default(),
+            idle,
+        }
+    }
+
+    pub fn mode(&self) -> InstanceMode {
+        self.mode
+    }
+
+    /// The actors `player` plays among, and the bus their doings go out on
+    pub fn for_player(&self, player: &ActorId) -> (Arc<Mutex<ActorManager>>, EventBus) {
+        if self.mode == InstanceMode::Shared {
+            return (self.shared.clone(), self.bus.clone());
+        }
+        let mut solo = self.solo.lock();
+        let instance = solo.entry(player.clone()).or_insert_with(|| {
+            info!(%player, "Opening a solo instance.");
+            let bus = EventBus::new();
+            Instance {
+                actors: Arc::new(Mutex::new(self.shared.lock().instance(bus.clone()))),
+                bus,
+                used: Instant::now(),
+            }
+        });
+        instance.used = Instant::now();
+        (instance.actors.clone(), instance.bus.clone())
+    }
+
+    /// Every world that moves with the clock, with its bus: the shared one,
+    /// or each open solo instance. Idle instances are closed first.
+    pub fn ticking(&self) -> Vec<(Arc<Mutex<ActorManager>>, EventBus)> {
+        if self.mode == InstanceMode::Shared {
+            return vec![(self.shared.clone(), self.bus.clone())];
+        }
+        let mut solo = self.solo.lock();
+        solo.retain(|player, instance| {
+            let keep = instance.used.elapsed() < self.idle;
+            if !keep {
+                info!(%player, "Closing an idle solo instance.");
+            }
+            keep
+        });
+        solo.values()
+            .map(|instance| (instance.actors.clone(), instance.bus.clone()))
+            .collect()
+    }
+
+    /// Every actor world there is, the shared one first, for changes to the
+    /// pages or definitions that all of them have to follow
+    pub fn all(&self) -> Vec<Arc<Mutex<ActorManager>>> {
+        let solo = self.solo.lock();
+        std::iter::once(self.shared.clone())
+            .chain(solo.values().map(|instance| instance.actors.clone()))
+            .collect()
+    }
+
+    /// How many solo instances are open
+    pub fn open(&self) -> usize {
+        self.solo.lock().len()
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::consumables::ItemEffect;
+use crate::mounts::Conveyance;
+use crate::status::Status;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ItemId(pub String);
+
+impl From<&str> for ItemId {
+    fn from(s: &str) -> Self {
+        ItemId(s.to_owned())
+    }
+}
+impl std::fmt::Display for ItemId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Definition of a kind of item a player can find and carry
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub id: ItemId,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub light_source: bool, // lights up dark pages for whoever carries it
+    #[serde(default)]
+    pub value: u32, // what it's worth in coins, to NPCs weighing up a trade
+    #[serde(default)]
+    pub conveys: Option<Conveyance>, // a vehicle, carrying whoever has it where it goes
+    #[serde(default)]
+    pub weight: u32, // counted against what whoever carries it can manage
+    #[serde(default)]
+    pub effects: Vec<ItemEffect>, // what using it does; can't be used without
+    #[serde(default)]
+    pub charges: Option<u32>, // uses before it's used up; None: never
+}
+
+// ItemCatalog is a HashMap keyed by id
+pub type ItemCatalog = HashMap<ItemId, Item>;
+
+pub fn load_items() -> ItemCatalog {
+    let mut items = ItemCatalog::new();
+
+    items.insert(
+        ItemId::from("lantern"),
+        Item {
+            id: ItemId::from("lantern"),
+            name: "Lantern".to_string(),
+            description: "A battered oil lantern. It still burns brightly.".to_string(),
+            light_source: true,
+            value: 12,
+            conveys: None,
+            weight: 3,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("pebble"),
+        Item {
+            id: ItemId::from("pebble"),
+            name: "Smooth Pebble".to_string(),
+            description: "A round, smooth pebble. Good for skipping.".to_string(),
+            light_source: false,
+            value: 1,
+            conveys: None,
+            weight: 1,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("old-map"),
+        Item {
+            id: ItemId::from("old-map"),
+            name: "Old Map".to_string(),
+            description: "A creased map of the region, drawn by a careful hand.".to_string(),
+            light_source: false,
+            value: 8,
+            conveys: None,
+            weight: 0,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("parcel"),
+        Item {
+            id: ItemId::from("parcel"),
+            name: "Parcel".to_string(),
+            description: "A small parcel wrapped in brown paper, addressed to Young Joey."
+                .to_string(),
+            light_source: false,
+            value: 0,
+            conveys: None,
+            weight: 2,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("field-notes"),
+        Item {
+            id: ItemId::from("field-notes"),
+            name: "Field Notes".to_string(),
+            description: "Professor Tree's notes on the local wildlife, dog-eared and smudged."
+                .to_string(),
+            light_source: false,
+            value: 6,
+            conveys: None,
+            weight: 1,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("stick"),
+        Item {
+            id: ItemId::from("stick"),
+            name: "Sturdy Stick".to_string(),
+            description: "A straight, dry length of driftwood.".to_string(),
+            light_source: false,
+            value: 1,
+            conveys: None,
+            weight: 2,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("oily-rag"),
+        Item {
+            id: ItemId::from("oily-rag"),
+            name: "Oily Rag".to_string(),
+            description: "A rag soaked in lamp oil. It smells awful.".to_string(),
+            light_source: false,
+            value: 1,
+            conveys: None,
+            weight: 0,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("torch"),
+        Item {
+            id: ItemId::from("torch"),
+            name: "Torch".to_string(),
+            description: "A stick with an oily rag bound round the end, burning smokily."
+                .to_string(),
+            light_source: true,
+            value: 4,
+            conveys: None,
+            weight: 2,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("canoe"),
+        Item {
+            id: ItemId::from("canoe"),
+            name: "Folding Canoe".to_string(),
+            description: "A canvas canoe that packs down into a bundle, with a paddle.".to_string(),
+            light_source: false,
+            value: 16,
+            conveys: Some(Conveyance::Boat),
+            weight: 14,
+            effects: Vec::new(),
+            charges: None,
+        },
+    );
+
+    items.insert(
+        ItemId::from("apple"),
+        Item {
+            id: ItemId::from("apple"),
+            name: "Crisp Apple".to_string(),
+            description: "A red apple, crisp and sweet. Just the thing on a long walk.".to_string(),
+            light_source: false,
+            value: 2,
+            conveys: None,
+            weight: 1,
+            effects: vec![ItemEffect::Rest(4)],
+            charges: Some(1),
+        },
+    );
+
+    items.insert(
+        ItemId::from("salve"),
+        Item {
+            id: ItemId::from("salve"),
+            name: "Healing Salve".to_string(),
+            description: "A little tin of green ointment that smells of mint. Good for scrapes, \
+                and it draws out a bite's poison."
+                .to_string(),
+            light_source: false,
+            value: 10,
+            conveys: None,
+            weight: 1,
+            effects: vec![ItemEffect::Heal(4), ItemEffect::Cure(Status::Poisoned)],
+            charges: Some(3),
+        },
+    );
+
+    items.insert(
+        ItemId::from("flask-of-tea"),
+        Item {
+            id: ItemId::from("flask-of-tea"),
+            name: "Flask of Strong Tea".to_string(),
+            description: "Stewed black tea, strong enough to stand a spoon in.".to_string(),
+            light_source: false,
+            value: 5,
+            conveys: None,
+            weight: 2,
+            effects: vec![
+                ItemEffect::Rest(2),
+                ItemEffect::Grant {
+                    flag: "wide_awake".to_string(),
+                    minutes: 120,
+                },
+            ],
+            charges: Some(2),
+        },
+    );
+
+    items.insert(
+        ItemId::from("four-leaf-clover"),
+        Item {
+            id: ItemId::from("four-leaf-clover"),
+            name: "Four-Leaf Clover".to_string(),
+            description: "Found where the gulls don't bother to land. Tuck it behind an ear \
+                for luck."
+                .to_string(),
+            light_source: false,
+            value: 6,
+            conveys: None,
+            weight: 0,
+            effects: vec![ItemEffect::Afflict {
+                status: Status::Blessed,
+                ticks: 60,
+            }],
+            charges: Some(1),
+        },
+    );
+
+    items
+}
+
+/// Total weight of the items `ids`, skipping unknown ones
+pub fn weight_of(ids: &[ItemId], catalog: &ItemCatalog) -> u32 {
+    ids.iter()
+        .filter_map(|id| catalog.get(id))
+        .map(|i| i.weight)
+        .sum()
+}
+
+/// Look up the definitions for a list of item ids, skipping unknown ones
+pub fn resolve<'a>(ids: &[ItemId], catalog: &'a ItemCatalog) -> Vec<&'a Item> {
+    ids.iter().filter_map(|id| catalog.get(id)).collect()
+}
@@ -0,0 +1,73 @@
+//! The world behind the chott server: pages, actors, weather and the tick
+//! that moves them along. `main.rs` serves it over HTTP; benches drive it
+//! directly.
+
+pub mod actor;
+pub mod admin;
+pub mod ambience;
+pub mod api;
+pub mod calendar;
+pub mod census;
+pub mod character;
+pub mod chat;
+pub mod clock;
+pub mod combat;
+pub mod companions;
+pub mod conditions;
+pub mod consumables;
+pub mod contagion;
+pub mod cooldown;
+pub mod crafting;
+pub mod dashboard;
+pub mod definitions;
+pub mod dialogue;
+pub mod ecology;
+pub mod encounters;
+pub mod environment;
+pub mod error;
+pub mod events;
+pub mod fixtures;
+pub mod forecast;
+pub mod generator;
+pub mod goals;
+pub mod handler;
+pub mod i18n;
+pub mod inspect;
+pub mod instances;
+pub mod items;
+pub mod live;
+pub mod map;
+pub mod metrics;
+pub mod mood;
+pub mod mounts;
+pub mod overlays;
+pub mod packs;
+pub mod pages;
+pub mod perception;
+pub mod persistence;
+pub mod plugins;
+pub mod profile;
+pub mod quests;
+pub mod regions;
+pub mod render;
+pub mod replay;
+pub mod reputation;
+pub mod rumors;
+pub mod saves;
+pub mod schedule;
+pub mod scripting;
+pub mod session;
+pub mod session_store;
+pub mod shops;
+pub mod spawner;
+pub mod status;
+pub mod stimuli;
+pub mod tick;
+pub mod trade;
+pub mod users;
+pub mod visibility;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm_plugins;
+pub mod weather;
+pub mod world;
+pub mod worlds;
@@ -0,0 +1,154 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::actor::{ActorId, ActorManager};
+use crate::admin::AdminToken;
+use crate::environment::EnvironmentManager;
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::handler::{ConditionSources, START_PAGE, dark_for_player};
+use crate::instances::{InstanceMode, Instances};
+use crate::items::ItemCatalog;
+use crate::map;
+use crate::session::{SESSION_KEY, UserSession, get_or_create_user_session};
+use crate::visibility::Visibility;
+
+#[derive(Deserialize)]
+pub struct LiveQuery {
+    #[serde(default)]
+    routes: bool, // also actors' routes after each tick, for the admin map
+    #[serde(default)]
+    actor: Option<String>, // comma-separated ids whose routes to send; all if unset
+}
+
+/// The player a stream is for, and what it takes to tell whom they can see
+struct Watcher {
+    player: UserSession,
+    sources: ConditionSources,
+    environment: web::Data<EnvironmentManager>,
+    items: web::Data<Arc<ItemCatalog>>,
+}
+
+impl Watcher {
+    /// Whether the player would notice `event`, with the actors of `world`
+    /// about: it happens on their page, and they can see everyone in it
+    fn notices(&self, event: &WorldEvent, world: &Mutex<ActorManager>) -> bool {
+        let here = &self.player.current_page;
+        if !event.concerns(here) {
+            return false;
+        }
+        let others: Vec<&ActorId> = event
+            .actors()
+            .into_iter()
+            .filter(|id| **id != self.player.player_id)
+            .collect();
+        if others.is_empty() {
+            return true;
+        }
+        let pages = self.sources.world.snapshot();
+        let (Some(page), Ok(environment)) =
+            (pages.get(here), self.environment.environment_for(here))
+        else {
+            return false;
+        };
+        let world_time = self.sources.clock.world_time();
+        let dark = dark_for_player(page, &world_time, &self.player, &self.items);
+        let conditions = self.sources.context(&self.player, here, &environment);
+        let world = world.lock();
+        // the gone (despawned, eaten) can't be seen either
+        others.into_iter().all(|id| {
+            world
+                .actors
+                .get(id)
+                .is_some_and(|actor| Visibility::of(actor, dark, &conditions) == Visibility::Seen)
+        })
+    }
+}
+
+/// Server-sent event stream of world events the player would notice on
+/// their current page. Pages reconnect on load, so the page is fixed per stream.
+/// Events about actors the player can't see there (in the dark, hidden,
+/// asleep) are left out. In solo mode the doings of the player's own
+/// instance are mixed in.
+/// With `?routes=true` an admin also gets an `ActorRoutes` event after
+/// every tick of the world their map shows, with where each actor (or
+/// each of `?actor=a,b`) has been and is going.
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn live_events_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    session: actix_session::Session,
+    sources: ConditionSources,
+    environment: web::Data<EnvironmentManager>,
+    items: web::Data<Arc<ItemCatalog>>,
+    actors: web::Data<Arc<Mutex<ActorManager>>>,
+    bus: web::Data<EventBus>,
+    instances: web::Data<Instances>,
+    query: web::Query<LiveQuery>,
+) -> Result<impl Responder, AppError> {
+    let LiveQuery { routes, actor } = query.into_inner();
+    // the same world the admin's map shows; nobody else hears from it
+    let shared = (actors.get_ref().clone(), bus.get_ref().clone());
+    let route_feed = if routes {
+        token.check(&req)?;
+        let player = get_or_create_user_session(&session, START_PAGE)?;
+        instances.for_player(&player.player_id)
+    } else {
+        (shared.0.clone(), EventBus::default())
+    };
+    let user_session = session.get::<UserSession>(SESSION_KEY).ok().flatten();
+    // shared worlds publish everything on the one bus; this one stays quiet
+    let own_feed = user_session
+        .as_ref()
+        .filter(|_| instances.mode() == InstanceMode::Solo)
+        .map(|s| instances.for_player(&s.player_id))
+        .unwrap_or_else(|| (shared.0.clone(), EventBus::default()));
+    let watcher = user_session.map(|player| Watcher {
+        player,
+        sources,
+        environment,
+        items,
+    });
+
+    // tagged with the world whose actors they name, and whether they come
+    // for the routes, which are worked out here after each tick, and only
+    // for an admin watching them
+    let tagged = |(world, bus): &(Arc<Mutex<ActorManager>>, EventBus), for_routes: bool| {
+        let world = world.clone();
+        BroadcastStream::new(bus.subscribe()).map(move |event| (world.clone(), for_routes, event))
+    };
+    let events = tagged(&shared, false)
+        .merge(tagged(&own_feed, false))
+        .merge(tagged(&route_feed, true));
+    let stream = events.filter_map(move |(world, for_routes, event)| {
+        // lagged receivers just skip what they missed
+        let event = event.ok()?;
+        let (kind, json) = match &event {
+            WorldEvent::WorldTicked { .. } if for_routes => {
+                let routes = map::routes(&world.lock(), actor.as_deref());
+                ("ActorRoutes", serde_json::to_string(&routes).ok()?)
+            }
+            _ if !for_routes
+                && watcher
+                    .as_ref()
+                    .is_some_and(|watcher| watcher.notices(&event, &world)) =>
+            {
+                (event.kind(), serde_json::to_string(&event).ok()?)
+            }
+            _ => return None,
+        };
+        Some(Ok::<_, Infallible>(web::Bytes::from(format!(
+            "event: {kind}\ndata: {json}\n\n"
+        ))))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream))
+}
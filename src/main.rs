@@ -1,24 +1,45 @@
 use actix_files::Files;
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
+use actix_session::SessionMiddleware;
 use actix_web::App;
-use actix_web::{HttpServer, cookie::Key, web};
-use chrono::Timelike;
-use std::sync::{Arc, Mutex};
+use actix_web::middleware::from_fn;
+use actix_web::{HttpServer, web};
+use parking_lot::Mutex;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tera::Tera;
+use tracing::{error, warn};
 use tracing_subscriber::{
     EnvFilter, fmt, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt,
 };
 
-use crate::actor::ActorManager;
-use crate::environment::WorldTime;
-use crate::pages::{PageGraph, load_page_graph};
-
-mod actor;
-mod environment;
-mod error;
-mod handler;
-mod pages;
-mod session;
+use chott::actor::ActorManager;
+use chott::admin::AdminToken;
+use chott::clock::{ClockConfig, WorldClock};
+use chott::crafting::RecipeBook;
+use chott::dialogue::DialogueBook;
+use chott::environment::EnvironmentTtl;
+use chott::events::{EventBus, EventLog};
+use chott::i18n::Translations;
+use chott::instances::{InstanceMode, Instances};
+use chott::items::{ItemCatalog, load_items};
+use chott::metrics::{EventCounters, Metrics};
+use chott::pages::{
+    DEFAULT_TEMPLATE, TEXT_TEMPLATE, apply_template_fallback, load_page_graph, validate_templates,
+    validate_variants,
+};
+use chott::plugins::PluginHost;
+use chott::quests::QuestBook;
+use chott::regions::Regions;
+use chott::replay::{Recorder, ReplaySource};
+use chott::saves::SaveSlots;
+use chott::scripting::ScriptHost;
+use chott::world::WorldGraph;
+use chott::worlds::{HostedWorld, Mount};
+use chott::{
+    admin, api, chat, clock, cooldown, crafting, definitions, dialogue, encounters, environment,
+    generator, i18n, metrics, persistence, plugins, quests, regions, render, schedule, scripting,
+    session_store, shops, spawner, tick, users, world, worlds,
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -33,55 +54,288 @@ async fn main() -> std::io::Result<()> {
         .with(env_filter)
         .init();
 
-    let tera = Tera::new("templates/*.html").unwrap();
-    let page_graph: Arc<PageGraph> = Arc::new(load_page_graph());
-    let actor_manager = Arc::new(Mutex::new(ActorManager::new()));
-    let environment_manager = environment::EnvironmentManager::new();
-
-    let actor_manager_bg = actor_manager.clone();
-    let pages_clone = page_graph.clone();
-
-    // Start background actor tick task
-    actix_rt::spawn(async move {
-        let mut intvl = actix_rt::time::interval(std::time::Duration::from_secs(2));
-        loop {
-            intvl.tick().await;
-            let tick_result = std::panic::catch_unwind(|| {
-                let now = chrono::Local::now();
-                let world_time = WorldTime {
-                    hour: now.hour() as u8,
-                    _minute: now.minute() as u8,
-                };
-                let mut guard = actor_manager_bg.lock().unwrap();
-                guard.tick_some(&world_time, &pages_clone);
-            });
-            if let Err(panic_info) = tick_result {
-                eprintln!("WORLD TICK PANIC! Continuing. Info: {panic_info:?}"); // placeholder
-            }
+    let mut tera = Tera::new("templates/*.html").unwrap();
+    render::register_time_filters(&mut tera);
+    let mut pages = load_page_graph();
+
+    // Procedural areas, grown onto the hand-authored pages
+    let areas_path =
+        std::env::var("CHOTT_AREAS").unwrap_or(generator::DEFAULT_AREAS_PATH.to_string());
+    let area_specs = generator::load_area_specs(areas_path.as_ref())
+        .unwrap_or_else(|e| panic!("Failed to load area recipes: {e}"));
+    for spec in area_specs {
+        let area = spec
+            .generate()
+            .and_then(|area| generator::attach(&mut pages, area));
+        if let Err(e) = area {
+            error!("Area '{}' not generated: {e}", spec.id);
+        }
+    }
+
+    // Report pages whose templates are missing now, rather than on first visit
+    let issues = validate_templates(&pages, &tera);
+    for issue in &issues {
+        warn!("Validation: {issue}");
+    }
+    let patched = apply_template_fallback(&mut pages, &tera, &issues);
+    if patched > 0 {
+        warn!("{patched} page(s) fall back to '{DEFAULT_TEMPLATE}'");
+    } else if !issues.is_empty() {
+        error!("'{DEFAULT_TEMPLATE}' is not loaded either; those pages will fail to render");
+    }
+    for issue in validate_variants(&pages, &tera) {
+        warn!("Validation: {issue}");
+    }
+    if !tera.get_template_names().any(|name| name == TEXT_TEMPLATE) {
+        warn!("'{TEXT_TEMPLATE}' is not loaded; text-only pages will fail to render");
+    }
+
+    let items: Arc<ItemCatalog> = Arc::new(load_items());
+    let regions_path =
+        std::env::var("CHOTT_REGIONS").unwrap_or(regions::DEFAULT_REGIONS_PATH.to_string());
+    let regions = Arc::new(
+        Regions::load(regions_path.as_ref())
+            .unwrap_or_else(|e| panic!("Failed to load regions: {e}")),
+    );
+
+    let clock = WorldClock::new(ClockConfig::from_env());
+    let dialogue_path =
+        std::env::var("CHOTT_DIALOGUE").unwrap_or(dialogue::DEFAULT_DIALOGUE_PATH.to_string());
+    let dialogue = Arc::new(
+        DialogueBook::load(dialogue_path.as_ref())
+            .unwrap_or_else(|e| panic!("Failed to load dialogue: {e}")),
+    );
+    let quests_path =
+        std::env::var("CHOTT_QUESTS").unwrap_or(quests::DEFAULT_QUESTS_PATH.to_string());
+    let quests = Arc::new(
+        QuestBook::load(quests_path.as_ref())
+            .unwrap_or_else(|e| panic!("Failed to load quests: {e}")),
+    );
+    let recipes_path =
+        std::env::var("CHOTT_RECIPES").unwrap_or(crafting::DEFAULT_RECIPES_PATH.to_string());
+    let recipes = Arc::new(
+        RecipeBook::load(recipes_path.as_ref(), &items)
+            .unwrap_or_else(|e| panic!("Failed to load recipes: {e}")),
+    );
+
+    let locales_path =
+        std::env::var("CHOTT_LOCALES").unwrap_or(i18n::DEFAULT_LOCALES_PATH.to_string());
+    let translations = Arc::new(
+        Translations::load(locales_path.as_ref())
+            .unwrap_or_else(|e| panic!("Failed to load translations: {e}")),
+    );
+    render::register_prose_filters(&mut tera, translations.clone(), clock.clone());
+
+    let scripts_path =
+        std::env::var("CHOTT_SCRIPTS").unwrap_or(scripting::DEFAULT_SCRIPTS_PATH.to_string());
+    let scripts = ScriptHost::load(scripts_path.as_ref())
+        .unwrap_or_else(|e| panic!("Failed to load scripts: {e}"));
+    let plugins_path =
+        std::env::var("CHOTT_PLUGINS").unwrap_or(plugins::DEFAULT_PLUGINS_PATH.to_string());
+    let plugins = PluginHost::load(plugins_path.as_ref())
+        .unwrap_or_else(|e| panic!("Failed to load plugins: {e}"));
+
+    // Internal event bus and its long-lived subscribers
+    let bus = EventBus::new();
+    let page_flags_path: PathBuf = std::env::var("CHOTT_PAGE_FLAGS")
+        .unwrap_or(persistence::DEFAULT_PAGE_FLAGS_PATH.to_string())
+        .into();
+    let page_flags = persistence::load_page_flags(&page_flags_path)
+        .unwrap_or_else(|e| panic!("Failed to load page flags: {e}"));
+    let world = WorldGraph::new(pages, bus.clone()).with_flags(page_flags);
+    persistence::spawn_page_flag_saver(&bus, world.clone(), page_flags_path);
+    let event_log = EventLog::default();
+    event_log.spawn_subscriber(&bus, clock.clone());
+    let chat_log = chat::ChatLog::new(bus.clone());
+    let shops = shops::ShopManager::default();
+    shops.spawn_restocker(&bus, world.clone());
+    let journal_path =
+        std::env::var("CHOTT_JOURNAL").unwrap_or(persistence::DEFAULT_JOURNAL_PATH.to_string());
+    persistence::spawn_journal(&bus, journal_path.into());
+    let event_counters = EventCounters::default();
+    event_counters.spawn_subscriber(&bus);
+    let metrics = Metrics::default();
+
+    let actors_path: PathBuf = std::env::var("CHOTT_ACTORS")
+        .unwrap_or(definitions::DEFAULT_ACTORS_PATH.to_string())
+        .into();
+    let actor_definitions = definitions::load_actor_definitions(&actors_path)
+        .unwrap_or_else(|e| panic!("Failed to load actor definitions: {e}"));
+    let spawns_path =
+        std::env::var("CHOTT_SPAWNS").unwrap_or(spawner::DEFAULT_SPAWNS_PATH.to_string());
+    let archetypes = definitions::load_archetypes(&actors_path)
+        .unwrap_or_else(|e| panic!("Failed to load actor archetypes: {e}"));
+    let spawner = spawner::Spawner::load(spawns_path.as_ref(), &archetypes)
+        .unwrap_or_else(|e| panic!("Failed to load spawn rules: {e}"));
+    let schedule_path =
+        std::env::var("CHOTT_SCHEDULE").unwrap_or(schedule::DEFAULT_SCHEDULE_PATH.to_string());
+    let schedule = schedule::Schedule::load(schedule_path.as_ref(), &archetypes)
+        .unwrap_or_else(|e| panic!("Failed to load scheduled events: {e}"));
+    let encounters_path = std::env::var("CHOTT_ENCOUNTERS")
+        .unwrap_or(encounters::DEFAULT_ENCOUNTERS_PATH.to_string());
+    let encounters = Arc::new(
+        encounters::Encounters::load(encounters_path.as_ref(), &archetypes)
+            .unwrap_or_else(|e| panic!("Failed to load encounter tables: {e}")),
+    );
+    let mut actor_manager = ActorManager::new(
+        bus.clone(),
+        actor_definitions,
+        regions.clone(),
+        scripts.clone(),
+    )
+    .with_tick_budget(clock::tick_budget_from_env())
+    .with_spawner(spawner)
+    .with_schedule(schedule);
+    // journal each tick of the shared world, for replaying from the admin API
+    let recorder =
+        Recorder::from_env().unwrap_or_else(|e| panic!("Failed to start recording ticks: {e}"));
+    let instance_mode = InstanceMode::from_env();
+    if let Some(recorder) = recorder {
+        if instance_mode == InstanceMode::Solo {
+            warn!("Solo instances aren't recorded, only the shared world they copy");
         }
-    });
+        actor_manager = actor_manager.with_recorder(recorder);
+    }
+    let actor_manager = Arc::new(Mutex::new(actor_manager));
+    let replay_source = ReplaySource::from_env();
+    // one world of actors for everyone, or a copy of it for each player
+    let instances = Instances::new(instance_mode, actor_manager.clone(), bus.clone());
+    definitions::spawn_reload_watcher(actors_path, instances.clone());
+    world::spawn_actor_notifier(&bus, instances.clone());
+    let environment_manager = environment::EnvironmentManager::new(
+        bus.clone(),
+        EnvironmentTtl::from_env(),
+        clock.clone(),
+        world.clone(),
+        regions.clone(),
+    );
+    plugins.spawn_dispatcher(
+        &bus,
+        world.clone(),
+        actor_manager.clone(),
+        environment_manager.clone(),
+    );
+
+    let save_slots = SaveSlots::from_env(
+        clock.clone(),
+        world.clone(),
+        actor_manager.clone(),
+        environment_manager.clone(),
+        shops.clone(),
+        bus.clone(),
+    );
+
+    let admin_token = AdminToken::from_env();
+    let cooldowns = cooldown::Cooldowns::from_env();
 
-    // cookie session storage
-    let secret_key = Key::generate();
+    let accounts_path: PathBuf = std::env::var("CHOTT_ACCOUNTS")
+        .unwrap_or(users::DEFAULT_ACCOUNTS_PATH.to_string())
+        .into();
+    let accounts = users::AccountStore::load(&accounts_path)
+        .unwrap_or_else(|e| panic!("Failed to load accounts: {e}"));
+    accounts.spawn_saver();
+    let saved_accounts = accounts.clone();
+
+    // The world ticks in its own task, restarted if it ever panics
+    tick::Ticker {
+        actors: instances.clone(),
+        environment: environment_manager.clone(),
+        world: world.clone(),
+        clock: clock.clone(),
+        bus: bus.clone(),
+        metrics: metrics.clone(),
+    }
+    .spawn_supervised();
+
+    // session storage: in the cookie, or server-side with only an id in the cookie
+    let session_backend = session_store::SessionBackend::from_env()
+        .unwrap_or_else(|e| panic!("Failed to load sessions: {e}"));
+    let secret_key = session_backend
+        .secret_key()
+        .unwrap_or_else(|e| panic!("Failed to load the session key: {e}"));
+
+    // extra worlds beside the live one, each under /w/<name>/
+    let worlds_path =
+        std::env::var("CHOTT_WORLDS").unwrap_or(worlds::DEFAULT_WORLDS_PATH.to_string());
+    let world_specs = worlds::load_world_specs(worlds_path.as_ref())
+        .unwrap_or_else(|e| panic!("Failed to load worlds: {e}"));
+    let hosted: Arc<Vec<HostedWorld>> = Arc::new(
+        world_specs
+            .iter()
+            .map(|spec| {
+                HostedWorld::open(spec, &tera, &regions, &scripts, &clock)
+                    .unwrap_or_else(|e| panic!("Failed to open world '{}': {e}", spec.name))
+            })
+            .collect(),
+    );
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(tera.clone()))
-            .app_data(web::Data::new(page_graph.clone()))
+            .app_data(web::Data::new(Mount::live()))
+            .app_data(web::Data::new(world.clone()))
+            .app_data(web::Data::new(items.clone()))
+            .app_data(web::Data::new(regions.clone()))
             .app_data(web::Data::new(actor_manager.clone()))
+            .app_data(web::Data::new(instances.clone()))
             .app_data(web::Data::new(environment_manager.clone()))
-            .wrap(SessionMiddleware::new(
-                CookieSessionStore::default(),
-                secret_key.clone(),
-            ))
+            .app_data(web::Data::new(bus.clone()))
+            .app_data(web::Data::new(clock.clone()))
+            .app_data(web::Data::new(event_log.clone()))
+            .app_data(web::Data::new(dialogue.clone()))
+            .app_data(web::Data::new(quests.clone()))
+            .app_data(web::Data::new(shops.clone()))
+            .app_data(web::Data::new(recipes.clone()))
+            .app_data(web::Data::new(scripts.clone()))
+            .app_data(web::Data::new(plugins.clone()))
+            .app_data(web::Data::new(translations.clone()))
+            .app_data(web::Data::new(encounters.clone()))
+            .app_data(web::Data::new(admin_token.clone()))
+            .app_data(web::Data::new(accounts.clone()))
+            .app_data(web::Data::new(chat_log.clone()))
+            .app_data(web::Data::new(cooldowns.clone()))
+            .app_data(web::Data::new(metrics.clone()))
+            .app_data(web::Data::new(event_counters.clone()))
+            .app_data(web::Data::new(session_backend.clone()))
+            .app_data(web::Data::new(replay_source.clone()))
+            .app_data(web::Data::new(save_slots.clone()))
+            .wrap(from_fn(metrics::time_requests))
+            .configure(|cfg| {
+                for hosted in hosted.iter() {
+                    hosted.configure(cfg, &session_backend, &secret_key);
+                }
+            })
+            // the live world, everything not under a hosted world's mount
             .service(
-                web::resource("/")
-                    .route(web::get().to(handler::index_handler))
-                    .route(web::post().to(handler::index_handler)),
+                web::scope("")
+                    .wrap(from_fn(render::error_pages))
+                    .wrap(SessionMiddleware::new(
+                        session_backend.clone(),
+                        secret_key.clone(),
+                    ))
+                    .configure(worlds::play_routes)
+                    .route("/metrics", web::get().to(metrics::metrics_handler))
+                    .route("/account", web::get().to(users::account_handler))
+                    .route("/account/register", web::post().to(users::register_handler))
+                    .route("/account/login", web::post().to(users::login_handler))
+                    .route("/account/logout", web::post().to(users::logout_handler))
+                    .configure(admin::configure)
+                    .configure(api::configure)
+                    .route(
+                        "/themes/{theme}.css",
+                        web::get().to(render::theme_stylesheet_handler),
+                    )
+                    .service(Files::new("/static", "./static").show_files_listing())
+                    .default_service(web::to(render::not_found)),
             )
-            .service(Files::new("/static", "./static").show_files_listing())
     })
     .bind(("127.0.0.1", 8080))?
     .run()
-    .await
+    .await?;
+
+    // characters changed since the saver last wrote
+    if let Err(e) = saved_accounts.write() {
+        error!("Failed to save accounts: {e}");
+    }
+    Ok(())
 }
@@ -0,0 +1,439 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, web};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+
+use crate::actor::{ActorFlag, ActorId, ActorManager};
+use crate::admin::AdminToken;
+use crate::error::AppError;
+use crate::goals::Goal;
+use crate::handler::START_PAGE;
+use crate::instances::Instances;
+use crate::items::ItemId;
+use crate::pages::{Page, PageGraph, PageId};
+use crate::session::{UserSession, get_or_create_user_session};
+use crate::world::WorldGraph;
+use crate::worlds::Mount;
+
+/// Carrying this shows the whole world on the map, not just where you've been
+const MAP_ITEM: &str = "old-map";
+
+// SVG layout, in pixels
+const CELL_W: i32 = 180;
+const CELL_H: i32 = 100;
+const BOX_W: i32 = 150;
+const BOX_H: i32 = 56;
+const MARGIN: i32 = 20;
+
+#[derive(Deserialize)]
+pub struct MapQuery {
+    #[serde(default)]
+    format: MapFormat,
+    #[serde(default)]
+    actors: bool, // also show where actors are right now; admins only
+    #[serde(default)]
+    routes: bool, // and where they've been and mean to go; admins only
+    #[serde(default)]
+    actor: Option<String>, // comma-separated ids whose routes to draw; all if unset
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum MapFormat {
+    #[default]
+    Svg,
+    Dot, // for Graphviz
+}
+
+/// What one player can see of the world
+struct MapView<'a> {
+    pages: Vec<&'a Page>,
+    here: &'a PageId,
+    actors: HashMap<&'a PageId, Vec<(ActorId, String)>>, // page -> ids and names of actors on it
+    base: &'a str,                                       // where the world is mounted, for links
+    routes: Option<Vec<Route>>,                          // admins only
+}
+
+/// Where an actor has lately been and is set on going, for the admin
+/// map's overlay; worked out only while someone is looking
+#[derive(Serialize)]
+pub struct Route {
+    actor: ActorId,
+    name: String,
+    page: PageId,
+    trail: Vec<PageId>,   // pages left behind, oldest first
+    planned: Vec<PageId>, // pages queued to move to, in order
+    goal: Option<Goal>,
+}
+
+/// The routes of the actors picked by an `?actor=a,b` selection, by id;
+/// every actor but the players when nothing is picked
+pub fn routes(manager: &ActorManager, selection: Option<&str>) -> Vec<Route> {
+    let picked: Option<HashSet<&str>> = selection
+        .map(|ids| {
+            ids.split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .collect()
+        })
+        .filter(|ids: &HashSet<&str>| !ids.is_empty());
+    let mut routes: Vec<Route> = manager
+        .actors
+        .values()
+        .filter(|actor| !actor.has_flag(ActorFlag::Player))
+        .filter(|actor| {
+            picked
+                .as_ref()
+                .is_none_or(|ids| ids.contains(actor.id.as_str()))
+        })
+        .map(|actor| Route {
+            actor: actor.id.clone(),
+            name: actor.name.clone(),
+            page: actor.location.clone(),
+            trail: actor.trail.iter().cloned().collect(),
+            planned: actor.planned_path().cloned().collect(),
+            goal: actor.state.goal.clone(),
+        })
+        .collect();
+    routes.sort_by(|a, b| a.actor.cmp(&b.actor));
+    routes
+}
+
+impl<'a> MapView<'a> {
+    fn shows(&self, page: &PageId) -> bool {
+        self.pages.iter().any(|p| p.id == *page)
+    }
+
+    /// Connections worth drawing: between shown pages, and not secret
+    /// unless the player has been on both ends
+    fn ways(&self, session: &UserSession) -> Vec<(&'a Page, &'a str, &'a PageId, bool)> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.connections.iter().map(move |conn| (*page, conn)))
+            .filter(|(page, conn)| {
+                self.shows(&conn.target)
+                    && (!conn.secret
+                        || session.has_visited(&page.id) && session.has_visited(&conn.target))
+            })
+            .map(|(page, conn)| (page, conn.name.as_str(), &conn.target, conn.secret))
+            .collect()
+    }
+}
+
+/// The world as a map: the pages the player has been to (or every page, with
+/// an old map in their pack), their current page highlighted and linking
+/// back to it. `?format=dot` gives Graphviz source instead of SVG.
+/// `?actors=true` adds every actor, hidden or not, and `?routes=true` their
+/// trails and planned paths (just those of `?actor=a,b`, if given), so
+/// both are for admins, who see every page.
+pub async fn map_handler(
+    req: HttpRequest,
+    token: web::Data<AdminToken>,
+    mount: web::Data<Mount>,
+    world: web::Data<WorldGraph>,
+    session: actix_session::Session,
+    instances: web::Data<Instances>,
+    query: web::Query<MapQuery>,
+) -> Result<impl Responder, AppError> {
+    let admin = query.actors || query.routes;
+    if admin {
+        token.check(&req)?;
+    }
+    let user_session = get_or_create_user_session(&session, START_PAGE)?;
+    let pages = world.snapshot();
+    let here = world
+        .resolve(&user_session.current_page)
+        .unwrap_or_else(|| PageId::from(START_PAGE));
+    let here = pages
+        .get(&here)
+        .map(|page| &page.id)
+        .ok_or_else(|| AppError::PageNotFound(here.to_string()))?;
+
+    let whole_world = admin || user_session.has_item(&ItemId::from(MAP_ITEM));
+    let mut shown: Vec<&Page> = pages
+        .values()
+        .filter(|page| whole_world || page.id == *here || user_session.has_visited(&page.id))
+        .collect();
+    shown.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut actors: HashMap<&PageId, Vec<(ActorId, String)>> = HashMap::new();
+    let mut shown_routes = None;
+    if admin {
+        let (actor_manager, _) = instances.for_player(&user_session.player_id);
+        let manager = actor_manager.lock();
+        if query.actors {
+            for actor in manager.actors.values() {
+                if let Some(page) = shown.iter().find(|p| p.id == actor.location) {
+                    actors
+                        .entry(&page.id)
+                        .or_default()
+                        .push((actor.id.clone(), actor.name.clone()));
+                }
+            }
+            actors
+                .values_mut()
+                .for_each(|names| names.sort_by(|a, b| a.1.cmp(&b.1)));
+        }
+        if query.routes {
+            shown_routes = Some(routes(&manager, query.actor.as_deref()));
+        }
+    }
+
+    let view = MapView {
+        pages: shown,
+        here,
+        actors,
+        base: &mount.base,
+        routes: shown_routes,
+    };
+    Ok(match query.format {
+        MapFormat::Svg => HttpResponse::Ok()
+            .content_type("image/svg+xml")
+            .body(render_svg(&view, &pages, &user_session)),
+        MapFormat::Dot => HttpResponse::Ok()
+            .content_type("text/vnd.graphviz")
+            .body(render_dot(&view, &user_session)),
+    })
+}
+
+fn render_dot(view: &MapView, session: &UserSession) -> String {
+    let mut out = String::from("digraph world {\n    node [shape=box];\n");
+    for page in &view.pages {
+        let mut label = page.title.clone();
+        for (_, name) in view.actors.get(&page.id).into_iter().flatten() {
+            label.push('\n');
+            label.push_str(name);
+        }
+        let style = if page.id == *view.here {
+            ", style=filled, fillcolor=gold"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "    {:?} [label={:?}{style}];",
+            page.id.as_str(),
+            label
+        );
+    }
+    for (from, name, to, secret) in view.ways(session) {
+        let style = if secret { ", style=dashed" } else { "" };
+        let _ = writeln!(
+            out,
+            "    {:?} -> {:?} [label={:?}{style}];",
+            from.id.as_str(),
+            to.as_str(),
+            name
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_svg(view: &MapView, pages: &PageGraph, session: &UserSession) -> String {
+    let cells = layout(view, pages);
+    let (min_x, min_y) = cells
+        .values()
+        .fold((0, 0), |(x, y), &(cx, cy)| (x.min(cx), y.min(cy)));
+    let (max_x, max_y) = cells
+        .values()
+        .fold((0, 0), |(x, y), &(cx, cy)| (x.max(cx), y.max(cy)));
+    // centre of a page's box, in pixels
+    let centre = |id: &PageId| {
+        let (x, y) = cells[id];
+        (
+            MARGIN + (x - min_x) * CELL_W + BOX_W / 2,
+            MARGIN + (y - min_y) * CELL_H + BOX_H / 2,
+        )
+    };
+    let width = 2 * MARGIN + (max_x - min_x) * CELL_W + BOX_W;
+    let height = 2 * MARGIN + (max_y - min_y) * CELL_H + BOX_H;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+        font-family=\"sans-serif\" font-size=\"12\">\n"
+    );
+    let mut drawn = HashSet::new();
+    for (from, _, to, secret) in view.ways(session) {
+        // one line per pair of pages, however many ways join them
+        if !drawn.insert(if from.id < *to {
+            (&from.id, to)
+        } else {
+            (to, &from.id)
+        }) {
+            continue;
+        }
+        let ((x1, y1), (x2, y2)) = (centre(&from.id), centre(to));
+        let dash = if secret {
+            " stroke-dasharray=\"4 3\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "  <line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"#888\"{dash}/>"
+        );
+    }
+    for page in &view.pages {
+        let (cx, cy) = centre(&page.id);
+        let (x, y) = (cx - BOX_W / 2, cy - BOX_H / 2);
+        let here = page.id == *view.here;
+        let fill = if here { "#ffd84d" } else { "#f4f1e8" };
+        // the player's own page takes them back to it
+        if here {
+            let _ = writeln!(out, "  <a href=\"{}/\">", escape(view.base));
+        }
+        let _ = writeln!(
+            out,
+            "  <rect x=\"{x}\" y=\"{y}\" width=\"{BOX_W}\" height=\"{BOX_H}\" rx=\"6\" \
+            fill=\"{fill}\" stroke=\"#333\" data-page=\"{}\"/>",
+            escape(page.id.as_str())
+        );
+        let _ = writeln!(
+            out,
+            "  <text x=\"{cx}\" y=\"{}\" text-anchor=\"middle\" font-weight=\"bold\">{}</text>",
+            y + 20,
+            escape(&page.title)
+        );
+        if here {
+            out.push_str("  </a>\n");
+        }
+        if let Some(names) = view.actors.get(&page.id) {
+            let _ = write!(
+                out,
+                "  <text x=\"{cx}\" y=\"{}\" text-anchor=\"middle\" font-size=\"10\">",
+                y + 40
+            );
+            for (n, (id, name)) in names.iter().enumerate() {
+                if n > 0 {
+                    out.push_str(", ");
+                }
+                let _ = write!(
+                    out,
+                    "<a href=\"{}/actor/{}\">{}</a>",
+                    escape(view.base),
+                    escape(id.as_str()),
+                    escape(name)
+                );
+            }
+            out.push_str("</text>\n");
+        }
+    }
+    if let Some(routes) = &view.routes {
+        // the live events redraw this group as the world ticks
+        out.push_str("  <g id=\"routes\" fill=\"none\" stroke-width=\"2\">\n");
+        for (n, route) in routes.iter().enumerate() {
+            // set each actor a little apart, so shared roads stay readable
+            let nudge = (n as i32 % 5 - 2) * 3;
+            let points = |pages: Vec<&PageId>| {
+                pages
+                    .into_iter()
+                    .filter(|page| cells.contains_key(page))
+                    .map(|page| {
+                        let (x, y) = centre(page);
+                        format!("{},{}", x + nudge, y + nudge)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let trail = points(route.trail.iter().chain([&route.page]).collect());
+            let planned = points([&route.page].into_iter().chain(&route.planned).collect());
+            let mut title = route.name.clone();
+            if let Some(goal) = &route.goal {
+                let _ = write!(title, ": {}", describe(goal));
+            }
+            let _ = writeln!(
+                out,
+                "    <g class=\"route\" data-actor=\"{}\"><title>{}</title>\
+                <polyline class=\"trail\" points=\"{trail}\" stroke=\"#999\" stroke-dasharray=\"2 3\"/>\
+                <polyline class=\"planned\" points=\"{planned}\" stroke=\"#2a6fdb\"/></g>",
+                escape(route.actor.as_str()),
+                escape(&title)
+            );
+        }
+        out.push_str("  </g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// What an actor has set out to do, as the admin map's overlay says it
+fn describe(goal: &Goal) -> String {
+    match goal {
+        Goal::BeAt(page) => format!("to be at {page}"),
+        Goal::Eat => "to find grazing".to_string(),
+    }
+}
+
+/// Grid cell for each shown page. Starting from the player's page, compass
+/// exits put the neighbour in that direction; anything else, or a clash,
+/// takes the nearest free cell. Pages not reachable from here go below.
+fn layout<'a>(view: &MapView<'a>, pages: &PageGraph) -> HashMap<&'a PageId, (i32, i32)> {
+    let mut cells: HashMap<&PageId, (i32, i32)> = HashMap::new();
+    let mut taken: HashSet<(i32, i32)> = HashSet::new();
+    let mut place = |id: &'a PageId, want: (i32, i32), cells: &mut HashMap<_, _>| {
+        let cell = nearest_free(want, &taken);
+        taken.insert(cell);
+        cells.insert(id, cell);
+    };
+
+    let mut queue = VecDeque::from([view.here]);
+    place(view.here, (0, 0), &mut cells);
+    while let Some(id) = queue.pop_front() {
+        let from = cells[id];
+        for conn in pages.get(id).into_iter().flat_map(|p| &p.connections) {
+            let Some(target) = view.pages.iter().find(|p| p.id == conn.target) else {
+                continue;
+            };
+            if cells.contains_key(&target.id) {
+                continue;
+            }
+            let (dx, dy) = compass_step(&conn.name);
+            place(&target.id, (from.0 + dx, from.1 + dy), &mut cells);
+            queue.push_back(&target.id);
+        }
+    }
+
+    let below = cells.values().map(|&(_, y)| y).max().unwrap_or(0) + 1;
+    let unreached: Vec<&PageId> = view
+        .pages
+        .iter()
+        .map(|p| &p.id)
+        .filter(|id| !cells.contains_key(id))
+        .collect();
+    for (n, id) in unreached.into_iter().enumerate() {
+        place(id, (n as i32, below), &mut cells);
+    }
+    cells
+}
+
+fn compass_step(name: &str) -> (i32, i32) {
+    match name.to_lowercase().as_str() {
+        "north" => (0, -1),
+        "south" => (0, 1),
+        "east" => (1, 0),
+        "west" => (-1, 0),
+        _ => (1, 1),
+    }
+}
+
+/// `want` if free, else the first free cell in growing rings around it
+fn nearest_free(want: (i32, i32), taken: &HashSet<(i32, i32)>) -> (i32, i32) {
+    (0..)
+        .flat_map(|r: i32| {
+            (-r..=r).flat_map(move |dx| {
+                (-r..=r)
+                    .filter(move |dy| dx.abs().max(dy.abs()) == r)
+                    .map(move |dy| (want.0 + dx, want.1 + dy))
+            })
+        })
+        .find(|cell| !taken.contains(cell))
+        .expect("the grid is unbounded")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,269 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, Responder, web};
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+
+use crate::actor::ActorManager;
+use crate::environment::EnvironmentManager;
+use crate::events::{EventBus, WorldEvent};
+use crate::instances::{InstanceMode, Instances};
+use crate::session_store::SessionBackend;
+
+/// How often (in world ticks) the event counts are logged
+const SUMMARY_EVERY: u64 = 30;
+
+/// Upper bounds, in seconds, of the duration histogram buckets
+const DURATION_BUCKETS: [f64; 11] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Running count of published events by kind
+#[derive(Clone, Default)]
+pub struct EventCounters {
+    counts: Arc<Mutex<HashMap<&'static str, u64>>>,
+}
+
+impl EventCounters {
+    /// Copy of the current counts, sorted by kind
+    pub fn snapshot(&self) -> Vec<(&'static str, u64)> {
+        let counts = self.counts.lock();
+        let mut snapshot: Vec<_> = counts.iter().map(|(k, v)| (*k, *v)).collect();
+        snapshot.sort();
+        snapshot
+    }
+
+    /// Subscribe to the bus and keep counting until it closes
+    pub fn spawn_subscriber(&self, bus: &EventBus) {
+        let mut rx = bus.subscribe();
+        let counters = self.clone();
+        actix_rt::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => counters.record(&event),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    fn record(&self, event: &WorldEvent) {
+        *self.counts.lock().entry(event.kind()).or_insert(0) += 1;
+        if let WorldEvent::WorldTicked { tick, .. } = event
+            && tick.is_multiple_of(SUMMARY_EVERY)
+        {
+            debug!(counts = ?self.snapshot(), "Event counts");
+        }
+    }
+}
+
+/// Durations counted into `DURATION_BUCKETS`, Prometheus style
+#[derive(Clone, Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()], // observations at or under each bound
+    count: u64,
+    sum: f64, // seconds
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    /// Append this histogram as `name`, with `labels` ("a=\"b\"" pairs, or empty)
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (bucket, bound) in self.buckets.iter().zip(DURATION_BUCKETS) {
+            let _ = writeln!(out, "{name}_bucket{{{labels}{sep}le=\"{bound}\"}} {bucket}");
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let braces = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{braces} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{braces} {}", self.count);
+    }
+}
+
+#[derive(Default)]
+struct Timings {
+    tick: Histogram,
+    requests: HashMap<(String, String), Histogram>, // (method, route) -> latency
+}
+
+/// Tick and request timings, exported with everything else on `/metrics`
+#[derive(Clone, Default)]
+pub struct Metrics {
+    timings: Arc<Mutex<Timings>>,
+}
+
+impl Metrics {
+    /// One world tick took `duration`
+    pub fn record_tick(&self, duration: Duration) {
+        self.lock().tick.observe(duration);
+    }
+
+    /// A request to `route` (the matched pattern, not the raw path) took `duration`
+    pub fn record_request(&self, method: &str, route: &str, duration: Duration) {
+        self.lock()
+            .requests
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .observe(duration);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Timings> {
+        self.timings.lock()
+    }
+}
+
+/// Time every request into the `Metrics` in app data
+pub async fn time_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+    let res = next.call(req).await?;
+    if let Some(metrics) = metrics {
+        // unmatched paths are lumped together so scanners can't grow the label set
+        let route = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics.record_request(res.request().method().as_str(), &route, started.elapsed());
+    }
+    Ok(res)
+}
+
+/// Escape a Prometheus label value
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The simulation's health in the Prometheus text format
+pub async fn metrics_handler(
+    metrics: web::Data<Metrics>,
+    events: web::Data<EventCounters>,
+    actors: web::Data<Arc<Mutex<ActorManager>>>,
+    instances: web::Data<Instances>,
+    environment: web::Data<EnvironmentManager>,
+    sessions: web::Data<SessionBackend>,
+) -> impl Responder {
+    let mut out = String::new();
+    {
+        let timings = metrics.lock();
+        out.push_str("# HELP chott_tick_duration_seconds Time taken by one world tick.\n");
+        out.push_str("# TYPE chott_tick_duration_seconds histogram\n");
+        timings
+            .tick
+            .write(&mut out, "chott_tick_duration_seconds", "");
+
+        out.push_str("# HELP chott_request_duration_seconds HTTP request latency by route.\n");
+        out.push_str("# TYPE chott_request_duration_seconds histogram\n");
+        let mut routes: Vec<_> = timings.requests.iter().collect();
+        routes.sort_by(|a, b| a.0.cmp(b.0));
+        for ((method, route), histogram) in routes {
+            let labels = format!("method=\"{}\",route=\"{}\"", label(method), label(route));
+            histogram.write(&mut out, "chott_request_duration_seconds", &labels);
+        }
+    }
+
+    {
+        let actors = actors.lock();
+        out.push_str("# HELP chott_actors Actors in the world, players included.\n");
+        out.push_str("# TYPE chott_actors gauge\n");
+        let _ = writeln!(out, "chott_actors {}", actors.actors.len());
+        out.push_str("# HELP chott_players Players seen in the world lately.\n");
+        out.push_str("# TYPE chott_players gauge\n");
+        let _ = writeln!(out, "chott_players {}", actors.player_count());
+        let last = actors.last_tick();
+        out.push_str(
+            "# HELP chott_tick_phase_seconds Time spent in each phase of the last tick.\n",
+        );
+        out.push_str("# TYPE chott_tick_phase_seconds gauge\n");
+        for (phase, took) in [
+            ("planning", last.planning),
+            ("acting", last.acting),
+            ("enduring", last.enduring),
+        ] {
+            let _ = writeln!(
+                out,
+                "chott_tick_phase_seconds{{phase=\"{phase}\"}} {}",
+                took.as_secs_f64()
+            );
+        }
+        out.push_str("# HELP chott_actor_decision_seconds Time each actor's last decision took.\n");
+        out.push_str("# TYPE chott_actor_decision_seconds gauge\n");
+        for (actor, took) in actors.decision_times() {
+            let _ = writeln!(
+                out,
+                "chott_actor_decision_seconds{{actor=\"{}\"}} {}",
+                label(actor),
+                took.as_secs_f64()
+            );
+        }
+        out.push_str("# HELP chott_actor_actions_total Actions taken by actors, by kind.\n");
+        out.push_str("# TYPE chott_actor_actions_total counter\n");
+        for (kind, count) in actors.actions_taken() {
+            let _ = writeln!(out, "chott_actor_actions_total{{kind=\"{kind}\"}} {count}");
+        }
+    }
+
+    if instances.mode() == InstanceMode::Solo {
+        out.push_str("# HELP chott_solo_instances Players' own instances of the world open now.\n");
+        out.push_str("# TYPE chott_solo_instances gauge\n");
+        let _ = writeln!(out, "chott_solo_instances {}", instances.open());
+    }
+
+    if let Some(count) = sessions.stored_sessions() {
+        out.push_str("# HELP chott_sessions Sessions held by the server-side store.\n");
+        out.push_str("# TYPE chott_sessions gauge\n");
+        let _ = writeln!(out, "chott_sessions {count}");
+    }
+
+    let (hits, misses) = environment.cache_stats();
+    out.push_str(
+        "# HELP chott_environment_cache_hits_total Environment lookups served from cache.\n",
+    );
+    out.push_str("# TYPE chott_environment_cache_hits_total counter\n");
+    let _ = writeln!(out, "chott_environment_cache_hits_total {hits}");
+    out.push_str(
+        "# HELP chott_environment_cache_misses_total Environment lookups that generated anew.\n",
+    );
+    out.push_str("# TYPE chott_environment_cache_misses_total counter\n");
+    let _ = writeln!(out, "chott_environment_cache_misses_total {misses}");
+
+    out.push_str("# HELP chott_events_total World events published, by kind.\n");
+    out.push_str("# TYPE chott_events_total counter\n");
+    for (kind, count) in events.snapshot() {
+        let _ = writeln!(out, "chott_events_total{{kind=\"{kind}\"}} {count}");
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(out)
+}
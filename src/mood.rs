@@ -0,0 +1,75 @@
+//! Moods: how an actor feels, which colours what it does and how it looks.
+//! Being set upon frightens most actors and angers fighters, seeing someone
+//! die frightens anyone but a predator, and a good night's sleep settles
+//! them again; otherwise a mood wears off on its own after a while.
+
+use serde::{Deserialize, Serialize};
+
+use crate::actor::{Actor, ActorFlag, ActorState};
+use crate::events::WorldEvent;
+use crate::pages::PageId;
+
+/// Ticks a mood lasts, unless something changes it sooner
+pub const MOOD_TICKS: u32 = 80;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mood {
+    #[default]
+    Content,
+    Scared, // keeps away from trouble, and won't stand and fight
+    Angry,  // goes after whoever it has a grudge against
+}
+
+impl Mood {
+    /// Key under `inspect` in the translations; nothing to say when content
+    pub fn key(self) -> Option<&'static str> {
+        match self {
+            Mood::Content => None,
+            Mood::Scared => Some("inspect.frightened"),
+            Mood::Angry => Some("inspect.angry"),
+        }
+    }
+}
+
+/// Where `event` happened, if it's the sort of thing that stirs feelings
+pub fn stirred_at(event: &WorldEvent) -> Option<&PageId> {
+    match event {
+        WorldEvent::ActorWounded { page, .. }
+        | WorldEvent::ActorDevoured { page, .. }
+        | WorldEvent::ActorStarved { page, .. } => Some(page),
+        _ => None,
+    }
+}
+
+/// What `actor`, on the page where `event` happened, comes to feel about it
+pub fn felt(actor: &Actor, event: &WorldEvent) -> Option<Mood> {
+    let fighter = actor.has_flag(ActorFlag::CanAttack) || actor.has_flag(ActorFlag::Predatory);
+    match event {
+        WorldEvent::ActorWounded { actor: hurt, .. } if *hurt == actor.id => {
+            Some(if fighter { Mood::Angry } else { Mood::Scared })
+        }
+        // a death, seen
+        WorldEvent::ActorWounded { health, .. } if *health > 0 => None,
+        WorldEvent::ActorWounded { .. }
+        | WorldEvent::ActorDevoured { .. }
+        | WorldEvent::ActorStarved { .. } => (actor.state.awake
+            && actor.has_flag(ActorFlag::Organic)
+            && !actor.has_flag(ActorFlag::Predatory))
+        .then_some(Mood::Scared),
+        _ => None,
+    }
+}
+
+/// Come to feel `mood`, for a while
+pub fn feel(state: &mut ActorState, mood: Mood) {
+    state.mood = mood;
+    state.mood_ticks = MOOD_TICKS;
+}
+
+/// One tick's wearing off of whatever the actor feels
+pub fn settle(state: &mut ActorState) {
+    state.mood_ticks = state.mood_ticks.saturating_sub(1);
+    if state.mood_ticks == 0 {
+        state.mood = Mood::Content;
+    }
+}
@@ -0,0 +1,75 @@
+//! Getting about on more than your own two feet: vehicles players carry and
+//! rideable companions. Some ways can only be travelled by one (a boat
+//! across the water), and a mount takes a player further before they tire
+//! and shoulders some of their load. A player carrying more than they can
+//! manage tires twice as fast and can't squeeze along tight ways.
+
+use serde::{Deserialize, Serialize};
+
+use crate::actor::{Actor, ActorFlag};
+use crate::items::{self, ItemCatalog};
+use crate::session::UserSession;
+
+/// How many times as many moves a rider makes before tiring as on foot
+pub const RIDING_STAMINA: usize = 2;
+
+/// Weight a mount carries for its rider
+pub const MOUNT_CAPACITY: u32 = 20;
+
+/// A way of getting about that some connections need
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conveyance {
+    Boat,  // over water
+    Mount, // a rideable companion
+}
+
+/// How a player is getting about
+#[derive(Debug, Default, Serialize)]
+pub struct Means {
+    pub conveyances: Vec<Conveyance>, // to hand
+    pub load: u32,                    // weight of what they carry
+    pub capacity: u32,                // most they carry without tiring for it
+}
+
+impl Means {
+    /// What the player has to hand: any vehicles they carry, and a mount if
+    /// a rideable companion of theirs is awake on their page
+    pub fn of<'a>(
+        player: &UserSession,
+        catalog: &ItemCatalog,
+        companions: impl IntoIterator<Item = &'a Actor>,
+    ) -> Self {
+        let mut conveyances: Vec<Conveyance> = player
+            .inventory
+            .iter()
+            .filter_map(|id| catalog.get(id)?.conveys)
+            .collect();
+        if companions.into_iter().any(|a| {
+            a.has_flag(ActorFlag::Rideable)
+                && a.state.awake
+                && a.travel.is_none()
+                && a.location == player.current_page
+        }) {
+            conveyances.push(Conveyance::Mount);
+        }
+        let mut capacity = player.capacity();
+        if conveyances.contains(&Conveyance::Mount) {
+            capacity += MOUNT_CAPACITY;
+        }
+        Means {
+            conveyances,
+            load: items::weight_of(&player.inventory, catalog),
+            capacity,
+        }
+    }
+
+    pub fn riding(&self) -> bool {
+        self.conveyances.contains(&Conveyance::Mount)
+    }
+
+    /// Carrying more than they can manage
+    pub fn encumbered(&self) -> bool {
+        self.load > self.capacity
+    }
+}
@@ -0,0 +1,88 @@
+//! Decision overlays: one actor's (or one archetype's) adjustments to the
+//! built-in behaviors, from the definitions file. An overlay can move the
+//! thresholds the behaviors go by, turn some of them off altogether, or
+//! give the actor routines of its own to pick from when it has nothing
+//! better to do. An actor's overlay is laid over its archetypes' a setting
+//! at a time, so it need only give what it changes.
+
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::pages::PageId;
+
+/// How much the built-in wandering counts for against an overlay's extra
+/// routines, when an actor with nothing better to do picks one
+pub const BUILT_IN_WEIGHT: u32 = 10;
+
+/// A built-in behavior an overlay can turn off
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Behavior {
+    Grudges,   // striking back at, or getting away from, whoever hurt it
+    Following, // keeping to a player's side
+    Migrating,
+    Hunting,
+    Tracking,   // following the scent of prey
+    Guarding,   // setting on strangers at home, if territorial
+    Players,    // slipping away from players, if shy, or going to see them
+    Heeding,    // noises and scents from round about
+    Sheltering, // from wet or dangerous weather
+    Conversing,
+    Wandering,
+    GoingHome, // to sleep
+}
+
+/// Something an actor with nothing better to do can take to doing
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Routine {
+    Idle,          // stays where it is
+    Nap,           // dozes off where it is
+    GoHome,        // takes a step toward home
+    Visit(PageId), // takes a step toward somewhere it likes to go
+}
+
+/// A routine, and how often it's picked
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Extra {
+    pub weight: u32,
+    pub does: Routine,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DecisionOverlay {
+    #[serde(default)]
+    pub sleep_fatigue: Option<u8>, // fatigue it turns in at
+    #[serde(default)]
+    pub move_odds: Option<u8>, // one in this many idle turns it wanders off
+    #[serde(default)]
+    pub converse_odds: Option<u8>, // one in this many, it strikes up a conversation
+    #[serde(default)]
+    pub disable: Vec<Behavior>,
+    #[serde(default)]
+    pub extra: Vec<Extra>,
+}
+
+impl DecisionOverlay {
+    /// Whether `behavior` is left on
+    pub fn allows(&self, behavior: Behavior) -> bool {
+        !self.disable.contains(&behavior)
+    }
+
+    /// Which of its extra routines the actor takes to this turn, if any;
+    /// None leaves it to the built-in wandering, if that's allowed
+    pub fn pick(&self) -> Option<&Routine> {
+        if self.extra.is_empty() {
+            return None;
+        }
+        let built_in = self.allows(Behavior::Wandering).then_some(None);
+        let options: Vec<Option<&Extra>> = built_in
+            .into_iter()
+            .chain(self.extra.iter().map(Some))
+            .collect();
+        let picked = options
+            .choose_weighted(&mut rand::rng(), |extra| {
+                extra.map_or(BUILT_IN_WEIGHT, |e| e.weight)
+            })
+            .ok()?;
+        picked.map(|extra| &extra.does)
+    }
+}
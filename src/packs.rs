@@ -0,0 +1,285 @@
+//! Packs: actors that run together. Members share a pack name, and the
+//! first of them by id leads: the rest go where it goes and set on whatever
+//! it sets on, hunting as one, and a blow to any of them is taken up by
+//! them all, so they stand or flee as one too. Packs are gathered afresh
+//! every tick from who is in them, so when a leader goes the next one
+//! takes over.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::actor::{ActorAction, ActorFlag, ActorId, ActorMap};
+use crate::ecology;
+use crate::events::WorldEvent;
+use crate::pages::PageGraph;
+
+/// The packs among the actors, as of the start of a tick
+#[derive(Debug, Default)]
+pub struct Packs {
+    members: BTreeMap<String, Vec<ActorId>>, // pack -> members by id, leader first
+    of: HashMap<ActorId, String>,            // member -> its pack
+}
+
+impl Packs {
+    /// Gather the packs out of `actors`; one left on its own is no pack
+    pub fn gather(actors: &ActorMap) -> Self {
+        let mut members: BTreeMap<String, Vec<ActorId>> = BTreeMap::new();
+        for actor in actors.values().filter(|a| !a.has_flag(ActorFlag::Player)) {
+            if let Some(pack) = &actor.pack {
+                members
+                    .entry(pack.clone())
+                    .or_default()
+                    .push(actor.id.clone());
+            }
+        }
+        members.retain(|_, ids| ids.len() > 1);
+        let mut of = HashMap::new();
+        for (pack, ids) in &mut members {
+            ids.sort();
+            of.extend(ids.iter().map(|id| (id.clone(), pack.clone())));
+        }
+        Packs { members, of }
+    }
+
+    /// Who leads `id`'s pack, which may be `id` itself
+    pub fn leader_of(&self, id: &ActorId) -> Option<&ActorId> {
+        self.of.get(id).and_then(|pack| self.members[pack].first())
+    }
+
+    /// Whether `a` and `b` run in the same pack
+    pub fn together(&self, a: &ActorId, b: &ActorId) -> bool {
+        self.of
+            .get(a)
+            .is_some_and(|pack| self.of.get(b) == Some(pack))
+    }
+
+    /// The rest of `id`'s pack
+    pub fn mates_of<'a>(&'a self, id: &'a ActorId) -> impl Iterator<Item = &'a ActorId> {
+        self.of
+            .get(id)
+            .into_iter()
+            .flat_map(|pack| &self.members[pack])
+            .filter(move |mate| *mate != id)
+    }
+}
+
+/// Bring this tick's plans into line with each pack's leader. Members that
+/// planned to wander off stay with it instead, or make for it if they've
+/// lost it; members with a leader that planned a move or an attack take
+/// that up in place of their own plans, whether it's their turn or not.
+pub fn rally(
+    packs: &Packs,
+    actors: &mut ActorMap,
+    plans: &mut Vec<(ActorId, Vec<ActorAction>)>,
+    page_graph: &PageGraph,
+) {
+    for (id, plan) in plans.iter_mut() {
+        let Some(leader) = packs
+            .leader_of(id)
+            .filter(|leader| *leader != id)
+            .and_then(|leader| actors.get(leader))
+        else {
+            continue;
+        };
+        let Some(member) = actors.get(id) else {
+            continue;
+        };
+        plan.retain(|action| !matches!(action, ActorAction::MoveTo(_)));
+        // the leader is where it's headed, if it's already on its way
+        let at = leader.travel.as_ref().map_or(&leader.location, |t| &t.to);
+        if *at != member.location
+            && let Some(step) = member
+                .route(|p| p == at, page_graph)
+                .and_then(|route| route.into_iter().next())
+        {
+            plan.push(ActorAction::MoveTo(step));
+        }
+    }
+
+    let led: Vec<(ActorId, Vec<ActorAction>)> = plans
+        .iter()
+        .filter(|(id, _)| packs.leader_of(id) == Some(id))
+        .map(|(id, plan)| {
+            let lead = plan
+                .iter()
+                .filter(|a| matches!(a, ActorAction::MoveTo(_) | ActorAction::Attack(_)))
+                .cloned()
+                .collect::<Vec<_>>();
+            (id.clone(), lead)
+        })
+        .filter(|(_, lead)| !lead.is_empty())
+        .collect();
+    for (leader, lead) in &led {
+        let Some(at) = actors.get(leader).map(|l| l.location.clone()) else {
+            continue;
+        };
+        for mate in packs.mates_of(leader) {
+            let Some(member) = actors
+                .get_mut(mate)
+                .filter(|m| m.location == at && m.travel.is_none() && m.state.awake && !m.paused)
+            else {
+                continue;
+            };
+            member.queue.clear();
+            match plans.iter_mut().find(|(id, _)| id == mate) {
+                Some((_, plan)) => plan.clone_from(lead),
+                None => plans.push((mate.clone(), lead.clone())),
+            }
+        }
+    }
+}
+
+/// A blow to one of a pack is a blow to all of it: every mate about that
+/// isn't already set on someone takes against whoever struck it
+pub fn take_up(packs: &Packs, actors: &mut ActorMap, blow: &WorldEvent) {
+    let WorldEvent::ActorWounded {
+        actor: struck,
+        attacker,
+        page,
+        ..
+    } = blow
+    else {
+        return;
+    };
+    if packs.together(struck, attacker) {
+        return;
+    }
+    for mate in packs.mates_of(struck) {
+        if let Some(mate) = actors
+            .get_mut(mate)
+            .filter(|m| m.location == *page && m.state.target.is_none())
+        {
+            mate.state.target = Some(attacker.clone());
+        }
+    }
+}
+
+/// A kill is shared out: every mate with `hunter` when it eats eats too
+pub fn share_kill(packs: &Packs, actors: &mut ActorMap, hunter: &ActorId) {
+    let Some(at) = actors.get(hunter).map(|h| h.location.clone()) else {
+        return;
+    };
+    for mate in packs.mates_of(hunter) {
+        if let Some(mate) = actors.get_mut(mate).filter(|m| m.location == at) {
+            ecology::devour(mate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use crate::actor::tests::{critters, ring};
+    use crate::pages::PageId;
+
+    fn id(n: usize) -> ActorId {
+        ActorId::from(format!("critter-{n}").as_str())
+    }
+
+    fn page(n: usize) -> PageId {
+        PageId::from(format!("ring-{n}"))
+    }
+
+    /// Critters 0 to 2 running as "wolves" on the first page of the ring,
+    /// led by critter-0, with critter-3 about on its own
+    fn wolves() -> ActorMap {
+        critters(4)
+            .into_iter()
+            .enumerate()
+            .map(|(n, mut definition)| {
+                definition.location = page(0);
+                definition.pack = (n < 3).then(|| "wolves".to_string());
+                let actor = Actor::from_definition(definition);
+                (actor.id.clone(), actor)
+            })
+            .collect()
+    }
+
+    fn plan_of(plans: &[(ActorId, Vec<ActorAction>)], n: usize) -> &[ActorAction] {
+        plans
+            .iter()
+            .find(|(who, _)| *who == id(n))
+            .map_or(&[], |(_, plan)| plan)
+    }
+
+    #[test]
+    fn the_first_by_id_leads_and_one_alone_is_no_pack() {
+        let packs = Packs::gather(&wolves());
+        assert_eq!(packs.leader_of(&id(2)), Some(&id(0)));
+        assert!(packs.together(&id(1), &id(2)));
+        assert_eq!(packs.leader_of(&id(3)), None);
+        assert!(!packs.together(&id(0), &id(3)));
+    }
+
+    #[test]
+    fn followers_go_where_the_leader_goes() {
+        let mut actors = wolves();
+        let packs = Packs::gather(&actors);
+        // critter-1 meant to wander off; critter-2 wasn't due at all
+        let mut plans = vec![
+            (id(0), vec![ActorAction::MoveTo(page(1))]),
+            (id(1), vec![ActorAction::MoveTo(page(5))]),
+            (id(3), vec![ActorAction::MoveTo(page(5))]),
+        ];
+        rally(&packs, &mut actors, &mut plans, &ring());
+        for n in [1, 2] {
+            assert_eq!(plan_of(&plans, n), [ActorAction::MoveTo(page(1))]);
+        }
+        assert_eq!(plan_of(&plans, 3), [ActorAction::MoveTo(page(5))]);
+    }
+
+    #[test]
+    fn one_that_lost_the_leader_makes_for_it() {
+        let mut actors = wolves();
+        actors.get_mut(&id(0)).unwrap().location = page(2);
+        let packs = Packs::gather(&actors);
+        let mut plans = vec![(id(1), vec![ActorAction::MoveTo(page(5))])];
+        rally(&packs, &mut actors, &mut plans, &ring());
+        assert_eq!(plan_of(&plans, 1), [ActorAction::MoveTo(page(1))]);
+    }
+
+    #[test]
+    fn the_pack_hunts_what_the_leader_hunts_and_flees_when_it_flees() {
+        let mut actors = wolves();
+        let packs = Packs::gather(&actors);
+        let prey = id(3);
+        let mut plans = vec![(id(0), vec![ActorAction::Attack(prey.clone())])];
+        rally(&packs, &mut actors, &mut plans, &ring());
+        for n in [1, 2] {
+            assert_eq!(plan_of(&plans, n), [ActorAction::Attack(prey.clone())]);
+        }
+
+        // the leader turns tail while the rest are still set on the fight
+        let mut plans = vec![
+            (id(0), vec![ActorAction::MoveTo(page(5))]),
+            (id(1), vec![ActorAction::Attack(prey.clone())]),
+            (id(2), vec![ActorAction::Attack(prey)]),
+        ];
+        rally(&packs, &mut actors, &mut plans, &ring());
+        for n in [1, 2] {
+            assert_eq!(plan_of(&plans, n), [ActorAction::MoveTo(page(5))]);
+        }
+    }
+
+    #[test]
+    fn a_blow_to_one_is_taken_up_by_the_rest() {
+        let mut actors = wolves();
+        let packs = Packs::gather(&actors);
+        let blow = WorldEvent::ActorWounded {
+            actor: id(1),
+            attacker: id(3),
+            page: page(0),
+            damage: 2,
+            health: 8,
+        };
+        take_up(&packs, &mut actors, &blow);
+        for n in [0, 2] {
+            assert_eq!(actors[&id(n)].state.target.as_ref(), Some(&id(3)));
+        }
+        assert_eq!(
+            actors[&id(1)].state.target,
+            None,
+            "the struck one stays as it was"
+        );
+    }
+}
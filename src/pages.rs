@@ -1,5 +1,25 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tera::{Context, Tera};
+use tracing::error;
+
+use crate::conditions::{Condition, ConditionContext};
+use crate::environment::{PartOfDay, WorldTime};
+use crate::error::AppError;
+use crate::fixtures::{Fixture, FixtureAction, FixtureEffect};
+use crate::items::{ItemCatalog, ItemId};
+use crate::mounts::{Conveyance, Means};
+use crate::perception::Sense;
+use crate::regions::RegionId;
+use crate::shops::{Shop, ShopItem};
+use crate::weather::WeatherKind;
+
+/// Generic template used for pages whose own template failed to load
+pub const DEFAULT_TEMPLATE: &str = "page.html";
+
+/// Template for players who asked for text-only pages, whatever the page's own
+pub const TEXT_TEMPLATE: &str = "text.html";
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Page {
@@ -9,14 +29,212 @@ pub struct Page {
     pub title: String,
     pub description: String,
     pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub variants: Vec<DescriptionVariant>, // checked in order, first match wins
+    // shorthand variants by weather or time of day, checked after `variants`:
+    // `description_night`, `description_rain`, `template_dawn` and so on
+    #[serde(default, flatten)]
+    pub alternates: BTreeMap<String, String>,
+    #[serde(default)]
+    pub lighting: Lighting,
+    #[serde(default)]
+    pub biome: Biome,
+    #[serde(default)]
+    pub region: Option<RegionId>,
+    #[serde(default)]
+    pub items: Vec<ItemId>, // items lying here for players to take
+    #[serde(default)]
+    pub shop: Option<Shop>,
+    #[serde(default)]
+    pub fixtures: Vec<Fixture>, // things on the page players can use
+}
+
+impl Page {
+    /// Whether this page is dark at `world_time`, before counting light sources
+    pub fn is_dark(&self, world_time: &WorldTime) -> bool {
+        if self.biome == Biome::Cave {
+            return true; // no daylight reaches underground, whatever `lighting` says
+        }
+        match self.lighting {
+            Lighting::Lit => false,
+            Lighting::DarkAtNight => world_time.is_night(),
+            Lighting::AlwaysDark => true,
+        }
+    }
+
+    /// Whether it is dark here only for want of daylight
+    pub fn is_night_dark(&self, world_time: &WorldTime) -> bool {
+        self.biome != Biome::Cave && self.lighting == Lighting::DarkAtNight && world_time.is_night()
+    }
+
+    /// Whether there is cover from the weather here (`shelter` metadata)
+    pub fn is_sheltered(&self) -> bool {
+        self.metadata.get("shelter").is_some_and(|v| v == "true")
+    }
+
+    /// Whether this is wild country, where players may run into things (`wild` metadata)
+    pub fn is_wild(&self) -> bool {
+        self.metadata.get("wild").is_some_and(|v| v == "true")
+    }
+
+    /// Whether something lying on this page gives off light
+    pub fn has_lit_object(&self, catalog: &ItemCatalog) -> bool {
+        self.items
+            .iter()
+            .filter_map(|id| catalog.get(id))
+            .any(|item| item.light_source)
+    }
+
+    /// Whether a visitor sees darkness here, given whether they carry a light
+    pub fn is_dark_for(
+        &self,
+        world_time: &WorldTime,
+        carries_light: bool,
+        catalog: &ItemCatalog,
+    ) -> bool {
+        self.is_dark(world_time) && !carries_light && !self.has_lit_object(catalog)
+    }
+
+    /// Pick the description this player should see, falling back to the plain one
+    pub fn description_for(&self, ctx: &ConditionContext) -> &str {
+        self.variants
+            .iter()
+            .filter_map(|v| v.text.as_deref().filter(|_| v.when.holds(ctx)))
+            .next()
+            .or_else(|| self.alternate("description", ctx))
+            .unwrap_or(&self.description)
+    }
+
+    /// Pick the template to draw the page with for this player
+    pub fn template_for(&self, ctx: &ConditionContext) -> &str {
+        self.variants
+            .iter()
+            .filter_map(|v| v.template.as_deref().filter(|_| v.when.holds(ctx)))
+            .next()
+            .or_else(|| self.alternate("template", ctx))
+            .unwrap_or(&self.template)
+    }
+
+    /// The `<field>_<band>` shorthand that applies now, if any. Weather
+    /// outranks the part of the day, which outranks plain day or night.
+    fn alternate(&self, field: &str, ctx: &ConditionContext) -> Option<&str> {
+        self.alternates
+            .iter()
+            .filter_map(|(key, value)| {
+                let band = key.strip_prefix(field)?.strip_prefix('_')?;
+                let (rank, when) = band_condition(band)?;
+                when.holds(ctx).then_some((rank, value.as_str()))
+            })
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, value)| value)
+    }
+}
+
+/// What a shorthand band like "night" or "rain" stands for, ranked by how
+/// specific it is (lowest first): a kind of weather, a part of the day, or
+/// plain day or night
+fn band_condition(band: &str) -> Option<(u8, Condition)> {
+    let weather = match band {
+        "clear" => Some(WeatherKind::Clear),
+        "cloudy" | "clouds" => Some(WeatherKind::Cloudy),
+        "rain" | "rainy" => Some(WeatherKind::Rainy),
+        "wind" | "windy" => Some(WeatherKind::Windy),
+        "fog" | "foggy" => Some(WeatherKind::Foggy),
+        "storm" | "stormy" => Some(WeatherKind::Stormy),
+        "snow" | "snowy" => Some(WeatherKind::Snowy),
+        _ => None,
+    };
+    if let Some(weather) = weather {
+        return Some((0, Condition::Weather(weather)));
+    }
+    match band {
+        "day" => return Some((2, Condition::Day)),
+        "night" => return Some((2, Condition::Night)),
+        _ => {}
+    }
+    let part = [
+        PartOfDay::DeepNight,
+        PartOfDay::Dawn,
+        PartOfDay::Morning,
+        PartOfDay::Midday,
+        PartOfDay::Afternoon,
+        PartOfDay::EarlyEvening,
+    ]
+    .into_iter()
+    .find(|part| part.key() == band)?;
+    Some((1, Condition::TimeOfDay(part)))
+}
+
+/// Ambient light on a page; dark pages hide their details without a light source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lighting {
+    #[default]
+    Lit,
+    DarkAtNight,
+    AlwaysDark, // caves, cellars
+}
+
+/// Lie of the land on a page; shapes the weather it gets
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Biome {
+    #[default]
+    Plains,
+    Forest,
+    Coastal,
+    Cave, // no weather and no daylight
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct PageId(pub String);
+impl Biome {
+    /// A line of scenery for this biome in `weather`, for templates
+    pub fn ambience(self, weather: WeatherKind) -> Option<&'static str> {
+        use WeatherKind::*;
+        match (self, weather) {
+            (Biome::Cave, _) => Some("The air down here is still and cool."),
+            (Biome::Coastal, Windy) => Some("Salt spray whips in off the sea."),
+            (Biome::Coastal, Stormy) => Some("Waves crash against the rocks below."),
+            (Biome::Coastal, Foggy) => Some("A sea mist rolls in, muffling the gulls."),
+            (Biome::Forest, Rainy) => Some("Rain patters on the leaves high above."),
+            (Biome::Forest, Foggy) => Some("Fog pools between the trunks."),
+            (Biome::Forest, Windy) => Some("The canopy sways and creaks overhead."),
+            _ => None,
+        }
+    }
+}
 
+/// Alternate description and/or template, used instead of the page's own when `when` holds
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DescriptionVariant {
+    pub when: Condition,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>, // e.g. a night scene
+}
+
+/// Identifies a page. Shared like `ActorId`, since every move and plan
+/// copies one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PageId(Arc<str>);
+
+impl PageId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
 impl From<&str> for PageId {
     fn from(s: &str) -> Self {
-        PageId(s.to_owned())
+        PageId(s.into())
+    }
+}
+impl From<String> for PageId {
+    fn from(s: String) -> Self {
+        PageId(s.into())
+    }
+}
+// lets maps keyed by PageId be looked up with a plain &str
+impl std::borrow::Borrow<str> for PageId {
+    fn borrow(&self) -> &str {
+        &self.0
     }
 }
 impl std::fmt::Display for PageId {
@@ -29,6 +247,57 @@ impl std::fmt::Display for PageId {
 pub struct PageConnection {
     pub name: String,   // e.g., "north", "down", "to city gate", "forwards"
     pub target: PageId, // page id (slug) you go to if you click this
+    // the way is only open while this holds (locked doors, bridges out at night)
+    #[serde(default)]
+    pub requires: Condition,
+    // hidden entirely while closed, rather than shown as locked
+    #[serde(default)]
+    pub secret: bool,
+    // shown on a closed, non-secret exit
+    #[serde(default)]
+    pub locked_text: Option<String>,
+    // ticks it takes an actor to get along (1 = next tick)
+    #[serde(default = "default_distance")]
+    pub distance: u32,
+    // perception a player needs to notice the way at all (0 = anyone)
+    #[serde(default)]
+    pub hidden: u8,
+    // only travelled by this (a boat across water); NPCs keep off it
+    #[serde(default)]
+    pub needs: Option<Conveyance>,
+    // too tight or steep to manage over-encumbered
+    #[serde(default)]
+    pub tight: bool,
+    // senses that don't carry along it, for actors making out what's beyond
+    #[serde(default)]
+    pub blocks: Vec<Sense>,
+}
+
+fn default_distance() -> u32 {
+    1
+}
+
+impl PageConnection {
+    pub fn is_open(&self, ctx: &ConditionContext) -> bool {
+        self.requires.holds(ctx)
+    }
+
+    /// Whether a player getting about by `means` can get along it
+    pub fn passable(&self, means: &Means) -> bool {
+        self.needs
+            .is_none_or(|needs| means.conveyances.contains(&needs))
+            && !(self.tight && means.encumbered())
+    }
+}
+
+/// An exit as the player sees it
+#[derive(Serialize)]
+pub struct ExitView<'a> {
+    pub name: &'a str,
+    pub target: &'a PageId,
+    pub open: bool,
+    pub locked_text: Option<&'a str>,
+    pub explored: bool, // the player has been where it leads
 }
 
 // PageGraph is a HashMap keyed by id
@@ -45,10 +314,63 @@ pub fn load_page_graph() -> PageGraph {
             connections: vec![PageConnection {
                 name: "North".to_string(),
                 target: PageId::from("route-1"),
+                requires: Condition::Always,
+                secret: false,
+                locked_text: None,
+                distance: 1,
+                hidden: 0,
+                needs: None,
+                tight: false,
+                blocks: Vec::new(),
             }],
             title: "Small Town".to_string(),
             description: "A quiet, peaceful town.".to_string(),
-            metadata: HashMap::new(),
+            metadata: HashMap::from([("shelter".to_string(), "true".to_string())]),
+            alternates: BTreeMap::new(),
+            variants: vec![DescriptionVariant {
+                when: Condition::FirstVisit,
+                text: Some(
+                    "You wake up in a quiet, peaceful town. Something about today feels new."
+                        .to_string(),
+                ),
+                template: None,
+            }],
+            lighting: Lighting::Lit, // street lamps
+            biome: Biome::Plains,
+            region: Some(RegionId::from("kanto-ish")),
+            items: vec![ItemId::from("lantern")],
+            shop: None,
+            fixtures: vec![
+                Fixture {
+                    id: "sign".to_string(),
+                    name: "Town Sign".to_string(),
+                    description: "A weathered wooden signpost by the road north.".to_string(),
+                    actions: vec![FixtureAction {
+                        verb: "read".to_string(),
+                        text: "\"SMALL TOWN. Route 1 north. Mind the tall grass.\"".to_string(),
+                        requires: Condition::Always,
+                        refused: None,
+                        effects: Vec::new(),
+                    }],
+                },
+                Fixture {
+                    id: "lamp".to_string(),
+                    name: "Street Lamp".to_string(),
+                    description: "An old oil lamp on an iron post, tended by the lamplighter."
+                        .to_string(),
+                    actions: vec![FixtureAction {
+                        verb: "light".to_string(),
+                        text: "You reach up and light the lamp. It burns with a warm glow."
+                            .to_string(),
+                        requires: Condition::Not(Box::new(Condition::PageFlag {
+                            flag: "lamp_lit".to_string(),
+                            page: None,
+                        })),
+                        refused: Some("The lamp is already lit.".to_string()),
+                        effects: vec![FixtureEffect::Script("lamplighter::light".to_string())],
+                    }],
+                },
+            ],
         },
     );
 
@@ -61,15 +383,78 @@ pub fn load_page_graph() -> PageGraph {
                 PageConnection {
                     name: "North".to_string(),
                     target: PageId::from("green-city"),
+                    requires: Condition::Always,
+                    secret: false,
+                    locked_text: None,
+                    distance: 3, // the long road north
+                    hidden: 0,
+                    needs: None,
+                    tight: false,
+                    blocks: Vec::new(),
                 },
                 PageConnection {
                     name: "South".to_string(),
                     target: PageId::from("small-town"),
+                    requires: Condition::Always,
+                    secret: false,
+                    locked_text: None,
+                    distance: 1,
+                    hidden: 0,
+                    needs: None,
+                    tight: false,
+                    blocks: Vec::new(),
+                },
+                PageConnection {
+                    name: "West".to_string(),
+                    target: PageId::from("dark-cave"),
+                    requires: Condition::Day,
+                    secret: false,
+                    locked_text: Some(
+                        "The cliff path down to the cave is too treacherous after dark."
+                            .to_string(),
+                    ),
+                    distance: 1,
+                    hidden: 0,
+                    needs: None,
+                    tight: false,
+                    blocks: Vec::new(),
+                },
+                PageConnection {
+                    name: "Paddle out to the rock".to_string(),
+                    target: PageId::from("gull-rock"),
+                    requires: Condition::Always,
+                    secret: false,
+                    locked_text: Some(
+                        "A rock stands out in the bay, too far to swim. You'd need a boat."
+                            .to_string(),
+                    ),
+                    distance: 2,
+                    hidden: 0,
+                    needs: Some(Conveyance::Boat),
+                    tight: false,
+                    blocks: vec![Sense::Smell], // too far across the water
                 },
             ],
             title: "Route 1".to_string(),
-            description: "A winding route with tall grass and wild things.".to_string(),
-            metadata: HashMap::new(),
+            description: "A winding route along the sea cliffs, with tall grass and wild things."
+                .to_string(),
+            metadata: HashMap::from([("wild".to_string(), "true".to_string())]), // the tall grass
+            alternates: BTreeMap::new(),
+            variants: vec![DescriptionVariant {
+                when: Condition::FirstVisit,
+                text: Some(
+                    "The town gives way to a winding route. The tall grass rustles; \
+                    something wild is watching you."
+                        .to_string(),
+                ),
+                template: None,
+            }],
+            lighting: Lighting::DarkAtNight,
+            biome: Biome::Coastal,
+            region: Some(RegionId::from("kanto-ish")),
+            items: vec![ItemId::from("stick"), ItemId::from("apple")],
+            shop: None,
+            fixtures: Vec::new(),
         },
     );
 
@@ -81,25 +466,524 @@ pub fn load_page_graph() -> PageGraph {
             connections: vec![PageConnection {
                 name: "South".to_string(),
                 target: PageId::from("route-1"),
+                requires: Condition::Always,
+                secret: false,
+                locked_text: None,
+                distance: 3,
+                hidden: 0,
+                needs: None,
+                tight: false,
+                blocks: Vec::new(),
             }],
             title: "Green City".to_string(),
             description: "A bustling city under the old trees.".to_string(),
+            metadata: HashMap::from([("shelter".to_string(), "true".to_string())]),
+            alternates: BTreeMap::from([(
+                "description_night".to_string(),
+                "Lanterns glow between the old trees. The city's bustle has \
+                    settled to a murmur behind shuttered windows."
+                    .to_string(),
+            )]),
+            variants: vec![
+                DescriptionVariant {
+                    when: Condition::Flag("parcel_delivered".to_string()),
+                    text: Some(
+                        "A bustling city under the old trees. Word of your errand has \
+                        reached the townsfolk, who nod as you pass."
+                            .to_string(),
+                    ),
+                    template: None,
+                },
+                DescriptionVariant {
+                    when: Condition::FirstVisit,
+                    text: Some(
+                        "The old trees part to reveal a bustling city, bigger than any \
+                        place you have seen."
+                            .to_string(),
+                    ),
+                    template: None,
+                },
+            ],
+            lighting: Lighting::Lit,
+            biome: Biome::Forest,
+            region: Some(RegionId::from("kanto-ish")),
+            items: vec![ItemId::from("old-map")],
+            shop: Some(Shop {
+                merchant: Some("susan".into()),
+                stock: vec![
+                    ShopItem {
+                        item: ItemId::from("lantern"),
+                        price: 15,
+                        max_stock: 2,
+                    },
+                    ShopItem {
+                        item: ItemId::from("oily-rag"),
+                        price: 3,
+                        max_stock: 5,
+                    },
+                    ShopItem {
+                        item: ItemId::from("pebble"),
+                        price: 1,
+                        max_stock: 10,
+                    },
+                    ShopItem {
+                        item: ItemId::from("canoe"),
+                        price: 18,
+                        max_stock: 1,
+                    },
+                    ShopItem {
+                        item: ItemId::from("salve"),
+                        price: 8,
+                        max_stock: 3,
+                    },
+                    ShopItem {
+                        item: ItemId::from("flask-of-tea"),
+                        price: 4,
+                        max_stock: 4,
+                    },
+                ],
+                restock_every: 60,
+            }),
+            fixtures: Vec::new(),
+        },
+    );
+
+    graph.insert(
+        PageId::from("gull-rock"),
+        Page {
+            id: PageId::from("gull-rock"),
+            template: "gull-rock.html".to_string(),
+            connections: vec![PageConnection {
+                name: "Paddle back to shore".to_string(),
+                target: PageId::from("route-1"),
+                requires: Condition::Always,
+                secret: false,
+                locked_text: Some("It's a long swim back. You'd need a boat.".to_string()),
+                distance: 2,
+                hidden: 0,
+                needs: Some(Conveyance::Boat),
+                tight: false,
+                blocks: vec![Sense::Smell],
+            }],
+            title: "Gull Rock".to_string(),
+            description: "A stack of bare rock out in the bay, white with gulls. The cliffs \
+                of Route 1 look small from here."
+                .to_string(),
             metadata: HashMap::new(),
+            alternates: BTreeMap::new(),
+            variants: Vec::new(),
+            lighting: Lighting::DarkAtNight,
+            biome: Biome::Coastal,
+            region: Some(RegionId::from("kanto-ish")),
+            items: vec![ItemId::from("pebble"), ItemId::from("four-leaf-clover")],
+            shop: None,
+            fixtures: Vec::new(),
+        },
+    );
+
+    graph.insert(
+        PageId::from("dark-cave"),
+        Page {
+            id: PageId::from("dark-cave"),
+            template: "dark-cave.html".to_string(),
+            connections: vec![
+                PageConnection {
+                    name: "East".to_string(),
+                    target: PageId::from("route-1"),
+                    requires: Condition::Always,
+                    secret: false,
+                    locked_text: None,
+                    distance: 1,
+                    hidden: 0,
+                    needs: None,
+                    tight: false,
+                    blocks: Vec::new(),
+                },
+                PageConnection {
+                    // a crawlway you only spot by lantern light
+                    name: "Crawl through the gap".to_string(),
+                    target: PageId::from("small-town"),
+                    requires: Condition::HasItem(ItemId::from("lantern")),
+                    secret: true,
+                    locked_text: None,
+                    distance: 1,
+                    hidden: 3,
+                    needs: None,
+                    tight: true,                // no squeezing through with a heavy pack
+                    blocks: vec![Sense::Sight], // too twisting to see along
+                },
+            ],
+            title: "Dark Cave".to_string(),
+            description: "A damp cave. Water drips somewhere deeper in, and smooth pebbles \
+                line the floor."
+                .to_string(),
+            metadata: HashMap::from([("shelter".to_string(), "true".to_string())]),
+            alternates: BTreeMap::new(),
+            variants: vec![DescriptionVariant {
+                when: Condition::PageFlag {
+                    flag: "cage_down".to_string(),
+                    page: None,
+                },
+                text: Some(
+                    "A damp cave. Water drips somewhere deeper in. A rickety mine cage \
+                    waits at the foot of the old shaft beside the lever."
+                        .to_string(),
+                ),
+                template: None,
+            }],
+            lighting: Lighting::AlwaysDark,
+            biome: Biome::Cave,
+            region: Some(RegionId::from("undercity")),
+            items: vec![ItemId::from("pebble")],
+            shop: None,
+            fixtures: vec![Fixture {
+                id: "lever".to_string(),
+                name: "Rusty Lever".to_string(),
+                description: "An iron lever set into the rock beside an old mine shaft."
+                    .to_string(),
+                actions: vec![FixtureAction {
+                    verb: "pull".to_string(),
+                    text: "With a groan of old chains, a cage rattles along the shaft.".to_string(),
+                    requires: Condition::Always,
+                    refused: None,
+                    effects: vec![
+                        FixtureEffect::ToggleConnection(PageConnection {
+                            name: "Ride the cage up".to_string(),
+                            target: PageId::from("green-city"),
+                            requires: Condition::Always,
+                            secret: false,
+                            locked_text: None,
+                            distance: 2,
+                            hidden: 0,
+                            needs: None,
+                            tight: false,
+                            blocks: Vec::new(),
+                        }),
+                        FixtureEffect::TogglePageFlag("cage_down".to_string()),
+                    ],
+                }],
+            }],
         },
     );
 
     graph
 }
 
-/// requested_connection = the user's POSTed button direction name ("north" etc)
+/// Exits a visitor can see from `page`. In the dark only the ways back
+/// towards light can be made out; closed secret ways aren't seen at all.
+pub fn visible_exits<'a>(
+    page: &'a Page,
+    pages: &PageGraph,
+    world_time: &WorldTime,
+    in_dark: bool,
+    catalog: &ItemCatalog,
+    means: &Means,
+    ctx: &ConditionContext,
+) -> Vec<ExitView<'a>> {
+    page.connections
+        .iter()
+        .filter(|conn| {
+            !in_dark
+                || pages
+                    .get(&conn.target)
+                    .is_some_and(|target| !target.is_dark_for(world_time, false, catalog))
+        })
+        .map(|conn| (conn, conn.is_open(ctx) && conn.passable(means)))
+        .filter(|(conn, open)| *open || !conn.secret)
+        .filter(|(conn, _)| conn.hidden <= ctx.session.perception())
+        .map(|(conn, open)| ExitView {
+            name: &conn.name,
+            target: &conn.target,
+            open,
+            locked_text: if open {
+                None
+            } else {
+                conn.locked_text.as_deref()
+            },
+            explored: ctx.session.has_visited(&conn.target),
+        })
+        .collect()
+}
+
+/// A problem found when checking the page graph against loaded resources
+#[derive(Debug)]
+pub enum ValidationIssue {
+    MissingTemplate { page: PageId, template: String },
+    MissingVariantTemplate { page: PageId, template: String },
+    UnknownField { page: PageId, field: String }, // not a field nor a `<field>_<band>` shorthand
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::MissingTemplate { page, template } => {
+                write!(f, "Page '{page}' uses missing template '{template}'")
+            }
+            ValidationIssue::MissingVariantTemplate { page, template } => {
+                write!(
+                    f,
+                    "Page '{page}' has a variant with missing template '{template}'"
+                )
+            }
+            ValidationIssue::UnknownField { page, field } => {
+                write!(f, "Page '{page}' has unknown field '{field}'")
+            }
+        }
+    }
+}
+
+/// Cross-check every page's template against the templates Tera has loaded.
+/// Run at startup (and on reload) so broken pages are reported up front.
+pub fn validate_templates(pages: &PageGraph, tera: &Tera) -> Vec<ValidationIssue> {
+    let loaded: Vec<&str> = tera.get_template_names().collect();
+    let mut issues: Vec<ValidationIssue> = pages
+        .values()
+        .filter(|page| !loaded.contains(&page.template.as_str()))
+        .map(|page| ValidationIssue::MissingTemplate {
+            page: page.id.clone(),
+            template: page.template.clone(),
+        })
+        .collect();
+    issues.sort_by_key(|issue| issue.to_string());
+    issues
+}
+
+/// Check the variants of every page: shorthands must name a known band, and
+/// the templates variants switch to must be loaded (else the page's own is used)
+pub fn validate_variants(pages: &PageGraph, tera: &Tera) -> Vec<ValidationIssue> {
+    let loaded: Vec<&str> = tera.get_template_names().collect();
+    let mut issues = Vec::new();
+    for page in pages.values() {
+        let mut templates: Vec<&str> = page
+            .variants
+            .iter()
+            .filter_map(|v| v.template.as_deref())
+            .collect();
+        for (key, value) in &page.alternates {
+            let band = ["description_", "template_"]
+                .iter()
+                .find_map(|field| key.strip_prefix(field));
+            if band.and_then(band_condition).is_none() {
+                issues.push(ValidationIssue::UnknownField {
+                    page: page.id.clone(),
+                    field: key.clone(),
+                });
+            } else if key.starts_with("template_") {
+                templates.push(value);
+            }
+        }
+        for template in templates.into_iter().filter(|t| !loaded.contains(t)) {
+            issues.push(ValidationIssue::MissingVariantTemplate {
+                page: page.id.clone(),
+                template: template.to_string(),
+            });
+        }
+    }
+    issues.sort_by_key(|issue| issue.to_string());
+    issues
+}
+
+/// Point pages with a missing template at `DEFAULT_TEMPLATE`, if that one is loaded.
+/// Returns how many pages were patched.
+pub fn apply_template_fallback(
+    pages: &mut PageGraph,
+    tera: &Tera,
+    issues: &[ValidationIssue],
+) -> usize {
+    if !tera
+        .get_template_names()
+        .any(|name| name == DEFAULT_TEMPLATE)
+    {
+        return 0;
+    }
+    let mut patched = 0;
+    for issue in issues {
+        let ValidationIssue::MissingTemplate { page, .. } = issue else {
+            continue;
+        };
+        if let Some(page) = pages.get_mut(page) {
+            page.template = DEFAULT_TEMPLATE.to_string();
+            patched += 1;
+        }
+    }
+    patched
+}
+
+/// Render `page` with `template` (its own, or one a variant picked), falling
+/// back to the page's own and then `DEFAULT_TEMPLATE` when that one isn't
+/// loaded (e.g. a page added since startup) or fails to render.
+/// Text-only pages all use `TEXT_TEMPLATE`.
+pub fn render_page(
+    tera: &Tera,
+    page: &Page,
+    template: &str,
+    ctx: &Context,
+    text_only: bool,
+) -> Result<String, AppError> {
+    if text_only {
+        return Ok(tera.render(TEXT_TEMPLATE, ctx)?);
+    }
+    let mut candidates = vec![template];
+    if page.template != template {
+        candidates.push(&page.template);
+    }
+    for candidate in candidates {
+        if candidate == DEFAULT_TEMPLATE || !tera.get_template_names().any(|name| name == candidate)
+        {
+            continue;
+        }
+        match tera.render(candidate, ctx) {
+            Ok(html) => return Ok(html),
+            Err(e) => error!(
+                "Template '{candidate}' failed for page {}, trying the next: {e:?}",
+                page.id
+            ),
+        }
+    }
+    Ok(tera.render(DEFAULT_TEMPLATE, ctx)?)
+}
+
+/// requested_connection = the user's POSTed button direction name ("north" etc).
+/// Closed connections, ones the player has nothing to get along by, and ones
+/// hidden past the player's perception are not valid moves.
 pub async fn valid_move<'a>(
     current_page_id: &'a PageId,
     requested_connection: &'a str,
     pages: &'a PageGraph,
+    means: &Means,
+    ctx: &ConditionContext<'_>,
 ) -> Option<&'a PageConnection> {
     pages.get(current_page_id).and_then(|page| {
-        page.connections
-            .iter()
-            .find(|conn| conn.name == requested_connection)
+        page.connections.iter().find(|conn| {
+            conn.name == requested_connection
+                && conn.hidden <= ctx.session.perception()
+                && conn.is_open(ctx)
+                && conn.passable(means)
+        })
     })
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::character::Character;
+    use crate::events::EventLog;
+    use crate::session::UserSession;
+    use crate::world::PageFlags;
+    use chrono::Local;
+    use std::sync::Arc;
+
+    /// A plain page with no way out, with whatever else `fields` gives it
+    pub(crate) fn page(id: &str, title: &str, fields: serde_json::Value) -> Page {
+        let mut page = serde_json::json!({
+            "id": id,
+            "template": DEFAULT_TEMPLATE,
+            "title": title,
+            "description": "",
+            "metadata": {},
+            "connections": [],
+        });
+        if let (Some(page), serde_json::Value::Object(fields)) = (page.as_object_mut(), fields) {
+            page.extend(fields);
+        }
+        serde_json::from_value(page).expect("a well-formed page")
+    }
+
+    /// A clearing with one way out of each kind
+    fn clearing() -> PageGraph {
+        let page = page(
+            "clearing",
+            "Clearing",
+            serde_json::json!({
+                "connections": [
+                    { "name": "north", "target": "meadow" },
+                    { "name": "gate", "target": "yard", "requires": { "Flag": "has_key" } },
+                    { "name": "crack", "target": "cave", "hidden": 3 },
+                    { "name": "river", "target": "far-bank", "needs": "boat" },
+                    { "name": "squeeze", "target": "den", "tight": true },
+                ],
+            }),
+        );
+        HashMap::from([(page.id.clone(), page)])
+    }
+
+    async fn moves(session: &UserSession, means: &Means) -> Vec<String> {
+        let pages = clearing();
+        let here = PageId::from("clearing");
+        let ctx = ConditionContext {
+            session,
+            page: &here,
+            now: Local::now(),
+            environment: None,
+            events: &EventLog::default(),
+            page_flags: Arc::new(PageFlags::new()),
+            scripts: None,
+        };
+        let mut valid = Vec::new();
+        for name in ["north", "gate", "crack", "river", "squeeze", "south"] {
+            if let Some(conn) = valid_move(&here, name, &pages, means, &ctx).await {
+                valid.push(conn.name.clone());
+            }
+        }
+        valid
+    }
+
+    #[actix_rt::test]
+    async fn only_open_noticed_passable_ways_are_valid_moves() {
+        let session = UserSession::new("clearing");
+        assert_eq!(
+            moves(&session, &Means::default()).await,
+            ["north", "squeeze"]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn a_way_opens_once_its_condition_holds() {
+        let mut session = UserSession::new("clearing");
+        session.flags.insert("has_key".to_string());
+        assert!(
+            moves(&session, &Means::default())
+                .await
+                .contains(&"gate".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn hidden_ways_need_the_perception_to_notice_them() {
+        let mut session = UserSession::new("clearing");
+        session.character = Some(Character {
+            name: "Sharp".to_string(),
+            stamina: 2,
+            perception: 3,
+            nocturnal: false,
+        });
+        assert!(
+            moves(&session, &Means::default())
+                .await
+                .contains(&"crack".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn ways_that_need_a_conveyance_or_a_light_load_are_kept_to_those_with_them() {
+        let session = UserSession::new("clearing");
+        let afloat = Means {
+            conveyances: vec![Conveyance::Boat],
+            ..Means::default()
+        };
+        assert!(
+            moves(&session, &afloat)
+                .await
+                .contains(&"river".to_string())
+        );
+        let laden = Means {
+            load: 10,
+            ..Means::default()
+        };
+        assert!(
+            !moves(&session, &laden)
+                .await
+                .contains(&"squeeze".to_string())
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Perception: what an actor makes out of the pages round about, for
+//! behaviors to reason over. Taken from the actor's own page out to so many
+//! steps, it holds who is on each page it can make out, the weather there
+//! and the noises and scents about. Each sense carries only along ways that
+//! don't block it, so a crawlway can hide what's beyond it from sight while
+//! letting a smell through.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::actor::{Actor, ActorMap, PageIndex};
+use crate::environment::Environment;
+use crate::pages::{PageGraph, PageId};
+use crate::stimuli::{Stimuli, Stimulus};
+use crate::weather::WeatherKind;
+
+/// Steps out from its own page an NPC takes in when it decides
+pub const SENSE_RANGE: usize = 1;
+
+/// A way of making out what's on another page
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sense {
+    Sight,   // who is there, and the weather
+    Hearing, // noises, though not who made them
+    Smell,   // who is there, and scents
+}
+
+impl Sense {
+    pub const ALL: [Sense; 3] = [Sense::Sight, Sense::Hearing, Sense::Smell];
+}
+
+/// One page as an actor makes it out
+#[derive(Debug)]
+pub struct Sensed<'a> {
+    pub page: &'a PageId,
+    pub hops: usize,                  // steps away; 0 for its own page
+    pub step: Option<&'a PageId>,     // the first step toward it, if it can go there on foot
+    pub senses: Vec<Sense>,           // those that carry this far
+    pub actors: Vec<&'a Actor>,       // who is there, if seen or smelled
+    pub weather: Option<WeatherKind>, // if it can see out there
+    pub stimuli: Vec<&'a Stimulus>,   // what it can hear and smell there
+}
+
+impl Sensed<'_> {
+    /// Whether `sense` carries this far
+    pub fn by(&self, sense: Sense) -> bool {
+        self.senses.contains(&sense)
+    }
+}
+
+/// What one actor makes out: its own page first, then the pages round
+/// about, nearest first
+#[derive(Debug)]
+pub struct Perception<'a> {
+    pages: Vec<Sensed<'a>>,
+}
+
+impl<'a> Perception<'a> {
+    /// Its own page, where it makes out everything
+    pub fn here(&self) -> &Sensed<'a> {
+        &self.pages[0]
+    }
+
+    /// The other pages it makes out anything of, nearest first
+    pub fn around(&self) -> &[Sensed<'a>] {
+        &self.pages[1..]
+    }
+
+    /// The nearest page away from here where `sense` makes out someone
+    /// `wanted`, and who. Hearing makes out nobody in particular.
+    pub fn nearest(
+        &self,
+        sense: Sense,
+        wanted: impl Fn(&Actor) -> bool,
+    ) -> Option<(&Sensed<'a>, &'a Actor)> {
+        if sense == Sense::Hearing {
+            return None;
+        }
+        self.around()
+            .iter()
+            .filter(|sensed| sensed.by(sense))
+            .find_map(|sensed| {
+                let found = sensed.actors.iter().find(|a| wanted(a))?;
+                Some((sensed, *found))
+            })
+    }
+}
+
+/// The world as it stands this tick, for actors to take in
+pub struct Surroundings<'a> {
+    pub page_graph: &'a PageGraph,
+    pub actors: &'a ActorMap,
+    pub by_page: &'a PageIndex,
+    pub environments: &'a HashMap<PageId, Environment>, // as far as they're known this tick
+    pub stimuli: &'a Stimuli,
+}
+
+impl<'a> Surroundings<'a> {
+    /// What `actor` makes out from where it stands, out to `range` steps
+    pub fn perceive(&self, actor: &'a Actor, range: usize) -> Perception<'a> {
+        let here = &actor.location;
+        // page -> steps away, the first step there on foot, and the senses reaching it
+        let mut reach: HashMap<&'a PageId, (usize, Option<&'a PageId>, Vec<Sense>)> =
+            HashMap::from([(here, (0, None, Sense::ALL.to_vec()))]);
+        for sense in Sense::ALL {
+            let mut seen = HashSet::from([here]);
+            // page, steps away, the first step, and whether it's all on foot
+            let mut frontier = VecDeque::from([(here, 0, None, true)]);
+            while let Some((page, hops, step, on_foot)) = frontier.pop_front() {
+                if hops == range {
+                    continue;
+                }
+                let exits = self
+                    .page_graph
+                    .get(page)
+                    .into_iter()
+                    .flat_map(|p| &p.connections);
+                for conn in exits.filter(|conn| !conn.blocks.contains(&sense)) {
+                    if !seen.insert(&conn.target) {
+                        continue;
+                    }
+                    let step = step.or(Some(&conn.target));
+                    let on_foot = on_foot && conn.needs.is_none();
+                    let way = on_foot.then_some(step).flatten();
+                    let known = reach
+                        .entry(&conn.target)
+                        .or_insert((hops + 1, way, Vec::new()));
+                    if hops + 1 < known.0 || (known.1.is_none() && way.is_some()) {
+                        (known.0, known.1) = (hops + 1, way);
+                    }
+                    known.2.push(sense);
+                    frontier.push_back((&conn.target, hops + 1, step, on_foot));
+                }
+            }
+        }
+
+        let mut pages: Vec<Sensed<'a>> = reach
+            .into_iter()
+            .map(|(page, (hops, step, senses))| self.sense(actor, page, hops, step, senses))
+            .collect();
+        pages.sort_by(|a, b| a.hops.cmp(&b.hops).then_with(|| a.page.cmp(b.page)));
+        Perception { pages }
+    }
+
+    /// `page` as `actor` makes it out by `senses`
+    fn sense(
+        &self,
+        actor: &Actor,
+        page: &'a PageId,
+        hops: usize,
+        step: Option<&'a PageId>,
+        senses: Vec<Sense>,
+    ) -> Sensed<'a> {
+        let knows_who = senses.contains(&Sense::Sight) || senses.contains(&Sense::Smell);
+        let actors = if knows_who {
+            self.by_page
+                .on(page)
+                .iter()
+                .filter(|id| **id != actor.id)
+                .filter_map(|id| self.actors.get(id))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let weather = senses
+            .contains(&Sense::Sight)
+            .then(|| self.environments.get(page))
+            .flatten()
+            .map(Environment::weather);
+        let stimuli = self
+            .stimuli
+            .on(page)
+            .iter()
+            .filter(|s| senses.contains(&s.kind.sense()))
+            .collect();
+        Sensed {
+            page,
+            hops,
+            step,
+            senses,
+            actors,
+            weather,
+            stimuli,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::tests::{critters, indexed};
+    use crate::pages::tests::page;
+
+    /// A den with a way to the yard and on to the field, a curtained way
+    /// to the hall and up to the attic, and a door shut fast on the cellar
+    fn burrow() -> PageGraph {
+        let way = |to: &str, blocks: &[&str]| serde_json::json!({ "name": to, "target": to, "blocks": blocks });
+        [
+            (
+                "den",
+                vec![
+                    way("yard", &[]),
+                    way("hall", &["Sight"]),
+                    way("cellar", &["Sight", "Smell"]),
+                ],
+            ),
+            ("yard", vec![way("den", &[]), way("field", &[])]),
+            ("hall", vec![way("den", &["Sight"]), way("attic", &[])]),
+            ("field", vec![way("yard", &[])]),
+            ("attic", vec![way("hall", &[])]),
+            ("cellar", vec![]),
+        ]
+        .into_iter()
+        .map(|(id, ways)| {
+            (
+                PageId::from(id),
+                page(id, id, serde_json::json!({ "connections": ways })),
+            )
+        })
+        .collect()
+    }
+
+    /// The first critter in the den, and one more on each page about it
+    fn burrowers() -> ActorMap {
+        ["den", "yard", "hall", "cellar", "field", "attic"]
+            .into_iter()
+            .zip(critters(6))
+            .map(|(at, mut definition)| {
+                definition.location = PageId::from(at);
+                let actor = Actor::from_definition(definition);
+                (actor.id.clone(), actor)
+            })
+            .collect()
+    }
+
+    fn sensed<'a>(perception: &'a Perception, page: &str) -> Option<&'a Sensed<'a>> {
+        perception.around().iter().find(|s| s.page.as_str() == page)
+    }
+
+    #[test]
+    fn each_sense_carries_only_along_ways_that_let_it() {
+        let (pages, actors) = (burrow(), burrowers());
+        let (by_page, environments, stimuli) =
+            (indexed(&actors), HashMap::new(), Stimuli::default());
+        let surroundings = Surroundings {
+            page_graph: &pages,
+            actors: &actors,
+            by_page: &by_page,
+            environments: &environments,
+            stimuli: &stimuli,
+        };
+        let perception = surroundings.perceive(&actors["critter-0"], 1);
+        assert_eq!(perception.here().page.as_str(), "den");
+
+        let yard = sensed(&perception, "yard").unwrap();
+        assert_eq!(yard.senses, Sense::ALL);
+        assert_eq!(yard.actors[0].id.as_str(), "critter-1");
+        // smelled through the curtain, though not seen
+        let hall = sensed(&perception, "hall").unwrap();
+        assert_eq!(hall.senses, [Sense::Hearing, Sense::Smell]);
+        assert_eq!(hall.actors[0].id.as_str(), "critter-2");
+        // heard behind the door, with no telling who
+        let cellar = sensed(&perception, "cellar").unwrap();
+        assert_eq!(cellar.senses, [Sense::Hearing]);
+        assert!(cellar.actors.is_empty());
+
+        assert!(sensed(&perception, "field").is_none(), "out of range");
+        let nearest = |sense| {
+            perception
+                .nearest(sense, |_| true)
+                .map(|(s, _)| s.page.as_str())
+        };
+        assert_eq!(nearest(Sense::Sight), Some("yard"));
+        assert_eq!(nearest(Sense::Hearing), None);
+    }
+
+    #[test]
+    fn further_out_a_sense_goes_no_further_than_it_got() {
+        let (pages, actors) = (burrow(), burrowers());
+        let (by_page, environments, stimuli) =
+            (indexed(&actors), HashMap::new(), Stimuli::default());
+        let surroundings = Surroundings {
+            page_graph: &pages,
+            actors: &actors,
+            by_page: &by_page,
+            environments: &environments,
+            stimuli: &stimuli,
+        };
+        let perception = surroundings.perceive(&actors["critter-0"], 2);
+
+        let field = sensed(&perception, "field").unwrap();
+        assert_eq!(
+            (field.hops, field.senses.as_slice()),
+            (2, Sense::ALL.as_slice())
+        );
+        assert_eq!(field.step.map(PageId::as_str), Some("yard"));
+        // the attic's way is open, but sight never got past the curtain
+        let attic = sensed(&perception, "attic").unwrap();
+        assert_eq!(
+            (attic.hops, attic.senses.as_slice()),
+            (2, [Sense::Hearing, Sense::Smell].as_slice())
+        );
+        assert_eq!(attic.actors[0].id.as_str(), "critter-5");
+        let seen: Vec<&str> = perception
+            .around()
+            .iter()
+            .filter(|s| s.by(Sense::Sight))
+            .map(|s| s.page.as_str())
+            .collect();
+        assert_eq!(seen, ["yard", "field"]);
+    }
+}
@@ -0,0 +1,108 @@
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::world::{PageFlags, WorldGraph};
+
+/// Default location of the event journal, relative to the working directory
+pub const DEFAULT_JOURNAL_PATH: &str = "journal.jsonl";
+/// Default location of the saved page flags, relative to the working directory
+pub const DEFAULT_PAGE_FLAGS_PATH: &str = "page_flags.json";
+
+/// One line of the journal file
+#[derive(Serialize)]
+struct JournalEntry<'a> {
+    at: u64, // unix seconds
+    #[serde(flatten)]
+    event: &'a WorldEvent,
+}
+
+/// Subscribe to the bus and append every event (bar tick heartbeats) to a
+/// JSON-lines journal at `path`.
+pub fn spawn_journal(bus: &EventBus, path: PathBuf) {
+    let mut rx = bus.subscribe();
+    actix_rt::spawn(async move {
+        let mut file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Could not open journal {}: {e}", path.display());
+                return;
+            }
+        };
+        loop {
+            let event = match rx.recv().await {
+                Ok(WorldEvent::WorldTicked { .. }) => continue,
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Journal fell behind, {missed} events not written");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let entry = JournalEntry { at, event: &event };
+            let written = serde_json::to_writer(&mut file, &entry)
+                .map_err(std::io::Error::from)
+                .and_then(|_| file.write_all(b"\n"));
+            if let Err(e) = written {
+                error!("Failed to write journal entry: {e}");
+            }
+        }
+    });
+}
+
+/// Page flags saved by an earlier run; none if the file doesn't exist yet
+pub fn load_page_flags(path: &Path) -> Result<PageFlags, AppError> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PageFlags::new()),
+        Err(e) => Err(AppError::OtherError(format!(
+            "Reading {}: {e}",
+            path.display()
+        ))),
+    }
+}
+
+/// Save every page's flags to `path` whenever one changes, so marks left
+/// on the world outlive a restart
+pub fn spawn_page_flag_saver(bus: &EventBus, world: WorldGraph, path: PathBuf) {
+    let mut rx = bus.subscribe();
+    actix_rt::spawn(async move {
+        loop {
+            match rx.recv().await {
+                // a lagged receiver may have missed a change, so save then too
+                Ok(WorldEvent::PageFlagChanged { .. } | WorldEvent::WorldRestored { .. })
+                | Err(RecvError::Lagged(_)) => {
+                    if let Err(e) = write_json_atomic(&path, &*world.page_flags()) {
+                        error!("Failed to save page flags: {e}");
+                    }
+                }
+                Ok(_) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Write `value` to `path` as JSON, through a temporary file so a crash
+/// part-way never leaves a half-written file behind
+pub fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<(), AppError> {
+    let tmp = path.with_extension("tmp");
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&tmp)?;
+        serde_json::to_writer(&mut file, value)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    };
+    write().map_err(|e| AppError::OtherError(format!("Writing {}: {e}", path.display())))
+}
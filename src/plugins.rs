@@ -0,0 +1,123 @@
+use parking_lot::Mutex;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info};
+
+use crate::actor::ActorManager;
+use crate::environment::EnvironmentManager;
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::pages::PageId;
+use crate::scripting::{self, ScriptEffect};
+use crate::world::WorldGraph;
+
+/// Default directory of compiled plugins, relative to the working directory
+pub const DEFAULT_PLUGINS_PATH: &str = "plugins";
+
+/// World logic that ships apart from the crate, e.g. as a WASM module.
+/// Every hook is optional. Like scripts, plugins only change the world
+/// through the effects they hand back.
+pub trait WorldPlugin: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The world has ticked over
+    fn on_tick(&self, _tick: u64) -> Vec<ScriptEffect> {
+        Vec::new()
+    }
+
+    /// A player went from one page to another
+    fn on_player_move(&self, _from: &PageId, _to: &PageId) -> Vec<ScriptEffect> {
+        Vec::new()
+    }
+
+    /// A page is being shown to a player; lines of text to add to it
+    fn on_page_render(&self, _page: &PageId) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Every loaded plugin, called in load order
+#[derive(Clone, Default)]
+pub struct PluginHost {
+    plugins: Arc<Vec<Box<dyn WorldPlugin>>>,
+}
+
+impl PluginHost {
+    pub fn new(plugins: Vec<Box<dyn WorldPlugin>>) -> Self {
+        PluginHost {
+            plugins: Arc::new(plugins),
+        }
+    }
+
+    /// Load the plugins in `dir` this build can run. WASM plugins need the
+    /// `wasm-plugins` feature; without it they are skipped with a warning.
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        #[cfg(feature = "wasm-plugins")]
+        let plugins = crate::wasm_plugins::load_dir(dir)?;
+        #[cfg(not(feature = "wasm-plugins"))]
+        let plugins: Vec<Box<dyn WorldPlugin>> = {
+            let skipped = std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "wasm"))
+                .count();
+            if skipped > 0 {
+                tracing::warn!(
+                    "{skipped} WASM plugin(s) in {} not loaded; build with --features wasm-plugins",
+                    dir.display()
+                );
+            }
+            Vec::new()
+        };
+        for plugin in &plugins {
+            info!("Plugin {} ready", plugin.name());
+        }
+        Ok(PluginHost::new(plugins))
+    }
+
+    /// What plugins have to add to `page`
+    pub fn render_notes(&self, page: &PageId) -> Vec<String> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.on_page_render(page))
+            .collect()
+    }
+
+    /// Call plugins' tick and movement hooks as those events come in, and
+    /// carry out what they ask for
+    pub fn spawn_dispatcher(
+        &self,
+        bus: &EventBus,
+        world: WorldGraph,
+        actors: Arc<Mutex<ActorManager>>,
+        environment: EnvironmentManager,
+    ) {
+        if self.plugins.is_empty() {
+            return;
+        }
+        let mut rx = bus.subscribe();
+        let bus = bus.clone();
+        let plugins = self.plugins.clone();
+        actix_rt::spawn(async move {
+            loop {
+                let effects: Vec<ScriptEffect> = match rx.recv().await {
+                    Ok(WorldEvent::WorldTicked { tick, .. }) => {
+                        plugins.iter().flat_map(|p| p.on_tick(tick)).collect()
+                    }
+                    Ok(WorldEvent::PlayerMoved { from, to }) => plugins
+                        .iter()
+                        .flat_map(|p| p.on_player_move(&from, &to))
+                        .collect(),
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                if let Err(e) = scripting::apply(effects, None, &world, &actors, &environment, &bus)
+                {
+                    error!("Plugin effects failed: {e}");
+                }
+            }
+        });
+    }
+}
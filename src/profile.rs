@@ -0,0 +1,85 @@
+use actix_session::Session;
+use actix_web::{HttpResponse, Responder, web};
+use serde::Serialize;
+use std::sync::Arc;
+use tera::Tera;
+
+use crate::clock::WorldClock;
+use crate::environment::EnvironmentManager;
+use crate::error::AppError;
+use crate::handler::START_PAGE;
+use crate::i18n::Translations;
+use crate::instances::Instances;
+use crate::render;
+use crate::session::get_or_create_user_session;
+use crate::world::WorldGraph;
+use crate::worlds::Mount;
+
+/// Where a player last saw an actor
+#[derive(Serialize)]
+struct Sighting {
+    title: String,
+    here: bool, // the player is on that page now
+}
+
+/// An actor as a page of its own: their name and, once the player has met
+/// them, what sort they are and where the player last saw them
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn actor_profile_handler(
+    tera: web::Data<Tera>,
+    mount: web::Data<Mount>,
+    session: Session,
+    world: web::Data<WorldGraph>,
+    instances: web::Data<Instances>,
+    clock: web::Data<WorldClock>,
+    environment: web::Data<EnvironmentManager>,
+    translations: web::Data<Arc<Translations>>,
+    id: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let player = get_or_create_user_session(&session, START_PAGE)?;
+    let (name, flags, regard) = {
+        let (actors, _) = instances.for_player(&player.player_id);
+        let manager = actors.lock();
+        let actor = manager
+            .actors
+            .get(id.as_str())
+            .filter(|actor| actor.id != player.player_id)
+            .ok_or_else(|| AppError::ActorNotFound(id.to_string()))?;
+        (
+            actor.name.clone(),
+            actor.flags.clone(),
+            player.reputation.regard(actor),
+        )
+    };
+    let lang = translations.language(player.language.as_deref());
+
+    // strangers give nothing away; flags there are no words for stay private
+    let met = player.seen.contains_key(id.as_str());
+    let traits: Vec<String> = flags
+        .iter()
+        .filter(|_| met)
+        .filter_map(|flag| translations.lookup(lang, &format!("actor.flags.{flag:?}")))
+        .collect();
+    let regard = met
+        .then(|| translations.lookup(lang, &format!("regard.{}", regard.key())))
+        .flatten();
+    let pages = world.snapshot();
+    let last_seen = player.seen.get(id.as_str()).and_then(|seen| {
+        let page = pages.get(seen)?;
+        Some(Sighting {
+            title: translations
+                .lookup(lang, &format!("pages.{}.title", page.id))
+                .unwrap_or_else(|| page.title.clone()),
+            here: page.id == player.current_page,
+        })
+    });
+
+    let mut ctx = render::base_context(&session, &player, &clock, &environment, &translations);
+    ctx.insert("base", &mount.base);
+    ctx.insert("name", &name);
+    ctx.insert("traits", &traits);
+    ctx.insert("regard", &regard);
+    ctx.insert("last_seen", &last_seen);
+    let html = tera.render("actor.html", &ctx)?;
+    Ok(HttpResponse::Ok().body(html))
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::conditions::{Condition, ConditionContext};
+use crate::environment::Environment;
+use crate::error::AppError;
+use crate::events::EventLog;
+use crate::items::ItemId;
+use crate::pages::PageId;
+use crate::scripting::ScriptHost;
+use crate::session::UserSession;
+use crate::world::PageFlags;
+
+/// Default location of the quests file, relative to the working directory
+pub const DEFAULT_QUESTS_PATH: &str = "data/quests.toml";
+
+/// One step of a quest, done once `done_when` holds
+#[derive(Clone, Debug, Deserialize)]
+pub struct QuestStage {
+    pub goal: String, // shown to the player while this stage is current
+    pub done_when: Condition,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct QuestDefinition {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    #[serde(default)]
+    pub starts_when: Option<Condition>, // None: only given by NPCs
+    pub stages: Vec<QuestStage>,
+    #[serde(default)]
+    pub start_items: Vec<ItemId>, // handed to the player when the quest starts
+    #[serde(default)]
+    pub hand_in: Vec<ItemId>, // taken back from the player on completion
+    #[serde(default)]
+    pub reward: QuestReward,
+}
+
+/// What finishing a quest earns the player
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct QuestReward {
+    #[serde(default)]
+    pub items: Vec<ItemId>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub reputation: BTreeMap<String, i32>, // actor id or faction -> standing gained
+}
+
+#[derive(Deserialize)]
+struct QuestFile {
+    #[serde(default)]
+    quest: Vec<QuestDefinition>,
+}
+
+/// How far a player has got with one quest
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct QuestProgress {
+    pub stage: usize, // index of the current stage
+    pub done: bool,
+}
+
+/// A started quest as shown on pages
+#[derive(Serialize)]
+pub struct QuestStatus<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+    pub goal: Option<&'a str>, // None once done
+    pub done: bool,
+}
+
+/// All quest definitions, in file order
+#[derive(Default)]
+pub struct QuestBook {
+    quests: Vec<QuestDefinition>,
+}
+
+impl QuestBook {
+    /// Read and parse quest definitions from a TOML file
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: QuestFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        if let Some(empty) = file.quest.iter().find(|q| q.stages.is_empty()) {
+            return Err(AppError::OtherError(format!(
+                "Quest '{}' has no stages",
+                empty.id
+            )));
+        }
+        Ok(QuestBook { quests: file.quest })
+    }
+
+    /// Give the player quest `id`, e.g. from dialogue. `None` if they already have it.
+    pub fn start(&self, player: &mut UserSession, id: &str) -> Option<String> {
+        let Some(quest) = self.quests.iter().find(|q| q.id == id) else {
+            warn!("Tried to give unknown quest {id}");
+            return None;
+        };
+        if player.quests.contains_key(id) {
+            return None;
+        }
+        Some(self.apply(player, quest, Step::Start))
+    }
+
+    /// Start, and move the player along, every quest whose conditions now
+    /// hold. Returns what to tell the player about it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn advance(
+        &self,
+        player: &mut UserSession,
+        page: &PageId,
+        now: DateTime<Local>,
+        environment: Option<&Environment>,
+        events: &EventLog,
+        page_flags: &Arc<PageFlags>,
+        scripts: Option<&ScriptHost>,
+    ) -> Vec<String> {
+        let mut news = Vec::new();
+        // one change per pass, since each can make the next condition hold
+        loop {
+            let ctx = ConditionContext {
+                session: player,
+                page,
+                now,
+                environment,
+                events,
+                page_flags: page_flags.clone(),
+                scripts,
+            };
+            let Some((quest, step)) = self.next_step(&ctx) else {
+                break;
+            };
+            news.push(self.apply(player, quest, step));
+        }
+        news
+    }
+
+    /// The first quest to start or stage to finish, if any
+    fn next_step<'a>(&'a self, ctx: &ConditionContext) -> Option<(&'a QuestDefinition, Step)> {
+        self.quests
+            .iter()
+            .find_map(|quest| match ctx.session.quests.get(&quest.id) {
+                None => quest
+                    .starts_when
+                    .as_ref()
+                    .filter(|c| c.holds(ctx))
+                    .map(|_| (quest, Step::Start)),
+                Some(progress) if progress.done => None,
+                Some(progress) => quest
+                    .stages
+                    .get(progress.stage)
+                    .filter(|stage| stage.done_when.holds(ctx))
+                    .map(|_| (quest, Step::FinishStage)),
+            })
+    }
+
+    fn apply(&self, player: &mut UserSession, quest: &QuestDefinition, step: Step) -> String {
+        match step {
+            Step::Start => {
+                info!("Quest {} started", quest.id);
+                player
+                    .quests
+                    .insert(quest.id.clone(), QuestProgress::default());
+                player.inventory.extend(quest.start_items.iter().cloned());
+                format!("New quest: {}", quest.title)
+            }
+            Step::FinishStage => {
+                let progress = player.quests.entry(quest.id.clone()).or_default();
+                progress.stage += 1;
+                if progress.stage >= quest.stages.len() {
+                    info!("Quest {} completed", quest.id);
+                    progress.done = true;
+                    player
+                        .inventory
+                        .retain(|item| !quest.hand_in.contains(item));
+                    player.inventory.extend(quest.reward.items.iter().cloned());
+                    player.flags.extend(quest.reward.flags.iter().cloned());
+                    for (with, change) in &quest.reward.reputation {
+                        player.reputation.adjust(with, *change);
+                    }
+                    format!("Quest complete: {}", quest.title)
+                } else {
+                    format!("{}: {}", quest.title, quest.stages[progress.stage].goal)
+                }
+            }
+        }
+    }
+
+    /// The player's started quests, unfinished ones first
+    pub fn status(&self, player: &UserSession) -> Vec<QuestStatus<'_>> {
+        let mut status: Vec<QuestStatus> = self
+            .quests
+            .iter()
+            .filter_map(|quest| {
+                let progress = player.quests.get(&quest.id)?;
+                Some(QuestStatus {
+                    title: &quest.title,
+                    description: &quest.description,
+                    goal: (!progress.done)
+                        .then(|| quest.stages.get(progress.stage))
+                        .flatten()
+                        .map(|stage| stage.goal.as_str()),
+                    done: progress.done,
+                })
+            })
+            .collect();
+        status.sort_by_key(|q| q.done);
+        status
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Start,
+    FinishStage,
+}
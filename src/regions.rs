@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::environment::Season;
+use crate::error::AppError;
+use crate::pages::{Page, PageGraph, PageId};
+use crate::session::UserSession;
+
+pub const DEFAULT_REGIONS_PATH: &str = "data/regions.toml";
+
+/// Most levels of nesting followed, in case of a cycle in `parent`
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RegionId(pub String);
+
+impl From<&str> for RegionId {
+    fn from(s: &str) -> Self {
+        RegionId(s.to_owned())
+    }
+}
+impl std::fmt::Display for RegionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A named group of pages ("The Undercity"), possibly inside a larger one.
+/// Its environment defaults apply to every page in it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Region {
+    pub id: RegionId,
+    pub name: String,
+    #[serde(default)]
+    pub parent: Option<RegionId>,
+    // added to the temperature of every page in the region
+    #[serde(default)]
+    pub temperature_offset: i32,
+    // every page in the region is under cover
+    #[serde(default)]
+    pub sheltered: bool,
+    // seasons migratory actors make for the region
+    #[serde(default)]
+    pub migrants_in: Vec<Season>,
+}
+
+#[derive(Deserialize)]
+struct RegionFile {
+    #[serde(default)]
+    region: Vec<Region>,
+}
+
+/// Every region, keyed by id
+#[derive(Default)]
+pub struct Regions(HashMap<RegionId, Region>);
+
+impl Regions {
+    /// Read regions from a TOML file. A missing file just means no regions.
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        if !path.exists() {
+            return Ok(Regions::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: RegionFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        Ok(Regions(
+            file.region
+                .into_iter()
+                .map(|region| (region.id.clone(), region))
+                .collect(),
+        ))
+    }
+
+    /// Every region, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &Region> {
+        self.0.values()
+    }
+
+    /// `region` and the regions around it, outermost first
+    pub fn ancestry(&self, region: Option<&RegionId>) -> Vec<&Region> {
+        let mut chain = Vec::new();
+        let mut next = region;
+        while let Some(region) = next.and_then(|id| self.0.get(id)) {
+            if chain.len() == MAX_DEPTH {
+                break;
+            }
+            chain.push(region);
+            next = region.parent.as_ref();
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Whether `region` is `outer` or nested somewhere inside it
+    pub fn within(&self, region: Option<&RegionId>, outer: &RegionId) -> bool {
+        self.ancestry(region).iter().any(|r| r.id == *outer)
+    }
+
+    /// Extra warmth on `page` from its regions
+    pub fn temperature_offset(&self, page: &Page) -> i32 {
+        self.ancestry(page.region.as_ref())
+            .iter()
+            .map(|r| r.temperature_offset)
+            .sum()
+    }
+
+    /// Where migratory actors make for in `season`: a region that sets it
+    /// aside for them, or failing that in winter the warmest there is. None
+    /// if they should keep to their own range.
+    pub fn migrating_to(&self, season: Season) -> Option<&RegionId> {
+        let mut regions: Vec<&Region> = self.0.values().collect();
+        regions.sort_by(|a, b| a.id.0.cmp(&b.id.0)); // the same pick every time
+        if let Some(region) = regions.iter().find(|r| r.migrants_in.contains(&season)) {
+            return Some(&region.id);
+        }
+        let warmth = |r: &Region| -> i32 {
+            self.ancestry(Some(&r.id))
+                .iter()
+                .map(|r| r.temperature_offset)
+                .sum()
+        };
+        regions
+            .into_iter()
+            .filter(|_| season == Season::Winter)
+            .filter(|r| warmth(r) > 0)
+            .max_by_key(|r| warmth(r))
+            .map(|r| &r.id)
+    }
+
+    /// Whether one of `page`'s regions puts it under cover
+    pub fn shelters(&self, page: &Page) -> bool {
+        self.ancestry(page.region.as_ref())
+            .iter()
+            .any(|r| r.sheltered)
+    }
+}
+
+/// Region names from outermost in, then the page title ("Kanto-ish > Route 1")
+pub fn breadcrumb(page: &Page, regions: &Regions) -> Vec<String> {
+    regions
+        .ancestry(page.region.as_ref())
+        .iter()
+        .map(|r| r.name.clone())
+        .chain(std::iter::once(page.title.clone()))
+        .collect()
+}
+
+/// One page on a region map
+#[derive(Serialize)]
+pub struct MapEntry<'a> {
+    pub id: &'a PageId,
+    pub title: &'a str,
+    pub visited: bool,
+    pub exits: Vec<&'a PageId>, // only those staying inside the region
+}
+
+/// The pages in `region` (and regions inside it), sorted by title, for a map.
+/// Pages the player hasn't been to are listed but marked unvisited.
+pub fn region_map<'a>(
+    region: &RegionId,
+    pages: &'a PageGraph,
+    regions: &Regions,
+    session: &UserSession,
+) -> Vec<MapEntry<'a>> {
+    let inside = |page: &Page| regions.within(page.region.as_ref(), region);
+    let mut entries: Vec<MapEntry> = pages
+        .values()
+        .filter(|page| inside(page))
+        .map(|page| MapEntry {
+            id: &page.id,
+            title: &page.title,
+            visited: session.has_visited(&page.id),
+            exits: page
+                .connections
+                .iter()
+                .filter(|conn| pages.get(&conn.target).is_some_and(inside))
+                .map(|conn| &conn.target)
+                .collect(),
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.title);
+    entries
+}
@@ -0,0 +1,412 @@
+use actix_session::{Session, SessionExt};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{HttpResponse, web};
+use chrono::{DateTime, TimeDelta, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tera::{Context, Tera, Value};
+use tracing::error;
+
+use crate::actor::{ActorState, Tiredness};
+use crate::clock::WorldClock;
+use crate::environment::{Environment, EnvironmentManager, PartOfDay, WorldTime};
+use crate::error::AppError;
+use crate::handler::START_PAGE;
+use crate::i18n::{DEFAULT_LANGUAGE, Translations};
+use crate::pages::{ExitView, PageId};
+use crate::session::{SESSION_KEY, UserSession, take_flashes};
+use crate::users::ACCOUNT_KEY;
+use crate::weather::WeatherKind;
+use crate::world::WorldGraph;
+use crate::worlds::Mount;
+
+/// How a player wants pages drawn, picked with `?view=text` or `?view=full`
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    Full,
+    Text, // semantic HTML only: no images, scripts or styling
+}
+
+#[derive(Deserialize)]
+pub struct ViewQuery {
+    pub view: Option<ViewMode>, // remembered for the rest of the session
+    pub closer: Option<usize>,  // page of a crowd to look through, from 1
+}
+
+/// Shown for errors without a template of their own (`404.html`, `500.html`, ...)
+const ERROR_TEMPLATE: &str = "error.html";
+
+/// Where theme stylesheets are served from, as `/themes/<theme>.css`
+pub const THEMES_DIR: &str = "static/themes";
+
+/// What every page shown to a player is rendered with: the world time, who
+/// they are, any messages left for them and the UI text in their language
+pub fn base_context(
+    session: &Session,
+    player: &UserSession,
+    clock: &WorldClock,
+    environment: &EnvironmentManager,
+    translations: &Translations,
+) -> Context {
+    let mut ctx = Context::new();
+    insert_language(&mut ctx, player, translations);
+    ctx.insert("clock", &clock.status());
+    insert_time(&mut ctx, clock, player, translations);
+    ctx.insert("theme", &theme(clock, environment, &player.current_page));
+    ctx.insert("character", &player.character);
+    ctx.insert("coins", &player.coins);
+    ctx.insert("text_only", &player.text_only);
+    ctx.insert(
+        "account",
+        &session.get::<String>(ACCOUNT_KEY).ok().flatten(),
+    );
+    ctx.insert("flashes", &take_flashes(session));
+    ctx
+}
+
+/// The world time as page templates see it, as `time`
+#[derive(Serialize)]
+struct TimeView {
+    hour: u8,
+    minute: u8,
+    daytime: bool,
+    part: &'static str, // e.g. "early_evening", a key under `t.time`
+    described: String,  // the part of day in the player's language
+}
+
+fn insert_time(
+    ctx: &mut Context,
+    clock: &WorldClock,
+    player: &UserSession,
+    translations: &Translations,
+) {
+    let now = clock.now();
+    let world_time = WorldTime::from_datetime(&now);
+    let part = world_time.part_of_day();
+    let lang = translations.language(player.language.as_deref());
+    ctx.insert(
+        "time",
+        &TimeView {
+            hour: now.hour() as u8,
+            minute: now.minute() as u8,
+            daytime: world_time.is_daytime(),
+            part: part.key(),
+            described: translations.text(lang, &format!("time.{}", part.key()), &[]),
+        },
+    );
+}
+
+/// How the site looks for the world as it is on `page`, as `theme`: the
+/// part of day, then the weather if there is any to speak of, e.g. "dawn",
+/// "night-rain". Each has a stylesheet at `/themes/<theme>.css`.
+pub fn theme(clock: &WorldClock, environment: &EnvironmentManager, page: &PageId) -> String {
+    let time = match WorldTime::from_datetime(&clock.now()).part_of_day() {
+        PartOfDay::Dawn => "dawn",
+        PartOfDay::Morning | PartOfDay::Midday | PartOfDay::Afternoon => "day",
+        PartOfDay::EarlyEvening => "dusk",
+        PartOfDay::Night | PartOfDay::DeepNight => "night",
+    };
+    let weather = environment
+        .environment_for(page)
+        .ok()
+        .and_then(|env| match env.weather() {
+            WeatherKind::Clear => None,
+            WeatherKind::Cloudy => Some("cloud"),
+            WeatherKind::Rainy => Some("rain"),
+            WeatherKind::Windy => Some("wind"),
+            WeatherKind::Foggy => Some("fog"),
+            WeatherKind::Stormy => Some("storm"),
+            WeatherKind::Snowy => Some("snow"),
+        });
+    match weather {
+        Some(weather) => format!("{time}-{weather}"),
+        None => time.to_string(),
+    }
+}
+
+/// The stylesheet for a theme: its own if there is one, else the one for
+/// its part of day, else nothing, so a site can style as few themes as it likes
+pub async fn theme_stylesheet_handler(theme: web::Path<String>) -> HttpResponse {
+    let css = HttpResponse::Ok()
+        .content_type("text/css; charset=utf-8")
+        .finish();
+    if !theme.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+        return css;
+    }
+    let time = theme.split('-').next().unwrap_or_default();
+    [theme.as_str(), time]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(format!("{THEMES_DIR}/{name}.css")).ok())
+        .map_or(css, |text| {
+            HttpResponse::Ok()
+                .content_type("text/css; charset=utf-8")
+                .body(text)
+        })
+}
+
+/// Filters for showing world time in templates:
+/// `{{ clock.now | clock_time }}` gives "18:05", or "6:05 pm" with
+/// `twelve_hour=true`; `{{ hour | part_of_day }}` gives the `t.time` key for
+/// that hour, e.g. "early_evening".
+pub fn register_time_filters(tera: &mut Tera) {
+    tera.register_filter("clock_time", clock_time_filter);
+    tera.register_filter("part_of_day", part_of_day_filter);
+}
+
+/// Filters for turning raw state into prose, in the player's language when
+/// given `lang=lang` (else the default one):
+/// `{{ npc | describe_actor }}` gives "Old Wick looks tired.";
+/// `{{ npc.state.fatigue | fatigue_text }}` gives "tired";
+/// `{{ event.at | relative_time }}` gives "3 hours ago" by the world clock.
+pub fn register_prose_filters(tera: &mut Tera, translations: Arc<Translations>, clock: WorldClock) {
+    let lang_of = |args: &HashMap<String, Value>| {
+        args.get("lang")
+            .and_then(Value::as_str)
+            .unwrap_or(DEFAULT_LANGUAGE)
+            .to_string()
+    };
+
+    let t = translations.clone();
+    tera.register_filter(
+        "describe_actor",
+        move |value: &Value, args: &HashMap<String, Value>| {
+            let name = value
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| tera::Error::msg("describe_actor expects an actor"))?;
+            let state: ActorState = value
+                .get("state")
+                .cloned()
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| tera::Error::msg(format!("describe_actor: {e}")))?
+                .ok_or_else(|| tera::Error::msg("describe_actor expects an actor"))?;
+            let lang = lang_of(args);
+            let sentences: Vec<String> = state
+                .looks()
+                .into_iter()
+                .map(|key| t.text(&lang, key, &[("name", name)]))
+                .collect();
+            Ok(Value::String(sentences.join(" ")))
+        },
+    );
+
+    let t = translations.clone();
+    tera.register_filter(
+        "fatigue_text",
+        move |value: &Value, args: &HashMap<String, Value>| {
+            let fatigue = value
+                .as_u64()
+                .and_then(|f| u8::try_from(f).ok())
+                .ok_or_else(|| tera::Error::msg("fatigue_text expects a fatigue, 0 to 255"))?;
+            let key = format!("fatigue.{}", Tiredness::of(fatigue).key());
+            Ok(Value::String(t.text(&lang_of(args), &key, &[])))
+        },
+    );
+
+    tera.register_filter(
+        "relative_time",
+        move |value: &Value, args: &HashMap<String, Value>| {
+            let text = value
+                .as_str()
+                .ok_or_else(|| tera::Error::msg("relative_time expects a timestamp"))?;
+            let then = DateTime::parse_from_rfc3339(text)
+                .map_err(|e| tera::Error::msg(format!("relative_time: {e}")))?;
+            let (key, count) = relative(clock.now().fixed_offset() - then);
+            Ok(Value::String(translations.text(
+                &lang_of(args),
+                &format!("relative.{key}"),
+                &[("count", &count.to_string())],
+            )))
+        },
+    );
+}
+
+/// How long `ago` was (negative for still to come), as a key under
+/// `relative` in the translations and the count it speaks of
+fn relative(ago: TimeDelta) -> (&'static str, i64) {
+    let future = ago < TimeDelta::zero();
+    let span = ago.abs();
+    let (unit, count) = if span < TimeDelta::minutes(1) {
+        return ("just_now", 0);
+    } else if span < TimeDelta::hours(1) {
+        ("minute", span.num_minutes())
+    } else if span < TimeDelta::days(1) {
+        ("hour", span.num_hours())
+    } else {
+        ("day", span.num_days())
+    };
+    let key = match (future, unit, count == 1) {
+        (false, "minute", true) => "minute_ago",
+        (false, "minute", false) => "minutes_ago",
+        (false, "hour", true) => "hour_ago",
+        (false, "hour", false) => "hours_ago",
+        (false, _, true) => "day_ago",
+        (false, _, false) => "days_ago",
+        (true, "minute", true) => "in_minute",
+        (true, "minute", false) => "in_minutes",
+        (true, "hour", true) => "in_hour",
+        (true, "hour", false) => "in_hours",
+        (true, _, true) => "in_day",
+        (true, _, false) => "in_days",
+    };
+    (key, count)
+}
+
+fn clock_time_filter(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let text = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("clock_time expects a timestamp"))?;
+    let time = DateTime::parse_from_rfc3339(text)
+        .map_err(|e| tera::Error::msg(format!("clock_time: {e}")))?;
+    let twelve_hour = args
+        .get("twelve_hour")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let shown = if twelve_hour {
+        let (pm, hour) = time.hour12();
+        format!(
+            "{hour}:{:02} {}",
+            time.minute(),
+            if pm { "pm" } else { "am" }
+        )
+    } else {
+        format!("{:02}:{:02}", time.hour(), time.minute())
+    };
+    Ok(Value::String(shown))
+}
+
+fn part_of_day_filter(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let hour = value
+        .as_u64()
+        .filter(|hour| *hour < 24)
+        .ok_or_else(|| tera::Error::msg("part_of_day expects an hour, 0 to 23"))?;
+    Ok(Value::String(PartOfDay::at(hour as u8).key().to_string()))
+}
+
+/// Add the player's language as `lang` and its text as `t`
+fn insert_language(ctx: &mut Context, player: &UserSession, translations: &Translations) {
+    let lang = translations.language(player.language.as_deref());
+    ctx.insert("lang", lang);
+    ctx.insert("languages", translations.languages());
+    ctx.insert("t", &*translations.table(lang));
+}
+
+/// Add what a player standing on a page has around them: its environment
+/// and the ways on they can see
+pub fn insert_surroundings(ctx: &mut Context, environment: &Environment, exits: &[ExitView]) {
+    ctx.insert("ambience", &environment.ambience());
+    ctx.insert("hazard", &environment.hazard().map(|h| h.warning()));
+    ctx.insert("environment", environment);
+    ctx.insert("exits", exits);
+}
+
+/// Replace the plain page of an `AppError` with `<status>.html` (or
+/// `ERROR_TEMPLATE`), linking back to the page the player is on.
+/// The API answers with its errors as JSON instead.
+pub async fn error_pages<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let res = next.call(req).await?;
+    let Some(app_error) = res
+        .response()
+        .error()
+        .and_then(|e| e.as_error::<AppError>())
+    else {
+        return Ok(res.map_into_left_body());
+    };
+    let request = res.request();
+    let base = request
+        .app_data::<web::Data<Mount>>()
+        .map(|mount| mount.base.clone())
+        .unwrap_or_default();
+    let path = request.path();
+    if path
+        .strip_prefix(base.as_str())
+        .unwrap_or(path)
+        .starts_with("/api/")
+    {
+        let (status, message) = app_error.describe();
+        let error = HttpResponse::build(status).json(serde_json::json!({
+            "status": status.as_u16(),
+            "error": message,
+        }));
+        return Ok(res.into_response(error).map_into_right_body());
+    }
+    let Some(tera) = request.app_data::<web::Data<Tera>>() else {
+        return Ok(res.map_into_left_body());
+    };
+
+    let (status, message) = app_error.describe();
+    let mut ctx = Context::new();
+    ctx.insert("status", &status.as_u16());
+    ctx.insert("reason", &status.canonical_reason());
+    ctx.insert("message", &message);
+    ctx.insert("base", &base);
+    let player = player_of(request);
+    if let Some(clock) = request.app_data::<web::Data<WorldClock>>()
+        && let Some(environment) = request.app_data::<web::Data<EnvironmentManager>>()
+    {
+        let page = player
+            .as_ref()
+            .map_or_else(|| PageId::from(START_PAGE), |p| p.current_page.clone());
+        ctx.insert("theme", &theme(clock, environment, &page));
+    }
+    let mut back = player.as_ref().and_then(|player| back_to(request, player));
+    if let Some(translations) = request.app_data::<web::Data<Arc<Translations>>>() {
+        let lang = translations.language(player.as_ref().and_then(|p| p.language.as_deref()));
+        ctx.insert("t", &*translations.table(lang));
+        if let Some((page, _)) = &back
+            && let Some(title) = translations.lookup(lang, &format!("pages.{page}.title"))
+        {
+            back = Some((page.clone(), title));
+        }
+    }
+    ctx.insert("back", &back.map(|(_, title)| title));
+
+    let own = format!("{}.html", status.as_u16());
+    let template = if tera.get_template_names().any(|name| name == own) {
+        own.as_str()
+    } else {
+        ERROR_TEMPLATE
+    };
+    match tera.render(template, &ctx) {
+        Ok(html) => {
+            let page = HttpResponse::build(status)
+                .content_type("text/html; charset=utf-8")
+                .body(html);
+            Ok(res.into_response(page).map_into_right_body())
+        }
+        Err(e) => {
+            error!("Error page '{template}' failed: {e:?}");
+            Ok(res.map_into_left_body())
+        }
+    }
+}
+
+/// Any URL nothing else answers
+pub async fn not_found(req: actix_web::HttpRequest) -> Result<HttpResponse, AppError> {
+    Err(AppError::PageNotFound(req.path().to_string()))
+}
+
+/// The player making `request`, if they have a session
+fn player_of(request: &actix_web::HttpRequest) -> Option<UserSession> {
+    request
+        .get_session()
+        .get::<UserSession>(SESSION_KEY)
+        .ok()
+        .flatten()
+}
+
+/// The page `player` is on and its title, if it's still there
+fn back_to(request: &actix_web::HttpRequest, player: &UserSession) -> Option<(PageId, String)> {
+    let world = request.app_data::<web::Data<WorldGraph>>()?;
+    let page = world.resolve(&player.current_page)?;
+    let title = world.snapshot().get(&page)?.title.clone();
+    Some((page, title))
+}
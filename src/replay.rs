@@ -0,0 +1,240 @@
+//! Recording the live world's ticks to disk, and replaying them to see the
+//! world as it stood at any recorded tick. For chasing down the emergent
+//! bugs players report: record while it happens, then replay to the tick
+//! where it went wrong.
+//!
+//! A recording is JSON lines. Every so many ticks a snapshot of all the
+//! actors is written; every tick after that, what went into it: the seed
+//! its rolls came from, the plans actors made, the actors spawned and the
+//! environments they all saw. Replaying starts from the last snapshot at or
+//! before the wanted tick and plays the ticks after it over again.
+//! What happens between ticks (admin edits, definition reloads, a player's
+//! blows) is only caught by the next snapshot, though where players stand
+//! is noted every tick.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use crate::actor::{Actor, ActorAction, ActorId, ActorManager};
+use crate::environment::{Environment, WorldTime};
+use crate::error::AppError;
+use crate::pages::{PageGraph, PageId};
+use crate::stimuli::Stimulus;
+
+/// Ticks between snapshots, unless `CHOTT_RECORD_SNAPSHOT_EVERY` says otherwise
+pub const DEFAULT_SNAPSHOT_EVERY: u64 = 100;
+
+/// Every actor and when each is next due, as the world stood before a tick
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tick: u64, // ticks done so far
+    pub actors: Vec<Actor>,
+    pub schedule: Vec<(u64, Vec<ActorId>)>, // tick -> actor ids due on it
+    pub players: Vec<(ActorId, u64)>,       // player id -> tick last seen
+    #[serde(default)]
+    pub grazed: Vec<(PageId, u32)>, // page -> bites of forage eaten off it
+    #[serde(default)]
+    pub stimuli: Vec<(PageId, Vec<Stimulus>)>, // page -> what can be heard and smelled there
+}
+
+/// What went into one tick, enough to play it again
+#[derive(Default, Serialize, Deserialize)]
+pub struct TickRecord {
+    pub tick: u64,
+    pub seed: u64, // every roll in the tick comes from this
+    pub world_time: WorldTime,
+    pub players: Vec<(Actor, u64)>, // as they stood when the tick began, and when last seen
+    pub spawned: Vec<Actor>,
+    #[serde(default)]
+    pub departed: Vec<ActorId>, // sent off, their scheduled event over
+    pub plans: Vec<(ActorId, Vec<ActorAction>)>,
+    pub environments: HashMap<PageId, Environment>,
+    pub teleports: Vec<(ActorId, PageId)>, // asked for by behavior scripts
+}
+
+/// One line of a recording
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entry {
+    Snapshot(Snapshot),
+    Tick(TickRecord),
+}
+
+/// Writes the live world's ticks to a recording as they happen
+pub struct Recorder {
+    file: BufWriter<File>,
+    snapshot_every: u64,
+}
+
+impl Recorder {
+    /// Record to `path`, adding on to what's recorded there already; each
+    /// run starts with a snapshot, so replays read the latest run's ticks
+    pub fn create(path: &Path, snapshot_every: u64) -> Result<Self, AppError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| AppError::OtherError(format!("Opening {}: {e}", path.display())))?;
+        info!("Recording ticks to {}", path.display());
+        Ok(Recorder {
+            file: BufWriter::new(file),
+            snapshot_every: snapshot_every.max(1),
+        })
+    }
+
+    /// The recorder `CHOTT_RECORD` asks for, if it's set
+    pub fn from_env() -> Result<Option<Self>, AppError> {
+        let Ok(path) = std::env::var("CHOTT_RECORD") else {
+            return Ok(None);
+        };
+        let every = std::env::var("CHOTT_RECORD_SNAPSHOT_EVERY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SNAPSHOT_EVERY);
+        Recorder::create(path.as_ref(), every).map(Some)
+    }
+
+    /// Whether a snapshot is due before tick `tick` + 1
+    pub fn snapshot_due(&self, tick: u64) -> bool {
+        tick.is_multiple_of(self.snapshot_every)
+    }
+
+    pub fn snapshot(&mut self, snapshot: Snapshot) {
+        self.write(&Entry::Snapshot(snapshot));
+    }
+
+    pub fn tick(&mut self, record: TickRecord) {
+        self.write(&Entry::Tick(record));
+    }
+
+    fn write(&mut self, entry: &Entry) {
+        let written = serde_json::to_writer(&mut self.file, entry)
+            .map_err(std::io::Error::from)
+            .and_then(|_| self.file.write_all(b"\n"))
+            .and_then(|_| self.file.flush());
+        if let Err(e) = written {
+            error!("Failed to write tick recording: {e}");
+        }
+    }
+}
+
+/// The recording the admin replay reads: `CHOTT_REPLAY`, else whatever is
+/// being recorded now
+#[derive(Clone)]
+pub struct ReplaySource(pub Option<PathBuf>);
+
+impl ReplaySource {
+    pub fn from_env() -> Self {
+        ReplaySource(
+            std::env::var("CHOTT_REPLAY")
+                .or_else(|_| std::env::var("CHOTT_RECORD"))
+                .ok()
+                .map(PathBuf::from),
+        )
+    }
+}
+
+/// The actors as they stood after tick `tick` in the recording at `path`,
+/// played out in `world`: a world of the same actors, regions and scripts,
+/// whose own state is thrown away
+pub fn replay(
+    path: &Path,
+    tick: u64,
+    mut world: ActorManager,
+    page_graph: &PageGraph,
+) -> Result<ActorManager, AppError> {
+    let file = File::open(path)
+        .map_err(|e| AppError::OtherError(format!("Opening {}: {e}", path.display())))?;
+    let mut start = None;
+    let mut ticks = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line =
+            line.map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let entry: Entry = serde_json::from_str(&line).map_err(|e| {
+            AppError::OtherError(format!("Line {} of {}: {e}", n + 1, path.display()))
+        })?;
+        match entry {
+            Entry::Snapshot(snapshot) if snapshot.tick <= tick => {
+                start = Some(snapshot);
+                ticks.clear();
+            }
+            Entry::Tick(record) if record.tick <= tick => ticks.push(record),
+            // later ticks, unless a restored save takes the world back
+            Entry::Snapshot(_) | Entry::Tick(_) => continue,
+        }
+    }
+    let start = start.ok_or_else(|| {
+        AppError::OtherError(format!(
+            "Tick {tick} was not recorded in {}",
+            path.display()
+        ))
+    })?;
+    let recorded = ticks.last().map_or(start.tick, |record| record.tick);
+    if recorded < tick {
+        return Err(AppError::OtherError(format!(
+            "{} only goes up to tick {recorded}",
+            path.display()
+        )));
+    }
+    info!(
+        from = start.tick,
+        to = tick,
+        "Replaying ticks from {}",
+        path.display()
+    );
+    world.restore(start);
+    for record in ticks {
+        world.replay_tick(record, page_graph);
+    }
+    Ok(world)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::tests::Setting;
+
+    const TICKS: u64 = 30;
+
+    #[test]
+    fn replaying_a_recording_gives_back_the_world_as_it_was() {
+        let path = std::env::temp_dir().join(format!("chott-replay-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let around = Setting::new();
+        let recorder = Recorder::create(&path, 10).unwrap();
+        let mut live = around.world(20).with_recorder(recorder);
+        let mut seen = Vec::new();
+        for _ in 0..TICKS {
+            around.tick(&mut live);
+            seen.push(serde_json::to_value(live.snapshot()).unwrap());
+        }
+        for tick in [1, 15, TICKS] {
+            let replayed = replay(
+                &path,
+                tick,
+                live.instance(around.bus.clone()),
+                &around.pages,
+            );
+            let replayed = serde_json::to_value(replayed.unwrap().snapshot()).unwrap();
+            assert_eq!(
+                replayed,
+                seen[tick as usize - 1],
+                "tick {tick} replayed otherwise"
+            );
+        }
+        assert!(
+            replay(
+                &path,
+                TICKS + 1,
+                live.instance(around.bus.clone()),
+                &around.pages
+            )
+            .is_err()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! What NPCs think of a player. Standing is kept per actor and per faction,
+//! in one table since faction names and actor ids don't overlap; what an
+//! actor thinks of a player is its own standing plus its faction's. Deeds
+//! move it, and it decides whether NPCs will talk, trade or fight.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::actor::Actor;
+
+/// Standing never goes past this, either way
+const MAX_STANDING: i32 = 100;
+
+/// Most standing any one gift buys, however much it's worth
+const MAX_GIFT_WEIGHT: i32 = 10;
+
+/// How an actor feels about a player, by their standing with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Regard {
+    Hostile, // won't talk or trade, and fighters go for the player
+    Wary,    // trades, at a markup
+    Neutral,
+    Friendly, // gives a discount
+    Devoted,  // gives a bigger one
+}
+
+impl Regard {
+    pub fn of(standing: i32) -> Self {
+        match standing {
+            ..=-30 => Regard::Hostile,
+            -29..=-10 => Regard::Wary,
+            -9..=9 => Regard::Neutral,
+            10..=29 => Regard::Friendly,
+            30.. => Regard::Devoted,
+        }
+    }
+
+    /// Key under `regard` in the translations
+    pub fn key(self) -> &'static str {
+        match self {
+            Regard::Hostile => "hostile",
+            Regard::Wary => "wary",
+            Regard::Neutral => "neutral",
+            Regard::Friendly => "friendly",
+            Regard::Devoted => "devoted",
+        }
+    }
+
+    /// What this actor charges for something worth `price` coins
+    pub fn charges(self, price: u32) -> u32 {
+        match self {
+            Regard::Hostile | Regard::Neutral => price,
+            Regard::Wary => price + price / 4,
+            Regard::Friendly => price - price / 10,
+            Regard::Devoted => price - price / 5,
+        }
+    }
+
+    /// What this actor pays for something it would give `price` coins for
+    pub fn pays(self, price: u32) -> u32 {
+        match self {
+            Regard::Hostile | Regard::Neutral => price,
+            Regard::Wary => price - price / 4,
+            Regard::Friendly => price + price / 10,
+            Regard::Devoted => price + price / 5,
+        }
+    }
+}
+
+/// Something a player did that NPCs take note of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Deed {
+    Attacked,
+    StruckDown,
+    Gifted(u32), // something worth this many coins
+    CaughtStealing,
+}
+
+impl Deed {
+    /// How much the deed moves the standing of whoever it was done to
+    pub fn weight(self) -> i32 {
+        match self {
+            Deed::Attacked => -15,
+            Deed::StruckDown => -25,
+            // even a pebble is the thought that counts
+            Deed::Gifted(value) => (1 + value as i32 / 2).min(MAX_GIFT_WEIGHT),
+            Deed::CaughtStealing => -20,
+        }
+    }
+}
+
+/// A player's standing with actors and factions, kept with their character
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Reputation {
+    standing: BTreeMap<String, i32>, // actor id or faction -> standing
+}
+
+impl Reputation {
+    /// Move the standing with `with` (an actor id or faction) by `change`
+    pub fn adjust(&mut self, with: &str, change: i32) {
+        let standing = self.standing.entry(with.to_string()).or_default();
+        *standing = (*standing + change).clamp(-MAX_STANDING, MAX_STANDING);
+    }
+
+    /// Take note of `deed` done to `actor`, and to its faction
+    pub fn record(&mut self, deed: Deed, actor: &Actor) {
+        self.adjust(actor.id.as_str(), deed.weight());
+        if let Some(faction) = &actor.faction {
+            self.adjust(faction, deed.weight() / 2);
+        }
+    }
+
+    /// The standing with an actor id or faction on its own
+    pub fn with(&self, with: &str) -> i32 {
+        self.standing.get(with).copied().unwrap_or_default()
+    }
+
+    /// What `actor` thinks of the player: its own standing and its faction's
+    pub fn standing(&self, actor: &Actor) -> i32 {
+        self.with(actor.id.as_str()) + actor.faction.as_deref().map_or(0, |f| self.with(f))
+    }
+
+    pub fn regard(&self, actor: &Actor) -> Regard {
+        Regard::of(self.standing(actor))
+    }
+}
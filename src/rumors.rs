@@ -0,0 +1,203 @@
+//! Rumors: what actors saw happen, carried about in their memory and passed
+//! on when they talk to each other. Every retelling weakens a rumor, and now
+//! and then twists it; a rumor nobody has been told again for long enough
+//! is forgotten.
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::environment::HazardKind;
+use crate::events::WorldEvent;
+use crate::pages::{PageGraph, PageId};
+
+/// Turns a rumor is remembered for when seen first hand
+pub const RUMOR_STRENGTH: u8 = 200;
+
+/// Turns of strength a rumor loses each time it is passed on
+const RETELLING_LOSS: u8 = 20;
+
+/// One in this many retellings gets a detail wrong
+const MUTATION_ODDS: u32 = 4;
+
+/// Most rumors an actor keeps in mind; the weakest go first
+const RUMORS_KEPT: usize = 5;
+
+/// Something that happened, as told by the people who saw it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Fact {
+    Fight { attacker: String, target: String },
+    StruckDown { who: String, by: String },
+    Theft { thief: String, victim: String },
+    Harmed { who: String, hazard: HazardKind },
+    Sickness { who: String },
+}
+
+impl Fact {
+    /// Key under `rumor` in the translations
+    pub fn key(&self) -> &'static str {
+        match self {
+            Fact::Fight { .. } => "fight",
+            Fact::StruckDown { .. } => "struck_down",
+            Fact::Theft { .. } => "theft",
+            Fact::Sickness { .. } => "sickness",
+            Fact::Harmed { hazard, .. } => match hazard {
+                HazardKind::Cold => "cold",
+                HazardKind::Heat => "heat",
+                HazardKind::Storm => "storm",
+            },
+        }
+    }
+
+    /// Names to fill into the translated text
+    pub fn args(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            Fact::Fight { attacker, target } => vec![("attacker", attacker), ("target", target)],
+            Fact::StruckDown { who, by } => vec![("who", who), ("by", by)],
+            Fact::Theft { thief, victim } => vec![("thief", thief), ("victim", victim)],
+            Fact::Harmed { who, .. } | Fact::Sickness { who } => vec![("who", who)],
+        }
+    }
+}
+
+/// A fact as one actor remembers it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rumor {
+    pub fact: Fact,
+    pub page: PageId, // where it is said to have happened
+    pub strength: u8, // turns left before it is forgotten
+    pub hops: u8,     // mouths it has passed through; 0 if seen first hand
+}
+
+impl Rumor {
+    /// What onlookers make of `event`, given a way to name the actors in it
+    pub fn witnessed(event: &WorldEvent, name: impl Fn(&str) -> String) -> Option<Self> {
+        let (fact, page) = match event {
+            WorldEvent::ActorAttacked {
+                attacker,
+                target,
+                page,
+            } => (
+                Fact::Fight {
+                    attacker: name(attacker.as_str()),
+                    target: name(target.as_str()),
+                },
+                page,
+            ),
+            WorldEvent::ActorWounded {
+                actor,
+                attacker,
+                page,
+                health,
+                ..
+            } if *health <= 0 => (
+                Fact::StruckDown {
+                    who: name(actor.as_str()),
+                    by: name(attacker.as_str()),
+                },
+                page,
+            ),
+            WorldEvent::ThiefCaught {
+                thief,
+                victim,
+                page,
+            } => (
+                Fact::Theft {
+                    thief: name(thief.as_str()),
+                    victim: name(victim.as_str()),
+                },
+                page,
+            ),
+            WorldEvent::ActorHarmed {
+                actor,
+                page,
+                hazard,
+                ..
+            } => (
+                Fact::Harmed {
+                    who: name(actor.as_str()),
+                    hazard: *hazard,
+                },
+                page,
+            ),
+            WorldEvent::ActorFellIll { actor, page } => (
+                Fact::Sickness {
+                    who: name(actor.as_str()),
+                },
+                page,
+            ),
+            _ => return None,
+        };
+        Some(Rumor {
+            fact,
+            page: page.clone(),
+            strength: RUMOR_STRENGTH,
+            hops: 0,
+        })
+    }
+
+    /// The rumor as the next person hears it: weaker, and with odds of a
+    /// detail gone astray. None once it is too weak to bother passing on.
+    pub fn retold(&self, rng: &mut StdRng, page_graph: &PageGraph) -> Option<Self> {
+        let strength = self.strength.checked_sub(RETELLING_LOSS)?;
+        let mut heard = Rumor {
+            strength,
+            hops: self.hops.saturating_add(1),
+            ..self.clone()
+        };
+        if rng.random_ratio(1, MUTATION_ODDS) {
+            heard.mutate(rng, page_graph);
+        }
+        Some(heard)
+    }
+
+    /// Get one detail wrong: who did what to whom, or where
+    fn mutate(&mut self, rng: &mut StdRng, page_graph: &PageGraph) {
+        if rng.random_bool(0.5)
+            && let Fact::Fight { attacker, target } = &mut self.fact
+        {
+            std::mem::swap(attacker, target);
+            return;
+        }
+        let nearby: Vec<&PageId> = page_graph
+            .get(&self.page)
+            .map(|page| page.connections.iter().map(|c| &c.target).collect())
+            .unwrap_or_default();
+        if !nearby.is_empty() {
+            self.page = nearby[rng.random_range(0..nearby.len())].clone();
+        }
+    }
+}
+
+/// Keep `rumor` in mind among `known`, unless the same tale is already
+/// known more strongly. The weakest is forgotten when there are too many.
+pub fn learn(known: &mut Vec<Rumor>, rumor: Rumor) {
+    if let Some(same) = known
+        .iter_mut()
+        .find(|r| r.fact == rumor.fact && r.page == rumor.page)
+    {
+        if rumor.strength > same.strength {
+            *same = rumor;
+        }
+        return;
+    }
+    known.push(rumor);
+    if known.len() > RUMORS_KEPT {
+        known.sort_by_key(|r| std::cmp::Reverse(r.strength));
+        known.truncate(RUMORS_KEPT);
+    }
+}
+
+/// One turn's fading of everything `known`; forgotten rumors are dropped
+pub fn fade(known: &mut Vec<Rumor>) {
+    known.retain_mut(|rumor| {
+        rumor.strength = rumor.strength.saturating_sub(1);
+        rumor.strength > 0
+    });
+}
+
+/// The freshest rumor among `known`, the one an actor would bring up first
+pub fn freshest(known: &[Rumor]) -> Option<&Rumor> {
+    known.iter().max_by_key(|r| r.strength)
+}
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Local};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::info;
+
+use crate::actor::ActorManager;
+use crate::clock::WorldClock;
+use crate::environment::{EnvironmentManager, WeatherOverride};
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::items::ItemId;
+use crate::pages::{PageGraph, PageId};
+use crate::persistence::write_json_atomic;
+use crate::replay::Snapshot;
+use crate::shops::ShopManager;
+use crate::weather::WeatherEngine;
+use crate::world::{PageFlags, WorldGraph};
+
+/// Default directory for save slots, relative to the working directory
+pub const DEFAULT_SAVES_DIR: &str = "saves";
+
+/// The whole world at one moment: everything a restore puts back.
+/// Players' own characters live in their sessions and aren't included.
+#[derive(Serialize, Deserialize)]
+pub struct WorldSave {
+    pub clock: DateTime<Local>, // world time when saved
+    pub pages: PageGraph,
+    pub redirects: HashMap<PageId, PageId>,
+    pub page_flags: PageFlags,
+    pub actors: Snapshot,
+    pub weather: WeatherEngine,
+    #[serde(default)]
+    pub weather_overrides: Vec<(PageId, WeatherOverride)>,
+    pub shops: HashMap<PageId, HashMap<ItemId, u32>>,
+}
+
+/// A save slot as listed for admins
+#[derive(Serialize)]
+pub struct SaveInfo {
+    pub name: String,
+    pub saved: DateTime<Local>, // real time the file was written
+}
+
+/// Named save slots for the live world, one JSON file each in a directory
+#[derive(Clone)]
+pub struct SaveSlots {
+    dir: PathBuf,
+    clock: WorldClock,
+    world: WorldGraph,
+    actors: Arc<Mutex<ActorManager>>, // the shared world's
+    environment: EnvironmentManager,
+    shops: ShopManager,
+    bus: EventBus,
+}
+
+impl SaveSlots {
+    /// Save slots in `CHOTT_SAVES`, else `DEFAULT_SAVES_DIR`, for the world
+    /// these parts make up
+    pub fn from_env(
+        clock: WorldClock,
+        world: WorldGraph,
+        actors: Arc<Mutex<ActorManager>>,
+        environment: EnvironmentManager,
+        shops: ShopManager,
+        bus: EventBus,
+    ) -> Self {
+        SaveSlots {
+            dir: std::env::var("CHOTT_SAVES")
+                .unwrap_or(DEFAULT_SAVES_DIR.to_string())
+                .into(),
+            clock,
+            world,
+            actors,
+            environment,
+            shops,
+            bus,
+        }
+    }
+
+    /// The file for slot `name`. Names are lowercase letters, digits and
+    /// dashes, so they can't reach outside the saves directory.
+    fn path(&self, name: &str) -> Result<PathBuf, AppError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid {
+            return Err(AppError::OtherError(format!(
+                "Save name '{name}' must be lowercase letters, digits and dashes"
+            )));
+        }
+        Ok(self.dir.join(format!("{name}.json")))
+    }
+
+    /// Every save slot, newest first. No directory yet means no saves.
+    pub fn list(&self) -> Result<Vec<SaveInfo>, AppError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(AppError::OtherError(format!(
+                    "Reading {}: {e}",
+                    self.dir.display()
+                )));
+            }
+        };
+        let mut saves: Vec<SaveInfo> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let saved = entry
+                    .metadata()
+                    .and_then(|meta| meta.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                Some(SaveInfo {
+                    name: path.file_stem()?.to_str()?.to_string(),
+                    saved: saved.into(),
+                })
+            })
+            .collect();
+        saves.sort_by(|a, b| b.saved.cmp(&a.saved).then_with(|| a.name.cmp(&b.name)));
+        Ok(saves)
+    }
+
+    /// Save the world as it is right now to slot `name`, replacing whatever
+    /// was there
+    pub fn save(&self, name: &str) -> Result<(), AppError> {
+        let path = self.path(name)?;
+        let save = WorldSave {
+            clock: self.clock.now(),
+            pages: PageGraph::clone(&self.world.snapshot()),
+            redirects: self.world.redirects(),
+            page_flags: PageFlags::clone(&self.world.page_flags()),
+            actors: self.actors.lock().snapshot(),
+            weather: self.environment.weather()?,
+            weather_overrides: self.environment.weather_overrides()?,
+            shops: self.shops.stock(),
+        };
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| AppError::OtherError(format!("Creating {}: {e}", self.dir.display())))?;
+        write_json_atomic(&path, &save)?;
+        info!("World saved to slot '{name}'");
+        Ok(())
+    }
+
+    /// Put the world back as slot `name` has it, announced as `WorldRestored`
+    pub fn restore(&self, name: &str) -> Result<(), AppError> {
+        let path = self.path(name)?;
+        let text = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::OtherError(format!("No save named '{name}'")),
+            _ => AppError::OtherError(format!("Reading {}: {e}", path.display())),
+        })?;
+        let save: WorldSave = serde_json::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        self.world
+            .restore(save.pages, save.redirects, save.page_flags);
+        self.actors.lock().restore(save.actors);
+        self.environment
+            .restore_weather(save.weather, save.weather_overrides)?;
+        self.shops.restore(save.shops);
+        self.clock.advance(save.clock - self.clock.now());
+        info!("World restored from slot '{name}'");
+        self.bus.publish(WorldEvent::WorldRestored {
+            save: name.to_string(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::tests::Setting;
+    use crate::clock::ClockConfig;
+    use crate::environment::EnvironmentTtl;
+    use crate::regions::Regions;
+
+    fn slots() -> SaveSlots {
+        let around = Setting::new();
+        let clock = WorldClock::new(ClockConfig::default());
+        let world = WorldGraph::new((*around.pages).clone(), around.bus.clone());
+        let environment = EnvironmentManager::new(
+            around.bus.clone(),
+            EnvironmentTtl::default(),
+            clock.clone(),
+            world.clone(),
+            Arc::new(Regions::default()),
+        );
+        SaveSlots {
+            dir: PathBuf::from("saves"),
+            clock,
+            world,
+            actors: Arc::new(Mutex::new(around.world(0))),
+            environment,
+            shops: ShopManager::default(),
+            bus: around.bus,
+        }
+    }
+
+    #[test]
+    fn slot_names_stay_inside_the_saves_directory() {
+        let slots = slots();
+        assert_eq!(
+            slots.path("before-the-flood-2").unwrap(),
+            PathBuf::from("saves/before-the-flood-2.json")
+        );
+        for name in ["", "../etc/passwd", "..", "a/b", "/tmp/x", "Spring", "a.b"] {
+            assert!(slots.path(name).is_err(), "{name:?} let through");
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Scheduled world events: things that happen at a set hour of the world's
+//! days, read from a data file. An event can bring actors to a page, as a
+//! market brings its merchants of a morning, and hold the weather over one,
+//! as a storm that blows in every few nights does, and either is over again
+//! after so many hours. Events fire as the world ticks, once on each day
+//! they're due.
+
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use crate::actor::{Actor, ActorId, ActorMap};
+use crate::definitions::Archetypes;
+use crate::environment::WorldTime;
+use crate::error::AppError;
+use crate::pages::{PageGraph, PageId};
+use crate::scripting::ScriptEffect;
+use crate::spawner::{self, Species};
+use crate::weather::{WeatherKind, WeatherState};
+
+/// Default location of the schedule file, relative to the working directory
+pub const DEFAULT_SCHEDULE_PATH: &str = "data/schedule.toml";
+
+/// Actors an event brings to a page, for as long as it lasts
+#[derive(Clone, Debug, Deserialize)]
+pub struct Arrival {
+    pub page: PageId,
+    #[serde(default = "default_count")]
+    pub count: usize,
+    pub species: Species,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+/// Weather an event holds over a page, for as long as it lasts
+#[derive(Clone, Debug, Deserialize)]
+pub struct Spell {
+    pub page: PageId,
+    pub kind: WeatherKind,
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+fn default_intensity() -> f32 {
+    0.8
+}
+
+/// Fires at hour `at` of every `every_days`th day, and is
+/// over `lasts_hours` later
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScheduledEvent {
+    pub id: String, // its actors are "<id>-<n>"; announced on its pages by this name
+    pub at: u8,
+    #[serde(default = "default_every_days")]
+    pub every_days: i64,
+    pub lasts_hours: u32,
+    #[serde(skip)]
+    pub spawn: Vec<Arrival>, // read apart, for their species' archetypes
+    #[serde(default)]
+    pub weather: Vec<Spell>,
+}
+
+fn default_every_days() -> i64 {
+    1
+}
+
+impl ScheduledEvent {
+    /// Whether it fires at `world_time`
+    fn due(&self, world_time: &WorldTime) -> bool {
+        world_time.hour == self.at % 24 && world_time.day.rem_euclid(self.every_days.max(1)) == 0
+    }
+
+    /// Every page it happens on, each once
+    fn pages(&self) -> Vec<&PageId> {
+        let mut pages: Vec<&PageId> = Vec::new();
+        for page in self
+            .spawn
+            .iter()
+            .map(|a| &a.page)
+            .chain(self.weather.iter().map(|s| &s.page))
+        {
+            if !pages.contains(&page) {
+                pages.push(page);
+            }
+        }
+        pages
+    }
+}
+
+#[derive(Deserialize)]
+struct ScheduleFile {
+    #[serde(default)]
+    event: Vec<toml::Table>,
+}
+
+/// An event that has fired and isn't over yet
+#[derive(Default)]
+struct Running {
+    until: i64,               // in hours since the common era began
+    actors: HashSet<ActorId>, // the ones it brought, still about
+}
+
+/// Fires the scheduled events, remembering what each one has out in the world
+#[derive(Default)]
+pub struct Schedule {
+    events: Vec<ScheduledEvent>,
+    fired: HashMap<String, i64>, // event id -> day it last fired
+    running: HashMap<String, Running>,
+    spawned: u64, // actors brought since startup, for ids
+}
+
+impl Schedule {
+    /// The same events with none of them fired yet, for a new world
+    pub fn fresh(&self) -> Schedule {
+        Schedule {
+            events: self.events.clone(),
+            ..Schedule::default()
+        }
+    }
+
+    /// Read scheduled events from a TOML file, species archetypes taken
+    /// from `archetypes`. A missing file just means nothing scheduled.
+    pub fn load(path: &Path, archetypes: &Archetypes) -> Result<Self, AppError> {
+        if !path.exists() {
+            return Ok(Schedule::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: ScheduleFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        let mut events = Vec::new();
+        for (i, mut table) in file.event.into_iter().enumerate() {
+            let invalid = |e: String| {
+                AppError::OtherError(format!("Event #{} in {}: {e}", i + 1, path.display()))
+            };
+            let arrivals = match table.remove("spawn") {
+                Some(toml::Value::Array(arrivals)) => arrivals,
+                Some(_) => return Err(invalid("spawn must be a list".to_string())),
+                None => Vec::new(),
+            };
+            let mut event: ScheduledEvent = toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| invalid(e.to_string()))?;
+            for arrival in arrivals {
+                let toml::Value::Table(arrival) = arrival else {
+                    return Err(invalid("each spawn must be a table".to_string()));
+                };
+                event
+                    .spawn
+                    .push(spawner::with_species(arrival, archetypes).map_err(invalid)?);
+            }
+            if event.at >= 24 {
+                return Err(invalid(format!("no hour {} in a day", event.at)));
+            }
+            events.push(event);
+        }
+        Ok(Schedule {
+            events,
+            ..Schedule::default()
+        })
+    }
+
+    /// Whether `id` is an actor one of the events brought
+    pub fn owns(&self, id: &ActorId) -> bool {
+        self.running.values().any(|r| r.actors.contains(id))
+    }
+
+    /// What the events due at `world_time` bring: new actors, and their
+    /// asks of the wider world, the weather they hold and their announcing
+    /// on each page they happen on
+    pub fn due(
+        &mut self,
+        world_time: &WorldTime,
+        actors: &ActorMap,
+        page_graph: &PageGraph,
+    ) -> (Vec<Actor>, Vec<ScriptEffect>) {
+        let mut born = Vec::new();
+        let mut effects = Vec::new();
+        for event in &self.events {
+            if !event.due(world_time) || self.fired.get(&event.id) == Some(&world_time.day) {
+                continue;
+            }
+            self.fired.insert(event.id.clone(), world_time.day);
+            info!(event = %event.id, day = world_time.day, "Scheduled event fires.");
+            let running = self.running.entry(event.id.clone()).or_default();
+            running.until = world_time.hours() + event.lasts_hours as i64;
+            for arrival in &event.spawn {
+                if !page_graph.contains_key(&arrival.page) {
+                    warn!(event = %event.id, page = %arrival.page, "No such page to spawn on");
+                    continue;
+                }
+                for _ in 0..arrival.count {
+                    let id = loop {
+                        self.spawned += 1;
+                        let id = ActorId::from(format!("{}-{}", event.id, self.spawned));
+                        if !actors.contains_key(&id) {
+                            break id;
+                        }
+                    };
+                    debug!(event = %event.id, %id, page = %arrival.page, "Brought by the event.");
+                    running.actors.insert(id.clone());
+                    let species = arrival.species.clone();
+                    born.push(Actor::from_definition(species.at(id, arrival.page.clone())));
+                }
+            }
+            for spell in &event.weather {
+                effects.push(ScriptEffect::SetWeather {
+                    page: spell.page.clone(),
+                    state: WeatherState {
+                        kind: spell.kind,
+                        intensity: spell.intensity.clamp(0.0, 1.0),
+                    },
+                    minutes: Some(event.lasts_hours * 60),
+                });
+            }
+            for page in event.pages() {
+                effects.push(ScriptEffect::Emit {
+                    page: page.clone(),
+                    name: event.id.clone(),
+                });
+            }
+        }
+        (born, effects)
+    }
+
+    /// The actors of the events that are over by `world_time`, to be on
+    /// their way; their weather wears off by itself
+    pub fn over(&mut self, world_time: &WorldTime, actors: &ActorMap) -> Vec<ActorId> {
+        let now = world_time.hours();
+        let mut leaving = Vec::new();
+        self.running.retain(|event, running| {
+            running.actors.retain(|id| actors.contains_key(id)); // gone, one way or another
+            if now < running.until {
+                return true;
+            }
+            debug!(%event, leaving = running.actors.len(), "Scheduled event over.");
+            leaving.extend(running.actors.drain());
+            false
+        });
+        leaving.sort();
+        leaving
+    }
+}
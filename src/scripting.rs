@@ -0,0 +1,479 @@
+use chrono::TimeDelta;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{AST, Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+
+use crate::actor::{ActorAction, ActorId, ActorManager};
+use crate::environment::{EnvironmentManager, OverrideSource, WorldTime};
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::pages::{PageGraph, PageId};
+use crate::session::UserSession;
+use crate::weather::{WeatherKind, WeatherState};
+use crate::world::{PageFlags, WorldGraph};
+
+/// Default directory of world scripts, relative to the working directory
+pub const DEFAULT_SCRIPTS_PATH: &str = "scripts";
+
+// What one script call may use up before it is stopped
+const MAX_OPERATIONS: u64 = 50_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 4_096;
+const MAX_ARRAY_SIZE: usize = 1_024;
+const MAX_MAP_SIZE: usize = 256;
+
+/// A change a script asked for. Scripts never touch the world directly;
+/// their effects are carried out once they have finished.
+#[derive(Clone, Debug)]
+pub enum ScriptEffect {
+    SetFlag(String), // on the player
+    ClearFlag(String),
+    SetPageFlag {
+        page: PageId,
+        flag: String,
+        set: bool,
+    },
+    MoveActor {
+        actor: ActorId,
+        to: PageId,
+    },
+    Emit {
+        page: PageId,
+        name: String,
+    },
+    /// Hold the weather on a page, as an admin's override would but giving way to one
+    SetWeather {
+        page: PageId,
+        state: WeatherState,
+        minutes: Option<u32>, // of world time; None holds until cleared
+    },
+    ClearWeather {
+        page: PageId,
+    },
+    /// Next action for the actor whose behavior is being run
+    Plan(ActorAction),
+}
+
+/// The `ctx` handed to every script function: what it can see of the world,
+/// and the changes it has asked for so far
+#[derive(Clone)]
+pub struct ScriptContext {
+    page: PageId,
+    hour: u8,
+    player_flags: Option<HashSet<String>>, // None: no player involved
+    page_flags: Arc<PageFlags>,
+    actor: Option<ActorId>, // the actor whose behavior this is
+    exits: Vec<PageId>,     // where that actor can go from here
+    read_only: bool,        // conditions look but don't touch
+    effects: Arc<Mutex<Vec<ScriptEffect>>>,
+}
+
+impl ScriptContext {
+    /// A script running on `page` at world hour `hour`
+    pub fn new(page: &PageId, hour: u8, page_flags: Arc<PageFlags>) -> Self {
+        ScriptContext {
+            page: page.clone(),
+            hour,
+            player_flags: None,
+            page_flags,
+            actor: None,
+            exits: Vec::new(),
+            read_only: false,
+            effects: Arc::default(),
+        }
+    }
+
+    /// On behalf of a player with these story flags
+    pub fn player(mut self, flags: &HashSet<String>) -> Self {
+        self.player_flags = Some(flags.clone());
+        self
+    }
+
+    /// As the behavior of actor `id`, which can leave by `page`'s connections
+    pub fn acting(mut self, id: &ActorId, pages: &PageGraph) -> Self {
+        self.actor = Some(id.clone());
+        self.exits = pages
+            .get(&self.page)
+            .map(|page| page.connections.iter().map(|c| c.target.clone()).collect())
+            .unwrap_or_default();
+        self
+    }
+
+    /// Only able to look, for conditions
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    fn push(&mut self, effect: ScriptEffect) -> Result<(), Box<EvalAltResult>> {
+        if self.read_only {
+            return Err("conditions can't change the world".into());
+        }
+        self.effects
+            .lock()
+            .expect("Failed to lock Mutex")
+            .push(effect);
+        Ok(())
+    }
+
+    fn has_page_flag(&self, page: &str, flag: &str) -> bool {
+        self.page_flags
+            .get(&PageId::from(page))
+            .is_some_and(|flags| flags.contains(flag))
+    }
+
+    fn set_weather(
+        &mut self,
+        page: PageId,
+        kind: &str,
+        intensity: f64,
+        minutes: Option<i64>,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let kind = WeatherKind::ALL
+            .into_iter()
+            .find(|k| k.to_string().eq_ignore_ascii_case(kind))
+            .ok_or_else(|| format!("no weather called '{kind}'"))?;
+        let minutes = minutes
+            .map(u32::try_from)
+            .transpose()
+            .map_err(|_| "weather can't be held for negative minutes")?;
+        self.push(ScriptEffect::SetWeather {
+            page,
+            state: WeatherState {
+                kind,
+                intensity: (intensity as f32).clamp(0.0, 1.0),
+            },
+            minutes,
+        })
+    }
+
+    fn plan(&mut self, action: ActorAction) -> Result<(), Box<EvalAltResult>> {
+        if self.actor.is_none() {
+            return Err("only actor behaviors can plan actions".into());
+        }
+        self.push(ScriptEffect::Plan(action))
+    }
+}
+
+/// Every script under the scripts directory, compiled once at startup, and
+/// the sandboxed engine that runs them. Scripts are referred to as
+/// "file::function", e.g. "lamplighter::act" for `fn act(ctx)` in
+/// `scripts/lamplighter.rhai`.
+#[derive(Clone)]
+pub struct ScriptHost {
+    engine: Arc<Engine>,
+    scripts: Arc<HashMap<String, AST>>, // file stem -> compiled script
+}
+
+impl Default for ScriptHost {
+    fn default() -> Self {
+        ScriptHost {
+            engine: Arc::new(sandboxed_engine()),
+            scripts: Arc::default(),
+        }
+    }
+}
+
+impl ScriptHost {
+    /// Compile every `.rhai` file in `dir`. A missing directory means no scripts.
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        let engine = sandboxed_engine();
+        let mut scripts = HashMap::new();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("No scripts directory at {}", dir.display());
+                return Ok(ScriptHost::default());
+            }
+            Err(e) => {
+                return Err(AppError::OtherError(format!(
+                    "Reading {}: {e}",
+                    dir.display()
+                )));
+            }
+        };
+        for entry in entries {
+            let path = entry
+                .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", dir.display())))?
+                .path();
+            if path.extension().is_none_or(|ext| ext != "rhai") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let ast = engine
+                .compile_file(path.clone())
+                .map_err(|e| AppError::OtherError(format!("Compiling {}: {e}", path.display())))?;
+            scripts.insert(name.to_string(), ast);
+        }
+        info!("Loaded {} script(s) from {}", scripts.len(), dir.display());
+        Ok(ScriptHost {
+            engine: Arc::new(engine),
+            scripts: Arc::new(scripts),
+        })
+    }
+
+    fn call<T: Clone + Send + Sync + 'static>(
+        &self,
+        script: &str,
+        ctx: ScriptContext,
+    ) -> Result<T, AppError> {
+        let (file, function) = script.split_once("::").ok_or_else(|| {
+            AppError::OtherError(format!("Script '{script}' should be 'file::function'"))
+        })?;
+        let ast = self
+            .scripts
+            .get(file)
+            .ok_or_else(|| AppError::OtherError(format!("No script file '{file}'")))?;
+        debug!("Running script {script}");
+        self.engine
+            .call_fn_with_options(
+                CallFnOptions::new().eval_ast(false),
+                &mut Scope::new(),
+                ast,
+                function,
+                (ctx,),
+            )
+            .map_err(|e| AppError::OtherError(format!("Script {script}: {e}")))
+    }
+
+    /// Whether the condition script holds. A failing script counts as false.
+    pub fn check(&self, script: &str, ctx: ScriptContext) -> bool {
+        self.call::<bool>(script, ctx.read_only())
+            .inspect_err(|e| warn!("{e}"))
+            .unwrap_or(false)
+    }
+
+    /// Run a script for what it does; returns the changes it asked for
+    pub fn run(&self, script: &str, ctx: ScriptContext) -> Result<Vec<ScriptEffect>, AppError> {
+        let effects = ctx.effects.clone();
+        let _ = self.call::<Dynamic>(script, ctx)?; // what it returns is ignored
+        let effects = std::mem::take(&mut *effects.lock().expect("Failed to lock Mutex"));
+        Ok(effects)
+    }
+
+    /// Run an actor's behavior script: the actions it planned, and any other
+    /// changes it asked for. Nothing planned leaves the built-in behavior to decide.
+    pub fn behave(
+        &self,
+        script: &str,
+        ctx: ScriptContext,
+    ) -> (Vec<ActorAction>, Vec<ScriptEffect>) {
+        let effects = self.run(script, ctx).unwrap_or_else(|e| {
+            warn!("{e}");
+            Vec::new()
+        });
+        let mut plan = Vec::new();
+        let mut rest = Vec::new();
+        for effect in effects {
+            match effect {
+                ScriptEffect::Plan(action) => plan.push(action),
+                other => rest.push(other),
+            }
+        }
+        (plan, rest)
+    }
+}
+
+/// Carry out what scripts asked for. Player flags go on `player`, if there is one.
+pub fn apply(
+    effects: Vec<ScriptEffect>,
+    mut player: Option<&mut UserSession>,
+    world: &WorldGraph,
+    actors: &parking_lot::Mutex<ActorManager>,
+    environment: &EnvironmentManager,
+    bus: &EventBus,
+) -> Result<(), AppError> {
+    for effect in effects {
+        match effect {
+            ScriptEffect::SetFlag(flag) => {
+                if let Some(player) = player.as_deref_mut() {
+                    player.flags.insert(flag);
+                }
+            }
+            ScriptEffect::ClearFlag(flag) => {
+                if let Some(player) = player.as_deref_mut() {
+                    player.flags.remove(&flag);
+                }
+            }
+            ScriptEffect::SetPageFlag { page, flag, set } => {
+                world.set_page_flag(&page, &flag, set)?;
+            }
+            ScriptEffect::MoveActor { actor, to } => {
+                if !world.snapshot().contains_key(&to) {
+                    return Err(AppError::PageNotFound(to.to_string()));
+                }
+                actors.lock().teleport(actor.as_str(), &to)?;
+            }
+            ScriptEffect::Emit { page, name } => {
+                bus.publish(WorldEvent::ScriptEmitted { page, name });
+            }
+            ScriptEffect::SetWeather {
+                page,
+                state,
+                minutes,
+            } => {
+                if !world.snapshot().contains_key(&page) {
+                    return Err(AppError::PageNotFound(page.to_string()));
+                }
+                let lasts = minutes.map(|minutes| TimeDelta::minutes(minutes.into()));
+                if !environment.override_weather(&page, state, lasts, OverrideSource::Script)? {
+                    debug!("Script weather on {page} refused; an admin's override holds");
+                }
+            }
+            ScriptEffect::ClearWeather { page } => {
+                environment.release_weather(&page, OverrideSource::Script)?;
+            }
+            ScriptEffect::Plan(action) => {
+                warn!("Planned {action:?} outside an actor behavior; ignored");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An engine that can't reach outside the world API: no imports, no `eval`,
+/// and bounded in how long it runs and how much it allocates
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE)
+        .on_print(|text| info!("script: {text}"))
+        .on_debug(|text, source, pos| debug!("script {source:?} {pos}: {text}"));
+
+    engine
+        .register_type_with_name::<ScriptContext>("Context")
+        .register_get("page", |ctx: &mut ScriptContext| ctx.page.to_string())
+        .register_get("hour", |ctx: &mut ScriptContext| ctx.hour as i64)
+        .register_get("night", |ctx: &mut ScriptContext| {
+            WorldTime {
+                hour: ctx.hour,
+                ..WorldTime::default()
+            }
+            .is_night()
+        })
+        .register_get("actor", |ctx: &mut ScriptContext| {
+            ctx.actor
+                .as_ref()
+                .map(ActorId::to_string)
+                .unwrap_or_default()
+        })
+        .register_get("exits", |ctx: &mut ScriptContext| -> Array {
+            ctx.exits
+                .iter()
+                .map(|page| Dynamic::from(page.to_string()))
+                .collect()
+        })
+        .register_fn("has_flag", |ctx: &mut ScriptContext, flag: &str| {
+            ctx.player_flags
+                .as_ref()
+                .is_some_and(|flags| flags.contains(flag))
+        })
+        .register_fn("set_flag", |ctx: &mut ScriptContext, flag: &str| {
+            ctx.push(ScriptEffect::SetFlag(flag.to_string()))
+        })
+        .register_fn("clear_flag", |ctx: &mut ScriptContext, flag: &str| {
+            ctx.push(ScriptEffect::ClearFlag(flag.to_string()))
+        })
+        .register_fn("page_flag", |ctx: &mut ScriptContext, flag: &str| {
+            let page = ctx.page.to_string();
+            ctx.has_page_flag(&page, flag)
+        })
+        .register_fn(
+            "page_flag",
+            |ctx: &mut ScriptContext, page: &str, flag: &str| ctx.has_page_flag(page, flag),
+        )
+        .register_fn(
+            "set_page_flag",
+            |ctx: &mut ScriptContext, flag: &str, set: bool| {
+                let page = ctx.page.clone();
+                ctx.push(ScriptEffect::SetPageFlag {
+                    page,
+                    flag: flag.to_string(),
+                    set,
+                })
+            },
+        )
+        .register_fn(
+            "set_page_flag",
+            |ctx: &mut ScriptContext, page: &str, flag: &str, set: bool| {
+                ctx.push(ScriptEffect::SetPageFlag {
+                    page: PageId::from(page),
+                    flag: flag.to_string(),
+                    set,
+                })
+            },
+        )
+        .register_fn(
+            "move_actor",
+            |ctx: &mut ScriptContext, actor: &str, to: &str| {
+                ctx.push(ScriptEffect::MoveActor {
+                    actor: ActorId::from(actor),
+                    to: PageId::from(to),
+                })
+            },
+        )
+        .register_fn("emit", |ctx: &mut ScriptContext, name: &str| {
+            let page = ctx.page.clone();
+            ctx.push(ScriptEffect::Emit {
+                page,
+                name: name.to_string(),
+            })
+        })
+        .register_fn(
+            "set_weather",
+            |ctx: &mut ScriptContext, kind: &str, intensity: f64| {
+                let page = ctx.page.clone();
+                ctx.set_weather(page, kind, intensity, None)
+            },
+        )
+        .register_fn(
+            "set_weather",
+            |ctx: &mut ScriptContext, kind: &str, intensity: f64, minutes: i64| {
+                let page = ctx.page.clone();
+                ctx.set_weather(page, kind, intensity, Some(minutes))
+            },
+        )
+        .register_fn(
+            "set_weather",
+            |ctx: &mut ScriptContext, page: &str, kind: &str, intensity: f64, minutes: i64| {
+                ctx.set_weather(PageId::from(page), kind, intensity, Some(minutes))
+            },
+        )
+        .register_fn("clear_weather", |ctx: &mut ScriptContext| {
+            let page = ctx.page.clone();
+            ctx.push(ScriptEffect::ClearWeather { page })
+        })
+        .register_fn("clear_weather", |ctx: &mut ScriptContext, page: &str| {
+            ctx.push(ScriptEffect::ClearWeather {
+                page: PageId::from(page),
+            })
+        })
+        .register_fn("move_to", |ctx: &mut ScriptContext, to: &str| {
+            let to = PageId::from(to);
+            if !ctx.exits.contains(&to) {
+                return Err(format!("no way to {to} from {}", ctx.page).into());
+            }
+            ctx.plan(ActorAction::MoveTo(to))
+        })
+        .register_fn("sleep", |ctx: &mut ScriptContext| {
+            ctx.plan(ActorAction::Sleep)
+        })
+        .register_fn("wake", |ctx: &mut ScriptContext| {
+            ctx.plan(ActorAction::WakeUp)
+        })
+        .register_fn("idle", |ctx: &mut ScriptContext| {
+            ctx.plan(ActorAction::Idle)
+        });
+    engine
+}
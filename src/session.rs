@@ -1,27 +1,339 @@
 use actix_session::Session;
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use tracing::{info, warn};
 
+use crate::actor::ActorId;
+use crate::character::{Character, DEFAULT_STAT};
+use crate::chat;
+use crate::cooldown::PlayerAction;
 use crate::error::AppError;
+use crate::items::{ItemCatalog, ItemId};
+use crate::mounts::{Means, RIDING_STAMINA};
 use crate::pages::PageId;
+use crate::quests::QuestProgress;
+use crate::reputation::Reputation;
+use crate::shops::STARTING_COINS;
 
 pub const SESSION_KEY: &str = "user_session";
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Session key of one-shot messages waiting for the player's next page
+const FLASH_KEY: &str = "flash";
+
+/// Layout version of `UserSession`. Bump it, and add a step to `MIGRATIONS`,
+/// when a change needs more than a serde default to read old sessions.
+pub const SESSION_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a stored session from version `n` to `n + 1`
+const MIGRATIONS: [fn(&mut Map<String, Value>); SESSION_VERSION as usize] = [migrate_v0];
+
+/// How many journal entries a session keeps (they live in the cookie)
+const JOURNAL_LEN: usize = 20;
+
+/// Window, in real seconds, over which moves count against stamina
+const STAMINA_WINDOW_SECS: i64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct UserSession {
+    #[serde(default)]
+    pub version: u32, // 0 for sessions from before versioning
+    #[serde(default = "new_player_id")]
+    pub player_id: ActorId, // id of the player's actor in the world
     pub current_page: PageId,
+    #[serde(default)]
+    pub visits: HashMap<PageId, u32>, // page id -> times arrived there
+    #[serde(default)]
+    pub flags: HashSet<String>, // quest/story flags
+    #[serde(default)]
+    pub inventory: Vec<ItemId>,
+    #[serde(default)]
+    pub journal: VecDeque<JournalEntry>, // recent things that happened to the player, oldest first
+    #[serde(default)]
+    pub character: Option<Character>, // None until the player makes one
+    #[serde(default)]
+    pub recent_moves: VecDeque<i64>, // real unix seconds of moves in the stamina window
+    #[serde(default)]
+    pub quests: HashMap<String, QuestProgress>, // quest id -> progress, once started
+    #[serde(default)]
+    pub talked_to: HashSet<String>, // ids of NPCs who have spoken to the player
+    #[serde(default = "starting_coins")]
+    pub coins: u32,
+    #[serde(default)]
+    pub language: Option<String>, // None: the default language
+    #[serde(default)]
+    pub text_only: bool, // plain pages, for screen readers and slow connections
+    #[serde(default)]
+    pub seen: HashMap<ActorId, PageId>, // actor id -> page the player last saw them on
+    #[serde(default)]
+    pub reputation: Reputation, // what NPCs and their factions think of the player
+    #[serde(default)]
+    pub wear: HashMap<ItemId, u32>, // item id -> uses spent of the one in hand
+    #[serde(default)]
+    pub flags_until: HashMap<String, i64>, // flag -> world clock (unix seconds) it wears off
+    #[serde(default)]
+    pub encounter: Option<ActorId>, // what sprang out at the player here, in sight until they move on
+}
+
+fn starting_coins() -> u32 {
+    STARTING_COINS
+}
+
+fn new_player_id() -> ActorId {
+    format!("player-{:016x}", rand::random::<u64>()).into()
+}
+
+/// Something that happened to the player, kept so content can refer back to it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub at: i64, // world clock, unix seconds
+    pub kind: JournalKind,
+    pub page: PageId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum JournalKind {
+    Arrived,
+    TookItem(ItemId),
 }
 
 impl UserSession {
     pub fn new(starting_page: &str) -> Self {
-        UserSession {
+        let mut session = UserSession {
+            version: SESSION_VERSION,
+            player_id: new_player_id(),
             current_page: PageId::from(starting_page),
+            visits: HashMap::new(),
+            flags: HashSet::new(),
+            inventory: Vec::new(),
+            journal: VecDeque::new(),
+            character: None,
+            recent_moves: VecDeque::new(),
+            quests: HashMap::new(),
+            talked_to: HashSet::new(),
+            coins: STARTING_COINS,
+            language: None,
+            text_only: false,
+            seen: HashMap::new(),
+            reputation: Reputation::default(),
+            wear: HashMap::new(),
+            flags_until: HashMap::new(),
+            encounter: None,
+        };
+        session.record_visit(&PageId::from(starting_page));
+        session
+    }
+
+    /// Count an arrival at `page_id`
+    pub fn record_visit(&mut self, page_id: &PageId) {
+        *self.visits.entry(page_id.clone()).or_insert(0) += 1;
+    }
+
+    pub fn visit_count(&self, page_id: &PageId) -> u32 {
+        self.visits.get(page_id).copied().unwrap_or(0)
+    }
+
+    /// Whether the player has ever arrived at `page_id`
+    pub fn has_visited(&self, page_id: &PageId) -> bool {
+        self.visits.contains_key(page_id)
+    }
+
+    /// Remember seeing `actor` on `page`; true if that's news
+    pub fn saw(&mut self, actor: &ActorId, page: &PageId) -> bool {
+        if self.seen.get(actor) == Some(page) {
+            return false;
         }
+        self.seen.insert(actor.clone(), page.clone());
+        true
+    }
+
+    /// Every page the player has been to, in no particular order
+    pub fn visited(&self) -> impl Iterator<Item = &PageId> {
+        self.visits.keys()
+    }
+
+    /// Add a journal entry at world time `at`, forgetting the oldest if full
+    pub fn note(&mut self, at: DateTime<Local>, kind: JournalKind, page: &PageId) {
+        if self.journal.len() == JOURNAL_LEN {
+            self.journal.pop_front();
+        }
+        self.journal.push_back(JournalEntry {
+            at: at.timestamp(),
+            kind,
+            page: page.clone(),
+        });
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    /// Set `flag` until world clock `until` (unix seconds)
+    pub fn grant_flag(&mut self, flag: &str, until: i64) {
+        self.flags.insert(flag.to_string());
+        self.flags_until.insert(flag.to_string(), until);
+    }
+
+    /// Clear flags that have worn off by world clock `now` (unix seconds).
+    /// Returns whether any did.
+    pub fn expire_flags(&mut self, now: i64) -> bool {
+        let expired: Vec<String> = self
+            .flags_until
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(flag, _)| flag.clone())
+            .collect();
+        for flag in &expired {
+            self.flags.remove(flag);
+            self.flags_until.remove(flag);
+        }
+        !expired.is_empty()
+    }
+
+    pub fn has_item(&self, item: &ItemId) -> bool {
+        self.inventory.contains(item)
+    }
+
+    pub fn perception(&self) -> u8 {
+        self.character
+            .as_ref()
+            .map_or(DEFAULT_STAT, |c| c.perception)
+    }
+
+    /// Whether the player sees by night without a light
+    pub fn is_nocturnal(&self) -> bool {
+        self.character.as_ref().is_some_and(|c| c.nocturnal)
+    }
+
+    /// Moves allowed in any one stamina window
+    pub fn moves_per_window(&self) -> usize {
+        let stamina = self.character.as_ref().map_or(DEFAULT_STAT, |c| c.stamina);
+        6 + 3 * stamina as usize
+    }
+
+    /// Weight the player carries before it starts to tell on them
+    pub fn capacity(&self) -> u32 {
+        let stamina = self.character.as_ref().map_or(DEFAULT_STAT, |c| c.stamina);
+        10 + 4 * stamina as u32
+    }
+
+    /// Count a move made at real time `now` (unix seconds) against stamina,
+    /// which goes further riding a mount and counts double over-encumbered.
+    /// Returns false, counting nothing, if the player is too tired to go on.
+    pub fn spend_stamina(&mut self, now: i64, means: &Means) -> bool {
+        while self
+            .recent_moves
+            .front()
+            .is_some_and(|t| now - t >= STAMINA_WINDOW_SECS)
+        {
+            self.recent_moves.pop_front();
+        }
+        let allowed = if means.riding() {
+            self.moves_per_window() * RIDING_STAMINA
+        } else {
+            self.moves_per_window()
+        };
+        let cost = if means.encumbered() { 2 } else { 1 };
+        if self.recent_moves.len() + cost > allowed {
+            return false;
+        }
+        self.recent_moves.extend(std::iter::repeat_n(now, cost));
+        true
+    }
+
+    /// True if the player carries anything that gives off light
+    pub fn carries_light(&self, catalog: &ItemCatalog) -> bool {
+        self.inventory
+            .iter()
+            .filter_map(|id| catalog.get(id))
+            .any(|item| item.light_source)
     }
 }
 
 #[derive(Deserialize)]
 pub struct UserAction {
-    pub go_to: String, // direction of movement
+    pub go_to: Option<String>,      // direction of movement
+    pub take: Option<String>,       // item id to pick up
+    pub emote: Option<Emote>,       // something to do for anyone else here to see
+    pub say: Option<String>,        // chat to everyone on the page
+    pub buy: Option<String>,        // item id to buy from the shop here
+    pub sell: Option<String>,       // item id to sell to the shop here
+    pub combine: Option<String>,    // comma-separated item ids to craft with
+    pub interact: Option<String>,   // "fixture:verb", e.g. "lever:pull"
+    pub inspect: Option<String>,    // actor or fixture id to take a closer look at
+    pub attack: Option<String>,     // id of an actor here to pick a fight with
+    pub trade_with: Option<String>, // id of an actor here to barter with...
+    pub give: Option<String>,       // ...offering this item id...
+    pub want: Option<String>,       // ...for this one of theirs
+    pub give_to: Option<String>,    // id of an actor here to hand `give` to
+    pub steal_from: Option<String>, // id of an actor here to lift `want` from
+    pub ask_along: Option<String>,  // id of an actor here to take along as a companion
+    pub part_ways: Option<String>,  // id of a companion to leave be
+    pub use_item: Option<String>,   // id of an item to use
+    pub let_be: Option<String>,     // id of what sprang out at the player, to let go
+}
+
+impl UserAction {
+    /// The throttled actions the form asks for, each as often as it asks
+    pub fn throttled(&self) -> Vec<PlayerAction> {
+        let dealing =
+            self.trade_with.is_some() || self.give_to.is_some() || self.steal_from.is_some();
+        [
+            (self.go_to.is_some(), PlayerAction::Move),
+            (self.take.is_some(), PlayerAction::Take),
+            (self.buy.is_some(), PlayerAction::Trade),
+            (self.sell.is_some(), PlayerAction::Trade),
+            (dealing, PlayerAction::Trade),
+            (self.use_item.is_some(), PlayerAction::Interact),
+            (self.combine.is_some(), PlayerAction::Craft),
+            (self.interact.is_some(), PlayerAction::Interact),
+            (self.attack.is_some(), PlayerAction::Attack),
+            (self.emote.is_some(), PlayerAction::Emote),
+            (
+                self.say.as_deref().and_then(chat::clean_message).is_some(),
+                PlayerAction::Say,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(asked, action)| asked.then_some(action))
+        .collect()
+    }
+}
+
+/// A small gesture players can make to each other
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Emote {
+    Wave,
+    Sit,
+    LookAround,
+    Bow,
+    Laugh,
+    Shout, // heard pages away
+}
+
+impl Emote {
+    pub const ALL: [Emote; 6] = [
+        Emote::Wave,
+        Emote::Sit,
+        Emote::LookAround,
+        Emote::Bow,
+        Emote::Laugh,
+        Emote::Shout,
+    ];
+
+    /// What others see, after the player's name ("Ash waves")
+    pub fn describe(self) -> &'static str {
+        match self {
+            Emote::Wave => "waves",
+            Emote::Sit => "sits down for a moment",
+            Emote::LookAround => "looks around",
+            Emote::Bow => "bows",
+            Emote::Laugh => "laughs",
+            Emote::Shout => "shouts",
+        }
+    }
 }
 
 /// Retrieve session or create a new one if missing
@@ -29,12 +341,54 @@ pub fn get_or_create_user_session(
     session: &Session,
     start_page: &str,
 ) -> Result<UserSession, AppError> {
-    match session.get::<UserSession>(SESSION_KEY) {
-        Ok(Some(val)) => Ok(val),
-        Ok(None) => create_new_session(session, start_page),
-        Err(e) => Err(AppError::SessionError(format!(
-            "Failed to retrieve session: {e}",
-        ))),
+    let stored = match session.get::<Value>(SESSION_KEY) {
+        Ok(Some(val)) => val,
+        Ok(None) => return create_new_session(session, start_page),
+        Err(e) => {
+            return Err(AppError::SessionError(format!(
+                "Failed to retrieve session: {e}",
+            )));
+        }
+    };
+    let Value::Object(mut fields) = stored else {
+        warn!("Session is not an object, starting afresh");
+        return create_new_session(session, start_page);
+    };
+
+    let version = fields.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    for migrate in MIGRATIONS.iter().skip(version as usize) {
+        migrate(&mut fields);
+    }
+    fields.insert("version".to_string(), Value::from(SESSION_VERSION));
+
+    match serde_json::from_value::<UserSession>(Value::Object(fields.clone())) {
+        Ok(user_session) => {
+            if version != SESSION_VERSION {
+                info!("Upgraded session from version {version} to {SESSION_VERSION}");
+                set_user_session(session, &user_session);
+            }
+            Ok(user_session)
+        }
+        Err(e) => {
+            // keep the player where they were, at least
+            let page = fields
+                .get("current_page")
+                .and_then(Value::as_str)
+                .unwrap_or(start_page)
+                .to_string();
+            warn!("Unreadable session (version {version}): {e}; starting afresh at {page}");
+            create_new_session(session, &page)
+        }
+    }
+}
+
+/// Sessions from before visits were counted had never "arrived" at their current page
+fn migrate_v0(fields: &mut Map<String, Value>) {
+    if !fields.contains_key("visits")
+        && let Some(page) = fields.get("current_page").cloned()
+        && let Some(page) = page.as_str()
+    {
+        fields.insert("visits".to_string(), serde_json::json!({ page: 1 }));
     }
 }
 
@@ -51,3 +405,50 @@ fn create_new_session(session: &Session, start_page: &str) -> Result<UserSession
 pub fn set_user_session(session: &Session, user_session: &UserSession) {
     let _ = session.insert(SESSION_KEY, user_session);
 }
+
+/// Leave a message for the next page the player sees, e.g. across a redirect
+pub fn flash(session: &Session, message: impl Into<String>) {
+    let mut queued: Vec<String> = session.get(FLASH_KEY).ok().flatten().unwrap_or_default();
+    queued.push(message.into());
+    let _ = session.insert(FLASH_KEY, queued);
+}
+
+/// Messages left for this page, oldest first. Each is shown once.
+pub fn take_flashes(session: &Session) -> Vec<String> {
+    session
+        .remove_as::<Vec<String>>(FLASH_KEY)
+        .and_then(Result::ok)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields(value: Value) -> Map<String, Value> {
+        let Value::Object(fields) = value else {
+            panic!("not an object: {value}");
+        };
+        fields
+    }
+
+    #[test]
+    fn a_session_from_before_visits_has_arrived_where_it_is() {
+        let mut old = fields(json!({ "current_page": "small-town", "flags": ["met_smith"] }));
+        migrate_v0(&mut old);
+        let upgraded: UserSession = serde_json::from_value(Value::Object(old)).unwrap();
+        assert_eq!(upgraded.visit_count(&PageId::from("small-town")), 1);
+        assert!(upgraded.has_flag("met_smith"));
+    }
+
+    #[test]
+    fn visits_already_counted_are_kept() {
+        let mut counted = fields(json!({
+            "current_page": "small-town",
+            "visits": { "small-town": 4, "forest": 2 },
+        }));
+        migrate_v0(&mut counted);
+        assert_eq!(counted["visits"], json!({ "small-town": 4, "forest": 2 }));
+    }
+}
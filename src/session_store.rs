@@ -0,0 +1,335 @@
+use actix_session::storage::{
+    CookieSessionStore, LoadError, SaveError, SessionKey, SessionStore, UpdateError,
+};
+use actix_web::cookie::Key;
+use actix_web::cookie::time::Duration;
+use actix_web::web;
+use chrono::Utc;
+use parking_lot::Mutex;
+use rand::Rng;
+use rand::distr::Alphanumeric;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::persistence::write_json_atomic;
+
+/// Default directory for the session files, relative to the working directory
+pub const DEFAULT_SESSIONS_DIR: &str = "sessions";
+
+const SESSION_KEY_LEN: usize = 64;
+
+type SessionState = HashMap<String, String>;
+
+/// One browser's session, as kept on the server
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredSession {
+    state: SessionState,
+    expires: i64, // real unix seconds
+}
+
+/// Sessions kept server-side, so the cookie only carries a random id.
+/// Held in memory and written through to a JSON file of their own when
+/// they change, off the async workers.
+#[derive(Clone)]
+pub struct FileSessionStore {
+    dir: PathBuf,
+    sessions: Arc<Mutex<HashMap<String, StoredSession>>>, // session id -> session
+}
+
+impl FileSessionStore {
+    /// Read the sessions in `dir`, deleting any that have expired.
+    /// A missing directory just means no sessions yet.
+    pub fn load(dir: &Path) -> Result<Self, AppError> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| AppError::OtherError(format!("Creating {}: {e}", dir.display())))?;
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", dir.display())))?;
+        let now = Utc::now().timestamp();
+        let mut sessions = HashMap::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", dir.display())))?
+                .path();
+            let Some(id) = session_id_of(&path) else {
+                continue;
+            };
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+            let session: StoredSession = serde_json::from_str(&text)
+                .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+            if session.expires > now {
+                sessions.insert(id, session);
+            } else if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Removing expired session {}: {e}", path.display());
+            }
+        }
+        info!("Loaded {} sessions from {}", sessions.len(), dir.display());
+        Ok(FileSessionStore {
+            dir: dir.to_path_buf(),
+            sessions: Arc::new(Mutex::new(sessions)),
+        })
+    }
+
+    /// Sessions held, counting any expired ones not yet pruned
+    pub fn len(&self) -> usize {
+        self.sessions.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn path_of(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    /// Store `state` under `id` and write its file, along with deleting
+    /// the files of any sessions that have expired
+    async fn put(
+        &self,
+        id: String,
+        state: SessionState,
+        ttl: &Duration,
+    ) -> Result<(), anyhow::Error> {
+        let now = Utc::now().timestamp();
+        let session = StoredSession {
+            state,
+            expires: now + ttl.whole_seconds(),
+        };
+        let path = self.path_of(&id);
+        let expired: Vec<PathBuf> = {
+            let mut sessions = self.sessions.lock();
+            let expired = sessions
+                .iter()
+                .filter(|(_, session)| session.expires <= now)
+                .map(|(id, _)| self.path_of(id))
+                .collect();
+            sessions.retain(|_, session| session.expires > now);
+            sessions.insert(id, session.clone());
+            expired
+        };
+        web::block(move || {
+            for gone in expired {
+                if let Err(e) = std::fs::remove_file(&gone) {
+                    warn!("Removing expired session {}: {e}", gone.display());
+                }
+            }
+            write_json_atomic(&path, &session)
+        })
+        .await?
+        .map_err(|e| anyhow::anyhow!("{e}"))
+    }
+}
+
+/// The session id a file in the sessions directory holds, if it holds one
+fn session_id_of(path: &Path) -> Option<String> {
+    if path.extension().is_none_or(|ext| ext != "json") {
+        return None;
+    }
+    let id = path.file_stem()?.to_str()?;
+    (id.len() == SESSION_KEY_LEN && id.chars().all(|c| c.is_ascii_alphanumeric()))
+        .then(|| id.to_string())
+}
+
+fn new_session_id() -> String {
+    rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(SESSION_KEY_LEN)
+        .map(char::from)
+        .collect()
+}
+
+impl SessionStore for FileSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        Ok(self
+            .sessions
+            .lock()
+            .get(session_key.as_ref())
+            .filter(|session| session.expires > Utc::now().timestamp())
+            .map(|session| session.state.clone()))
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let id = new_session_id();
+        self.put(id.clone(), session_state, ttl)
+            .await
+            .map_err(SaveError::Other)?;
+        SessionKey::try_from(id).map_err(|e| SaveError::Other(e.into()))
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let known = self.sessions.lock().contains_key(session_key.as_ref());
+        if !known {
+            // expired (or pruned) meanwhile; start it afresh under a new id
+            return self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(e) => UpdateError::Serialization(e),
+                SaveError::Other(e) => UpdateError::Other(e),
+            });
+        }
+        self.put(session_key.as_ref().to_string(), session_state, ttl)
+            .await
+            .map_err(UpdateError::Other)?;
+        Ok(session_key)
+    }
+
+    /// Only extended in memory; it reaches the file with the next change
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &Duration,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(session) = self.sessions.lock().get_mut(session_key.as_ref()) {
+            session.expires = Utc::now().timestamp() + ttl.whole_seconds();
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        if self.sessions.lock().remove(session_key.as_ref()).is_none() {
+            return Ok(());
+        }
+        let path = self.path_of(session_key.as_ref());
+        web::block(move || std::fs::remove_file(&path))
+            .await?
+            .map_err(|e| anyhow::anyhow!("Removing session: {e}"))
+    }
+}
+
+/// Where session state lives, chosen at startup by `CHOTT_SESSION_STORE`
+#[derive(Clone)]
+pub enum SessionBackend {
+    Cookie, // everything in the (encrypted) cookie
+    File(FileSessionStore),
+}
+
+impl SessionBackend {
+    /// `CHOTT_SESSION_STORE=file` keeps sessions in files in the directory
+    /// `CHOTT_SESSIONS` (default `sessions`); anything else keeps them in
+    /// the cookie
+    pub fn from_env() -> Result<Self, AppError> {
+        match std::env::var("CHOTT_SESSION_STORE").as_deref() {
+            Ok("file") => {
+                let dir =
+                    std::env::var("CHOTT_SESSIONS").unwrap_or(DEFAULT_SESSIONS_DIR.to_string());
+                Ok(SessionBackend::File(FileSessionStore::load(dir.as_ref())?))
+            }
+            _ => Ok(SessionBackend::Cookie),
+        }
+    }
+
+    /// The key cookies are signed and encrypted with, from `CHOTT_SESSION_KEY`
+    /// (64 bytes or more). Sessions kept in files outlive a restart, and
+    /// their cookies only stay good with the same key, so it's required
+    /// then; cookie sessions make do with a fresh one each start.
+    pub fn secret_key(&self) -> Result<Key, AppError> {
+        match std::env::var("CHOTT_SESSION_KEY") {
+            Ok(key) => Key::try_from(key.as_bytes()).map_err(|_| {
+                AppError::OtherError("CHOTT_SESSION_KEY must be at least 64 bytes".to_string())
+            }),
+            Err(_) if matches!(self, SessionBackend::File(_)) => Err(AppError::OtherError(
+                "CHOTT_SESSION_KEY must be set to keep sessions in files".to_string(),
+            )),
+            Err(_) => Ok(Key::generate()),
+        }
+    }
+
+    /// Sessions held server-side; `None` when they live in cookies
+    pub fn stored_sessions(&self) -> Option<usize> {
+        match self {
+            SessionBackend::Cookie => None,
+            SessionBackend::File(store) => Some(store.len()),
+        }
+    }
+}
+
+impl SessionStore for SessionBackend {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        match self {
+            SessionBackend::Cookie => CookieSessionStore::default().load(session_key).await,
+            SessionBackend::File(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            SessionBackend::Cookie => CookieSessionStore::default().save(session_state, ttl).await,
+            SessionBackend::File(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            SessionBackend::Cookie => {
+                CookieSessionStore::default()
+                    .update(session_key, session_state, ttl)
+                    .await
+            }
+            SessionBackend::File(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &Duration,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            SessionBackend::Cookie => {
+                CookieSessionStore::default()
+                    .update_ttl(session_key, ttl)
+                    .await
+            }
+            SessionBackend::File(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        match self {
+            SessionBackend::Cookie => CookieSessionStore::default().delete(session_key).await,
+            SessionBackend::File(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn a_session_survives_reloading_the_store() {
+        let dir = std::env::temp_dir().join(format!("chott-sessions-{}", std::process::id()));
+        let store = FileSessionStore::load(&dir).unwrap();
+        let state = SessionState::from([("user".to_string(), "\"alice\"".to_string())]);
+        let key = store
+            .save(state.clone(), &Duration::hours(1))
+            .await
+            .unwrap();
+
+        let reloaded = FileSessionStore::load(&dir).unwrap();
+        let loaded = reloaded.load(&key).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded, Some(state));
+    }
+}
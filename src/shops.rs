@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+use crate::actor::{ActorId, ActorManager};
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::items::{Item, ItemCatalog, ItemId};
+use crate::pages::{Page, PageGraph, PageId};
+use crate::reputation::Regard;
+use crate::session::UserSession;
+use crate::world::WorldGraph;
+
+/// Coins a new player starts out with
+pub const STARTING_COINS: u32 = 20;
+
+/// What a merchant who can't stand the player tells them
+const REFUSED: &str = "They won't deal with the likes of you.";
+
+/// Goods for sale on a page, minded by `merchant` if set
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Shop {
+    #[serde(default)]
+    pub merchant: Option<ActorId>, // the shop is shut while they're away or asleep
+    pub stock: Vec<ShopItem>,
+    #[serde(default = "default_restock_every")]
+    pub restock_every: u64, // ticks between restocking one of each item
+}
+
+fn default_restock_every() -> u64 {
+    30
+}
+
+/// One line of a shop's stock
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShopItem {
+    pub item: ItemId,
+    pub price: u32,
+    pub max_stock: u32, // restocked up to this; also how many it will buy back
+}
+
+impl ShopItem {
+    /// What the shop pays for one
+    pub fn buys_for(&self) -> u32 {
+        self.price / 2
+    }
+}
+
+/// What came of a trade, to tell the player: what happened, or why it didn't
+pub type TradeOutcome = Result<String, String>;
+
+/// A line of stock as shown on the page
+#[derive(Serialize)]
+pub struct Listing<'a> {
+    pub item: &'a Item,
+    pub price: u32,
+    pub buys_for: u32,
+    pub in_stock: u32,
+}
+
+/// How many of each item every shop has on hand, which changes as players
+/// trade and merchants restock. What shops sell, and for how much, comes
+/// from their pages.
+#[derive(Clone, Default)]
+pub struct ShopManager {
+    stock: Arc<Mutex<HashMap<PageId, HashMap<ItemId, u32>>>>,
+}
+
+impl ShopManager {
+    /// Restock shops as the world ticks over
+    pub fn spawn_restocker(&self, bus: &EventBus, world: WorldGraph) {
+        let mut rx = bus.subscribe();
+        let shops = self.clone();
+        actix_rt::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(WorldEvent::WorldTicked { tick, .. }) => {
+                        shops.restock(tick, &world.snapshot())
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Shops due a restock at `tick` get one more of each item
+    fn restock(&self, tick: u64, pages: &PageGraph) {
+        let mut stock = self.stock.lock().expect("Failed to lock Mutex");
+        for page in pages.values() {
+            let Some(shop) = &page.shop else { continue };
+            if !tick.is_multiple_of(shop.restock_every.max(1)) {
+                continue;
+            }
+            let on_hand = stock.entry(page.id.clone()).or_default();
+            for line in &shop.stock {
+                let count = on_hand.entry(line.item.clone()).or_insert(line.max_stock);
+                *count = (*count + 1).min(line.max_stock);
+            }
+        }
+    }
+
+    /// How many of `item` the shop on `page` has; a shop nobody has traded
+    /// with yet is fully stocked
+    fn on_hand(
+        stock: &mut HashMap<PageId, HashMap<ItemId, u32>>,
+        page: &PageId,
+        line: &ShopItem,
+    ) -> u32 {
+        *stock
+            .entry(page.clone())
+            .or_default()
+            .entry(line.item.clone())
+            .or_insert(line.max_stock)
+    }
+
+    /// What every shop has on hand, for a save
+    pub fn stock(&self) -> HashMap<PageId, HashMap<ItemId, u32>> {
+        self.stock.lock().expect("Failed to lock Mutex").clone()
+    }
+
+    /// Put every shop's stock back as a save had it
+    pub fn restore(&self, stock: HashMap<PageId, HashMap<ItemId, u32>>) {
+        *self.stock.lock().expect("Failed to lock Mutex") = stock;
+    }
+
+    /// The shop on `page` as `player` sees it, priced by what its merchant
+    /// thinks of them
+    pub fn listings<'a>(
+        &self,
+        page: &Page,
+        catalog: &'a ItemCatalog,
+        player: &UserSession,
+        actors: &ActorManager,
+    ) -> Vec<Listing<'a>> {
+        let Some(shop) = &page.shop else {
+            return Vec::new();
+        };
+        let regard = Self::merchant_regard(shop, player, actors);
+        let mut stock = self.stock.lock().expect("Failed to lock Mutex");
+        shop.stock
+            .iter()
+            .filter_map(|line| {
+                Some(Listing {
+                    item: catalog.get(&line.item)?,
+                    price: regard.charges(line.price),
+                    buys_for: regard.pays(line.buys_for()),
+                    in_stock: Self::on_hand(&mut stock, &page.id, line),
+                })
+            })
+            .collect()
+    }
+
+    /// Buy one `item` from the shop on `page`. Shortfalls come back as a
+    /// message for the player rather than an error.
+    pub fn buy(
+        &self,
+        page: &Page,
+        item: &ItemId,
+        player: &mut UserSession,
+        actors: &parking_lot::Mutex<ActorManager>,
+    ) -> Result<TradeOutcome, AppError> {
+        let (shop, line) = Self::line_for(page, item)?;
+        let mut actors = actors.lock();
+        if let Err(closed) = Self::check_open(shop, page, &actors) {
+            return Ok(Err(closed));
+        }
+        let regard = Self::merchant_regard(shop, player, &actors);
+        if regard == Regard::Hostile {
+            return Ok(Err(REFUSED.to_string()));
+        }
+        let price = regard.charges(line.price);
+        let mut stock = self.stock.lock().expect("Failed to lock Mutex");
+        if Self::on_hand(&mut stock, &page.id, line) == 0 {
+            return Ok(Err("They're all sold out.".to_string()));
+        }
+        if player.coins < price {
+            return Ok(Err(format!(
+                "That costs {price} coins; you have {}.",
+                player.coins
+            )));
+        }
+
+        player.coins -= price;
+        player.inventory.push(item.clone());
+        *stock
+            .get_mut(&page.id)
+            .and_then(|s| s.get_mut(item))
+            .expect("counted above") -= 1;
+        if let Some(merchant) = shop
+            .merchant
+            .as_ref()
+            .and_then(|id| actors.actors.get_mut(id))
+        {
+            merchant.state.coins += price;
+        }
+        info!("Sold {item} on {} for {price}", page.id);
+        Ok(Ok(format!("Bought for {price} coins.")))
+    }
+
+    /// Sell one `item` to the shop on `page`. It only buys what it sells.
+    pub fn sell(
+        &self,
+        page: &Page,
+        item: &ItemId,
+        player: &mut UserSession,
+        actors: &parking_lot::Mutex<ActorManager>,
+    ) -> Result<TradeOutcome, AppError> {
+        let (shop, line) = Self::line_for(page, item)?;
+        let Some(carried) = player.inventory.iter().position(|i| i == item) else {
+            return Err(AppError::SessionError(format!("You have no {item}")));
+        };
+        let mut actors = actors.lock();
+        if let Err(closed) = Self::check_open(shop, page, &actors) {
+            return Ok(Err(closed));
+        }
+        let regard = Self::merchant_regard(shop, player, &actors);
+        if regard == Regard::Hostile {
+            return Ok(Err(REFUSED.to_string()));
+        }
+        let mut stock = self.stock.lock().expect("Failed to lock Mutex");
+        if Self::on_hand(&mut stock, &page.id, line) >= line.max_stock {
+            return Ok(Err("They have plenty of those already.".to_string()));
+        }
+        let paid = regard.pays(line.buys_for());
+        if let Some(merchant) = shop
+            .merchant
+            .as_ref()
+            .and_then(|id| actors.actors.get_mut(id))
+        {
+            if merchant.state.coins < paid {
+                return Ok(Err(format!("{} can't afford it.", merchant.name)));
+            }
+            merchant.state.coins -= paid;
+        }
+
+        player.inventory.remove(carried);
+        player.coins += paid;
+        *stock
+            .get_mut(&page.id)
+            .and_then(|s| s.get_mut(item))
+            .expect("counted above") += 1;
+        info!("Bought {item} on {} for {paid}", page.id);
+        Ok(Ok(format!("Sold for {paid} coins.")))
+    }
+
+    fn line_for<'a>(page: &'a Page, item: &ItemId) -> Result<(&'a Shop, &'a ShopItem), AppError> {
+        page.shop
+            .as_ref()
+            .and_then(|shop| Some((shop, shop.stock.iter().find(|l| l.item == *item)?)))
+            .ok_or_else(|| AppError::SessionError(format!("No trade in {item} here")))
+    }
+
+    /// What the merchant minding `shop` thinks of `player`; an unminded
+    /// shop has no opinion
+    fn merchant_regard(shop: &Shop, player: &UserSession, actors: &ActorManager) -> Regard {
+        shop.merchant
+            .as_ref()
+            .and_then(|id| actors.actors.get(id))
+            .map_or(Regard::Neutral, |merchant| {
+                player.reputation.regard(merchant)
+            })
+    }
+
+    /// A merchant's shop is only open while they are here and awake
+    fn check_open(shop: &Shop, page: &Page, actors: &ActorManager) -> Result<(), String> {
+        let Some(id) = &shop.merchant else {
+            return Ok(());
+        };
+        match actors.actors.get(id) {
+            Some(merchant) if merchant.location == page.id && merchant.state.awake => Ok(()),
+            _ => Err("The shop is shut; nobody is minding it.".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pages::tests::page;
+    use crate::regions::Regions;
+    use crate::scripting::ScriptHost;
+
+    /// A stall nobody minds, with two lanterns to sell at 10 coins each
+    fn stall() -> Page {
+        page(
+            "market",
+            "Market",
+            serde_json::json!({
+                "shop": { "stock": [{ "item": "lantern", "price": 10, "max_stock": 2 }] },
+            }),
+        )
+    }
+
+    fn nobody() -> parking_lot::Mutex<ActorManager> {
+        parking_lot::Mutex::new(ActorManager::new(
+            EventBus::new(),
+            Vec::new(),
+            Arc::new(Regions::default()),
+            ScriptHost::default(),
+        ))
+    }
+
+    fn lantern() -> ItemId {
+        ItemId::from("lantern")
+    }
+
+    #[test]
+    fn buying_trades_coins_for_the_item() {
+        let (shops, page, actors) = (ShopManager::default(), stall(), nobody());
+        let mut player = UserSession::new("market");
+        let bought = shops.buy(&page, &lantern(), &mut player, &actors).unwrap();
+        assert!(bought.is_ok());
+        assert_eq!(player.coins, STARTING_COINS - 10);
+        assert!(player.has_item(&lantern()));
+    }
+
+    #[test]
+    fn a_sold_out_shop_takes_no_coins() {
+        let (shops, page, actors) = (ShopManager::default(), stall(), nobody());
+        let mut player = UserSession::new("market");
+        player.coins = 100;
+        for _ in 0..2 {
+            shops
+                .buy(&page, &lantern(), &mut player, &actors)
+                .unwrap()
+                .unwrap();
+        }
+        let sold_out = shops.buy(&page, &lantern(), &mut player, &actors).unwrap();
+        assert!(sold_out.is_err());
+        assert_eq!(player.coins, 80);
+        assert_eq!(player.inventory.len(), 2);
+    }
+
+    #[test]
+    fn nothing_changes_hands_when_the_player_cannot_pay() {
+        let (shops, page, actors) = (ShopManager::default(), stall(), nobody());
+        let mut player = UserSession::new("market");
+        player.coins = 9;
+        let refused = shops.buy(&page, &lantern(), &mut player, &actors).unwrap();
+        assert!(refused.is_err());
+        assert_eq!(player.coins, 9);
+        assert!(!player.has_item(&lantern()));
+    }
+
+    #[test]
+    fn a_shop_buys_back_at_half_price_until_it_is_full() {
+        let (shops, page, actors) = (ShopManager::default(), stall(), nobody());
+        let mut player = UserSession::new("market");
+        player.inventory = vec![lantern(), lantern()];
+        // fully stocked to begin with
+        let full = shops.sell(&page, &lantern(), &mut player, &actors).unwrap();
+        assert!(full.is_err());
+        player.coins = 100;
+        shops
+            .buy(&page, &lantern(), &mut player, &actors)
+            .unwrap()
+            .unwrap();
+        shops
+            .sell(&page, &lantern(), &mut player, &actors)
+            .unwrap()
+            .unwrap();
+        assert_eq!(player.coins, 95);
+        assert_eq!(player.inventory.len(), 2);
+    }
+
+    #[test]
+    fn only_what_the_player_carries_can_be_sold() {
+        let (shops, page, actors) = (ShopManager::default(), stall(), nobody());
+        let mut player = UserSession::new("market");
+        assert!(shops.sell(&page, &lantern(), &mut player, &actors).is_err());
+    }
+}
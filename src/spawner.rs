@@ -0,0 +1,395 @@
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tracing::{debug, warn};
+
+use crate::actor::{self, Actor, ActorFlag, ActorId, ActorMap};
+use crate::conditions::Condition;
+use crate::definitions::{ActorDefinition, Archetypes};
+use crate::ecology::PECKISH;
+use crate::environment::WorldTime;
+use crate::error::AppError;
+use crate::overlays::DecisionOverlay;
+use crate::pages::{PageGraph, PageId};
+use crate::regions::{RegionId, Regions};
+use crate::status::Status;
+
+/// Default location of the spawn rules file, relative to the working directory
+pub const DEFAULT_SPAWNS_PATH: &str = "data/spawns.toml";
+
+/// What a spawned actor is like; an actor definition without id or location.
+/// Like a definition, it can start from an archetype.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Species {
+    pub name: String,
+    pub health: i32,
+    #[serde(default)]
+    pub flags: Vec<ActorFlag>,
+    #[serde(default = "actor::default_tick_rate")]
+    pub tick_rate: u32,
+    #[serde(default = "actor::default_action_points")]
+    pub action_points: u8,
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(default)]
+    pub seen_when: Condition,
+    #[serde(default)]
+    pub faction: Option<String>,
+    #[serde(default)]
+    pub decision_overlays: DecisionOverlay,
+}
+
+impl Species {
+    /// One of the species, called `id`, turned up on `location` and keeping
+    /// to it as home
+    pub fn at(self, id: ActorId, location: PageId) -> ActorDefinition {
+        ActorDefinition {
+            id,
+            name: self.name,
+            location: location.clone(),
+            health: self.health,
+            flags: self.flags,
+            tick_rate: self.tick_rate,
+            action_points: self.action_points,
+            roams: None,
+            home: Some(location),
+            faction: self.faction,
+            pack: None,
+            items: Vec::new(),
+            coins: 0,
+            script: self.script,
+            seen_when: self.seen_when,
+            decision_overlays: self.decision_overlays,
+            appointments: Vec::new(),
+        }
+    }
+}
+
+/// When a rule may spawn, by world time of day
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum SpawnTime {
+    #[default]
+    Any,
+    Day,
+    Night,
+}
+
+/// Keeps up to `max` actors of a species about, on one page or anywhere in
+/// a region, spawning at most one every `every_ticks` ticks, or a whole
+/// `pack` of them at once
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnRule {
+    pub id: String, // spawned actors are "<id>-<n>"
+    #[serde(default)]
+    pub page: Option<PageId>,
+    #[serde(default)]
+    pub region: Option<RegionId>, // spawned actors roam it too
+    pub max: usize,
+    #[serde(default = "default_every_ticks")]
+    pub every_ticks: u64,
+    #[serde(default)]
+    pub time: SpawnTime,
+    #[serde(default)]
+    pub pack: Option<usize>, // how many turn up together, running as a pack
+    pub species: Species,
+}
+
+fn default_every_ticks() -> u64 {
+    10
+}
+
+/// Lets a species breed: every `every_ticks` ticks, two of it on one page,
+/// both awake, fed, well and over their last litter, have one young, made
+/// from the species as a spawn rule's would be. Parents are whoever goes by
+/// the species' name, spawned, bred or defined; the young roam where the
+/// first parent does.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BreedRule {
+    pub id: String, // young are "<id>-<tick>-<n>"
+    pub max: usize, // most of the species in the whole world
+    #[serde(default)]
+    pub max_per_region: Option<usize>, // and in any one region
+    #[serde(default = "default_every_ticks")]
+    pub every_ticks: u64,
+    #[serde(default = "default_cooldown")]
+    pub cooldown: u64, // ticks a parent waits before breeding again
+    pub species: Species,
+}
+
+fn default_cooldown() -> u64 {
+    200
+}
+
+#[derive(Deserialize)]
+struct SpawnFile {
+    #[serde(default)]
+    spawn: Vec<toml::Table>,
+    #[serde(default)]
+    breed: Vec<toml::Table>,
+}
+
+/// Runs the spawn and breeding rules, remembering which actors they have
+/// out in the world
+#[derive(Default)]
+pub struct Spawner {
+    rules: Vec<SpawnRule>,
+    breeding: Vec<BreedRule>,
+    live: HashMap<String, HashSet<ActorId>>, // rule id -> ids of its actors still about
+    bred: HashSet<ActorId>,                  // young still about
+    spawned: u64,                            // actors spawned since startup, for ids
+}
+
+impl Spawner {
+    /// The same rules with nothing spawned yet, for a new world
+    pub fn fresh(&self) -> Spawner {
+        Spawner {
+            rules: self.rules.clone(),
+            breeding: self.breeding.clone(),
+            ..Spawner::default()
+        }
+    }
+
+    /// Read spawn rules from a TOML file, species archetypes taken from
+    /// `archetypes`. A missing file just means no spawning.
+    pub fn load(path: &Path, archetypes: &Archetypes) -> Result<Self, AppError> {
+        if !path.exists() {
+            return Ok(Spawner::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let file: SpawnFile = toml::from_str(&text)
+            .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+        let mut rules: Vec<SpawnRule> = Vec::new();
+        for (i, table) in file.spawn.into_iter().enumerate() {
+            let rule: SpawnRule = with_species(table, archetypes).map_err(|e| {
+                AppError::OtherError(format!("Spawn rule #{} in {}: {e}", i + 1, path.display()))
+            })?;
+            if rule.page.is_none() == rule.region.is_none() {
+                return Err(AppError::OtherError(format!(
+                    "Spawn rule '{}' needs exactly one of page or region",
+                    rule.id
+                )));
+            }
+            rules.push(rule);
+        }
+        let breeding = file
+            .breed
+            .into_iter()
+            .enumerate()
+            .map(|(i, table)| {
+                with_species(table, archetypes).map_err(|e| {
+                    AppError::OtherError(format!(
+                        "Breeding rule #{} in {}: {e}",
+                        i + 1,
+                        path.display()
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Spawner {
+            rules,
+            breeding,
+            ..Spawner::default()
+        })
+    }
+
+    /// Whether `id` is an actor one of the rules spawned, or bred
+    pub fn owns(&self, id: &ActorId) -> bool {
+        self.bred.contains(id) || self.live.values().any(|ids| ids.contains(id))
+    }
+
+    /// New actors due on world tick `tick`: one for each rule that is due,
+    /// or a pack's worth, in its hours and under its cap
+    pub fn due(
+        &mut self,
+        tick: u64,
+        world_time: &WorldTime,
+        actors: &ActorMap,
+        page_graph: &PageGraph,
+        regions: &Regions,
+    ) -> Vec<Actor> {
+        let mut born = Vec::new();
+        for rule in &self.rules {
+            let live = self.live.entry(rule.id.clone()).or_default();
+            live.retain(|id| actors.contains_key(id)); // gone, one way or another
+            let in_hours = match rule.time {
+                SpawnTime::Any => true,
+                SpawnTime::Day => world_time.is_daytime(),
+                SpawnTime::Night => world_time.is_night(),
+            };
+            if !tick.is_multiple_of(rule.every_ticks.max(1)) || !in_hours || live.len() >= rule.max
+            {
+                continue;
+            }
+            let Some(location) = spawn_page(rule, page_graph, regions) else {
+                warn!(rule = %rule.id, "Nowhere to spawn");
+                continue;
+            };
+            // a pack turns up all together, as far as the cap allows
+            let size = rule.pack.unwrap_or(1).max(1).min(rule.max - live.len());
+            let mut pack = None;
+            for _ in 0..size {
+                let id = loop {
+                    self.spawned += 1;
+                    let id = ActorId::from(format!("{}-{}", rule.id, self.spawned));
+                    if !actors.contains_key(&id) {
+                        break id;
+                    }
+                };
+                debug!(rule = %rule.id, %id, %location, "Spawn rule fires.");
+                live.insert(id.clone());
+                if rule.pack.is_some() && pack.is_none() {
+                    pack = Some(id.to_string()); // named for the first of it
+                }
+                let species = rule.species.clone();
+                born.push(Actor::from_definition(ActorDefinition {
+                    id,
+                    name: species.name,
+                    location: location.clone(),
+                    health: species.health,
+                    flags: species.flags,
+                    tick_rate: species.tick_rate,
+                    action_points: species.action_points,
+                    roams: rule.region.clone(),
+                    home: Some(location.clone()), // where it first turned up
+                    faction: species.faction,
+                    pack: pack.clone(),
+                    items: Vec::new(),
+                    coins: 0,
+                    script: species.script,
+                    seen_when: species.seen_when,
+                    decision_overlays: species.decision_overlays,
+                    appointments: Vec::new(),
+                }));
+            }
+        }
+        born
+    }
+
+    /// Young born on world tick `tick`, to pairs among `actors` that are
+    /// ready to breed, as far as the breeding rules' caps allow. The parents
+    /// are marked as having bred. Nothing is left to chance, so a replay
+    /// breeds the same young.
+    pub fn breed(
+        &mut self,
+        tick: u64,
+        actors: &mut ActorMap,
+        page_graph: &PageGraph,
+    ) -> Vec<Actor> {
+        self.bred.retain(|id| actors.contains_key(id));
+        let region_of = |page: &PageId| page_graph.get(page).and_then(|p| p.region.clone());
+        let mut born = Vec::new();
+        for rule in &self.breeding {
+            if !tick.is_multiple_of(rule.every_ticks.max(1)) {
+                continue;
+            }
+            let kind: Vec<&Actor> = actors
+                .values()
+                .filter(|a| a.name == rule.species.name && !a.has_flag(ActorFlag::Player))
+                .collect();
+            let mut count = kind.len();
+            let mut by_region: HashMap<Option<RegionId>, usize> = HashMap::new();
+            for actor in &kind {
+                *by_region.entry(region_of(&actor.location)).or_default() += 1;
+            }
+            // ready parents by page, in a set order
+            let mut ready: BTreeMap<&str, Vec<&ActorId>> = BTreeMap::new();
+            for actor in kind.iter().filter(|a| ready_to_breed(a, rule, tick)) {
+                ready
+                    .entry(actor.location.as_str())
+                    .or_default()
+                    .push(&actor.id);
+            }
+            let mut pairs: Vec<(ActorId, ActorId)> = Vec::new();
+            for parents in ready.values_mut() {
+                parents.sort();
+                for pair in parents.chunks_exact(2) {
+                    let region = region_of(&actors[pair[0]].location);
+                    let in_region = by_region.entry(region).or_default();
+                    if count >= rule.max || rule.max_per_region.is_some_and(|max| *in_region >= max)
+                    {
+                        break;
+                    }
+                    count += 1;
+                    *in_region += 1;
+                    pairs.push((pair[0].clone(), pair[1].clone()));
+                }
+            }
+            for (n, (first, second)) in pairs.into_iter().enumerate() {
+                for parent in [&first, &second] {
+                    if let Some(parent) = actors.get_mut(parent) {
+                        parent.state.bred_at = Some(tick);
+                    }
+                }
+                let parent = &actors[&first];
+                let id = ActorId::from(format!("{}-{tick}-{n}", rule.id));
+                debug!(rule = %rule.id, %id, %first, %second, "Young born.");
+                self.bred.insert(id.clone());
+                let species = rule.species.clone();
+                born.push(Actor::from_definition(ActorDefinition {
+                    id,
+                    name: species.name,
+                    location: parent.location.clone(),
+                    health: species.health,
+                    flags: species.flags,
+                    tick_rate: species.tick_rate,
+                    action_points: species.action_points,
+                    roams: parent.roams.clone(),
+                    home: Some(parent.location.clone()), // where it was born
+                    faction: species.faction,
+                    pack: parent.pack.clone(), // raised in its parent's pack
+                    items: Vec::new(),
+                    coins: 0,
+                    script: species.script,
+                    seen_when: species.seen_when,
+                    decision_overlays: species.decision_overlays,
+                    appointments: Vec::new(),
+                }));
+            }
+        }
+        born
+    }
+}
+
+/// Whether `actor` could have young under `rule` on world tick `tick`
+fn ready_to_breed(actor: &Actor, rule: &BreedRule, tick: u64) -> bool {
+    !actor.paused
+        && actor.travel.is_none()
+        && actor.state.awake
+        && actor.state.health > 0
+        && actor.state.hunger < PECKISH
+        && !actor.has_status(Status::Sick)
+        && actor
+            .state
+            .bred_at
+            .is_none_or(|at| tick >= at + rule.cooldown)
+}
+
+/// A rule read from `table`, its species' archetypes filled in
+pub(crate) fn with_species<T: serde::de::DeserializeOwned>(
+    mut table: toml::Table,
+    archetypes: &Archetypes,
+) -> Result<T, String> {
+    if let Some(toml::Value::Table(species)) = table.remove("species") {
+        let species = archetypes.resolve(species)?;
+        table.insert("species".to_string(), toml::Value::Table(species));
+    }
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())
+}
+
+/// The rule's page, or a random page in its region
+fn spawn_page(rule: &SpawnRule, page_graph: &PageGraph, regions: &Regions) -> Option<PageId> {
+    if let Some(page) = &rule.page {
+        return page_graph.contains_key(page).then(|| page.clone());
+    }
+    let region = rule.region.as_ref()?;
+    let pages: Vec<&PageId> = page_graph
+        .values()
+        .filter(|page| regions.within(page.region.as_ref(), region))
+        .map(|page| &page.id)
+        .collect();
+    pages.choose(&mut rand::rng()).map(|page| (*page).clone())
+}
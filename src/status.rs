@@ -0,0 +1,149 @@
+//! Status effects: passing conditions on an actor, players' included, that
+//! wear off after so many world ticks. Blows, the weather and items put them
+//! on; while they last they sway what the actor does and how it fares.
+
+use serde::{Deserialize, Serialize};
+
+use crate::contagion::{IMMUNE_TICKS, SICK_TICKS};
+
+/// Ticks a predator's poison lasts
+pub const POISON_TICKS: u32 = 20;
+
+/// One in this many blows from a predator poisons
+pub const POISON_ODDS: u32 = 3;
+
+/// Ticks an actor stays soaked after it's out of the rain
+pub const SOAKED_TICKS: u32 = 10;
+
+/// Ticks between each hurt poison does
+const POISON_EVERY: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    Poisoned,   // hurts now and then, saps strength, keeps the actor still
+    Blessed,    // blows land harder
+    Soaked,     // tires faster, and heads for shelter
+    Incubating, // caught a sickness that hasn't shown yet
+    Sick,       // tires, keeps still, and passes it on
+    Immune,     // got over it, and won't catch it again for a while
+}
+
+impl Status {
+    /// Key under `inspect` in the translations, for how it looks on someone;
+    /// None for what doesn't show
+    pub fn key(self) -> Option<&'static str> {
+        match self {
+            Status::Poisoned => Some("inspect.poisoned"),
+            Status::Blessed => Some("inspect.blessed"),
+            Status::Soaked => Some("inspect.soaked"),
+            Status::Sick => Some("inspect.sick"),
+            Status::Incubating | Status::Immune => None,
+        }
+    }
+
+    /// What it turns into when it wears off, and for how long
+    fn then(self) -> Option<StatusEffect> {
+        let (status, ticks) = match self {
+            Status::Incubating => (Status::Sick, SICK_TICKS),
+            Status::Sick => (Status::Immune, IMMUNE_TICKS),
+            _ => return None,
+        };
+        Some(StatusEffect { status, ticks })
+    }
+}
+
+/// A status on an actor and how long it has left
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub status: Status,
+    pub ticks: u32, // left before it wears off
+}
+
+/// Put `status` on for `ticks`, or keep it on that long if it's already on
+pub fn afflict(effects: &mut Vec<StatusEffect>, status: Status, ticks: u32) {
+    match effects.iter_mut().find(|e| e.status == status) {
+        Some(effect) => effect.ticks = effect.ticks.max(ticks),
+        None => effects.push(StatusEffect { status, ticks }),
+    }
+}
+
+pub fn cure(effects: &mut Vec<StatusEffect>, status: Status) {
+    effects.retain(|e| e.status != status);
+}
+
+pub fn has(effects: &[StatusEffect], status: Status) -> bool {
+    effects.iter().any(|e| e.status == status)
+}
+
+/// One tick of wear on `effects`, dropping what's worn off, or moving it on
+/// to what comes next. Returns the health it costs, from poison.
+pub fn wear(effects: &mut Vec<StatusEffect>) -> i32 {
+    let mut hurt = 0;
+    let mut next = Vec::new();
+    effects.retain_mut(|effect| {
+        effect.ticks = effect.ticks.saturating_sub(1);
+        if effect.status == Status::Poisoned && effect.ticks % POISON_EVERY == 0 {
+            hurt += 1;
+        }
+        if effect.ticks > 0 {
+            return true;
+        }
+        next.extend(effect.status.then());
+        false
+    });
+    effects.append(&mut next);
+    hurt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_status_wears_off_after_its_ticks() {
+        let mut effects = Vec::new();
+        afflict(&mut effects, Status::Blessed, 3);
+        afflict(&mut effects, Status::Soaked, 1);
+        wear(&mut effects);
+        assert!(has(&effects, Status::Blessed));
+        assert!(!has(&effects, Status::Soaked));
+        wear(&mut effects);
+        wear(&mut effects);
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn afflicting_again_tops_up_but_never_shortens() {
+        let mut effects = Vec::new();
+        afflict(&mut effects, Status::Soaked, 2);
+        afflict(&mut effects, Status::Soaked, SOAKED_TICKS);
+        afflict(&mut effects, Status::Soaked, 1);
+        assert_eq!(effects.len(), 1);
+        assert_eq!(effects[0].ticks, SOAKED_TICKS);
+    }
+
+    #[test]
+    fn poison_hurts_every_few_ticks_while_it_lasts() {
+        let mut effects = Vec::new();
+        afflict(&mut effects, Status::Poisoned, POISON_TICKS);
+        let hurt: i32 = (0..POISON_TICKS + 5).map(|_| wear(&mut effects)).sum();
+        assert_eq!(hurt, (POISON_TICKS / POISON_EVERY) as i32);
+        assert!(effects.is_empty());
+    }
+
+    #[test]
+    fn a_sickness_shows_then_passes_into_immunity() {
+        let mut effects = Vec::new();
+        afflict(&mut effects, Status::Incubating, 1);
+        wear(&mut effects);
+        assert!(has(&effects, Status::Sick) && !has(&effects, Status::Incubating));
+        for _ in 0..SICK_TICKS {
+            wear(&mut effects);
+        }
+        assert!(has(&effects, Status::Immune) && !has(&effects, Status::Sick));
+        for _ in 0..IMMUNE_TICKS {
+            wear(&mut effects);
+        }
+        assert!(effects.is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+//! Stimuli: noises and scents that carry past the page they start on. Each
+//! is left on its page at full strength and on the pages round about,
+//! fainter for every page it crosses, and fades a little every tick until
+//! it's gone. Actors that notice one go to see what it was, or keep away.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use crate::events::WorldEvent;
+use crate::pages::{PageGraph, PageId};
+use crate::perception::Sense;
+use crate::session::Emote;
+
+/// How loud a fight is where it breaks out
+pub const FIGHT_NOISE: u8 = 12;
+
+/// How loud a player's shout is
+pub const SHOUT_NOISE: u8 = 16;
+
+/// How strong the smell of fresh blood is where it's spilt
+pub const BLOOD_SCENT: u8 = 30;
+
+/// What sort of thing is noticed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StimulusKind {
+    Noise,
+    Scent, // carries less far, but lingers
+}
+
+impl StimulusKind {
+    /// The sense that picks it up
+    pub fn sense(self) -> Sense {
+        match self {
+            StimulusKind::Noise => Sense::Hearing,
+            StimulusKind::Scent => Sense::Smell,
+        }
+    }
+
+    /// Strength it loses for each page it carries across
+    fn carry_loss(self) -> u8 {
+        match self {
+            StimulusKind::Noise => 4,
+            StimulusKind::Scent => 15,
+        }
+    }
+}
+
+/// The noise or smell `event` makes, where, and how strong: fights are
+/// loud, wounds bleed and shouting carries
+pub fn stirred_by(event: &WorldEvent) -> Option<(StimulusKind, &PageId, u8)> {
+    match event {
+        WorldEvent::ActorAttacked { page, .. } => Some((StimulusKind::Noise, page, FIGHT_NOISE)),
+        WorldEvent::ActorWounded { page, .. } => Some((StimulusKind::Scent, page, BLOOD_SCENT)),
+        WorldEvent::PlayerEmoted {
+            page,
+            emote: Emote::Shout,
+            ..
+        } => Some((StimulusKind::Noise, page, SHOUT_NOISE)),
+        _ => None,
+    }
+}
+
+/// A noise or scent as it reaches one page
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stimulus {
+    pub kind: StimulusKind,
+    pub from: PageId, // where it started
+    pub strength: u8, // and ticks left before it's gone
+}
+
+/// What can be heard and smelled on each page; pages with nothing going
+/// on aren't kept
+#[derive(Clone, Debug, Default)]
+pub struct Stimuli(HashMap<PageId, Vec<Stimulus>>);
+
+impl Stimuli {
+    /// Start a `kind` of stimulus on `page` at `strength`, carried out to
+    /// the pages round about for as long as there's anything left of it.
+    /// One already there from the same page is only ever made stronger.
+    pub fn emit(
+        &mut self,
+        kind: StimulusKind,
+        page: &PageId,
+        strength: u8,
+        page_graph: &PageGraph,
+    ) {
+        let mut reached: HashMap<&PageId, u8> = HashMap::from([(page, strength)]);
+        let mut frontier = VecDeque::from([page]);
+        while let Some(at) = frontier.pop_front() {
+            let onward = reached[at].saturating_sub(kind.carry_loss());
+            if onward == 0 {
+                continue;
+            }
+            let exits = page_graph.get(at).into_iter().flat_map(|p| &p.connections);
+            for conn in exits {
+                if !reached.contains_key(&conn.target) {
+                    reached.insert(&conn.target, onward);
+                    frontier.push_back(&conn.target);
+                }
+            }
+        }
+        for (at, strength) in reached {
+            let here = self.0.entry(at.clone()).or_default();
+            match here.iter_mut().find(|s| s.kind == kind && s.from == *page) {
+                Some(same) => same.strength = same.strength.max(strength),
+                None => here.push(Stimulus {
+                    kind,
+                    from: page.clone(),
+                    strength,
+                }),
+            }
+        }
+    }
+
+    /// One tick's fading of everything; what's faded away is dropped
+    pub fn fade(&mut self) {
+        self.0.retain(|_, here| {
+            here.retain_mut(|stimulus| {
+                stimulus.strength = stimulus.strength.saturating_sub(1);
+                stimulus.strength > 0
+            });
+            !here.is_empty()
+        });
+    }
+
+    /// What can be noticed on `page`
+    pub fn on(&self, page: &PageId) -> &[Stimulus] {
+        self.0.get(page).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Everything on every page, for snapshots
+    pub fn all(&self) -> Vec<(PageId, Vec<Stimulus>)> {
+        let mut all: Vec<_> = self
+            .0
+            .iter()
+            .map(|(page, here)| (page.clone(), here.clone()))
+            .collect();
+        all.sort_by(|(a, _), (b, _)| a.cmp(b));
+        all
+    }
+
+    pub fn restore(all: Vec<(PageId, Vec<Stimulus>)>) -> Self {
+        Stimuli(all.into_iter().collect())
+    }
+}
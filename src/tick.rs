@@ -0,0 +1,127 @@
+use std::any::Any;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use crate::clock::WorldClock;
+use crate::environment::EnvironmentManager;
+use crate::events::{EventBus, WorldEvent};
+use crate::instances::Instances;
+use crate::metrics::Metrics;
+use crate::scripting;
+use crate::world::WorldGraph;
+
+/// Wait before restarting the ticker after its first crash; doubled on each
+/// crash in a row, up to `MAX_RESTART_BACKOFF`
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A ticker that has run this long without crashing starts over at the first backoff
+const HEALTHY_RUN: Duration = Duration::from_secs(5 * 60);
+
+/// Run one world tick at the clock's current time: due actors take their
+/// turns in every instance (and whatever their scripts asked for is done),
+/// then the weather moves on if it is time.
+/// Shared by the background loop and admin fast-forward.
+pub fn tick_world(
+    instances: &Instances,
+    environment: &EnvironmentManager,
+    world: &WorldGraph,
+    clock: &WorldClock,
+) {
+    let world_time = clock.world_time();
+    let pages = world.snapshot();
+    let page_flags = world.page_flags();
+    for (actors, bus) in instances.ticking() {
+        let scripted = actors
+            .lock()
+            .tick_some(&world_time, &pages, environment, &page_flags);
+        if let Err(e) = scripting::apply(scripted, None, world, &actors, environment, &bus) {
+            error!("Behavior script effects failed: {e}");
+        }
+    }
+    if let Err(e) = environment.advance_weather(&pages) {
+        error!("Weather step failed: {e}");
+    }
+}
+
+/// Everything the ticker needs, cloned into each run of it
+#[derive(Clone)]
+pub struct Ticker {
+    pub actors: Instances,
+    pub environment: EnvironmentManager,
+    pub world: WorldGraph,
+    pub clock: WorldClock,
+    pub bus: EventBus,
+    pub metrics: Metrics,
+}
+
+impl Ticker {
+    /// Tick the world every tick interval unless the clock is paused. A new
+    /// interval takes over after the tick already waited for.
+    async fn run(self) {
+        let mut period = self.clock.tick_interval();
+        let mut interval = actix_rt::time::interval(period);
+        loop {
+            interval.tick().await;
+            let wanted = self.clock.tick_interval();
+            if wanted != period {
+                info!(
+                    interval_ms = wanted.as_millis() as u64,
+                    "Tick interval changed"
+                );
+                period = wanted;
+                interval =
+                    actix_rt::time::interval_at(actix_rt::time::Instant::now() + period, period);
+            }
+            if self.clock.is_paused() {
+                continue; // the world stands still
+            }
+            let started = Instant::now();
+            tick_world(&self.actors, &self.environment, &self.world, &self.clock);
+            self.metrics.record_tick(started.elapsed());
+        }
+    }
+
+    /// Run the ticker in its own task, restarting it with backoff whenever
+    /// it panics. Each restart is published as `TickerRestarted`.
+    pub fn spawn_supervised(self) {
+        actix_rt::spawn(async move {
+            let mut restarts = 0;
+            let mut backoff = RESTART_BACKOFF;
+            loop {
+                let started = Instant::now();
+                let reason = match actix_rt::spawn(self.clone().run()).await {
+                    Ok(()) => return,
+                    Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                    Err(_) => return, // cancelled; the runtime is shutting down
+                };
+                if started.elapsed() >= HEALTHY_RUN {
+                    backoff = RESTART_BACKOFF;
+                }
+                restarts += 1;
+                error!(
+                    restarts,
+                    backoff_ms = backoff.as_millis() as u64,
+                    "World ticker panicked: {reason}. Restarting."
+                );
+                self.bus.publish(WorldEvent::TickerRestarted {
+                    restarts,
+                    reason,
+                    backoff_ms: backoff.as_millis() as u64,
+                });
+                actix_rt::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+                info!(restarts, "World ticker restarted");
+            }
+        });
+    }
+}
+
+/// What a panic said, if it said it with a string
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
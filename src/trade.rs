@@ -0,0 +1,112 @@
+//! Bartering with NPCs: the player offers one thing they carry for one thing
+//! the NPC carries, and the NPC weighs up what each is worth, by how it
+//! regards the player. Players can also just give things away, which NPCs
+//! remember kindly, or try to take them, which they don't.
+
+use rand::Rng;
+
+use crate::actor::{Actor, ActorFlag};
+use crate::events::WorldEvent;
+use crate::items::{self, ItemCatalog, ItemId};
+use crate::reputation::{Deed, Regard};
+use crate::session::UserSession;
+
+/// A theft comes off when a roll under this is below twice the player's
+/// perception, so even the sharpest-eyed thief is caught now and then
+const STEAL_ROLL: u32 = 12;
+
+/// Offer `give` to `npc` for `want`. Either way comes back as a `flash.*`
+/// text key to tell the player, with `{name}`, `{give}` and `{want}` to fill in.
+pub fn barter(
+    player: &mut UserSession,
+    npc: &mut Actor,
+    give: &ItemId,
+    want: &ItemId,
+    catalog: &ItemCatalog,
+) -> Result<&'static str, &'static str> {
+    if !npc.has_flag(ActorFlag::CanSpeak) || npc.has_flag(ActorFlag::Player) {
+        return Err("flash.trade_no_interest");
+    }
+    let regard = player.reputation.regard(npc);
+    if regard == Regard::Hostile {
+        return Err("flash.trade_refused");
+    }
+    let Some(given) = player.inventory.iter().position(|item| item == give) else {
+        return Err("flash.trade_not_carried");
+    };
+    let Some(wanted) = npc.inventory.iter().position(|item| item == want) else {
+        return Err("flash.trade_not_theirs");
+    };
+    let value = |item: &ItemId| catalog.get(item).map_or(0, |i| i.value);
+    // friends let things go for less than they're worth; the wary want more
+    if value(give) < regard.charges(value(want)) {
+        return Err("flash.trade_declined");
+    }
+    let weight = |item: &ItemId| catalog.get(item).map_or(0, |i| i.weight);
+    if items::weight_of(&npc.inventory, catalog) - weight(want) + weight(give) > npc.capacity() {
+        return Err("flash.too_heavy_for_them");
+    }
+
+    let give = player.inventory.remove(given);
+    let want = npc.inventory.remove(wanted);
+    npc.inventory.push(give);
+    player.inventory.push(want);
+    Ok("flash.traded")
+}
+
+/// Hand `give` to `npc` for nothing, which it thinks the better of the
+/// player for, the more so the more it's worth
+pub fn gift(
+    player: &mut UserSession,
+    npc: &mut Actor,
+    give: &ItemId,
+    catalog: &ItemCatalog,
+) -> Result<&'static str, &'static str> {
+    // a mount can be won over with a treat as well as anyone
+    let takes_gifts = npc.has_flag(ActorFlag::CanSpeak) || npc.has_flag(ActorFlag::Rideable);
+    if !takes_gifts || npc.has_flag(ActorFlag::Player) {
+        return Err("flash.trade_no_interest");
+    }
+    let Some(given) = player.inventory.iter().position(|item| item == give) else {
+        return Err("flash.trade_not_carried");
+    };
+    let (value, weight) = catalog.get(give).map_or((0, 0), |i| (i.value, i.weight));
+    if items::weight_of(&npc.inventory, catalog) + weight > npc.capacity() {
+        return Err("flash.too_heavy_for_them");
+    }
+    npc.inventory.push(player.inventory.remove(given));
+    player.reputation.record(Deed::Gifted(value), npc);
+    Ok("flash.gifted")
+}
+
+/// Try to lift `want` off `npc` unnoticed, with odds by the player's
+/// perception. A thief who is caught is set upon and talked about: the
+/// event of it comes back to publish, as well as the text key to tell them.
+pub fn steal(
+    player: &mut UserSession,
+    npc: &mut Actor,
+    want: &ItemId,
+    rng: &mut impl Rng,
+) -> Result<(&'static str, Option<WorldEvent>), &'static str> {
+    if npc.has_flag(ActorFlag::Player) {
+        return Err("flash.no_pickpocketing");
+    }
+    let Some(wanted) = npc.inventory.iter().position(|item| item == want) else {
+        return Err("flash.trade_not_theirs");
+    };
+    if rng.random_range(0..STEAL_ROLL) < u32::from(player.perception()) * 2 {
+        player.inventory.push(npc.inventory.remove(wanted));
+        return Ok(("flash.stole", None));
+    }
+
+    npc.state.awake = true;
+    npc.state.target = Some(player.player_id.clone());
+    npc.state.following = None;
+    player.reputation.record(Deed::CaughtStealing, npc);
+    let caught = WorldEvent::ThiefCaught {
+        thief: player.player_id.clone(),
+        victim: npc.id.clone(),
+        page: npc.location.clone(),
+    };
+    Ok(("flash.caught_stealing", Some(caught)))
+}
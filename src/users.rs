@@ -0,0 +1,419 @@
+use actix_session::Session;
+use actix_web::{HttpResponse, Responder, web};
+use argon2::Argon2;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use parking_lot::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tera::Tera;
+use tokio::sync::Notify;
+use tracing::{error, info};
+
+use crate::clock::WorldClock;
+use crate::environment::EnvironmentManager;
+use crate::error::AppError;
+use crate::handler::START_PAGE;
+use crate::i18n::Translations;
+use crate::persistence::write_json_atomic;
+use crate::render;
+use crate::session::{UserSession, flash, get_or_create_user_session, set_user_session};
+use crate::worlds::Mount;
+
+/// Default location of the accounts file, relative to the working directory
+pub const DEFAULT_ACCOUNTS_PATH: &str = "accounts.json";
+
+/// Session key holding the name of the logged-in account, if any
+pub const ACCOUNT_KEY: &str = "account";
+
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_USERNAME_LEN: usize = 32;
+
+/// How long saved characters wait before the accounts file is written, so
+/// a run of moves is written once
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// What passwords for unknown usernames are checked against, so a login
+/// takes as long whether or not the account exists
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(b"not anyone's password", &salt)
+        .expect("hashing a fixed password")
+        .to_string()
+});
+
+/// A registered player and the character they left off as
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub username: String,
+    password_hash: String, // argon2, PHC string format
+    pub character: UserSession,
+}
+
+/// Every account, kept in memory and written to a JSON file on change:
+/// at once for a new account, a little later, off the async workers, for
+/// a character that has moved on
+#[derive(Clone)]
+pub struct AccountStore {
+    path: PathBuf,
+    accounts: Arc<Mutex<HashMap<String, Account>>>, // username -> account
+    changed: Arc<Notify>,                           // a character changed since the last write
+    writing: Arc<Mutex<()>>,                        // one write to the file at a time
+}
+
+impl AccountStore {
+    /// Read accounts from `path`. A missing file just means no accounts yet.
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let accounts = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+            serde_json::from_str(&text)
+                .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?
+        } else {
+            HashMap::new()
+        };
+        LazyLock::force(&DUMMY_HASH); // not on the first unknown login, where it would show
+        Ok(AccountStore {
+            path: path.to_path_buf(),
+            accounts: Arc::new(Mutex::new(accounts)),
+            changed: Arc::new(Notify::new()),
+            writing: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Write the accounts out a moment after characters change
+    pub fn spawn_saver(&self) {
+        let store = self.clone();
+        actix_rt::spawn(async move {
+            loop {
+                store.changed.notified().await;
+                actix_rt::time::sleep(SAVE_DELAY).await; // let the changes gather
+                let saver = store.clone();
+                match web::block(move || saver.write()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => error!("Failed to save accounts: {e}"),
+                    Err(e) => error!("Failed to save accounts: {e}"),
+                }
+            }
+        });
+    }
+
+    /// Write every account to the file now
+    pub fn write(&self) -> Result<(), AppError> {
+        let _writing = self.writing.lock();
+        let accounts = self.lock().clone();
+        write_json_atomic(&self.path, &accounts)
+    }
+
+    /// Create an account that starts out as `character`
+    pub fn register(
+        &self,
+        username: &str,
+        password: &str,
+        character: UserSession,
+    ) -> Result<(), AppError> {
+        if username.is_empty()
+            || username.len() > MAX_USERNAME_LEN
+            || !username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(AppError::SessionError(format!(
+                "Usernames are 1-{MAX_USERNAME_LEN} letters, digits, '-' or '_'"
+            )));
+        }
+        if password.len() < MIN_PASSWORD_LEN {
+            return Err(AppError::SessionError(format!(
+                "Passwords need at least {MIN_PASSWORD_LEN} characters"
+            )));
+        }
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| AppError::OtherError(format!("Hashing password: {e}")))?
+            .to_string();
+
+        {
+            let mut accounts = self.lock();
+            if accounts.contains_key(username) {
+                return Err(AppError::SessionError(format!(
+                    "The name '{username}' is taken"
+                )));
+            }
+            accounts.insert(
+                username.to_string(),
+                Account {
+                    username: username.to_string(),
+                    password_hash,
+                    character,
+                },
+            );
+        }
+        self.write()
+    }
+
+    /// The account's saved character, if the password is right
+    pub fn log_in(&self, username: &str, password: &str) -> Result<UserSession, AppError> {
+        let refused = || AppError::Unauthorized("Wrong username or password".to_string());
+        // verify without holding the lock; it is slow on purpose
+        let account = self.lock().get(username).cloned();
+        let stored = account
+            .as_ref()
+            .map_or(DUMMY_HASH.as_str(), |account| &account.password_hash);
+        let hash = PasswordHash::new(stored)
+            .map_err(|e| AppError::OtherError(format!("Bad stored hash for {username}: {e}")))?;
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+        match account {
+            Some(account) if verified => Ok(account.character),
+            _ => Err(refused()),
+        }
+    }
+
+    /// Remember where the account's character is now; it reaches the file
+    /// shortly, if anything changed
+    pub fn save_character(&self, username: &str, character: &UserSession) -> Result<(), AppError> {
+        let mut accounts = self.lock();
+        let account = accounts
+            .get_mut(username)
+            .ok_or_else(|| AppError::SessionError(format!("No account '{username}'")))?;
+        if account.character != *character {
+            account.character = character.clone();
+            self.changed.notify_one();
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Account>> {
+        self.accounts.lock()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+fn account_page(
+    tera: &Tera,
+    session: &Session,
+    clock: &WorldClock,
+    environment: &EnvironmentManager,
+    translations: &Translations,
+    error: Option<&str>,
+) -> Result<HttpResponse, AppError> {
+    let player = get_or_create_user_session(session, START_PAGE)?;
+    let mut context = render::base_context(session, &player, clock, environment, translations);
+    context.insert("error", &error);
+    let html = tera.render("account.html", &context)?;
+    Ok(match error {
+        Some(_) => HttpResponse::BadRequest().body(html),
+        None => HttpResponse::Ok().body(html),
+    })
+}
+
+fn back_to_game(mount: &Mount) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header(("Location", mount.path("/")))
+        .finish()
+}
+
+/// Log in / register forms, or who you are logged in as
+pub async fn account_handler(
+    tera: web::Data<Tera>,
+    session: Session,
+    clock: web::Data<WorldClock>,
+    environment: web::Data<EnvironmentManager>,
+    translations: web::Data<Arc<Translations>>,
+) -> Result<impl Responder, AppError> {
+    account_page(&tera, &session, &clock, &environment, &translations, None)
+}
+
+/// Register an account. The character so far comes along with it.
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn register_handler(
+    tera: web::Data<Tera>,
+    session: Session,
+    clock: web::Data<WorldClock>,
+    environment: web::Data<EnvironmentManager>,
+    translations: web::Data<Arc<Translations>>,
+    accounts: web::Data<AccountStore>,
+    mount: web::Data<Mount>,
+    form: web::Form<Credentials>,
+) -> Result<impl Responder, AppError> {
+    let character = get_or_create_user_session(&session, START_PAGE)?;
+    let character_language = character.language.clone();
+    let Credentials { username, password } = form.into_inner();
+    let store = accounts.get_ref().clone();
+    let name = username.clone();
+    // hashing is deliberately slow; keep it off the async workers
+    let registered = web::block(move || store.register(&name, &password, character))
+        .await
+        .map_err(|e| AppError::OtherError(e.to_string()))?;
+    match registered {
+        Ok(()) => {
+            info!("Registered account {username}");
+            session.renew();
+            session
+                .insert(ACCOUNT_KEY, &username)
+                .map_err(|e| AppError::SessionError(e.to_string()))?;
+            let lang = translations.language(character_language.as_deref());
+            flash(
+                &session,
+                translations.text(lang, "flash.registered", &[("name", &username)]),
+            );
+            Ok(back_to_game(&mount))
+        }
+        Err(AppError::SessionError(reason)) => account_page(
+            &tera,
+            &session,
+            &clock,
+            &environment,
+            &translations,
+            Some(&reason),
+        ),
+        Err(e) => Err(e),
+    }
+}
+
+/// Log in, picking the account's character up where it was left
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn login_handler(
+    tera: web::Data<Tera>,
+    session: Session,
+    clock: web::Data<WorldClock>,
+    environment: web::Data<EnvironmentManager>,
+    translations: web::Data<Arc<Translations>>,
+    accounts: web::Data<AccountStore>,
+    mount: web::Data<Mount>,
+    form: web::Form<Credentials>,
+) -> Result<impl Responder, AppError> {
+    let Credentials { username, password } = form.into_inner();
+    let store = accounts.get_ref().clone();
+    let name = username.clone();
+    let character = web::block(move || store.log_in(&name, &password))
+        .await
+        .map_err(|e| AppError::OtherError(e.to_string()))?;
+    match character {
+        Ok(character) => {
+            info!("Account {username} logged in");
+            session.renew();
+            set_user_session(&session, &character);
+            session
+                .insert(ACCOUNT_KEY, &username)
+                .map_err(|e| AppError::SessionError(e.to_string()))?;
+            let lang = translations.language(character.language.as_deref());
+            flash(
+                &session,
+                translations.text(lang, "flash.welcome_back", &[("name", &username)]),
+            );
+            Ok(back_to_game(&mount))
+        }
+        Err(AppError::Unauthorized(reason)) => account_page(
+            &tera,
+            &session,
+            &clock,
+            &environment,
+            &translations,
+            Some(&reason),
+        ),
+        Err(e) => Err(e),
+    }
+}
+
+/// Log out and start again as a fresh anonymous character
+pub async fn logout_handler(session: Session, mount: web::Data<Mount>) -> impl Responder {
+    session.purge();
+    back_to_game(&mount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> (AccountStore, PathBuf) {
+        let path =
+            std::env::temp_dir().join(format!("chott-accounts-{name}-{}.json", std::process::id()));
+        (AccountStore::load(&path).unwrap(), path)
+    }
+
+    #[test]
+    fn names_and_passwords_are_checked_before_registering() {
+        let (accounts, path) = store("checked");
+        let character = UserSession::new(START_PAGE);
+        for username in [
+            "",
+            "has space",
+            "semi;colon",
+            &"x".repeat(MAX_USERNAME_LEN + 1),
+        ] {
+            assert!(matches!(
+                accounts.register(username, "long enough", character.clone()),
+                Err(AppError::SessionError(_))
+            ));
+        }
+        assert!(matches!(
+            accounts.register("alice", "short", character.clone()),
+            Err(AppError::SessionError(_))
+        ));
+        assert!(!path.exists(), "nothing written for a refused account");
+
+        accounts
+            .register("alice_2-b", "long enough", character)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_taken_name_or_a_wrong_password_is_refused() {
+        let (accounts, path) = store("refused");
+        let character = UserSession::new(START_PAGE);
+        accounts
+            .register("alice", "long enough", character.clone())
+            .unwrap();
+        assert!(matches!(
+            accounts.register("alice", "another one", character.clone()),
+            Err(AppError::SessionError(_))
+        ));
+
+        assert!(matches!(
+            accounts.log_in("alice", "not the one"),
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            accounts.log_in("bob", "long enough"),
+            Err(AppError::Unauthorized(_))
+        ));
+        let back = accounts.log_in("alice", "long enough").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(back == character);
+    }
+
+    #[actix_rt::test]
+    async fn an_unchanged_character_is_not_saved_again() {
+        let (accounts, path) = store("unchanged");
+        let mut character = UserSession::new(START_PAGE);
+        accounts
+            .register("alice", "long enough", character.clone())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let saving =
+            || actix_rt::time::timeout(Duration::from_millis(50), accounts.changed.notified());
+
+        accounts.save_character("alice", &character).unwrap();
+        assert!(
+            saving().await.is_err(),
+            "nothing changed, so nothing to save"
+        );
+
+        character.coins += 1;
+        accounts.save_character("alice", &character).unwrap();
+        assert!(saving().await.is_ok());
+    }
+}
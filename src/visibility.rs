@@ -0,0 +1,118 @@
+use crate::actor::Actor;
+use crate::conditions::ConditionContext;
+
+/// How an actor on the player's page shows up for them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Seen,
+    Sleeping, // a shape curled up somewhere, no telling who
+    Hidden,
+}
+
+impl Visibility {
+    /// How `actor` shows up to the player `conditions` are about. Nobody is
+    /// seen in the dark, nor while their `seen_when` doesn't hold, unless
+    /// they've sprung out at the player.
+    pub fn of(actor: &Actor, dark: bool, conditions: &ConditionContext) -> Self {
+        let encountered = conditions.session.encounter.as_ref() == Some(&actor.id);
+        if dark || !(encountered || actor.seen_when.holds(conditions)) {
+            Visibility::Hidden
+        } else if !actor.state.awake {
+            Visibility::Sleeping
+        } else {
+            Visibility::Seen
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::tests::critters;
+    use crate::conditions::Condition;
+    use crate::events::EventLog;
+    use crate::items::ItemId;
+    use crate::pages::PageId;
+    use crate::session::UserSession;
+    use crate::world::PageFlags;
+    use chrono::{Local, TimeZone};
+    use std::sync::Arc;
+
+    /// A critter that shows itself only while `seen_when` holds
+    fn critter(seen_when: Condition) -> Actor {
+        let mut definition = critters(1).remove(0);
+        definition.seen_when = seen_when;
+        Actor::from_definition(definition)
+    }
+
+    /// How `actor` shows up to `player` at noon
+    fn seen_by(actor: &Actor, dark: bool, player: &UserSession) -> Visibility {
+        let here = PageId::from("ring-0");
+        let events = EventLog::default();
+        let conditions = ConditionContext {
+            session: player,
+            page: &here,
+            now: Local.with_ymd_and_hms(2026, 6, 1, 12, 0, 0).unwrap(),
+            environment: None,
+            events: &events,
+            page_flags: Arc::new(PageFlags::new()),
+            scripts: None,
+        };
+        Visibility::of(actor, dark, &conditions)
+    }
+
+    #[test]
+    fn nobody_is_seen_in_the_dark() {
+        let player = UserSession::new("ring-0");
+        let actor = critter(Condition::Always);
+        assert_eq!(seen_by(&actor, false, &player), Visibility::Seen);
+        assert_eq!(seen_by(&actor, true, &player), Visibility::Hidden);
+    }
+
+    #[test]
+    fn an_actor_shows_only_while_it_lets_itself_be_seen() {
+        let player = UserSession::new("ring-0");
+        assert_eq!(
+            seen_by(&critter(Condition::Night), false, &player),
+            Visibility::Hidden
+        );
+        assert_eq!(
+            seen_by(&critter(Condition::Day), false, &player),
+            Visibility::Seen
+        );
+    }
+
+    #[test]
+    fn a_sleeper_is_a_shape_but_not_in_the_dark() {
+        let player = UserSession::new("ring-0");
+        let mut actor = critter(Condition::Always);
+        actor.state.awake = false;
+        assert_eq!(seen_by(&actor, false, &player), Visibility::Sleeping);
+        assert_eq!(seen_by(&actor, true, &player), Visibility::Hidden);
+    }
+
+    #[test]
+    fn a_stealthy_actor_shows_to_whoever_has_what_reveals_it() {
+        let lens = ItemId::from("seeing-stone");
+        let by_item = critter(Condition::HasItem(lens.clone()));
+        let by_flag = critter(Condition::Flag("keen_eyed".to_string()));
+        let mut player = UserSession::new("ring-0");
+        assert_eq!(seen_by(&by_item, false, &player), Visibility::Hidden);
+        assert_eq!(seen_by(&by_flag, false, &player), Visibility::Hidden);
+
+        player.inventory.push(lens);
+        player.flags.insert("keen_eyed".to_string());
+        assert_eq!(seen_by(&by_item, false, &player), Visibility::Seen);
+        assert_eq!(seen_by(&by_flag, false, &player), Visibility::Seen);
+        // what reveals it is no light to see by
+        assert_eq!(seen_by(&by_item, true, &player), Visibility::Hidden);
+    }
+
+    #[test]
+    fn one_that_sprang_out_at_the_player_is_seen_however_stealthy() {
+        let mut player = UserSession::new("ring-0");
+        let actor = critter(Condition::Flag("keen_eyed".to_string()));
+        player.encounter = Some(actor.id.clone());
+        assert_eq!(seen_by(&actor, false, &player), Visibility::Seen);
+    }
+}
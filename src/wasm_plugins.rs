@@ -0,0 +1,314 @@
+//! Plugins compiled to WebAssembly, run with wasmtime.
+//!
+//! A plugin module exports its `memory`, an `alloc(len: i32) -> i32` the host
+//! writes strings into, and any of these hooks:
+//!
+//! - `on_tick(tick: i64)`
+//! - `on_player_move(from_ptr: i32, from_len: i32, to_ptr: i32, to_len: i32)`
+//! - `on_page_render(page_ptr: i32, page_len: i32) -> i64`, returning
+//!   `ptr << 32 | len` of UTF-8 text to add to the page (one line per line), or 0
+//!
+//! It may import these from the "chott" module; strings are (ptr, len) pairs
+//! in its memory:
+//!
+//! - `log(text)`
+//! - `set_page_flag(page, flag, set: i32)`
+//! - `move_actor(actor, page)`
+//! - `emit(page, name)`
+
+use parking_lot::Mutex;
+use std::path::Path;
+use tracing::{error, info, warn};
+use wasmtime::{
+    Caller, Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::error::AppError;
+use crate::pages::PageId;
+use crate::plugins::WorldPlugin;
+use crate::scripting::ScriptEffect;
+
+/// Fuel (roughly, wasm instructions) one hook call may burn before it is stopped
+const FUEL_PER_CALL: u64 = 1_000_000;
+/// Bytes of linear memory a plugin may grow to
+const MEMORY_LIMIT: usize = 16 << 20;
+
+/// What a plugin's host calls can reach while a hook runs
+struct PluginState {
+    name: String,
+    effects: Vec<ScriptEffect>,
+    limits: StoreLimits,
+}
+
+/// One instantiated plugin module. Calls into it take turns.
+pub struct WasmPlugin {
+    name: String,
+    instance: Mutex<(Store<PluginState>, Instance)>,
+}
+
+/// Instantiate every `.wasm` file in `dir`. A missing directory means no plugins.
+pub fn load_dir(dir: &Path) -> Result<Vec<Box<dyn WorldPlugin>>, AppError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(AppError::OtherError(format!(
+                "Reading {}: {e}",
+                dir.display()
+            )));
+        }
+    };
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config).map_err(plugin_error)?;
+    let linker = host_functions(&engine)?;
+
+    let mut plugins: Vec<Box<dyn WorldPlugin>> = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", dir.display())))?
+            .path();
+        if path.extension().is_none_or(|ext| ext != "wasm") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("plugin")
+            .to_string();
+        let bytes = std::fs::read(&path)
+            .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+        let module = Module::new(&engine, bytes)
+            .map_err(|e| AppError::OtherError(format!("Compiling {}: {e}", path.display())))?;
+        let mut store = Store::new(
+            &engine,
+            PluginState {
+                name: name.clone(),
+                effects: Vec::new(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MEMORY_LIMIT)
+                    .instances(1)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(plugin_error)?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| AppError::OtherError(format!("Instantiating {}: {e}", path.display())))?;
+        plugins.push(Box::new(WasmPlugin {
+            name,
+            instance: Mutex::new((store, instance)),
+        }));
+    }
+    Ok(plugins)
+}
+
+fn plugin_error(e: wasmtime::Error) -> AppError {
+    AppError::OtherError(format!("WASM plugin: {e}"))
+}
+
+/// A string the plugin passed as (ptr, len) in its memory
+fn read_str(caller: &mut Caller<'_, PluginState>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let bytes = memory
+        .data(&caller)
+        .get(ptr as usize..(ptr as usize).checked_add(len as usize)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// The "chott" functions plugins can import
+fn host_functions(engine: &Engine) -> Result<Linker<PluginState>, AppError> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap(
+            "chott",
+            "log",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+                if let Some(text) = read_str(&mut caller, ptr, len) {
+                    info!("plugin {}: {text}", caller.data().name);
+                }
+            },
+        )
+        .map_err(plugin_error)?;
+    linker
+        .func_wrap(
+            "chott",
+            "set_page_flag",
+            |mut caller: Caller<'_, PluginState>,
+             page_ptr: i32,
+             page_len: i32,
+             flag_ptr: i32,
+             flag_len: i32,
+             set: i32| {
+                let page = read_str(&mut caller, page_ptr, page_len);
+                let flag = read_str(&mut caller, flag_ptr, flag_len);
+                if let (Some(page), Some(flag)) = (page, flag) {
+                    caller.data_mut().effects.push(ScriptEffect::SetPageFlag {
+                        page: PageId::from(page),
+                        flag,
+                        set: set != 0,
+                    });
+                }
+            },
+        )
+        .map_err(plugin_error)?;
+    linker
+        .func_wrap(
+            "chott",
+            "move_actor",
+            |mut caller: Caller<'_, PluginState>,
+             actor_ptr: i32,
+             actor_len: i32,
+             page_ptr: i32,
+             page_len: i32| {
+                let actor = read_str(&mut caller, actor_ptr, actor_len);
+                let page = read_str(&mut caller, page_ptr, page_len);
+                if let (Some(actor), Some(page)) = (actor, page) {
+                    caller.data_mut().effects.push(ScriptEffect::MoveActor {
+                        actor: actor.into(),
+                        to: PageId::from(page),
+                    });
+                }
+            },
+        )
+        .map_err(plugin_error)?;
+    linker
+        .func_wrap(
+            "chott",
+            "emit",
+            |mut caller: Caller<'_, PluginState>,
+             page_ptr: i32,
+             page_len: i32,
+             name_ptr: i32,
+             name_len: i32| {
+                let page = read_str(&mut caller, page_ptr, page_len);
+                let name = read_str(&mut caller, name_ptr, name_len);
+                if let (Some(page), Some(name)) = (page, name) {
+                    caller.data_mut().effects.push(ScriptEffect::Emit {
+                        page: PageId::from(page),
+                        name,
+                    });
+                }
+            },
+        )
+        .map_err(plugin_error)?;
+    Ok(linker)
+}
+
+impl WasmPlugin {
+    /// Run `call` with a fresh fuel budget; returns its result and the
+    /// effects it asked for. Failures are logged and count as doing nothing.
+    fn call<R>(
+        &self,
+        hook: &str,
+        call: impl FnOnce(&mut Store<PluginState>, &Instance) -> wasmtime::Result<R>,
+    ) -> Option<(R, Vec<ScriptEffect>)> {
+        let mut guard = self.instance.lock();
+        let (store, instance) = &mut *guard;
+        let result = store
+            .set_fuel(FUEL_PER_CALL)
+            .and_then(|_| call(store, instance));
+        let effects = std::mem::take(&mut store.data_mut().effects);
+        match result {
+            Ok(value) => Some((value, effects)),
+            Err(e) => {
+                error!("Plugin {} failed in {hook}: {e}", self.name);
+                None
+            }
+        }
+    }
+
+    /// Copy `text` into the plugin's memory through its `alloc`
+    fn write_str(
+        store: &mut Store<PluginState>,
+        instance: &Instance,
+        text: &str,
+    ) -> wasmtime::Result<(i32, i32)> {
+        let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("no exported memory"))?;
+        let len = i32::try_from(text.len())?;
+        let ptr = alloc.call(&mut *store, len)?;
+        memory.write(&mut *store, ptr as usize, text.as_bytes())?;
+        Ok((ptr, len))
+    }
+
+    fn exports(&self, hook: &str) -> bool {
+        let mut guard = self.instance.lock();
+        let (store, instance) = &mut *guard;
+        instance.get_func(store, hook).is_some()
+    }
+}
+
+impl WorldPlugin for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_tick(&self, tick: u64) -> Vec<ScriptEffect> {
+        if !self.exports("on_tick") {
+            return Vec::new();
+        }
+        self.call("on_tick", |store, instance| {
+            instance
+                .get_typed_func::<i64, ()>(&mut *store, "on_tick")?
+                .call(store, tick as i64)
+        })
+        .map(|(_, effects)| effects)
+        .unwrap_or_default()
+    }
+
+    fn on_player_move(&self, from: &PageId, to: &PageId) -> Vec<ScriptEffect> {
+        if !self.exports("on_player_move") {
+            return Vec::new();
+        }
+        self.call("on_player_move", |store, instance| {
+            let (from_ptr, from_len) = Self::write_str(store, instance, &from.0)?;
+            let (to_ptr, to_len) = Self::write_str(store, instance, &to.0)?;
+            instance
+                .get_typed_func::<(i32, i32, i32, i32), ()>(&mut *store, "on_player_move")?
+                .call(store, (from_ptr, from_len, to_ptr, to_len))
+        })
+        .map(|(_, effects)| effects)
+        .unwrap_or_default()
+    }
+
+    fn on_page_render(&self, page: &PageId) -> Vec<String> {
+        if !self.exports("on_page_render") {
+            return Vec::new();
+        }
+        let text = self.call("on_page_render", |store, instance| {
+            let (ptr, len) = Self::write_str(store, instance, &page.0)?;
+            let packed = instance
+                .get_typed_func::<(i32, i32), i64>(&mut *store, "on_page_render")?
+                .call(&mut *store, (ptr, len))?;
+            if packed == 0 {
+                return Ok(String::new());
+            }
+            let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            let memory = instance
+                .get_memory(&mut *store, "memory")
+                .ok_or_else(|| wasmtime::Error::msg("no exported memory"))?;
+            let bytes = memory
+                .data(&*store)
+                .get(ptr..ptr + len)
+                .ok_or_else(|| wasmtime::Error::msg("text out of bounds"))?;
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        });
+        let Some((text, effects)) = text else {
+            return Vec::new();
+        };
+        if !effects.is_empty() {
+            warn!(
+                "Plugin {} tried to change the world while rendering; ignored",
+                self.name
+            );
+        }
+        text.lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
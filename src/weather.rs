@@ -0,0 +1,253 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::environment::Season;
+use crate::pages::{Biome, PageGraph, PageId};
+
+/// Chance (percent) per step that a page takes on a neighbouring page's weather
+const DRIFT_CHANCE: u32 = 25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WeatherKind {
+    Clear,
+    Cloudy,
+    Rainy,
+    Windy,
+    Foggy,
+    Stormy,
+    Snowy,
+}
+
+impl std::fmt::Display for WeatherKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl WeatherKind {
+    pub const ALL: [WeatherKind; 7] = [
+        WeatherKind::Clear,
+        WeatherKind::Cloudy,
+        WeatherKind::Rainy,
+        WeatherKind::Windy,
+        WeatherKind::Foggy,
+        WeatherKind::Stormy,
+        WeatherKind::Snowy,
+    ];
+
+    /// How likely this weather is to set in during `season` (0 = never)
+    fn seasonal_weight(self, season: Season) -> u32 {
+        use WeatherKind::*;
+        match (self, season) {
+            (Snowy, Season::Winter) => 4,
+            (Snowy, _) => 0,
+            (Stormy, Season::Summer) => 3,
+            (Stormy, Season::Winter) => 0,
+            (Stormy, _) => 1,
+            (Clear, Season::Summer) => 6,
+            (Clear, Season::Winter) => 2,
+            (Clear, _) => 4,
+            (Rainy, Season::Spring) | (Rainy, Season::Autumn) => 4,
+            (Rainy, Season::Winter) => 1,
+            (Rainy, _) => 2,
+            (Foggy, Season::Autumn) => 3,
+            (Foggy, _) => 1,
+            (Cloudy, _) => 3,
+            (Windy, Season::Autumn) => 3,
+            (Windy, _) => 2,
+        }
+    }
+
+    /// `seasonal_weight` adjusted for the lie of the land
+    fn weight(self, season: Season, biome: Biome) -> u32 {
+        use WeatherKind::*;
+        let base = self.seasonal_weight(season);
+        match (biome, self) {
+            (Biome::Cave, Clear) => 1, // sheltered from the sky: no weather at all
+            (Biome::Cave, _) => 0,
+            (_, _) if base == 0 => 0,
+            (Biome::Coastal, Windy) => base * 2 + 2,
+            (Biome::Coastal, Foggy) | (Biome::Coastal, Stormy) => base + 1,
+            (Biome::Forest, Windy) => base / 2,
+            (Biome::Forest, Foggy) => base + 2,
+            _ => base,
+        }
+    }
+}
+
+/// Current weather over one page, with intensity from 0.0 (barely) to 1.0 (severe)
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WeatherState {
+    pub kind: WeatherKind,
+    pub intensity: f32,
+}
+
+impl WeatherState {
+    /// Pick fresh weather suited to `season` and `biome`
+    fn seasonal(season: Season, biome: Biome, rng: &mut impl Rng) -> Self {
+        WeatherState {
+            kind: pick_weighted(
+                WeatherKind::ALL.map(|kind| (kind, kind.weight(season, biome))),
+                rng,
+            ),
+            intensity: rng.random_range(0.2..0.8),
+        }
+    }
+
+    /// One step of the Markov chain: weather tends to persist, otherwise it
+    /// changes to something plausible for the season and biome.
+    fn transition(self, season: Season, biome: Biome, rng: &mut impl Rng) -> Self {
+        let persist_weight = if self.kind.weight(season, biome) == 0 {
+            0 // out-of-place weather (snow in Summer) clears right away
+        } else {
+            12
+        };
+        let next = pick_weighted(
+            WeatherKind::ALL.map(|kind| {
+                let weight = kind.weight(season, biome);
+                if kind == self.kind {
+                    (kind, persist_weight)
+                } else {
+                    (kind, weight)
+                }
+            }),
+            rng,
+        );
+        let intensity = if next == self.kind {
+            (self.intensity + rng.random_range(-0.15..0.15)).clamp(0.05, 1.0)
+        } else {
+            rng.random_range(0.1..0.5) // new weather starts off mild
+        };
+        WeatherState {
+            kind: next,
+            intensity,
+        }
+    }
+}
+
+fn pick_weighted<T: Copy>(options: [(T, u32); 7], rng: &mut impl Rng) -> T {
+    let total: u32 = options.iter().map(|(_, w)| w).sum();
+    let mut roll = rng.random_range(0..total.max(1));
+    for (option, weight) in options {
+        if roll < weight {
+            return option;
+        }
+        roll -= weight;
+    }
+    options[0].0
+}
+
+/// Simulates weather per page. Each step every page moves along its Markov
+/// chain, then fronts drift: a page may take on a connected page's weather.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct WeatherEngine {
+    states: HashMap<PageId, WeatherState>,
+}
+
+impl WeatherEngine {
+    pub fn new() -> Self {
+        WeatherEngine::default()
+    }
+
+    /// The simulated weather on `page_id`, if it has started there
+    pub fn current(&self, page_id: &PageId) -> Option<WeatherState> {
+        self.states.get(page_id).copied()
+    }
+
+    /// Current weather on `page_id`, starting it off if the page is new
+    pub fn weather_at(&mut self, page_id: &PageId, season: Season, biome: Biome) -> WeatherState {
+        *self
+            .states
+            .entry(page_id.clone())
+            .or_insert_with(|| WeatherState::seasonal(season, biome, &mut rand::rng()))
+    }
+
+    /// Advance the simulation one step, the pages in `held` kept at their
+    /// weather. Returns the pages whose kind of weather changed.
+    pub fn step(
+        &mut self,
+        season: Season,
+        pages: &PageGraph,
+        held: &HashMap<PageId, WeatherState>,
+    ) -> Vec<(PageId, WeatherState)> {
+        self.step_with(season, pages, held, &mut rand::rng())
+    }
+
+    /// One way the weather might go from here, without changing it: the
+    /// weather everywhere after each of `steps`, each with its season and
+    /// the pages held then
+    pub fn play_forward(
+        &self,
+        steps: &[(Season, HashMap<PageId, WeatherState>)],
+        pages: &PageGraph,
+        rng: &mut impl Rng,
+    ) -> Vec<HashMap<PageId, WeatherState>> {
+        let mut engine = self.clone();
+        steps
+            .iter()
+            .map(|(season, held)| {
+                engine.step_with(*season, pages, held, rng);
+                engine.states.clone()
+            })
+            .collect()
+    }
+
+    /// The weather everywhere it has started
+    pub fn states(&self) -> &HashMap<PageId, WeatherState> {
+        &self.states
+    }
+
+    fn step_with(
+        &mut self,
+        season: Season,
+        pages: &PageGraph,
+        held: &HashMap<PageId, WeatherState>,
+        rng: &mut impl Rng,
+    ) -> Vec<(PageId, WeatherState)> {
+        let before = self.states.clone();
+
+        // local transitions
+        let mut next: HashMap<PageId, WeatherState> = pages
+            .values()
+            .map(|page| {
+                let state = match before.get(&page.id) {
+                    Some(state) => state.transition(season, page.biome, rng),
+                    None => WeatherState::seasonal(season, page.biome, rng),
+                };
+                (page.id.clone(), state)
+            })
+            .collect();
+
+        // fronts drift in from neighbours, weakening as they go
+        for page in pages.values() {
+            if page.connections.is_empty() || rng.random_range(0..100) >= DRIFT_CHANCE {
+                continue;
+            }
+            let idx = rng.random_range(0..page.connections.len());
+            if let Some(neighbour) = before.get(&page.connections[idx].target)
+                && neighbour.kind.weight(season, page.biome) > 0
+            {
+                next.insert(
+                    page.id.clone(),
+                    WeatherState {
+                        kind: neighbour.kind,
+                        intensity: neighbour.intensity * 0.8,
+                    },
+                );
+            }
+        }
+
+        // held pages hold whatever drifts in around them, and carry on from
+        // their held weather once let go
+        next.extend(held.iter().map(|(id, state)| (id.clone(), *state)));
+
+        let changed = next
+            .iter()
+            .filter(|(id, state)| before.get(*id).is_none_or(|old| old.kind != state.kind))
+            .map(|(id, state)| (id.clone(), *state))
+            .collect();
+        self.states = next;
+        changed
+    }
+}
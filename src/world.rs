@@ -0,0 +1,281 @@
+use parking_lot::{Mutex, MutexGuard, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+use crate::error::AppError;
+use crate::events::{EventBus, WorldEvent};
+use crate::generator::{self, Area};
+use crate::instances::Instances;
+use crate::pages::{Page, PageConnection, PageGraph, PageId};
+
+/// Most redirects followed when resolving where a removed page went
+const MAX_REDIRECTS: usize = 16;
+
+/// Named marks left on pages (`door_open`, `lamp_lit`), shared by everyone
+pub type PageFlags = HashMap<PageId, HashSet<String>>;
+
+/// The page graph, changeable while the server runs.
+///
+/// Readers take a cheap `snapshot` and keep it for as long as they like;
+/// writers copy the graph, change the copy and swap it in. Every change is
+/// published on the bus. Removed pages leave a redirect behind so players
+/// (whose location lives in their cookie) can be moved somewhere safe.
+#[derive(Clone)]
+pub struct WorldGraph {
+    pages: Arc<RwLock<Arc<PageGraph>>>,
+    redirects: Arc<Mutex<HashMap<PageId, PageId>>>, // removed page -> where to go instead
+    flags: Arc<RwLock<Arc<PageFlags>>>,             // snapshotted like the pages
+    bus: EventBus,
+}
+
+impl WorldGraph {
+    pub fn new(pages: PageGraph, bus: EventBus) -> Self {
+        WorldGraph {
+            pages: Arc::new(RwLock::new(Arc::new(pages))),
+            redirects: Arc::new(Mutex::new(HashMap::new())),
+            flags: Arc::new(RwLock::new(Arc::new(PageFlags::new()))),
+            bus,
+        }
+    }
+
+    /// Start out with page flags saved by an earlier run
+    pub fn with_flags(self, flags: PageFlags) -> Self {
+        *self.flags.write() = Arc::new(flags);
+        self
+    }
+
+    /// Every page's flags as they are right now
+    pub fn page_flags(&self) -> Arc<PageFlags> {
+        self.flags.read().clone()
+    }
+
+    /// Set or clear `flag` on a page. Returns whether that changed anything.
+    pub fn set_page_flag(&self, page_id: &PageId, flag: &str, set: bool) -> Result<bool, AppError> {
+        if !self.snapshot().contains_key(page_id) {
+            return Err(AppError::PageNotFound(page_id.to_string()));
+        }
+        let mut flags = self.flags.write();
+        let mut next = PageFlags::clone(&flags);
+        let on_page = next.entry(page_id.clone()).or_default();
+        let changed = if set {
+            on_page.insert(flag.to_string())
+        } else {
+            on_page.remove(flag)
+        };
+        if !changed {
+            return Ok(false);
+        }
+        next.retain(|_, on_page| !on_page.is_empty());
+        *flags = Arc::new(next);
+        drop(flags);
+        info!(
+            "Flag '{flag}' {} on {page_id}",
+            if set { "set" } else { "cleared" }
+        );
+        self.bus.publish(WorldEvent::PageFlagChanged {
+            page: page_id.clone(),
+            flag: flag.to_string(),
+            set,
+        });
+        Ok(true)
+    }
+
+    /// The graph as it is right now. Later changes don't affect the snapshot.
+    pub fn snapshot(&self) -> Arc<PageGraph> {
+        self.pages.read().clone()
+    }
+
+    /// Where someone on `page_id` should be: the page itself if it still
+    /// exists, else wherever it was redirected to when removed
+    pub fn resolve(&self, page_id: &PageId) -> Option<PageId> {
+        let pages = self.snapshot();
+        let redirects = self.lock_redirects();
+        let mut current = page_id.clone();
+        for _ in 0..MAX_REDIRECTS {
+            if pages.contains_key(&current) {
+                return Some(current);
+            }
+            current = redirects.get(&current)?.clone();
+        }
+        None
+    }
+
+    /// Add a page, or replace the page with the same id
+    pub fn add_page(&self, page: Page) -> Result<(), AppError> {
+        let id = page.id.clone();
+        self.mutate(|pages| {
+            pages.insert(id.clone(), page);
+            Ok(())
+        })?;
+        self.lock_redirects().remove(&id);
+        info!("Page {id} added to the world");
+        self.bus.publish(WorldEvent::PageAdded { page: id });
+        Ok(())
+    }
+
+    /// Remove a page and every connection into it. Anyone on it is sent to `fallback`.
+    pub fn remove_page(&self, page_id: &PageId, fallback: &PageId) -> Result<Page, AppError> {
+        if page_id == fallback {
+            return Err(AppError::OtherError(format!(
+                "Can't fall back from {page_id} to itself"
+            )));
+        }
+        let removed = self.mutate(|pages| {
+            if !pages.contains_key(fallback) {
+                return Err(AppError::PageNotFound(fallback.to_string()));
+            }
+            let removed = pages
+                .remove(page_id)
+                .ok_or_else(|| AppError::PageNotFound(page_id.to_string()))?;
+            for page in pages.values_mut() {
+                page.connections.retain(|conn| conn.target != *page_id);
+            }
+            Ok(removed)
+        })?;
+        self.lock_redirects()
+            .insert(page_id.clone(), fallback.clone());
+        info!("Page {page_id} removed from the world, falling back to {fallback}");
+        self.bus.publish(WorldEvent::PageRemoved {
+            page: page_id.clone(),
+            fallback: fallback.clone(),
+        });
+        Ok(removed)
+    }
+
+    /// Graft a generated area onto the world in one change
+    pub fn add_area(&self, area: Area) -> Result<(), AppError> {
+        let ids: Vec<PageId> = area.pages.iter().map(|p| p.id.clone()).collect();
+        let (from, to, name) = (
+            area.attach_to.clone(),
+            area.entrance.target.clone(),
+            area.entrance.name.clone(),
+        );
+        self.mutate(|pages| generator::attach(pages, area))?;
+        self.lock_redirects().retain(|id, _| !ids.contains(id));
+        info!("Area of {} pages added off {from}", ids.len());
+        for page in ids {
+            self.bus.publish(WorldEvent::PageAdded { page });
+        }
+        self.bus
+            .publish(WorldEvent::ConnectionAdded { from, to, name });
+        Ok(())
+    }
+
+    /// Add a connection leading out of `from`, replacing one with the same name
+    pub fn connect(&self, from: &PageId, connection: PageConnection) -> Result<(), AppError> {
+        let (name, to) = (connection.name.clone(), connection.target.clone());
+        self.mutate(|pages| {
+            if !pages.contains_key(&to) {
+                return Err(AppError::PageNotFound(to.to_string()));
+            }
+            let page = pages
+                .get_mut(from)
+                .ok_or_else(|| AppError::PageNotFound(from.to_string()))?;
+            page.connections.retain(|conn| conn.name != name);
+            page.connections.push(connection);
+            Ok(())
+        })?;
+        info!("Connection '{name}' added from {from} to {to}");
+        self.bus.publish(WorldEvent::ConnectionAdded {
+            from: from.clone(),
+            to,
+            name,
+        });
+        Ok(())
+    }
+
+    /// Remove the connection called `name` leading out of `from` (a collapsed tunnel)
+    pub fn disconnect(&self, from: &PageId, name: &str) -> Result<PageConnection, AppError> {
+        let removed = self.mutate(|pages| {
+            let page = pages
+                .get_mut(from)
+                .ok_or_else(|| AppError::PageNotFound(from.to_string()))?;
+            let idx = page
+                .connections
+                .iter()
+                .position(|conn| conn.name == name)
+                .ok_or_else(|| AppError::OtherError(format!("No connection '{name}' on {from}")))?;
+            Ok(page.connections.remove(idx))
+        })?;
+        info!("Connection '{name}' from {from} removed");
+        self.bus.publish(WorldEvent::ConnectionRemoved {
+            from: from.clone(),
+            to: removed.target.clone(),
+            name: name.to_string(),
+        });
+        Ok(removed)
+    }
+
+    /// Where each removed page sends those who were on it
+    pub fn redirects(&self) -> HashMap<PageId, PageId> {
+        self.lock_redirects().clone()
+    }
+
+    /// Swap in a whole world at once: pages, redirects and page flags, as a
+    /// save had them
+    pub fn restore(&self, pages: PageGraph, redirects: HashMap<PageId, PageId>, flags: PageFlags) {
+        *self.pages.write() = Arc::new(pages);
+        *self.lock_redirects() = redirects;
+        *self.flags.write() = Arc::new(flags);
+    }
+
+    /// Apply `change` to a copy of the graph and swap it in if it succeeds
+    fn mutate<R>(
+        &self,
+        change: impl FnOnce(&mut PageGraph) -> Result<R, AppError>,
+    ) -> Result<R, AppError> {
+        let mut pages = self.pages.write();
+        let mut next = PageGraph::clone(&pages);
+        let result = change(&mut next)?;
+        *pages = Arc::new(next);
+        Ok(result)
+    }
+
+    fn lock_redirects(&self) -> MutexGuard<'_, HashMap<PageId, PageId>> {
+        self.redirects.lock()
+    }
+}
+
+/// Keep actors consistent with the graph: evacuate removed pages and drop
+/// plans that lead through ways that no longer exist.
+pub fn spawn_actor_notifier(bus: &EventBus, instances: Instances) {
+    let mut rx = bus.subscribe();
+    actix_rt::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(WorldEvent::PageRemoved { page, fallback }) => {
+                    for actors in instances.all() {
+                        actors.lock().page_removed(&page, &fallback)
+                    }
+                }
+                Ok(WorldEvent::ConnectionRemoved { to, .. }) => {
+                    for actors in instances.all() {
+                        actors.lock().forget_plans_through(&to)
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::tests::ring;
+
+    #[test]
+    fn a_change_that_panics_leaves_the_world_usable() {
+        let world = WorldGraph::new(ring(), EventBus::new());
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            world.mutate(|_| -> Result<(), AppError> { panic!("a bad change") })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(world.snapshot().len(), ring().len());
+        let ring_0 = PageId::from("ring-0");
+        assert!(world.set_page_flag(&ring_0, "trampled", true).unwrap());
+    }
+}
@@ -0,0 +1,266 @@
+//! Extra named worlds served beside the live one, e.g. for staging or
+//! testing. Each has its own pages, actors and weather, and is mounted
+//! under `/w/<name>/` with a session cookie of its own, so a player's
+//! character there is separate from their live one. The clock is shared.
+
+use actix_session::SessionMiddleware;
+use actix_web::middleware::from_fn;
+use actix_web::web;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::Tera;
+use tracing::{error, info};
+
+use crate::actor::ActorManager;
+use crate::chat::ChatLog;
+use crate::clock::{self, WorldClock};
+use crate::environment::{EnvironmentManager, EnvironmentTtl};
+use crate::error::AppError;
+use crate::events::{EventBus, EventLog};
+use crate::instances::{InstanceMode, Instances};
+use crate::metrics::Metrics;
+use crate::pages::{apply_template_fallback, load_page_graph, validate_templates};
+use crate::regions::Regions;
+use crate::scripting::ScriptHost;
+use crate::session_store::SessionBackend;
+use crate::shops::ShopManager;
+use crate::world::{self, WorldGraph};
+use crate::{
+    api, character, definitions, generator, handler, i18n, live, map, profile, render, schedule,
+    spawner, tick,
+};
+
+pub const DEFAULT_WORLDS_PATH: &str = "data/worlds.toml";
+
+/// Where a world is served from, for links in its pages
+#[derive(Clone, Debug)]
+pub struct Mount {
+    pub name: String,
+    pub base: String, // "" for the live world, else "/w/<name>"
+}
+
+impl Mount {
+    pub fn live() -> Self {
+        Mount {
+            name: "live".to_string(),
+            base: String::new(),
+        }
+    }
+
+    /// `path` within this world, e.g. "/character"
+    pub fn path(&self, path: &str) -> String {
+        format!("{}{path}", self.base)
+    }
+}
+
+/// An extra world, as given in the worlds file
+#[derive(Debug, Deserialize)]
+pub struct WorldSpec {
+    pub name: String, // lowercase letters, digits and dashes
+    pub actors: PathBuf,
+    #[serde(default)]
+    pub spawns: Option<PathBuf>, // no spawning without
+    #[serde(default)]
+    pub schedule: Option<PathBuf>, // no scheduled events without
+    #[serde(default)]
+    pub areas: Option<PathBuf>, // procedural areas grown onto the built-in pages
+}
+
+#[derive(Deserialize)]
+struct WorldFile {
+    #[serde(default)]
+    world: Vec<WorldSpec>,
+}
+
+/// Read the extra worlds to host. A missing file just means only the live one.
+pub fn load_world_specs(path: &Path) -> Result<Vec<WorldSpec>, AppError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppError::OtherError(format!("Reading {}: {e}", path.display())))?;
+    let file: WorldFile = toml::from_str(&text)
+        .map_err(|e| AppError::OtherError(format!("Parsing {}: {e}", path.display())))?;
+    let mut names = HashSet::new();
+    for spec in &file.world {
+        let valid = !spec.name.is_empty()
+            && spec
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid || spec.name == "live" {
+            return Err(AppError::OtherError(format!(
+                "World name '{}' in {} must be lowercase letters, digits and dashes, and not 'live'",
+                spec.name,
+                path.display()
+            )));
+        }
+        if !names.insert(spec.name.as_str()) {
+            return Err(AppError::OtherError(format!(
+                "World '{}' is named twice in {}",
+                spec.name,
+                path.display()
+            )));
+        }
+    }
+    Ok(file.world)
+}
+
+/// Everything one extra world runs on
+pub struct HostedWorld {
+    pub mount: Mount,
+    pub world: WorldGraph,
+    pub bus: EventBus,
+    pub actors: Arc<Mutex<ActorManager>>,
+    pub instances: Instances,
+    pub environment: EnvironmentManager,
+    pub event_log: EventLog,
+    pub chat_log: ChatLog,
+    pub shops: ShopManager,
+}
+
+impl HostedWorld {
+    /// Build the world `spec` describes and set it ticking. Its page flags
+    /// and journal are not saved; it starts over with each restart.
+    pub fn open(
+        spec: &WorldSpec,
+        tera: &Tera,
+        regions: &Arc<Regions>,
+        scripts: &ScriptHost,
+        clock: &WorldClock,
+    ) -> Result<Self, AppError> {
+        let mut pages = load_page_graph();
+        if let Some(areas) = &spec.areas {
+            for area in generator::load_area_specs(areas)? {
+                let grown = area
+                    .generate()
+                    .and_then(|grown| generator::attach(&mut pages, grown));
+                if let Err(e) = grown {
+                    error!(
+                        "Area '{}' not generated in world '{}': {e}",
+                        area.id, spec.name
+                    );
+                }
+            }
+        }
+        let issues = validate_templates(&pages, tera);
+        apply_template_fallback(&mut pages, tera, &issues);
+
+        let bus = EventBus::new();
+        let world = WorldGraph::new(pages, bus.clone());
+        let event_log = EventLog::default();
+        event_log.spawn_subscriber(&bus, clock.clone());
+        let chat_log = ChatLog::new(bus.clone());
+        let shops = ShopManager::default();
+        shops.spawn_restocker(&bus, world.clone());
+
+        let definitions = definitions::load_actor_definitions(&spec.actors)?;
+        let archetypes = definitions::load_archetypes(&spec.actors)?;
+        let spawner = match &spec.spawns {
+            Some(spawns) => spawner::Spawner::load(spawns, &archetypes)?,
+            None => spawner::Spawner::default(),
+        };
+        let schedule = match &spec.schedule {
+            Some(schedule) => schedule::Schedule::load(schedule, &archetypes)?,
+            None => schedule::Schedule::default(),
+        };
+        let actors = Arc::new(Mutex::new(
+            ActorManager::new(bus.clone(), definitions, regions.clone(), scripts.clone())
+                .with_tick_budget(clock::tick_budget_from_env())
+                .with_spawner(spawner)
+                .with_schedule(schedule),
+        ));
+        let instances = Instances::new(InstanceMode::from_env(), actors.clone(), bus.clone());
+        definitions::spawn_reload_watcher(spec.actors.clone(), instances.clone());
+        world::spawn_actor_notifier(&bus, instances.clone());
+        let environment = EnvironmentManager::new(
+            bus.clone(),
+            EnvironmentTtl::from_env(),
+            clock.clone(),
+            world.clone(),
+            regions.clone(),
+        );
+
+        tick::Ticker {
+            actors: instances.clone(),
+            environment: environment.clone(),
+            world: world.clone(),
+            clock: clock.clone(),
+            bus: bus.clone(),
+            metrics: Metrics::default(), // only the live world's ticks are reported
+        }
+        .spawn_supervised();
+
+        let mount = Mount {
+            name: spec.name.clone(),
+            base: format!("/w/{}", spec.name),
+        };
+        info!("World '{}' is served under {}/", mount.name, mount.base);
+        Ok(HostedWorld {
+            mount,
+            world,
+            bus,
+            actors,
+            instances,
+            environment,
+            event_log,
+            chat_log,
+            shops,
+        })
+    }
+
+    /// Serve this world under its mount, its data standing in for the live
+    /// world's and its sessions kept apart from everyone else's
+    pub fn configure(
+        &self,
+        cfg: &mut web::ServiceConfig,
+        session_backend: &SessionBackend,
+        secret_key: &actix_web::cookie::Key,
+    ) {
+        let sessions = SessionMiddleware::builder(session_backend.clone(), secret_key.clone())
+            .cookie_name(format!("world-{}", self.mount.name))
+            .cookie_path(self.mount.base.clone())
+            .build();
+        cfg.service(
+            web::scope(&self.mount.base)
+                .app_data(web::Data::new(self.mount.clone()))
+                .app_data(web::Data::new(self.world.clone()))
+                .app_data(web::Data::new(self.bus.clone()))
+                .app_data(web::Data::new(self.actors.clone()))
+                .app_data(web::Data::new(self.instances.clone()))
+                .app_data(web::Data::new(self.environment.clone()))
+                .app_data(web::Data::new(self.event_log.clone()))
+                .app_data(web::Data::new(self.chat_log.clone()))
+                .app_data(web::Data::new(self.shops.clone()))
+                .wrap(from_fn(render::error_pages))
+                .wrap(sessions)
+                .configure(play_routes)
+                .configure(api::configure)
+                .default_service(web::to(render::not_found)),
+        );
+    }
+}
+
+/// The routes a player plays through, the same in every world
+pub fn play_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/")
+            .route(web::get().to(handler::index_handler))
+            .route(web::post().to(handler::index_handler)),
+    )
+    .route("/events", web::get().to(live::live_events_handler))
+    .route("/map", web::get().to(map::map_handler))
+    .route("/actor/{id}", web::get().to(profile::actor_profile_handler))
+    .route("/language", web::post().to(i18n::language_handler))
+    .route(
+        "/character",
+        web::get().to(character::character_page_handler),
+    )
+    .route(
+        "/character",
+        web::post().to(character::create_character_handler),
+    );
+}